@@ -50,6 +50,18 @@ pub enum Commands {
         /// Duration in seconds
         #[arg(short, long, default_value = "60")]
         duration: u64,
+
+        /// Render from a recorded data log (JSON Lines) instead of live sources
+        #[arg(long)]
+        data: Option<PathBuf>,
+
+        /// Data log playback speed (e.g. 1440 renders a day of data in a minute)
+        #[arg(long, default_value = "1.0", requires = "data", value_parser = parse_speed)]
+        speed: f64,
+
+        /// Save live source data to a data log for later re-rendering
+        #[arg(long, conflicts_with = "data")]
+        capture: Option<PathBuf>,
     },
 
     /// List available audio devices
@@ -75,3 +87,34 @@ pub enum Commands {
     /// Generate an example configuration file
     Init,
 }
+
+/// Parse a playback speed: a finite number above zero
+fn parse_speed(value: &str) -> Result<f64, String> {
+    let speed: f64 = value.parse().map_err(|_| format!("'{}' is not a number", value))?;
+    if !speed.is_finite() || speed <= 0.0 {
+        return Err("speed must be a number above 0".to_string());
+    }
+    Ok(speed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(speed: &str) -> Result<Cli, clap::Error> {
+        let speed = format!("--speed={}", speed);
+        Cli::try_parse_from(["drift", "record", "-o", "out.wav", "--data", "log.jsonl", speed.as_str()])
+    }
+
+    #[test]
+    fn test_record_speed() {
+        let Commands::Record { speed, .. } = record("1440").unwrap().command else {
+            panic!("expected record");
+        };
+        assert_eq!(speed, 1440.0);
+
+        for speed in ["0", "-2", "inf", "NaN", "fast"] {
+            assert!(record(speed).is_err(), "{}", speed);
+        }
+    }
+}
@@ -40,7 +40,10 @@ fn substitute_env_vars(input: &str) -> String {
 pub fn load_config(path: &Path) -> Result<DriftConfig> {
     let contents = std::fs::read_to_string(path)?;
     let contents = substitute_env_vars(&contents);
-    let config: DriftConfig = serde_yaml::from_str(&contents)?;
+    let mut config: DriftConfig = serde_yaml::from_str(&contents)?;
+    if let Some(dir) = path.parent() {
+        config.resolve_paths(dir);
+    }
     config.validate()?;
     Ok(config)
 }
@@ -72,6 +75,32 @@ layers: []
         assert_eq!(config.master.volume, 0.7);
     }
 
+    #[test]
+    fn test_sample_paths_resolve_against_config_dir() {
+        let yaml = r#"
+audio: {}
+master: {}
+sources:
+  - name: system
+    kind: system
+layers:
+  - name: grains
+    voice: texture
+    source: system
+    samples:
+      - path: sounds/rain.wav
+      - path: /abs/wind.wav
+"#;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("drift.yaml");
+        std::fs::write(&path, yaml).unwrap();
+
+        let config = load_config(&path).unwrap();
+        let samples = &config.layers[0].samples;
+        assert_eq!(samples[0].path, dir.path().join("sounds/rain.wav"));
+        assert_eq!(samples[1].path, Path::new("/abs/wind.wav"));
+    }
+
     #[test]
     fn test_substitute_env_vars() {
         env::set_var("DRIFT_TEST_VAR", "hello");
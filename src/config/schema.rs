@@ -1,8 +1,16 @@
 //! Configuration schema definitions
 
-use anyhow::{bail, Result};
+use crate::effects::{Chorus, Convolver, Delay, Flanger, Phaser, Reverb};
+use crate::engine::NoteDivision;
+use crate::mapping::{parse_key, Scale};
+use crate::synth::{
+    find_param, DroneVoice, FmVoice, LfoShape, MelodyVoice, ParamKind, ParamSpec, PercussionVoice, SamplerVoice,
+    TextureVoice, BUILTIN_TABLES,
+};
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 /// Main configuration for Drift
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,9 +28,42 @@ pub struct DriftConfig {
     /// Sound layers
     #[serde(default)]
     pub layers: Vec<LayerConfig>,
+    
+    /// Send buses shared by layers
+    #[serde(default)]
+    pub buses: Vec<BusConfig>,
 }
 
 impl DriftConfig {
+    /// Resolve relative file paths against a base directory
+    pub fn resolve_paths(&mut self, base: &Path) {
+        for layer in &mut self.layers {
+            for sample in &mut layer.samples {
+                if sample.path.is_relative() {
+                    sample.path = base.join(&sample.path);
+                }
+            }
+            if let Some(table) = &mut layer.wavetable {
+                if is_wav_path(table) && Path::new(table.as_str()).is_relative() {
+                    *table = base.join(table.as_str()).to_string_lossy().into_owned();
+                }
+            }
+        }
+        let effects = self
+            .layers
+            .iter_mut()
+            .flat_map(|l| &mut l.effects)
+            .chain(self.buses.iter_mut().flat_map(|b| &mut b.effects))
+            .chain(&mut self.master.effects);
+        for effect in effects {
+            if let Some(ir) = &mut effect.ir {
+                if ir.is_relative() {
+                    *ir = base.join(&*ir);
+                }
+            }
+        }
+    }
+    
     /// Validate the configuration
     pub fn validate(&self) -> Result<()> {
         // Validate audio settings
@@ -32,6 +73,9 @@ impl DriftConfig {
         if self.audio.buffer_size < 64 || self.audio.buffer_size > 8192 {
             bail!("Buffer size must be between 64 and 8192");
         }
+        if !(1..=2).contains(&self.audio.channels) {
+            bail!("Audio channels must be 1 (mono) or 2 (stereo)");
+        }
         
         // Validate master settings
         if self.master.volume < 0.0 || self.master.volume > 1.0 {
@@ -40,6 +84,25 @@ impl DriftConfig {
         if self.master.bpm < 20.0 || self.master.bpm > 300.0 {
             bail!("BPM must be between 20 and 300");
         }
+        if !(1..=16).contains(&self.master.beats_per_bar) {
+            bail!("Beats per bar must be between 1 and 16");
+        }
+        if parse_key(&self.master.key).is_none() {
+            bail!("Unknown key '{}' (expected a note name like C, F# or Bb)", self.master.key);
+        }
+        if Scale::from_name(&self.master.scale).is_none() {
+            bail!("Unknown scale '{}'", self.master.scale);
+        }
+        validate_effects(&self.master.effects).context("Master effects")?;
+        
+        // Validate interpolation intervals
+        for source in &self.sources {
+            if let Some(interval) = source.interpolation.interval {
+                if interval <= 0.0 {
+                    bail!("Source '{}' interpolation interval must be positive", source.name);
+                }
+            }
+        }
         
         // Validate layers reference existing sources
         for layer in &self.layers {
@@ -48,10 +111,276 @@ impl DriftConfig {
             }
         }
         
+        // Validate voices that need samples
+        for layer in &self.layers {
+            if layer.voice == VoiceKind::Sampler && layer.samples.is_empty() {
+                bail!("Layer '{}' uses the sampler voice but has no samples", layer.name);
+            }
+        }
+        
+        // Validate polyphony
+        for layer in &self.layers {
+            if layer.polyphony == 0 || layer.polyphony > MAX_POLYPHONY {
+                bail!("Layer '{}' polyphony must be between 1 and {}", layer.name, MAX_POLYPHONY);
+            }
+        }
+        
+        // Validate stereo placement
+        for layer in &self.layers {
+            if !(-1.0..=1.0).contains(&layer.pan) {
+                bail!("Layer '{}' pan must be between -1.0 and 1.0", layer.name);
+            }
+            if !(0.0..=2.0).contains(&layer.width) {
+                bail!("Layer '{}' width must be between 0.0 and 2.0", layer.name);
+            }
+        }
+        
+        // Validate smoothing
+        for layer in &self.layers {
+            let times = layer.smoothing.params.values().filter_map(|p| p.time);
+            if std::iter::once(layer.smoothing.time).chain(times).any(|t| !(0.0..=60.0).contains(&t)) {
+                bail!("Layer '{}' smoothing times must be between 0 and 60 seconds", layer.name);
+            }
+            if !(0.0..=10.0).contains(&layer.portamento) {
+                bail!("Layer '{}' portamento must be between 0 and 10 seconds", layer.name);
+            }
+            let params = layer.voice.params();
+            if let Some(name) = layer.smoothing.params.keys().find(|name| find_param(params, name).is_none()) {
+                let names: Vec<&str> = params.iter().map(|p| p.name).collect();
+                bail!(
+                    "Layer '{}' smoothing names unknown parameter '{}' (available: {})",
+                    layer.name,
+                    name,
+                    names.join(", ")
+                );
+            }
+        }
+        
+        // Validate filters
+        for layer in &self.layers {
+            let Some(filter) = &layer.filter else { continue };
+            if !(20.0..=20000.0).contains(&filter.cutoff) {
+                bail!("Layer '{}' filter cutoff must be between 20 and 20000 Hz", layer.name);
+            }
+            if !(0.1..=20.0).contains(&filter.resonance) {
+                bail!("Layer '{}' filter resonance must be between 0.1 and 20", layer.name);
+            }
+            if !(-24.0..=24.0).contains(&filter.gain) {
+                bail!("Layer '{}' filter gain must be between -24 and 24 dB", layer.name);
+            }
+            if !(1.0..=10.0).contains(&filter.drive) {
+                bail!("Layer '{}' filter drive must be between 1 and 10", layer.name);
+            }
+            if filter.model == FilterModelKind::Ladder
+                && !matches!(filter.kind, FilterKind::Lowpass | FilterKind::Highpass | FilterKind::Bandpass)
+            {
+                bail!("Layer '{}': the ladder filter only supports lowpass, highpass and bandpass", layer.name);
+            }
+        }
+        
+        // Validate modulation
+        for layer in &self.layers {
+            let modulation = &layer.modulation;
+            for (name, source) in &modulation.sources {
+                if source.kind == ModSourceKind::Lfo && LfoShape::from_name(&source.shape).is_none() {
+                    bail!("Layer '{}' modulation source '{}' has unknown shape '{}'", layer.name, name, source.shape);
+                }
+                if source.kind == ModSourceKind::Data && source.field.is_none() {
+                    bail!("Layer '{}' modulation source '{}' needs a field", layer.name, name);
+                }
+                if source.kind == ModSourceKind::Envelope {
+                    let times = [source.attack, source.decay, source.release];
+                    let owner = format!("Layer '{}' modulation source '{}'", layer.name, name);
+                    validate_envelope(&owner, &times, Some(source.sustain), &source.envelope)?;
+                }
+                if let Some(sync) = &source.sync {
+                    NoteDivision::parse(sync)
+                        .with_context(|| format!("Layer '{}' modulation source '{}'", layer.name, name))?;
+                }
+                let lfo_only = source.phase != 0.0
+                    || source.fade_in != 0.0
+                    || source.retrigger
+                    || source.polarity.is_some()
+                    || source.rate_source.is_some();
+                if lfo_only && source.kind != ModSourceKind::Lfo {
+                    bail!(
+                        "Layer '{}' modulation source '{}': phase, fade_in, retrigger, polarity and rate_source only apply to lfo sources",
+                        layer.name, name
+                    );
+                }
+                if !(0.0..=1.0).contains(&source.phase) {
+                    bail!("Layer '{}' modulation source '{}' phase must be between 0 and 1", layer.name, name);
+                }
+                if source.fade_in < 0.0 {
+                    bail!("Layer '{}' modulation source '{}' fade_in can't be negative", layer.name, name);
+                }
+                if let Some(rate_source) = &source.rate_source {
+                    if rate_source == name || !modulation.sources.contains_key(rate_source) {
+                        bail!("Layer '{}' modulation source '{}' has unknown rate_source '{}'", layer.name, name, rate_source);
+                    }
+                    if source.sync.is_some() {
+                        bail!("Layer '{}' modulation source '{}' can't use both sync and rate_source", layer.name, name);
+                    }
+                    match source.rate_max {
+                        Some(max) if max > 0.0 => {}
+                        _ => bail!("Layer '{}' modulation source '{}' needs a positive rate_max for rate_source", layer.name, name),
+                    }
+                }
+            }
+            for route in &modulation.routes {
+                if !modulation.sources.contains_key(&route.source) {
+                    bail!("Layer '{}' modulation route uses unknown source '{}'", layer.name, route.source);
+                }
+                let params = layer.voice.params();
+                if find_param(params, &route.target).is_none() {
+                    let names: Vec<&str> = params.iter().map(|p| p.name).collect();
+                    bail!(
+                        "Layer '{}' modulation route targets unknown parameter '{}' (available: {})",
+                        layer.name,
+                        route.target,
+                        names.join(", ")
+                    );
+                }
+            }
+        }
+        
+        // Validate patches against the voice's parameters
+        for layer in &self.layers {
+            let params = layer.voice.params();
+            for (name, value) in &layer.patch {
+                value
+                    .resolve(params, name)
+                    .with_context(|| format!("Layer '{}' patch", layer.name))?;
+            }
+        }
+        
+        // Validate amplitude envelopes
+        for layer in &self.layers {
+            let Some(envelope) = &layer.envelope else { continue };
+            if layer.voice == VoiceKind::Fm {
+                bail!("Layer '{}': the fm voice has per-operator envelopes, not one envelope", layer.name);
+            }
+            let times: Vec<f64> = [envelope.attack, envelope.decay, envelope.release].into_iter().flatten().collect();
+            validate_envelope(&format!("Layer '{}'", layer.name), &times, envelope.sustain, &envelope.shape)?;
+        }
+        
+        // Validate sequencer steps
+        for layer in &self.layers {
+            if let Some(step) = &layer.step {
+                if layer.voice != VoiceKind::Melody {
+                    bail!("Layer '{}': step is only supported by the melody voice", layer.name);
+                }
+                let beats = NoteDivision::parse(step)
+                    .with_context(|| format!("Layer '{}' step", layer.name))?
+                    .beats(self.master.beats_per_bar);
+                if !(0.125..=4.0).contains(&beats) {
+                    bail!("Layer '{}' step must be between 1/32 and a whole note", layer.name);
+                }
+            }
+        }
+        
+        // Validate wavetables
+        for layer in &self.layers {
+            if let Some(table) = &layer.wavetable {
+                if layer.voice != VoiceKind::Drone {
+                    bail!("Layer '{}': wavetables are only supported by the drone voice", layer.name);
+                }
+                if !is_wav_path(table) && !BUILTIN_TABLES.contains(&table.as_str()) {
+                    bail!(
+                        "Layer '{}' uses unknown wavetable '{}' (built-in: {}, or a .wav path)",
+                        layer.name,
+                        table,
+                        BUILTIN_TABLES.join(", ")
+                    );
+                }
+            }
+        }
+        
+        // Validate layer effects
+        for layer in &self.layers {
+            validate_effects(&layer.effects).with_context(|| format!("Layer '{}'", layer.name))?;
+            if let Some(effect) = layer.effects.iter().find(|e| matches!(e.name(), "filter" | "send")) {
+                bail!("Layer '{}' effect name '{}' is reserved", layer.name, effect.name());
+            }
+        }
+        
+        // Validate buses
+        for (i, bus) in self.buses.iter().enumerate() {
+            if bus.name == "master" {
+                bail!("Bus name 'master' is reserved");
+            }
+            if self.buses[..i].iter().any(|b| b.name == bus.name) {
+                bail!("Duplicate bus name '{}'", bus.name);
+            }
+            if bus.volume < 0.0 || bus.volume > 1.0 {
+                bail!("Bus '{}' volume must be between 0.0 and 1.0", bus.name);
+            }
+            validate_effects(&bus.effects).with_context(|| format!("Bus '{}'", bus.name))?;
+            if !bus.mappings.is_empty() {
+                let Some(source) = &bus.source else {
+                    bail!("Bus '{}' has mappings but no source", bus.name);
+                };
+                if !self.sources.iter().any(|s| &s.name == source) {
+                    bail!("Bus '{}' references unknown source '{}'", bus.name, source);
+                }
+            }
+            for target in bus.mappings.keys() {
+                let known = target.split_once('.').is_some_and(|(effect, param)| {
+                    bus.effects
+                        .iter()
+                        .any(|e| e.name() == effect && find_param(e.kind.params(), param).is_some())
+                });
+                if !known {
+                    bail!("Bus '{}' maps unknown parameter '{}' (expected <effect>.<param>)", bus.name, target);
+                }
+            }
+        }
+        
+        // Validate layer sends reference existing buses
+        for layer in &self.layers {
+            for (bus, level) in &layer.sends {
+                if !self.buses.iter().any(|b| &b.name == bus) {
+                    bail!("Layer '{}' sends to unknown bus '{}'", layer.name, bus);
+                }
+                if *level < 0.0 || *level > 1.0 {
+                    bail!("Layer '{}' send to '{}' must be between 0.0 and 1.0", layer.name, bus);
+                }
+            }
+        }
+        
         Ok(())
     }
 }
 
+/// Check a chain of effects: unique names, known parameters and files
+fn validate_effects(effects: &[EffectConfig]) -> Result<()> {
+    for (i, effect) in effects.iter().enumerate() {
+        if effects[..i].iter().any(|e| e.name() == effect.name()) {
+            bail!("two effects are named '{}' (give one a name)", effect.name());
+        }
+        let params = effect.kind.params();
+        for (name, value) in &effect.params {
+            value.resolve(params, name).with_context(|| format!("effect '{}'", effect.name()))?;
+        }
+        match (effect.kind, &effect.ir) {
+            (EffectKind::Convolution, None) => bail!("effect '{}' needs an ir (impulse response WAV file)", effect.name()),
+            (EffectKind::Convolution, Some(_)) => {}
+            (_, Some(_)) => bail!("effect '{}' takes no ir (only convolution does)", effect.name()),
+            (_, None) => {}
+        }
+        if let Some(sync) = &effect.sync {
+            if effect.kind != EffectKind::Delay {
+                bail!("effect '{}' takes no sync (only delay does)", effect.name());
+            }
+            NoteDivision::parse(sync).with_context(|| format!("effect '{}'", effect.name()))?;
+            if effect.params.contains_key("time") {
+                bail!("effect '{}' sets both time and sync", effect.name());
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Audio output configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AudioConfig {
@@ -68,10 +397,15 @@ pub struct AudioConfig {
     
     /// Output file path (for recording)
     pub output_file: Option<String>,
+    
+    /// Output channels: 1 for mono, 2 for stereo (default: 2)
+    #[serde(default = "default_channels")]
+    pub channels: u16,
 }
 
 fn default_sample_rate() -> u32 { 44100 }
 fn default_buffer_size() -> usize { 512 }
+fn default_channels() -> u16 { 2 }
 
 /// Master settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -91,12 +425,34 @@ pub struct MasterConfig {
     /// Master volume 0.0-1.0 (default: 0.7)
     #[serde(default = "default_volume")]
     pub volume: f32,
+    
+    /// Beats per bar for the transport (default: 4)
+    #[serde(default = "default_beats_per_bar")]
+    pub beats_per_bar: u32,
+    
+    /// Effects applied in order to the final mix, before the volume
+    #[serde(default)]
+    pub effects: Vec<EffectConfig>,
+}
+
+impl Default for MasterConfig {
+    fn default() -> Self {
+        Self {
+            bpm: default_bpm(),
+            key: default_key(),
+            scale: default_scale(),
+            volume: default_volume(),
+            beats_per_bar: default_beats_per_bar(),
+            effects: Vec::new(),
+        }
+    }
 }
 
 fn default_bpm() -> f32 { 60.0 }
 fn default_key() -> String { "C".to_string() }
 fn default_scale() -> String { "minor_pentatonic".to_string() }
 fn default_volume() -> f32 { 0.7 }
+fn default_beats_per_bar() -> u32 { 4 }
 
 /// Data source configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -107,53 +463,759 @@ pub struct SourceConfig {
     /// Source type
     pub kind: SourceKind,
     
-    /// Whether this source is enabled
-    #[serde(default = "default_enabled")]
-    pub enabled: bool,
+    /// Whether this source is enabled
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    
+    /// Source-specific settings
+    #[serde(default)]
+    pub settings: HashMap<String, serde_yaml::Value>,
+    
+    /// How numeric fields move between polls
+    #[serde(default)]
+    pub interpolation: InterpolationConfig,
+}
+
+fn default_enabled() -> bool { true }
+
+/// Interpolation of a source's fields between data points
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InterpolationConfig {
+    /// Mode for every numeric field (default: step)
+    #[serde(default)]
+    pub mode: InterpolationMode,
+    
+    /// Per-field modes (field_name -> mode)
+    #[serde(default)]
+    pub fields: HashMap<String, InterpolationMode>,
+    
+    /// Expected seconds between data points (default: the last measured gap)
+    pub interval: Option<f64>,
+}
+
+impl InterpolationConfig {
+    /// Get the mode for a field
+    pub fn mode_for(&self, field: &str) -> InterpolationMode {
+        self.fields.get(field).copied().unwrap_or(self.mode)
+    }
+}
+
+/// How a field moves from one data point to the next
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum InterpolationMode {
+    /// Jump to each new value (default)
+    #[default]
+    Step,
+    /// Straight line to the new value over one interval
+    Linear,
+    /// Eased (half-cosine) curve to the new value over one interval
+    Cosine,
+    /// Cubic curve that keeps the slope continuous between points
+    Spline,
+    /// Head toward where the trend predicts the next value will be
+    Extrapolate,
+}
+
+/// Types of data sources
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceKind {
+    /// Weather data from API
+    Weather,
+    /// Local system metrics
+    System,
+    /// Git repository events
+    Git,
+    /// Price data from API
+    Price,
+    /// A kind registered by a library user (see `sources::SourceRegistry`)
+    #[serde(untagged)]
+    Custom(String),
+}
+
+impl SourceKind {
+    /// Get the kind name as written in config files
+    pub fn name(&self) -> &str {
+        match self {
+            SourceKind::Weather => "weather",
+            SourceKind::System => "system",
+            SourceKind::Git => "git",
+            SourceKind::Price => "price",
+            SourceKind::Custom(name) => name,
+        }
+    }
+}
+
+/// Sound layer configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerConfig {
+    /// Unique name for this layer
+    pub name: String,
+    
+    /// Voice type for this layer
+    pub voice: VoiceKind,
+    
+    /// Name of the data source
+    pub source: String,
+    
+    /// Parameter mappings (parameter_name -> source_field)
+    #[serde(default)]
+    pub mappings: HashMap<String, MappingConfig>,
+    
+    /// Layer volume 0.0-1.0 (default: 1.0)
+    #[serde(default = "default_layer_volume")]
+    pub volume: f32,
+    
+    /// Send levels to buses (bus_name -> level 0.0-1.0)
+    #[serde(default)]
+    pub sends: HashMap<String, f32>,
+    
+    /// Stereo position from -1.0 (left) to 1.0 (right) (default: 0, centre)
+    #[serde(default)]
+    pub pan: f64,
+    
+    /// Stereo width: 0.0 folds to mono, 1.0 keeps the voice's spread, up to
+    /// 2.0 widens it (default: 1.0)
+    #[serde(default = "default_layer_width")]
+    pub width: f64,
+    
+    /// Source events that trigger this layer's voice (e.g. "commit")
+    #[serde(default)]
+    pub triggers: Vec<String>,
+    
+    /// Audio files used by sample-based voices (texture grains, sampler)
+    #[serde(default)]
+    pub samples: Vec<SampleConfig>,
+    
+    /// Wavetable for the drone voice: a built-in name or a path to a WAV file
+    #[serde(default)]
+    pub wavetable: Option<String>,
+    
+    /// Number of voices that can sound at once (default: 1)
+    #[serde(default = "default_polyphony")]
+    pub polyphony: usize,
+    
+    /// Which voice to take over when all are busy (default: oldest)
+    #[serde(default)]
+    pub steal: StealMode,
+    
+    /// Modulation sources and their routes to voice parameters
+    #[serde(default)]
+    pub modulation: ModulationConfig,
+    
+    /// How parameter changes glide to their new values
+    #[serde(default)]
+    pub smoothing: SmoothingConfig,
+    
+    /// Pitch glide time in seconds (default: 0, pitch follows `smoothing`)
+    #[serde(default)]
+    pub portamento: f64,
+    
+    /// Filter applied to the layer's output (default: none)
+    #[serde(default)]
+    pub filter: Option<FilterConfig>,
+    
+    /// Melody step length as a note division, e.g. "1/16" (default: 1/8)
+    #[serde(default)]
+    pub step: Option<String>,
+    
+    /// Reshapes the voice's amplitude envelope (default: the voice's own)
+    #[serde(default)]
+    pub envelope: Option<EnvelopeConfig>,
+    
+    /// Starting values for voice parameters (parameter -> number, option
+    /// name or on/off)
+    #[serde(default)]
+    pub patch: HashMap<String, PatchValue>,
+    
+    /// Effects on the layer's output, after the filter and before pan and
+    /// volume (parameters are `<effect>.<param>`)
+    #[serde(default)]
+    pub effects: Vec<EffectConfig>,
+}
+
+impl LayerConfig {
+    /// Create a layer config with default volume and no mappings or sends
+    pub fn new(name: impl Into<String>, voice: VoiceKind, source: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            voice,
+            source: source.into(),
+            mappings: HashMap::new(),
+            volume: default_layer_volume(),
+            sends: HashMap::new(),
+            pan: 0.0,
+            width: default_layer_width(),
+            triggers: Vec::new(),
+            samples: Vec::new(),
+            wavetable: None,
+            polyphony: default_polyphony(),
+            steal: StealMode::default(),
+            modulation: ModulationConfig::default(),
+            smoothing: SmoothingConfig::default(),
+            portamento: 0.0,
+            filter: None,
+            step: None,
+            envelope: None,
+            patch: HashMap::new(),
+            effects: Vec::new(),
+        }
+    }
+}
+
+fn default_layer_volume() -> f32 { 1.0 }
+
+fn default_layer_width() -> f64 { 1.0 }
+
+fn default_polyphony() -> usize { 1 }
+
+/// A patch value: a number, a named option (`model: snare`) or a switch
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum PatchValue {
+    Number(f64),
+    Switch(bool),
+    Option(String),
+}
+
+impl PatchValue {
+    /// Resolve to a value for one of a voice's parameters
+    ///
+    /// Numbers must lie in the parameter's range, option names must be
+    /// among its options, and switches only fit on/off parameters.
+    pub fn resolve(&self, params: &[ParamSpec], name: &str) -> Result<f64> {
+        let Some(spec) = find_param(params, name) else {
+            let names: Vec<&str> = params.iter().map(|p| p.name).collect();
+            bail!("unknown parameter '{}' (available: {})", name, names.join(", "));
+        };
+        match self {
+            PatchValue::Number(value) => {
+                if !(spec.min..=spec.max).contains(value) {
+                    bail!("'{}' must be between {} and {}", name, spec.min, spec.max);
+                }
+                Ok(*value)
+            }
+            PatchValue::Switch(on) => {
+                if spec.kind != ParamKind::Stepped || spec.min != 0.0 || spec.max != 1.0 {
+                    bail!("'{}' is not an on/off parameter", name);
+                }
+                Ok(if *on { 1.0 } else { 0.0 })
+            }
+            PatchValue::Option(option) => {
+                if spec.options.is_empty() {
+                    bail!("'{}' takes a number, not '{}'", name, option);
+                }
+                spec.option_value(option).ok_or_else(|| {
+                    anyhow::anyhow!("'{}' has no option '{}' (options: {})", name, option, spec.options.join(", "))
+                })
+            }
+        }
+    }
+}
+
+/// Maximum voices per layer
+pub const MAX_POLYPHONY: usize = 32;
+
+/// Voice stealing strategy for polyphonic layers
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StealMode {
+    /// Take the voice that started longest ago
+    #[default]
+    Oldest,
+    /// Take the voice with the lowest output level
+    Quietest,
+}
+
+/// Layer output filter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterConfig {
+    /// Response (default: lowpass)
+    #[serde(default, rename = "type")]
+    pub kind: FilterKind,
+    
+    /// Circuit (default: biquad)
+    #[serde(default)]
+    pub model: FilterModelKind,
+    
+    /// Cutoff or center frequency in Hz (default: 1000)
+    #[serde(default = "default_filter_cutoff")]
+    pub cutoff: f64,
+    
+    /// Resonance as a Q factor (default: 0.707)
+    #[serde(default = "default_filter_resonance")]
+    pub resonance: f64,
+    
+    /// Boost or cut in dB for peaking and shelf filters (default: 0)
+    #[serde(default)]
+    pub gain: f64,
+    
+    /// Ladder input drive, 1 is clean (default: 1)
+    #[serde(default = "default_filter_drive")]
+    pub drive: f64,
+}
+
+fn default_filter_cutoff() -> f64 { 1000.0 }
+
+fn default_filter_resonance() -> f64 { 0.707 }
+
+fn default_filter_drive() -> f64 { 1.0 }
+
+/// Filter responses
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterKind {
+    #[default]
+    Lowpass,
+    Highpass,
+    Bandpass,
+    Notch,
+    Peaking,
+    LowShelf,
+    HighShelf,
+    Allpass,
+}
+
+/// Filter circuits
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterModelKind {
+    /// RBJ biquad
+    #[default]
+    Biquad,
+    /// State-variable filter (smooth under fast modulation)
+    Svf,
+    /// Moog-style ladder with drive (lowpass, highpass and bandpass only)
+    Ladder,
+}
+
+/// Check if a wavetable setting names a WAV file rather than a built-in table
+fn is_wav_path(value: &str) -> bool {
+    value.to_lowercase().ends_with(".wav")
+}
+
+/// Parameter smoothing for a layer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmoothingConfig {
+    /// Glide time in seconds for continuous parameters (default: 0.02, 0 disables)
+    #[serde(default = "default_smoothing_time")]
+    pub time: f64,
+    
+    /// Glide shape (default: exponential)
+    #[serde(default)]
+    pub curve: CurveKind,
+    
+    /// Per-parameter overrides (parameter_name -> settings)
+    #[serde(default)]
+    pub params: HashMap<String, ParamSmoothingConfig>,
+}
+
+impl Default for SmoothingConfig {
+    fn default() -> Self {
+        Self {
+            time: default_smoothing_time(),
+            curve: CurveKind::default(),
+            params: HashMap::new(),
+        }
+    }
+}
+
+fn default_smoothing_time() -> f64 { 0.02 }
+
+/// Smoothing override for one parameter
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ParamSmoothingConfig {
+    /// Glide time in seconds (default: the layer's)
+    pub time: Option<f64>,
+    
+    /// Glide shape (default: the layer's)
+    pub curve: Option<CurveKind>,
+}
+
+/// Shapes of parameter glides
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CurveKind {
+    /// Constant rate
+    Linear,
+    /// Fast start, slow finish (default)
+    #[default]
+    Exponential,
+}
+
+/// Modulation matrix for a layer
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModulationConfig {
+    /// Named modulation sources
+    #[serde(default)]
+    pub sources: HashMap<String, ModSourceConfig>,
+    
+    /// Routes from sources to voice parameters
+    #[serde(default)]
+    pub routes: Vec<ModRouteConfig>,
+}
+
+/// A modulation source
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModSourceConfig {
+    /// Source type
+    pub kind: ModSourceKind,
+    
+    /// Rate in Hz for lfo and random sources (default: 0.5)
+    #[serde(default = "default_mod_rate")]
+    pub rate: f64,
+    
+    /// Tempo-synced length of one cycle for lfo and random sources, as a
+    /// note division like "1/4", "1/8T" or "2 bars" (replaces `rate`)
+    #[serde(default)]
+    pub sync: Option<String>,
+    
+    /// LFO shape: sine, triangle, saw, square, sample_and_hold,
+    /// smooth_random, drift (default: sine)
+    #[serde(default = "default_mod_shape")]
+    pub shape: String,
+    
+    /// LFO phase offset as part of a cycle, 0.0-1.0 (default: 0)
+    #[serde(default)]
+    pub phase: f64,
+    
+    /// Seconds for an LFO to fade in, restarted by each trigger (default: 0)
+    #[serde(default)]
+    pub fade_in: f64,
+    
+    /// Restart the LFO cycle on each note or trigger event (default: false)
+    #[serde(default)]
+    pub retrigger: bool,
+    
+    /// LFO output range: bipolar (-1 to 1, default) or unipolar (0 to 1)
+    #[serde(default)]
+    pub polarity: Option<ModPolarity>,
+    
+    /// Another source in the matrix that sweeps the LFO rate from `rate`
+    /// up to `rate_max`
+    #[serde(default)]
+    pub rate_source: Option<String>,
+    
+    /// Highest LFO rate in Hz reached through `rate_source`
+    #[serde(default)]
+    pub rate_max: Option<f64>,
+    
+    /// Glide between random values instead of stepping (default: true)
+    #[serde(default = "default_true")]
+    pub smooth: bool,
+    
+    /// Envelope attack in seconds
+    #[serde(default = "default_mod_attack")]
+    pub attack: f64,
+    
+    /// Envelope decay in seconds
+    #[serde(default = "default_mod_decay")]
+    pub decay: f64,
+    
+    /// Envelope sustain level 0.0-1.0
+    #[serde(default = "default_mod_sustain")]
+    pub sustain: f64,
+    
+    /// Envelope release in seconds
+    #[serde(default = "default_mod_release")]
+    pub release: f64,
+    
+    /// Envelope delay, hold, curves, looping and breakpoints
+    #[serde(flatten)]
+    pub envelope: EnvelopeShapeConfig,
+    
+    /// Source field for data sources
+    pub field: Option<String>,
+    
+    /// Input range minimum for data sources (default: 0)
+    pub in_min: Option<f64>,
+    
+    /// Input range maximum for data sources (default: 100)
+    pub in_max: Option<f64>,
+}
+
+fn default_mod_rate() -> f64 { 0.5 }
+fn default_mod_shape() -> String { "sine".to_string() }
+fn default_true() -> bool { true }
+fn default_mod_attack() -> f64 { 0.5 }
+fn default_mod_decay() -> f64 { 0.5 }
+fn default_mod_sustain() -> f64 { 0.7 }
+fn default_mod_release() -> f64 { 1.0 }
+
+/// A voice's amplitude envelope (unset times keep the voice's own)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnvelopeConfig {
+    /// Attack time in seconds
+    pub attack: Option<f64>,
+    
+    /// Decay time in seconds
+    pub decay: Option<f64>,
+    
+    /// Sustain level 0.0-1.0
+    pub sustain: Option<f64>,
+    
+    /// Release time in seconds
+    pub release: Option<f64>,
+    
+    /// Delay, hold, curves, looping and breakpoints
+    #[serde(flatten)]
+    pub shape: EnvelopeShapeConfig,
+}
+
+/// Envelope settings beyond ADSR times
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnvelopeShapeConfig {
+    /// Seconds before the attack starts (default: 0)
+    #[serde(default)]
+    pub delay: f64,
+    
+    /// Seconds the peak is held before the decay (default: 0)
+    #[serde(default)]
+    pub hold: f64,
+    
+    /// Curve for every segment (default: linear)
+    #[serde(default)]
+    pub curve: EnvelopeCurveKind,
+    
+    /// Attack curve (default: `curve`)
+    pub attack_curve: Option<EnvelopeCurveKind>,
+    
+    /// Decay curve (default: `curve`)
+    pub decay_curve: Option<EnvelopeCurveKind>,
+    
+    /// Release curve (default: `curve`)
+    pub release_curve: Option<EnvelopeCurveKind>,
+    
+    /// Repeat until released (default: false)
+    #[serde(default, rename = "loop")]
+    pub looping: bool,
+    
+    /// Breakpoints to follow instead of ADSR
+    #[serde(default)]
+    pub points: Vec<BreakpointConfig>,
+    
+    /// Index of the breakpoint held while the note is on (default: none, one-shot)
+    pub sustain_point: Option<usize>,
+}
+
+/// One breakpoint of a multi-stage envelope
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BreakpointConfig {
+    /// Level to reach 0.0-1.0
+    pub level: f64,
+    
+    /// Seconds to reach it
+    pub time: f64,
+    
+    /// Segment curve (default: the envelope's `curve`)
+    pub curve: Option<EnvelopeCurveKind>,
+}
+
+/// Shapes of envelope segments
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvelopeCurveKind {
+    /// Constant rate
+    #[default]
+    Linear,
+    /// Fast at first, easing into the target (natural decays)
+    Exponential,
+    /// Slow at first, speeding up toward the target (swells)
+    Logarithmic,
+}
+
+/// Check envelope settings, naming `owner` in errors
+fn validate_envelope(owner: &str, times: &[f64], sustain: Option<f64>, shape: &EnvelopeShapeConfig) -> Result<()> {
+    let point_times = shape.points.iter().map(|p| p.time);
+    let mut all_times = times.iter().copied().chain([shape.delay, shape.hold]).chain(point_times);
+    if all_times.any(|t| !(0.0..=60.0).contains(&t)) {
+        bail!("{} envelope times must be between 0 and 60 seconds", owner);
+    }
+    let mut levels = sustain.into_iter().chain(shape.points.iter().map(|p| p.level));
+    if levels.any(|l| !(0.0..=1.0).contains(&l)) {
+        bail!("{} envelope levels must be between 0.0 and 1.0", owner);
+    }
+    if let Some(point) = shape.sustain_point {
+        if point >= shape.points.len() {
+            bail!("{} envelope sustain_point {} is past the last breakpoint", owner, point);
+        }
+    }
+    Ok(())
+}
+
+/// Types of modulation sources
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModSourceKind {
+    /// Low frequency oscillator (-1 to 1)
+    Lfo,
+    /// ADSR started by each note (0 to 1)
+    Envelope,
+    /// A source field scaled from its input range (0 to 1)
+    Data,
+    /// Random values at a rate (-1 to 1)
+    Random,
+}
+
+/// A route from a modulation source to a parameter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModRouteConfig {
+    /// Source name
+    pub source: String,
+    
+    /// Voice parameter to modulate
+    pub target: String,
+    
+    /// Amount in parameter units at full source output
+    pub depth: f64,
+    
+    /// Range the source is mapped into (default: the source's own)
+    #[serde(default)]
+    pub polarity: Option<ModPolarity>,
+}
+
+/// Range a modulation route maps its source into
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModPolarity {
+    /// -1.0 to 1.0
+    Bipolar,
+    /// 0.0 to 1.0
+    Unipolar,
+}
+
+/// Audio file used by a layer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SampleConfig {
+    /// Path to a WAV file (relative paths are resolved against the config file)
+    pub path: PathBuf,
+    
+    /// Source events that play this sample (sampler voice)
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+/// Send bus configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BusConfig {
+    /// Unique name for this bus (layers send to it by name)
+    pub name: String,
+    
+    /// Bus volume 0.0-1.0 (default: 1.0)
+    #[serde(default = "default_layer_volume")]
+    pub volume: f32,
+    
+    /// Effects applied in order to the summed bus signal
+    #[serde(default)]
+    pub effects: Vec<EffectConfig>,
     
-    /// Source-specific settings
+    /// Data source for the bus mappings
     #[serde(default)]
-    pub settings: HashMap<String, serde_yaml::Value>,
+    pub source: Option<String>,
+    
+    /// Effect parameter mappings (`<effect>.<param>` -> source_field)
+    #[serde(default)]
+    pub mappings: HashMap<String, MappingConfig>,
 }
 
-fn default_enabled() -> bool { true }
-
-/// Types of data sources
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-#[serde(rename_all = "snake_case")]
-pub enum SourceKind {
-    /// Weather data from API
-    Weather,
-    /// Local system metrics
-    System,
-    /// Git repository events
-    Git,
-    /// Price data from API
-    Price,
+impl BusConfig {
+    /// Create a bus config with no effects
+    pub fn new(name: impl Into<String>, volume: f32) -> Self {
+        Self {
+            name: name.into(),
+            volume,
+            effects: Vec::new(),
+            source: None,
+            mappings: HashMap::new(),
+        }
+    }
 }
 
-/// Sound layer configuration
+/// An effect on a bus or layer
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LayerConfig {
-    /// Unique name for this layer
-    pub name: String,
+pub struct EffectConfig {
+    /// Effect type
+    pub kind: EffectKind,
     
-    /// Voice type for this layer
-    pub voice: VoiceKind,
+    /// Name used to address the effect's parameters (default: the kind)
+    #[serde(default)]
+    pub name: Option<String>,
     
-    /// Name of the data source
-    pub source: String,
+    /// Impulse response WAV file (convolution)
+    #[serde(default)]
+    pub ir: Option<PathBuf>,
     
-    /// Parameter mappings (parameter_name -> source_field)
+    /// Tempo-synced delay time as a note division like "1/8." (delay)
     #[serde(default)]
-    pub mappings: HashMap<String, MappingConfig>,
+    pub sync: Option<String>,
     
-    /// Layer volume 0.0-1.0 (default: 1.0)
-    #[serde(default = "default_layer_volume")]
-    pub volume: f32,
+    /// Starting values for the effect's parameters
+    #[serde(flatten)]
+    pub params: HashMap<String, PatchValue>,
 }
 
-fn default_layer_volume() -> f32 { 1.0 }
+impl EffectConfig {
+    /// Create an effect config with default parameters
+    pub fn new(kind: EffectKind) -> Self {
+        Self {
+            kind,
+            name: None,
+            ir: None,
+            sync: None,
+            params: HashMap::new(),
+        }
+    }
+
+    /// Get the name that addresses this effect's parameters
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(self.kind.name())
+    }
+}
+
+/// Types of effects
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EffectKind {
+    /// Feedback delay network reverb
+    Reverb,
+    /// Impulse response reverb (needs `ir`)
+    Convolution,
+    /// Stereo delay with filtered feedback
+    Delay,
+    /// Multi-voice chorus
+    Chorus,
+    /// Swept comb filter
+    Flanger,
+    /// Swept allpass notches
+    Phaser,
+}
+
+impl EffectKind {
+    /// Get the kind name as written in config files
+    pub fn name(&self) -> &'static str {
+        match self {
+            EffectKind::Reverb => "reverb",
+            EffectKind::Convolution => "convolution",
+            EffectKind::Delay => "delay",
+            EffectKind::Chorus => "chorus",
+            EffectKind::Flanger => "flanger",
+            EffectKind::Phaser => "phaser",
+        }
+    }
+
+    /// Get the parameters this kind of effect accepts
+    pub fn params(&self) -> &'static [ParamSpec] {
+        match self {
+            EffectKind::Reverb => Reverb::PARAMS,
+            EffectKind::Convolution => Convolver::PARAMS,
+            EffectKind::Delay => Delay::PARAMS,
+            EffectKind::Chorus => Chorus::PARAMS,
+            EffectKind::Flanger => Flanger::PARAMS,
+            EffectKind::Phaser => Phaser::PARAMS,
+        }
+    }
+}
 
 /// Types of voices (sound generators)
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -167,6 +1229,24 @@ pub enum VoiceKind {
     Melody,
     /// Noise and grain
     Texture,
+    /// Sample playback
+    Sampler,
+    /// Four-operator FM
+    Fm,
+}
+
+impl VoiceKind {
+    /// Get the parameters this kind of voice accepts
+    pub fn params(&self) -> &'static [ParamSpec] {
+        match self {
+            VoiceKind::Drone => DroneVoice::PARAMS,
+            VoiceKind::Percussion => PercussionVoice::PARAMS,
+            VoiceKind::Melody => MelodyVoice::PARAMS,
+            VoiceKind::Texture => TextureVoice::PARAMS,
+            VoiceKind::Sampler => SamplerVoice::PARAMS,
+            VoiceKind::Fm => FmVoice::PARAMS,
+        }
+    }
 }
 
 /// Mapping configuration for a parameter
@@ -237,6 +1317,18 @@ settings:
         assert!(config.enabled);
     }
 
+    #[test]
+    fn test_custom_source_kind() {
+        let yaml = r#"
+name: sensor
+kind: air_quality
+"#;
+        let config: SourceConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.kind, SourceKind::Custom("air_quality".to_string()));
+        assert_eq!(config.kind.name(), "air_quality");
+        assert_eq!(SourceKind::Git.name(), "git");
+    }
+
     #[test]
     fn test_layer_config() {
         let yaml = r#"
@@ -258,6 +1350,20 @@ mappings:
         assert_eq!(config.voice, VoiceKind::Drone);
         assert_eq!(config.volume, 0.8);
         assert!(config.mappings.contains_key("pitch"));
+        assert!(config.triggers.is_empty());
+    }
+
+    #[test]
+    fn test_layer_triggers() {
+        let yaml = r#"
+name: commits
+voice: percussion
+source: git
+triggers: [commit, branch_change]
+"#;
+        let config: LayerConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.voice, VoiceKind::Percussion);
+        assert_eq!(config.triggers, vec!["commit", "branch_change"]);
     }
 
     #[test]
@@ -268,12 +1374,15 @@ mappings:
                 buffer_size: 512,
                 device: None,
                 output_file: None,
+                channels: 2,
             },
             master: MasterConfig {
                 bpm: 60.0,
                 key: "C".to_string(),
                 scale: "minor_pentatonic".to_string(),
                 volume: 0.7,
+                beats_per_bar: 4,
+                effects: Vec::new(),
             },
             sources: vec![
                 SourceConfig {
@@ -281,17 +1390,13 @@ mappings:
                     kind: SourceKind::Weather,
                     enabled: true,
                     settings: HashMap::new(),
+                    interpolation: InterpolationConfig::default(),
                 }
             ],
             layers: vec![
-                LayerConfig {
-                    name: "drone".to_string(),
-                    voice: VoiceKind::Drone,
-                    source: "weather".to_string(),
-                    mappings: HashMap::new(),
-                    volume: 1.0,
-                }
+                LayerConfig::new("drone", VoiceKind::Drone, "weather")
             ],
+            buses: vec![],
         };
         
         assert!(config.validate().is_ok());
@@ -305,25 +1410,531 @@ mappings:
                 buffer_size: 512,
                 device: None,
                 output_file: None,
+                channels: 2,
             },
             master: MasterConfig {
                 bpm: 60.0,
                 key: "C".to_string(),
                 scale: "minor_pentatonic".to_string(),
                 volume: 0.7,
+                beats_per_bar: 4,
+                effects: Vec::new(),
             },
             sources: vec![],
             layers: vec![
-                LayerConfig {
-                    name: "drone".to_string(),
-                    voice: VoiceKind::Drone,
-                    source: "nonexistent".to_string(),
-                    mappings: HashMap::new(),
-                    volume: 1.0,
-                }
+                LayerConfig::new("drone", VoiceKind::Drone, "nonexistent")
             ],
+            buses: vec![],
         };
         
         assert!(config.validate().is_err());
     }
+
+    fn config_with_bus(send_to: &str) -> DriftConfig {
+        let yaml = format!(r#"
+audio:
+  sample_rate: 44100
+master:
+  volume: 0.7
+sources:
+  - name: system
+    kind: system
+buses:
+  - name: reverb
+    volume: 0.5
+layers:
+  - name: drone
+    voice: drone
+    source: system
+    sends:
+      {}: 0.4
+"#, send_to);
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    #[test]
+    fn test_bus_config() {
+        let config = config_with_bus("reverb");
+        assert_eq!(config.buses.len(), 1);
+        assert_eq!(config.buses[0].volume, 0.5);
+        assert_eq!(config.layers[0].sends.get("reverb"), Some(&0.4));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_layer_send() {
+        let config = config_with_bus("delay");
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("unknown bus 'delay'"));
+    }
+
+    #[test]
+    fn test_reserved_bus_name() {
+        let mut config = config_with_bus("reverb");
+        config.buses.push(BusConfig::new("master", 1.0));
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_key_and_scale() {
+        let mut config = config_with_bus("reverb");
+        config.master.key = "F#".to_string();
+        assert!(config.validate().is_ok());
+
+        config.master.key = "X".to_string();
+        assert!(config.validate().is_err());
+
+        config.master.key = "C".to_string();
+        config.master.scale = "lydian_augmented".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_sampler_needs_samples() {
+        let mut config = config_with_bus("reverb");
+        let mut layer = LayerConfig::new("hits", VoiceKind::Sampler, "system");
+        config.layers.push(layer.clone());
+        assert!(config.validate().is_err());
+
+        layer.samples.push(SampleConfig {
+            path: "commit.wav".into(),
+            events: vec!["commit".to_string()],
+        });
+        config.layers[1] = layer;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_layer_wavetable() {
+        let mut config = config_with_bus("reverb");
+        config.layers[0].wavetable = Some("harmonics".to_string());
+        assert!(config.validate().is_ok());
+
+        config.layers[0].wavetable = Some("tables/vowels.WAV".to_string());
+        assert!(config.validate().is_ok());
+        config.resolve_paths(Path::new("/patches"));
+        assert_eq!(config.layers[0].wavetable.as_deref(), Some("/patches/tables/vowels.WAV"));
+
+        config.layers[0].wavetable = Some("vowels".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_layer_modulation() {
+        let mut config = config_with_bus("reverb");
+        config.layers[0].modulation = serde_yaml::from_str(
+            r#"
+sources:
+  sweep: {kind: lfo, rate: 0.1, shape: saw}
+  load: {kind: data, field: cpu_usage}
+routes:
+  - {source: sweep, target: filter, depth: 800, polarity: unipolar}
+  - {source: load, target: pitch, depth: 20}
+"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        let route = &config.layers[0].modulation.routes[0];
+        assert_eq!(route.polarity, Some(ModPolarity::Unipolar));
+
+        config.layers[0].modulation.routes[1].source = "missing".to_string();
+        assert!(config.validate().is_err());
+        config.layers[0].modulation.routes[1].source = "load".to_string();
+
+        config.layers[0].modulation.sources.get_mut("load").unwrap().field = None;
+        assert!(config.validate().is_err());
+        config.layers[0].modulation.sources.get_mut("load").unwrap().field = Some("cpu_usage".to_string());
+
+        config.layers[0].modulation.sources.get_mut("sweep").unwrap().shape = "wobbly".to_string();
+        assert!(config.validate().is_err());
+        config.layers[0].modulation.sources.get_mut("sweep").unwrap().shape = "saw".to_string();
+
+        // Targets must be parameters of the voice (aliases included)
+        config.layers[0].modulation.routes[0].target = "cutoff".to_string();
+        assert!(config.validate().is_ok());
+        config.layers[0].modulation.routes[0].target = "typo".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_layer_patch() {
+        let mut config = config_with_bus("reverb");
+        config.layers[0].voice = VoiceKind::Percussion;
+        config.layers[0].patch = serde_yaml::from_str("{model: snare, decay: 0.2, tone: 0.6}").unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.layers[0].patch["model"], PatchValue::Option("snare".to_string()));
+        assert_eq!(config.layers[0].patch["model"].resolve(VoiceKind::Percussion.params(), "model").unwrap(), 1.0);
+
+        for bad in ["{model: cowbell}", "{decay: 50}", "{waveform: saw}", "{tone: bright}", "{tone: true}"] {
+            config.layers[0].patch = serde_yaml::from_str(bad).unwrap();
+            assert!(config.validate().is_err(), "{}", bad);
+        }
+
+        // Patches follow the layer's voice
+        config.layers[0].voice = VoiceKind::Sampler;
+        config.layers[0].samples = vec![SampleConfig {
+            path: PathBuf::from("hit.wav"),
+            events: Vec::new(),
+        }];
+        config.layers[0].patch = serde_yaml::from_str("{loop: true, rate: 0.5}").unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_lfo_options() {
+        let mut config = config_with_bus("reverb");
+        config.layers[0].modulation = serde_yaml::from_str(
+            r#"
+sources:
+  wind: {kind: data, field: wind_speed, in_max: 20}
+  sway:
+    kind: lfo
+    shape: drift
+    rate: 0.05
+    rate_source: wind
+    rate_max: 0.5
+    phase: 0.25
+    fade_in: 10
+    retrigger: true
+    polarity: unipolar
+routes:
+  - {source: sway, target: filter, depth: 800}
+"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        let sway = &config.layers[0].modulation.sources["sway"];
+        assert_eq!(sway.polarity, Some(ModPolarity::Unipolar));
+        assert_eq!(sway.fade_in, 10.0);
+
+        let sources = &mut config.layers[0].modulation.sources;
+        sources.get_mut("sway").unwrap().rate_max = None;
+        assert!(config.validate().is_err());
+
+        let sources = &mut config.layers[0].modulation.sources;
+        sources.get_mut("sway").unwrap().rate_max = Some(0.5);
+        sources.get_mut("sway").unwrap().rate_source = Some("gusts".to_string());
+        assert!(config.validate().is_err());
+
+        let sources = &mut config.layers[0].modulation.sources;
+        sources.get_mut("sway").unwrap().rate_source = None;
+        sources.get_mut("sway").unwrap().phase = 1.5;
+        assert!(config.validate().is_err());
+
+        let sources = &mut config.layers[0].modulation.sources;
+        sources.get_mut("sway").unwrap().phase = 0.0;
+        sources.get_mut("wind").unwrap().retrigger = true;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_source_interpolation() {
+        let yaml = r#"
+name: weather
+kind: weather
+interpolation:
+  mode: cosine
+  fields:
+    wind_speed: extrapolate
+    condition_code: step
+"#;
+        let source: SourceConfig = serde_yaml::from_str(yaml).unwrap();
+        let interpolation = &source.interpolation;
+        assert_eq!(interpolation.mode_for("temperature"), InterpolationMode::Cosine);
+        assert_eq!(interpolation.mode_for("wind_speed"), InterpolationMode::Extrapolate);
+        assert_eq!(interpolation.mode_for("condition_code"), InterpolationMode::Step);
+        assert_eq!(interpolation.interval, None);
+
+        let mut config = config_with_bus("reverb");
+        assert_eq!(config.sources[0].interpolation.mode, InterpolationMode::Step);
+        config.sources[0].interpolation.interval = Some(0.0);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_layer_filter() {
+        let mut config = config_with_bus("reverb");
+        assert!(config.layers[0].filter.is_none());
+
+        let yaml = "type: low_shelf\nmodel: svf\ncutoff: 200\ngain: -6\n";
+        let filter: FilterConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(filter.kind, FilterKind::LowShelf);
+        assert_eq!(filter.model, FilterModelKind::Svf);
+        assert_eq!(filter.resonance, 0.707);
+        assert_eq!(filter.drive, 1.0);
+        config.layers[0].filter = Some(filter.clone());
+        assert!(config.validate().is_ok());
+
+        config.layers[0].filter = Some(FilterConfig { gain: 30.0, ..filter.clone() });
+        assert!(config.validate().is_err());
+
+        // The ladder has no shelf response
+        config.layers[0].filter = Some(FilterConfig { model: FilterModelKind::Ladder, ..filter });
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_tempo_sync() {
+        let mut config = config_with_bus("reverb");
+        assert_eq!(config.master.beats_per_bar, 4);
+
+        let yaml = "sources:\n  wobble:\n    kind: lfo\n    sync: 1/8T\nroutes: []\n";
+        config.layers[0].modulation = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate().is_ok());
+        config.layers[0].modulation.sources.get_mut("wobble").unwrap().sync = Some("1/7x".to_string());
+        assert!(config.validate().is_err());
+        config.layers[0].modulation = ModulationConfig::default();
+
+        // Steps belong to the melody sequencer
+        config.layers[0].step = Some("1/16".to_string());
+        assert!(config.validate().is_err());
+        config.layers[0].voice = VoiceKind::Melody;
+        assert!(config.validate().is_ok());
+        config.layers[0].step = Some("2 bars".to_string());
+        assert!(config.validate().is_err());
+
+        config.layers[0].step = None;
+        config.master.beats_per_bar = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_envelopes() {
+        let mut config = config_with_bus("reverb");
+        let yaml = r#"
+attack: 2.0
+release_curve: exponential
+loop: true
+points:
+  - {level: 1.0, time: 0.5, curve: logarithmic}
+  - {level: 0.4, time: 1.0}
+sustain_point: 1
+"#;
+        let envelope: EnvelopeConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(envelope.attack, Some(2.0));
+        assert_eq!(envelope.decay, None);
+        assert_eq!(envelope.shape.curve, EnvelopeCurveKind::Linear);
+        assert_eq!(envelope.shape.release_curve, Some(EnvelopeCurveKind::Exponential));
+        assert!(envelope.shape.looping);
+        assert_eq!(envelope.shape.points[0].curve, Some(EnvelopeCurveKind::Logarithmic));
+        config.layers[0].envelope = Some(envelope.clone());
+        assert!(config.validate().is_ok());
+
+        let mut bad = envelope.clone();
+        bad.shape.sustain_point = Some(2);
+        config.layers[0].envelope = Some(bad);
+        assert!(config.validate().is_err());
+
+        config.layers[0].voice = VoiceKind::Fm;
+        config.layers[0].envelope = Some(envelope);
+        assert!(config.validate().is_err());
+
+        // Modulation envelopes take the same shape settings
+        let yaml = "sources:\n  swell: {kind: envelope, hold: 2, curve: exponential}\nroutes: []\n";
+        let modulation: ModulationConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(modulation.sources["swell"].envelope.hold, 2.0);
+        assert_eq!(modulation.sources["swell"].envelope.curve, EnvelopeCurveKind::Exponential);
+    }
+
+    #[test]
+    fn test_layer_smoothing() {
+        let yaml = r#"
+name: pad
+voice: drone
+source: weather
+smoothing:
+  time: 0.1
+  curve: linear
+  params:
+    filter: {time: 2.0}
+portamento: 0.3
+"#;
+        let layer: LayerConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(layer.smoothing.time, 0.1);
+        assert_eq!(layer.smoothing.curve, CurveKind::Linear);
+        assert_eq!(layer.smoothing.params["filter"].time, Some(2.0));
+        assert_eq!(layer.smoothing.params["filter"].curve, None);
+        assert_eq!(layer.portamento, 0.3);
+
+        let defaults = LayerConfig::new("a", VoiceKind::Drone, "b");
+        assert_eq!(defaults.smoothing.time, 0.02);
+        assert_eq!(defaults.smoothing.curve, CurveKind::Exponential);
+
+        let mut config = config_with_bus("reverb");
+        config.layers[0].smoothing.time = -1.0;
+        assert!(config.validate().is_err());
+        config.layers[0].smoothing.time = 0.05;
+        config.layers[0].portamento = 11.0;
+        assert!(config.validate().is_err());
+        config.layers[0].portamento = 0.0;
+
+        // Per-parameter settings must name parameters of the voice
+        config.layers[0].smoothing.params = layer.smoothing.params.clone();
+        assert!(config.validate().is_ok());
+        config.layers[0].smoothing.params = serde_yaml::from_str("{cuttoff: {time: 2.0}}").unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_layer_polyphony() {
+        let yaml = r#"
+name: ticks
+voice: fm
+source: price
+polyphony: 6
+steal: quietest
+"#;
+        let layer: LayerConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(layer.polyphony, 6);
+        assert_eq!(layer.steal, StealMode::Quietest);
+
+        let layer: LayerConfig = serde_yaml::from_str("{name: a, voice: drone, source: b}").unwrap();
+        assert_eq!(layer.polyphony, 1);
+        assert_eq!(layer.steal, StealMode::Oldest);
+
+        let mut config = config_with_bus("reverb");
+        config.layers[0].polyphony = 0;
+        assert!(config.validate().is_err());
+        config.layers[0].polyphony = MAX_POLYPHONY + 1;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_stereo_settings() {
+        let layer: LayerConfig = serde_yaml::from_str("{name: a, voice: drone, source: b, pan: -0.5}").unwrap();
+        assert_eq!(layer.pan, -0.5);
+        assert_eq!(layer.width, 1.0);
+        let audio: AudioConfig = serde_yaml::from_str("sample_rate: 48000").unwrap();
+        assert_eq!(audio.channels, 2);
+
+        let mut config = config_with_bus("reverb");
+        config.layers[0].pan = 1.5;
+        assert!(config.validate().is_err());
+        config.layers[0].pan = 1.0;
+        config.layers[0].width = 2.5;
+        assert!(config.validate().is_err());
+        config.layers[0].width = 0.0;
+        assert!(config.validate().is_ok());
+        config.audio.channels = 6;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_bus_effects() {
+        let yaml = r#"
+name: space
+volume: 0.6
+source: system
+effects:
+  - kind: reverb
+    size: 0.9
+    decay: 8
+  - kind: reverb
+    name: room
+mappings:
+  reverb.size:
+    field: cpu
+"#;
+        let bus: BusConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(bus.effects[0].kind, EffectKind::Reverb);
+        assert_eq!(bus.effects[0].name(), "reverb");
+        assert_eq!(bus.effects[0].params["decay"], PatchValue::Number(8.0));
+        assert_eq!(bus.effects[1].name(), "room");
+
+        let mut config = config_with_bus("reverb");
+        config.buses[0] = bus.clone();
+        config.layers[0].sends.clear();
+        assert!(config.validate().is_ok());
+
+        // Effect names must be unique within a bus
+        config.buses[0].effects[1].name = None;
+        assert!(config.validate().is_err());
+        config.buses[0].effects.pop();
+
+        config.buses[0].effects[0].params.insert("size".to_string(), PatchValue::Number(2.0));
+        assert!(config.validate().is_err());
+        config.buses[0] = bus.clone();
+
+        config.buses[0].mappings = serde_yaml::from_str("{reverb.colour: {field: cpu}}").unwrap();
+        assert!(config.validate().is_err());
+        config.buses[0] = BusConfig { source: None, ..bus };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_convolution_effect() {
+        let yaml = r#"
+volume: 0.7
+effects:
+  - kind: convolution
+    ir: irs/hall.wav
+    mix: 0.3
+"#;
+        let master: MasterConfig = serde_yaml::from_str(yaml).unwrap();
+        let effect = &master.effects[0];
+        assert_eq!(effect.kind, EffectKind::Convolution);
+        assert_eq!(effect.ir.as_deref(), Some(Path::new("irs/hall.wav")));
+        assert!(!effect.params.contains_key("ir"));
+
+        let mut config = config_with_bus("reverb");
+        config.master = master;
+        assert!(config.validate().is_ok());
+        config.resolve_paths(Path::new("/spaces"));
+        assert_eq!(config.master.effects[0].ir.as_deref(), Some(Path::new("/spaces/irs/hall.wav")));
+
+        // Convolution needs an IR, and only convolution takes one
+        config.master.effects[0].ir = None;
+        assert!(config.validate().is_err());
+        let mut reverb = EffectConfig::new(EffectKind::Reverb);
+        reverb.ir = Some(PathBuf::from("hall.wav"));
+        config.master.effects = vec![reverb];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_delay_effect() {
+        let mut config = config_with_bus("reverb");
+        config.buses[0].effects = serde_yaml::from_str(
+            "[{kind: delay, sync: 1/8., feedback: 0.6, ping_pong: true, filter_type: highpass}]",
+        )
+        .unwrap();
+        let delay = &config.buses[0].effects[0];
+        assert_eq!(delay.kind, EffectKind::Delay);
+        assert_eq!(delay.sync.as_deref(), Some("1/8."));
+        assert_eq!(delay.params["ping_pong"], PatchValue::Switch(true));
+        assert!(config.validate().is_ok());
+
+        config.buses[0].effects[0].sync = Some("1/0".to_string());
+        assert!(config.validate().is_err());
+        config.buses[0].effects[0].sync = Some("1/4".to_string());
+        config.buses[0].effects[0].params.insert("time".to_string(), PatchValue::Number(300.0));
+        assert!(config.validate().is_err());
+
+        // Only the delay follows the tempo
+        let mut reverb = EffectConfig::new(EffectKind::Reverb);
+        reverb.sync = Some("1/4".to_string());
+        config.buses[0].effects = vec![reverb];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_layer_effects() {
+        let mut config = config_with_bus("reverb");
+        config.layers[0].effects = serde_yaml::from_str(
+            "[{kind: chorus, voices: 3, shape: triangle}, {kind: phaser, name: sweep, stages: 8}]",
+        )
+        .unwrap();
+        assert_eq!(config.layers[0].effects[0].kind, EffectKind::Chorus);
+        assert_eq!(config.layers[0].effects[1].name(), "sweep");
+        assert!(config.validate().is_ok());
+
+        // Unknown parameters and out-of-range values are rejected
+        config.layers[0].effects[1].params.insert("stages".to_string(), PatchValue::Number(30.0));
+        assert!(config.validate().is_err());
+        config.layers[0].effects[1].params.clear();
+
+        // `filter.` and `send.` already address the layer
+        config.layers[0].effects[1].name = Some("filter".to_string());
+        assert!(config.validate().is_err());
+    }
 }
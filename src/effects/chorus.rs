@@ -0,0 +1,240 @@
+//! Stereo chorus
+//!
+//! Up to four copies of the signal, each read from a short delay line whose
+//! time drifts with its own LFO. The copies beat against each other and the
+//! dry signal the way a section of slightly detuned players does. The right
+//! channel's LFOs run behind the left's, so even a mono source comes out wide.
+
+use super::sweep::Sweep;
+use super::{DelayLine, Effect};
+use crate::synth::{Frame, LfoShape, ParamSpec, Smoother, SmoothingCurve};
+
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec::logarithmic("rate", 0.01, 10.0, 0.4),
+    ParamSpec::continuous("depth", 0.0, 10.0, 2.5),
+    ParamSpec::continuous("delay", 2.0, 40.0, 12.0),
+    ParamSpec::stepped("voices", 1.0, 4.0, 2.0),
+    ParamSpec::continuous("spread", 0.0, 1.0, 0.5),
+    ParamSpec::stepped("shape", 0.0, 1.0, 0.0).with_options(&["sine", "triangle"]),
+    ParamSpec::continuous("mix", 0.0, 1.0, 0.5).with_aliases(&["wet"]),
+];
+
+/// Longest base delay in milliseconds
+const MAX_DELAY: f64 = 40.0;
+
+/// Deepest time modulation in milliseconds
+const MAX_DEPTH: f64 = 10.0;
+
+/// Glide time for delay, depth and mix changes
+const GLIDE: f64 = 0.05;
+
+/// Multi-voice stereo chorus
+pub struct Chorus {
+    lines: [DelayLine; 2],
+    sweep: Sweep,
+    /// Base delay in milliseconds
+    delay: Smoother,
+    /// Time modulation depth in milliseconds
+    depth: Smoother,
+    mix: Smoother,
+    sample_rate: f64,
+}
+
+impl Chorus {
+    /// Parameters of the chorus
+    pub const PARAMS: &'static [ParamSpec] = PARAMS;
+
+    /// Create a chorus with the default settings
+    pub fn new(sample_rate: f64) -> Self {
+        let max_delay = ((MAX_DELAY + MAX_DEPTH) * 0.001 * sample_rate).ceil() as usize + 1;
+        let mut sweep = Sweep::new(sample_rate, 2);
+        sweep.set_rate(0.4);
+        sweep.set_spread(0.5);
+
+        Self {
+            lines: [0, 1].map(|_| DelayLine::new(max_delay)),
+            sweep,
+            delay: Smoother::new(12.0, SmoothingCurve::Exponential, false),
+            depth: Smoother::new(2.5, SmoothingCurve::Exponential, false),
+            mix: Smoother::new(0.5, SmoothingCurve::Exponential, false),
+            sample_rate,
+        }
+    }
+
+    /// Set the LFO rate in Hz
+    pub fn with_rate(mut self, hz: f64) -> Self {
+        self.set_parameter("rate", hz);
+        self
+    }
+
+    /// Set the time modulation depth in milliseconds
+    pub fn with_depth(mut self, ms: f64) -> Self {
+        self.set_parameter("depth", ms);
+        self.depth.snap();
+        self
+    }
+
+    /// Set the number of voices (1 to 4)
+    pub fn with_voices(mut self, voices: usize) -> Self {
+        self.sweep.set_taps(voices);
+        self
+    }
+
+    /// Set the dry/wet mix (0.0 dry to 1.0 wet)
+    pub fn with_mix(mut self, mix: f64) -> Self {
+        self.set_parameter("mix", mix);
+        self.mix.snap();
+        self
+    }
+}
+
+impl Effect for Chorus {
+    fn set_parameter(&mut self, name: &str, value: f64) {
+        let tick = 1.0 / self.sample_rate;
+        match name {
+            "rate" => self.sweep.set_rate(value.clamp(0.01, 10.0)),
+            "depth" => self.depth.set_target(value.clamp(0.0, MAX_DEPTH), GLIDE, tick),
+            "delay" => self.delay.set_target(value.clamp(2.0, MAX_DELAY), GLIDE, tick),
+            "voices" => self.sweep.set_taps(value.round().max(1.0) as usize),
+            "spread" => self.sweep.set_spread(value),
+            "shape" => self.sweep.set_shape(LfoShape::from_index(value.clamp(0.0, 1.0))),
+            "mix" | "wet" => self.mix.set_target(value.clamp(0.0, 1.0), GLIDE, tick),
+            _ => {}
+        }
+    }
+
+    fn get_parameter(&self, name: &str) -> Option<f64> {
+        match name {
+            "rate" => Some(self.sweep.rate()),
+            "depth" => Some(self.depth.target()),
+            "delay" => Some(self.delay.target()),
+            "voices" => Some(self.sweep.taps() as f64),
+            "spread" => Some(self.sweep.spread()),
+            "shape" => Some(self.sweep.shape().index() as f64),
+            "mix" | "wet" => Some(self.mix.target()),
+            _ => None,
+        }
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn process(&mut self, input: Frame) -> Frame {
+        self.lines[0].write(input.left);
+        self.lines[1].write(input.right);
+
+        let to_samples = 0.001 * self.sample_rate;
+        let delay = self.delay.advance();
+        // The swing can't reach below one sample, where the line would pin it
+        let depth = self.depth.advance().min(delay - 1.0 / to_samples);
+        let taps = self.sweep.taps();
+        let mut wet = [0.0; 2];
+        for [left, right] in self.sweep.process().into_iter().take(taps) {
+            wet[0] += self.lines[0].read((delay + depth * left) * to_samples);
+            wet[1] += self.lines[1].read((delay + depth * right) * to_samples);
+        }
+        // Voices sum roughly in power, as they drift in and out of phase
+        let wet = Frame::new(wet[0], wet[1]) * (1.0 / (taps as f64).sqrt());
+
+        let mix = self.mix.advance();
+        input * (1.0 - mix) + wet * mix
+    }
+
+    fn reset(&mut self) {
+        for line in &mut self.lines {
+            line.clear();
+        }
+        self.sweep.reset();
+        self.delay.snap();
+        self.depth.snap();
+        self.mix.snap();
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        let mut chorus = Chorus::new(sample_rate);
+        for spec in PARAMS {
+            if let Some(value) = self.get_parameter(spec.name) {
+                chorus.set_parameter(spec.name, value);
+            }
+        }
+        chorus.reset();
+        *self = chorus;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(n: usize) -> Frame {
+        Frame::mono((n as f64 * 2.0 * std::f64::consts::PI * 220.0 / 44100.0).sin())
+    }
+
+    #[test]
+    fn test_chorus_widens_mono() {
+        let mut chorus = Chorus::new(44100.0).with_rate(2.0).with_depth(4.0);
+        let output: Vec<Frame> = (0..44100).map(|n| chorus.process(sine(n))).collect();
+
+        let side: f64 = output.iter().map(|f| f.side().abs()).sum::<f64>() / output.len() as f64;
+        let peak = output.iter().map(Frame::peak).fold(0.0, f64::max);
+        assert!(side > 0.05, "{}", side);
+        assert!(peak < 2.0, "{}", peak);
+    }
+
+    #[test]
+    fn test_chorus_dry_and_in_step() {
+        // Fully dry passes the input through
+        let mut chorus = Chorus::new(44100.0).with_mix(0.0);
+        for n in 0..1000 {
+            assert_eq!(chorus.process(sine(n)), sine(n));
+        }
+
+        // Without spread the channels match
+        let mut chorus = Chorus::new(44100.0).with_voices(3);
+        chorus.set_parameter("spread", 0.0);
+        for n in 0..5000 {
+            let output = chorus.process(sine(n));
+            assert!(output.side().abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_chorus_depth_beyond_delay() {
+        // Fed a ramp, each wet sample shows the delay it was read at
+        let mut chorus = Chorus::new(44100.0).with_rate(2.0).with_depth(10.0).with_voices(1).with_mix(1.0);
+        chorus.set_parameter("delay", 2.0);
+        chorus.reset();
+        let delays: Vec<f64> = (0..44100)
+            .map(|n| n as f64 - chorus.process(Frame::mono(n as f64)).left + 1.0)
+            .skip(22050)
+            .collect();
+
+        // The sweep still moves through the bottom instead of sitting there
+        let bottom = 1.0 + 1e-6;
+        let pinned = delays.iter().filter(|&&d| d <= bottom).count();
+        assert!(delays.iter().all(|&d| d >= 1.0 - 1e-6));
+        assert!(pinned < 10, "{} samples at the bottom", pinned);
+        let top = delays.iter().fold(0.0, |a: f64, &b| a.max(b));
+        assert!((top - 4.0 * 44.1 + 1.0).abs() < 1.0, "{}", top);
+    }
+
+    #[test]
+    fn test_chorus_parameters() {
+        let mut chorus = Chorus::new(44100.0);
+        chorus.set_parameter("voices", 9.0);
+        assert_eq!(chorus.get_parameter("voices"), Some(4.0));
+        chorus.set_parameter("shape", 1.0);
+        assert_eq!(chorus.get_parameter("shape"), Some(1.0));
+        chorus.set_parameter("delay", 100.0);
+        assert_eq!(chorus.get_parameter("delay"), Some(MAX_DELAY));
+        for spec in chorus.params() {
+            assert!(chorus.get_parameter(spec.name).is_some(), "{}", spec.name);
+        }
+
+        // Settings survive a sample rate change
+        chorus.set_sample_rate(48000.0);
+        assert_eq!(chorus.get_parameter("voices"), Some(4.0));
+        assert_eq!(chorus.get_parameter("delay"), Some(MAX_DELAY));
+    }
+}
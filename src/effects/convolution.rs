@@ -0,0 +1,442 @@
+//! Convolution reverb
+//!
+//! Places the signal in a recorded space by convolving it with an impulse
+//! response (IR) loaded from a WAV file. The IR is cut into partitions of
+//! `PARTITION` samples whose spectra are precomputed; each block of input is
+//! transformed once and multiplied against every partition through a
+//! frequency-domain delay line (uniformly partitioned overlap-save), at the
+//! cost of `PARTITION` samples of latency.
+//!
+//! Only the first partition needs the block that has just completed; the
+//! later ones meet older blocks, so their products are summed a few
+//! partitions per sample while the next block fills. A block boundary then
+//! costs two FFTs and one partition per channel, and the rest of the work is
+//! spread evenly over the samples, whatever the audio buffer size.
+//!
+//! Mono IRs are applied to both channels; stereo IRs convolve left with the
+//! first channel and right with the second. IRs are resampled to the engine
+//! rate and normalized to unit energy, so recordings of any level land at a
+//! similar loudness.
+
+use super::{DelayLine, Effect};
+use crate::synth::{Frame, ParamSpec, SampleBuffer, Smoother, SmoothingCurve};
+use anyhow::{bail, Context, Result};
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use std::path::Path;
+use std::sync::Arc;
+
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec::continuous("predelay", 0.0, 0.25, 0.0),
+    ParamSpec::continuous("mix", 0.0, 1.0, 1.0).with_aliases(&["wet"]),
+];
+
+/// Samples per partition (and the effect's latency)
+pub const PARTITION: usize = 256;
+
+/// Longest impulse response used, in seconds (longer files are cut)
+pub const MAX_IR_SECONDS: f64 = 10.0;
+
+/// Glide time for mix changes
+const MIX_GLIDE: f64 = 0.02;
+
+/// Spectra of one IR channel's partitions
+type Partitions = Arc<Vec<Vec<Complex<f64>>>>;
+
+/// Convolution of one channel with one IR channel
+struct Channel {
+    partitions: Partitions,
+    /// Spectra of the most recent input blocks, newest at `head`
+    history: Vec<Vec<Complex<f64>>>,
+    head: usize,
+    /// The previous and current input blocks (the overlap-save window)
+    window: Vec<f64>,
+    /// Output of the last block
+    output: Vec<f64>,
+    /// Spectrum being accumulated
+    accumulator: Vec<Complex<f64>>,
+    /// Sum of the later partitions' products for the block being filled
+    tail: Vec<Complex<f64>>,
+    /// Next partition to add to `tail`
+    next_partition: usize,
+}
+
+impl Channel {
+    fn new(partitions: Partitions) -> Self {
+        let size = 2 * PARTITION;
+        Self {
+            history: vec![vec![Complex::default(); size]; partitions.len()],
+            partitions,
+            head: 0,
+            window: vec![0.0; size],
+            output: vec![0.0; PARTITION],
+            accumulator: vec![Complex::default(); size],
+            tail: vec![Complex::default(); size],
+            next_partition: 1,
+        }
+    }
+
+    /// Add up to `budget` of the later partitions to the tail sum
+    fn accumulate(&mut self, budget: usize) {
+        // Partition p meets the input from p blocks before the one filling,
+        // which goes in at `head`
+        let count = self.partitions.len();
+        let end = (self.next_partition + budget).min(count);
+        for p in self.next_partition..end {
+            let input = &self.history[(self.head + count - p) % count];
+            for ((sum, x), h) in self.tail.iter_mut().zip(input).zip(&self.partitions[p]) {
+                *sum += x * h;
+            }
+        }
+        self.next_partition = end;
+    }
+
+    /// Convolve the block of input in the second half of the window
+    fn process_block(&mut self, forward: &dyn Fft<f64>, inverse: &dyn Fft<f64>, scratch: &mut [Complex<f64>]) {
+        let spectrum = &mut self.history[self.head];
+        for (bin, &sample) in spectrum.iter_mut().zip(&self.window) {
+            *bin = Complex::new(sample, 0.0);
+        }
+        forward.process_with_scratch(spectrum, scratch);
+
+        // Finish the tail (normally done by now), then add the new block
+        let count = self.partitions.len();
+        self.accumulate(count);
+        for (((sum, tail), x), h) in self.accumulator.iter_mut().zip(&self.tail).zip(&self.history[self.head]).zip(&self.partitions[0]) {
+            *sum = tail + x * h;
+        }
+        inverse.process_with_scratch(&mut self.accumulator, scratch);
+
+        // The second half is free of wrap-around
+        let scale = 1.0 / (2 * PARTITION) as f64;
+        for (out, bin) in self.output.iter_mut().zip(&self.accumulator[PARTITION..]) {
+            *out = bin.re * scale;
+        }
+        self.head = (self.head + 1) % count;
+        self.window.copy_within(PARTITION.., 0);
+        self.tail.fill(Complex::default());
+        self.next_partition = 1;
+    }
+
+    fn clear(&mut self) {
+        for spectrum in &mut self.history {
+            spectrum.fill(Complex::default());
+        }
+        self.window.fill(0.0);
+        self.output.fill(0.0);
+        self.tail.fill(Complex::default());
+        self.next_partition = 1;
+    }
+}
+
+/// Partitioned FFT convolution with an impulse response
+pub struct Convolver {
+    channels: [Channel; 2],
+    forward: Arc<dyn Fft<f64>>,
+    inverse: Arc<dyn Fft<f64>>,
+    scratch: Vec<Complex<f64>>,
+    /// Position within the current block
+    position: usize,
+    /// Later partitions to accumulate per sample
+    budget: usize,
+    /// The impulse response as given, kept for sample rate changes
+    ir: Vec<SampleBuffer>,
+    predelay_lines: [DelayLine; 2],
+    /// Pre-delay in seconds
+    predelay: f64,
+    mix: Smoother,
+    sample_rate: f64,
+}
+
+impl Convolver {
+    /// Parameters of the convolution reverb
+    pub const PARAMS: &'static [ParamSpec] = PARAMS;
+
+    /// Load an impulse response from a WAV file
+    pub fn load(path: &Path, sample_rate: f64) -> Result<Self> {
+        let ir = SampleBuffer::load_channels(path)
+            .with_context(|| format!("failed to load impulse response {:?}", path))?;
+        Self::new(ir, sample_rate)
+    }
+
+    /// Create a convolver from one (mono) or two (stereo) IR channels
+    ///
+    /// Extra channels are ignored.
+    pub fn new(mut ir: Vec<SampleBuffer>, sample_rate: f64) -> Result<Self> {
+        if ir.is_empty() || ir.iter().any(|channel| channel.is_empty()) {
+            bail!("impulse response is empty");
+        }
+        ir.truncate(2);
+
+        let resampled: Vec<Vec<f64>> = ir.iter().map(|channel| Self::resample(channel, sample_rate)).collect();
+        let energy = resampled.iter().flatten().map(|s| s * s).sum::<f64>() / resampled.len() as f64;
+        if energy <= 0.0 {
+            bail!("impulse response is silent");
+        }
+        let gain = 1.0 / energy.sqrt();
+
+        let mut planner = FftPlanner::new();
+        let forward = planner.plan_fft_forward(2 * PARTITION);
+        let inverse = planner.plan_fft_inverse(2 * PARTITION);
+        let scratch_len = forward.get_inplace_scratch_len().max(inverse.get_inplace_scratch_len());
+        let mut scratch = vec![Complex::default(); scratch_len];
+
+        let mut spectra: Vec<Partitions> = resampled
+            .iter()
+            .map(|samples| Arc::new(Self::partition(samples, gain, forward.as_ref(), &mut scratch)))
+            .collect();
+        let right = spectra.pop().unwrap_or_default();
+        let left = spectra.pop().unwrap_or_else(|| right.clone());
+        let budget = (right.len() - 1).div_ceil(PARTITION);
+
+        Ok(Self {
+            channels: [Channel::new(left), Channel::new(right)],
+            forward,
+            inverse,
+            scratch,
+            position: 0,
+            budget,
+            ir,
+            predelay_lines: [0, 1].map(|_| DelayLine::new((0.25 * sample_rate).ceil() as usize + 1)),
+            predelay: 0.0,
+            mix: Smoother::new(1.0, SmoothingCurve::Exponential, false),
+            sample_rate,
+        })
+    }
+
+    /// Set the dry/wet mix (0.0 dry to 1.0 wet)
+    pub fn with_mix(mut self, mix: f64) -> Self {
+        self.set_parameter("mix", mix);
+        self.mix.snap();
+        self
+    }
+
+    /// Get the impulse response length in seconds (after any cut)
+    pub fn duration(&self) -> f64 {
+        (self.channels[0].partitions.len() * PARTITION) as f64 / self.sample_rate
+    }
+
+    /// Get the latency in samples
+    pub fn latency(&self) -> usize {
+        PARTITION
+    }
+
+    /// Resample an IR channel to the engine rate (linear interpolation),
+    /// cutting it at `MAX_IR_SECONDS`
+    fn resample(channel: &SampleBuffer, sample_rate: f64) -> Vec<f64> {
+        let step = channel.sample_rate() / sample_rate;
+        let len = ((channel.len() as f64 / step).ceil() as usize).min((MAX_IR_SECONDS * sample_rate) as usize);
+        (0..len).map(|n| channel.read(n as f64 * step)).collect()
+    }
+
+    /// Split an IR into zero-padded partitions and transform them
+    fn partition(
+        samples: &[f64],
+        gain: f64,
+        forward: &dyn Fft<f64>,
+        scratch: &mut [Complex<f64>],
+    ) -> Vec<Vec<Complex<f64>>> {
+        samples
+            .chunks(PARTITION)
+            .map(|chunk| {
+                let mut spectrum = vec![Complex::default(); 2 * PARTITION];
+                for (bin, &sample) in spectrum.iter_mut().zip(chunk) {
+                    *bin = Complex::new(sample * gain, 0.0);
+                }
+                forward.process_with_scratch(&mut spectrum, scratch);
+                spectrum
+            })
+            .collect()
+    }
+}
+
+impl Effect for Convolver {
+    fn set_parameter(&mut self, name: &str, value: f64) {
+        match name {
+            "predelay" => {
+                self.predelay = value.clamp(0.0, 0.25);
+            }
+            "mix" | "wet" => {
+                self.mix.set_target(value.clamp(0.0, 1.0), MIX_GLIDE, 1.0 / self.sample_rate);
+            }
+            _ => {}
+        }
+    }
+
+    fn get_parameter(&self, name: &str) -> Option<f64> {
+        match name {
+            "predelay" => Some(self.predelay),
+            "mix" | "wet" => Some(self.mix.target()),
+            _ => None,
+        }
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn process(&mut self, input: Frame) -> Frame {
+        let predelay = self.predelay * self.sample_rate;
+        let mut wet = [0.0; 2];
+        for (index, sample) in [input.left, input.right].into_iter().enumerate() {
+            let line = &mut self.predelay_lines[index];
+            line.write(sample);
+            let delayed = if predelay >= 1.0 { line.read(predelay) } else { sample };
+
+            let channel = &mut self.channels[index];
+            channel.window[PARTITION + self.position] = delayed;
+            wet[index] = channel.output[self.position];
+            channel.accumulate(self.budget);
+        }
+
+        self.position += 1;
+        if self.position == PARTITION {
+            self.position = 0;
+            for channel in &mut self.channels {
+                channel.process_block(self.forward.as_ref(), self.inverse.as_ref(), &mut self.scratch);
+            }
+        }
+
+        let mix = self.mix.advance();
+        input * (1.0 - mix) + Frame::new(wet[0], wet[1]) * mix
+    }
+
+    fn reset(&mut self) {
+        for channel in &mut self.channels {
+            channel.clear();
+        }
+        for line in &mut self.predelay_lines {
+            line.clear();
+        }
+        self.position = 0;
+        self.mix.snap();
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        if let Ok(convolver) = Convolver::new(self.ir.clone(), sample_rate) {
+            let (predelay, mix) = (self.predelay, self.mix.target());
+            *self = convolver.with_mix(mix);
+            self.predelay = predelay;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::{WavSpec, WavWriter};
+    use tempfile::NamedTempFile;
+
+    /// Deterministic noise for test signals
+    fn noise(len: usize, seed: u64) -> Vec<f64> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                (state >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_convolver_matches_direct_convolution() {
+        // Longer than several partitions, and not a whole number of them
+        let ir: Vec<f32> = noise(3 * PARTITION + 37, 1)
+            .iter()
+            .enumerate()
+            .map(|(n, s)| (s * (-(n as f64) / 300.0).exp()) as f32)
+            .collect();
+        let mut convolver = Convolver::new(vec![SampleBuffer::from_samples(ir.clone(), 44100.0)], 44100.0).unwrap();
+        // Expect the IR normalized to unit energy
+        let energy = ir.iter().map(|&s| s as f64 * s as f64).sum::<f64>();
+        let ir: Vec<f64> = ir.iter().map(|&s| s as f64 / energy.sqrt()).collect();
+
+        let input = noise(6 * PARTITION, 2);
+        let output: Vec<Frame> = input.iter().map(|&x| convolver.process(Frame::new(x, -x))).collect();
+
+        // Delayed by one partition
+        for (t, frame) in output[PARTITION..].iter().enumerate() {
+            let expected: f64 = (0..=t.min(ir.len() - 1)).map(|k| ir[k] * input[t - k]).sum();
+            assert!((frame.left - expected).abs() < 1e-6, "sample {}", t);
+            assert!((frame.right + expected).abs() < 1e-6, "sample {}", t);
+        }
+    }
+
+    #[test]
+    fn test_convolver_long_ir() {
+        // More partitions than samples per block, so each sample adds several
+        let len = 300 * PARTITION;
+        let taps = [0, 777, len - 1];
+        let mut ir = vec![0.0; len];
+        for &tap in &taps {
+            ir[tap] = 1.0;
+        }
+        let mut convolver = Convolver::new(vec![SampleBuffer::from_samples(ir, 44100.0)], 44100.0).unwrap();
+        assert_eq!(convolver.budget, 2);
+
+        let output: Vec<f64> = (0..len + 2 * PARTITION)
+            .map(|n| convolver.process(if n == 0 { Frame::mono(1.0) } else { Frame::ZERO }).left)
+            .collect();
+        let gain = 1.0 / 3f64.sqrt();
+        for (n, &sample) in output.iter().enumerate() {
+            let expected = if taps.iter().any(|&tap| tap + PARTITION == n) { gain } else { 0.0 };
+            assert!((sample - expected).abs() < 1e-9, "sample {}: {}", n, sample);
+        }
+    }
+
+    #[test]
+    fn test_convolver_stereo_ir() {
+        let left = SampleBuffer::from_samples(vec![1.0], 44100.0);
+        let right = SampleBuffer::from_samples(vec![0.0, 0.0, 1.0], 44100.0);
+        let mut convolver = Convolver::new(vec![left, right], 44100.0).unwrap();
+        assert_eq!(convolver.latency(), PARTITION);
+
+        let output: Vec<Frame> = (0..PARTITION + 4)
+            .map(|n| convolver.process(if n == 0 { Frame::mono(1.0) } else { Frame::ZERO }))
+            .collect();
+        // Each channel hears its own IR
+        assert!((output[PARTITION].left - 1.0).abs() < 1e-9);
+        assert!(output[PARTITION].right.abs() < 1e-9);
+        assert!((output[PARTITION + 2].right - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_convolver_loads_and_resamples_wav() {
+        let file = NamedTempFile::new().unwrap();
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 22050,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        {
+            let mut writer = WavWriter::create(file.path(), spec).unwrap();
+            for n in 0..22050 {
+                writer.write_sample((-(n as f32) / 2000.0).exp() * 0.5).unwrap();
+            }
+            writer.finalize().unwrap();
+        }
+
+        // One second at 22.05 kHz becomes one second at 44.1 kHz
+        let convolver = Convolver::load(file.path(), 44100.0).unwrap();
+        assert!((convolver.duration() - 1.0).abs() < PARTITION as f64 / 44100.0);
+
+        let err = Convolver::load(Path::new("/nonexistent/hall.wav"), 44100.0).err().unwrap();
+        assert!(err.to_string().contains("impulse response"));
+        let silent = SampleBuffer::from_samples(vec![0.0; 10], 44100.0);
+        assert!(Convolver::new(vec![silent], 44100.0).is_err());
+    }
+
+    #[test]
+    fn test_convolver_mix() {
+        let ir = SampleBuffer::from_samples(vec![1.0, 0.5], 44100.0);
+        let mut convolver = Convolver::new(vec![ir], 44100.0).unwrap().with_mix(0.0);
+        let input = Frame::new(0.4, 0.1);
+        assert_eq!(convolver.process(input), input);
+
+        convolver.set_parameter("predelay", 1.0);
+        assert_eq!(convolver.get_parameter("predelay"), Some(0.25));
+        convolver.set_parameter("wet", 0.5);
+        assert_eq!(convolver.get_parameter("mix"), Some(0.5));
+    }
+}
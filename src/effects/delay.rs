@@ -0,0 +1,326 @@
+//! Stereo delay
+//!
+//! Echoes with a filter in the feedback loop, so each repeat comes back
+//! darker (or thinner) than the last, and a slow LFO on the delay time for
+//! tape-style wobble. The time is set in milliseconds or locked to the tempo
+//! as a number of beats. Ping-pong mode sends the echoes back and forth
+//! between the channels.
+//!
+//! The feedback path saturates softly, so high feedback grows into a
+//! sustained wash instead of running away.
+
+use super::{DelayLine, Effect};
+use crate::synth::{Filter, FilterType, Frame, Lfo, ParamSpec, Smoother, SmoothingCurve};
+
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec::continuous("time", 1.0, 4000.0, 375.0),
+    ParamSpec::continuous("feedback", 0.0, 0.98, 0.4),
+    ParamSpec::logarithmic("cutoff", 20.0, 20000.0, 4000.0).with_aliases(&["filter"]),
+    ParamSpec::continuous("resonance", 0.1, 20.0, 0.707),
+    ParamSpec::stepped("filter_type", 0.0, 7.0, 0.0).with_options(&[
+        "lowpass", "highpass", "bandpass", "notch", "peaking", "low_shelf", "high_shelf", "allpass",
+    ]),
+    ParamSpec::logarithmic("mod_rate", 0.01, 10.0, 0.5),
+    ParamSpec::continuous("mod_depth", 0.0, 20.0, 0.0),
+    ParamSpec::stepped("ping_pong", 0.0, 1.0, 0.0),
+    ParamSpec::continuous("mix", 0.0, 1.0, 1.0).with_aliases(&["wet"]),
+];
+
+/// Longest delay time in milliseconds
+const MAX_TIME: f64 = 4000.0;
+
+/// Deepest time modulation in milliseconds
+const MAX_MOD_DEPTH: f64 = 20.0;
+
+/// Glide time for delay time changes (the echoes bend in pitch meanwhile)
+const TIME_GLIDE: f64 = 0.25;
+
+/// Glide time for feedback and mix changes
+const LEVEL_GLIDE: f64 = 0.02;
+
+/// Stereo delay with filtered feedback
+pub struct Delay {
+    lines: [DelayLine; 2],
+    filters: [Filter; 2],
+    lfo: Lfo,
+    /// Delay time in milliseconds
+    time: Smoother,
+    feedback: Smoother,
+    mix: Smoother,
+    /// Time modulation depth in milliseconds
+    mod_depth: f64,
+    ping_pong: bool,
+    /// Beats per echo when locked to the tempo
+    sync: Option<f64>,
+    sample_rate: f64,
+}
+
+impl Delay {
+    /// Parameters of the delay
+    pub const PARAMS: &'static [ParamSpec] = PARAMS;
+
+    /// Create a delay with the default settings
+    pub fn new(sample_rate: f64) -> Self {
+        let max_delay = ((MAX_TIME + MAX_MOD_DEPTH) * 0.001 * sample_rate).ceil() as usize + 1;
+        let filters = [0, 1].map(|_| {
+            let mut filter = Filter::with_type(sample_rate, FilterType::LowPass);
+            filter.set_cutoff(4000.0);
+            filter
+        });
+
+        Self {
+            lines: [0, 1].map(|_| DelayLine::new(max_delay)),
+            filters,
+            lfo: Lfo::new(sample_rate),
+            time: Smoother::new(375.0, SmoothingCurve::Exponential, false),
+            feedback: Smoother::new(0.4, SmoothingCurve::Exponential, false),
+            mix: Smoother::new(1.0, SmoothingCurve::Exponential, false),
+            mod_depth: 0.0,
+            ping_pong: false,
+            sync: None,
+            sample_rate,
+        }
+    }
+
+    /// Set the delay time in milliseconds
+    pub fn with_time(mut self, ms: f64) -> Self {
+        self.set_parameter("time", ms);
+        self.time.snap();
+        self
+    }
+
+    /// Lock the delay time to the tempo: one echo every `beats` beats
+    ///
+    /// The time follows the tempo from the next `sync`.
+    pub fn with_sync(mut self, beats: f64) -> Self {
+        self.sync = Some(beats).filter(|b| *b > 0.0);
+        self
+    }
+
+    /// Set the feedback (0.0 single echo to 0.98 near-endless)
+    pub fn with_feedback(mut self, feedback: f64) -> Self {
+        self.set_parameter("feedback", feedback);
+        self.feedback.snap();
+        self
+    }
+
+    /// Bounce the echoes between the channels
+    pub fn with_ping_pong(mut self, ping_pong: bool) -> Self {
+        self.ping_pong = ping_pong;
+        self
+    }
+
+    /// Set the dry/wet mix (0.0 dry to 1.0 wet)
+    pub fn with_mix(mut self, mix: f64) -> Self {
+        self.set_parameter("mix", mix);
+        self.mix.snap();
+        self
+    }
+
+    /// Get the beats per echo when tempo-synced
+    pub fn sync_beats(&self) -> Option<f64> {
+        self.sync
+    }
+}
+
+impl Effect for Delay {
+    fn set_parameter(&mut self, name: &str, value: f64) {
+        let tick = 1.0 / self.sample_rate;
+        match name {
+            "time" => {
+                // A free time replaces the tempo lock
+                self.sync = None;
+                self.time.set_target(value.clamp(1.0, MAX_TIME), TIME_GLIDE, tick);
+            }
+            "feedback" => {
+                self.feedback.set_target(value.clamp(0.0, 0.98), LEVEL_GLIDE, tick);
+            }
+            "cutoff" | "filter" => {
+                for filter in &mut self.filters {
+                    filter.set_cutoff(value);
+                }
+            }
+            "resonance" => {
+                for filter in &mut self.filters {
+                    filter.set_resonance(value);
+                }
+            }
+            "filter_type" => {
+                for filter in &mut self.filters {
+                    filter.set_type(FilterType::from_index(value));
+                }
+            }
+            "mod_rate" => {
+                self.lfo.set_frequency(value.clamp(0.01, 10.0));
+            }
+            "mod_depth" => {
+                self.mod_depth = value.clamp(0.0, MAX_MOD_DEPTH);
+            }
+            "ping_pong" => {
+                self.ping_pong = value >= 0.5;
+            }
+            "mix" | "wet" => {
+                self.mix.set_target(value.clamp(0.0, 1.0), LEVEL_GLIDE, tick);
+            }
+            _ => {}
+        }
+    }
+
+    fn get_parameter(&self, name: &str) -> Option<f64> {
+        match name {
+            "time" => Some(self.time.target()),
+            "feedback" => Some(self.feedback.target()),
+            "cutoff" | "filter" => Some(self.filters[0].cutoff()),
+            "resonance" => Some(self.filters[0].resonance()),
+            "filter_type" => Some(self.filters[0].filter_type().index() as f64),
+            "mod_rate" => Some(self.lfo.frequency()),
+            "mod_depth" => Some(self.mod_depth),
+            "ping_pong" => Some(if self.ping_pong { 1.0 } else { 0.0 }),
+            "mix" | "wet" => Some(self.mix.target()),
+            _ => None,
+        }
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn process(&mut self, input: Frame) -> Frame {
+        // The channels wobble in opposite directions
+        let wobble = self.lfo.process() * self.mod_depth;
+        let time = self.time.advance();
+        let to_samples = 0.001 * self.sample_rate;
+        let echoes = [
+            self.lines[0].read((time + wobble) * to_samples),
+            self.lines[1].read((time - wobble) * to_samples),
+        ];
+
+        let feedback = self.feedback.advance();
+        let fed: [f64; 2] = std::array::from_fn(|i| (self.filters[i].process(echoes[i]) * feedback).tanh());
+        if self.ping_pong {
+            self.lines[0].write(input.mid() + fed[1]);
+            self.lines[1].write(fed[0]);
+        } else {
+            self.lines[0].write(input.left + fed[0]);
+            self.lines[1].write(input.right + fed[1]);
+        }
+
+        let mix = self.mix.advance();
+        input * (1.0 - mix) + Frame::new(echoes[0], echoes[1]) * mix
+    }
+
+    fn sync(&mut self, bpm: f64, _beat: f64) {
+        if let Some(beats) = self.sync {
+            let ms = (beats * 60000.0 / bpm).clamp(1.0, MAX_TIME);
+            self.time.set_target(ms, TIME_GLIDE, 1.0 / self.sample_rate);
+        }
+    }
+
+    fn reset(&mut self) {
+        for line in &mut self.lines {
+            line.clear();
+        }
+        for filter in &mut self.filters {
+            filter.reset();
+        }
+        self.lfo.reset();
+        self.time.snap();
+        self.feedback.snap();
+        self.mix.snap();
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        let mut delay = Delay::new(sample_rate);
+        for spec in PARAMS {
+            if let Some(value) = self.get_parameter(spec.name) {
+                delay.set_parameter(spec.name, value);
+            }
+        }
+        delay.sync = self.sync;
+        delay.reset();
+        *self = delay;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run an impulse through and return the output
+    fn impulse(delay: &mut Delay, len: usize) -> Vec<Frame> {
+        (0..len)
+            .map(|n| delay.process(if n == 0 { Frame::mono(1.0) } else { Frame::ZERO }))
+            .collect()
+    }
+
+    /// Index of the loudest sample in a range of one channel
+    fn loudest(output: &[Frame], range: std::ops::Range<usize>, channel: fn(&Frame) -> f64) -> usize {
+        range.max_by(|&a, &b| channel(&output[a]).abs().total_cmp(&channel(&output[b]).abs())).unwrap()
+    }
+
+    #[test]
+    fn test_delay_echoes() {
+        let mut delay = Delay::new(44100.0).with_time(100.0).with_feedback(0.5);
+        delay.set_parameter("cutoff", 20000.0);
+        let output = impulse(&mut delay, 10000);
+
+        // Echoes every 4410 samples, each quieter than the last
+        assert_eq!(loudest(&output, 1..6000, |f| f.left), 4410);
+        assert!(loudest(&output, 6000..10000, |f| f.left).abs_diff(8820) <= 2);
+        let first = output[4410].left;
+        let second = output[8815..8825].iter().map(|f| f.left.abs()).fold(0.0, f64::max);
+        assert!((first - 1.0).abs() < 1e-9);
+        assert!(second > 0.3 && second < 0.6, "{}", second);
+        assert!(output[100].left.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_delay_ping_pong() {
+        let mut delay = Delay::new(44100.0).with_time(50.0).with_feedback(0.8).with_ping_pong(true);
+        let output = impulse(&mut delay, 5000);
+
+        // The first echo is on the left, the second on the right
+        assert!(output[2205].left.abs() > 0.5);
+        assert!(output[2205].right.abs() < 1e-9);
+        assert!(output[4410].left.abs() < 1e-9);
+        assert!(output[3500..4600].iter().any(|f| f.right.abs() > 0.1));
+    }
+
+    #[test]
+    fn test_delay_tempo_sync() {
+        let mut delay = Delay::new(44100.0).with_sync(0.5);
+        delay.sync(120.0, 0.0);
+        delay.reset();
+        assert_eq!(delay.get_parameter("time"), Some(250.0));
+
+        delay.sync(60.0, 0.0);
+        assert_eq!(delay.get_parameter("time"), Some(500.0));
+
+        // Setting a time leaves the tempo
+        delay.set_parameter("time", 80.0);
+        assert_eq!(delay.sync_beats(), None);
+        delay.sync(120.0, 0.0);
+        assert_eq!(delay.get_parameter("time"), Some(80.0));
+    }
+
+    #[test]
+    fn test_delay_feedback_stays_bounded() {
+        let mut delay = Delay::new(44100.0).with_time(20.0).with_feedback(0.98);
+        delay.set_parameter("resonance", 10.0);
+        delay.set_parameter("cutoff", 800.0);
+        delay.set_parameter("mod_depth", 5.0);
+        for n in 0..200000 {
+            let input = Frame::mono(if n < 4410 { ((n as f64) * 0.1).sin() } else { 0.0 });
+            let output = delay.process(input);
+            assert!(output.peak() < 3.0, "sample {}: {:?}", n, output);
+        }
+
+        delay.set_parameter("ping_pong", 1.0);
+        assert_eq!(delay.get_parameter("ping_pong"), Some(1.0));
+        delay.set_parameter("filter_type", 1.0);
+        assert_eq!(delay.get_parameter("filter_type"), Some(1.0));
+        for spec in delay.params() {
+            assert!(delay.get_parameter(spec.name).is_some(), "{}", spec.name);
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Fractional delay line
+//!
+//! A circular buffer read at any delay up to its length, with linear
+//! interpolation between samples so delay times can sweep smoothly.
+
+/// A circular delay buffer
+#[derive(Debug, Clone)]
+pub struct DelayLine {
+    buffer: Vec<f64>,
+    /// Index the next sample is written to
+    write: usize,
+}
+
+impl DelayLine {
+    /// Create a delay line holding up to `max_delay` samples
+    pub fn new(max_delay: usize) -> Self {
+        Self {
+            buffer: vec![0.0; max_delay.max(1) + 1],
+            write: 0,
+        }
+    }
+
+    /// Get the longest delay in samples
+    pub fn max_delay(&self) -> usize {
+        self.buffer.len() - 1
+    }
+
+    /// Read the sample written `delay` samples ago (1.0 = the last one)
+    ///
+    /// Fractional delays interpolate; the delay is clamped to the line.
+    pub fn read(&self, delay: f64) -> f64 {
+        let delay = delay.clamp(1.0, self.max_delay() as f64);
+        let whole = delay.floor();
+        let fraction = delay - whole;
+
+        let len = self.buffer.len();
+        let index = (self.write + len - whole as usize) % len;
+        let a = self.buffer[index];
+        if fraction == 0.0 {
+            return a;
+        }
+        let b = self.buffer[(index + len - 1) % len];
+        a + (b - a) * fraction
+    }
+
+    /// Write the next sample
+    pub fn write(&mut self, sample: f64) {
+        self.buffer[self.write] = sample;
+        self.write = (self.write + 1) % self.buffer.len();
+    }
+
+    /// Silence the line
+    pub fn clear(&mut self) {
+        self.buffer.fill(0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_line_read() {
+        let mut line = DelayLine::new(8);
+        for sample in [1.0, 2.0, 3.0, 4.0] {
+            line.write(sample);
+        }
+
+        assert_eq!(line.read(1.0), 4.0);
+        assert_eq!(line.read(3.0), 2.0);
+        assert_eq!(line.read(1.5), 3.5);
+        // Longer than anything written yet
+        assert_eq!(line.read(8.0), 0.0);
+
+        line.clear();
+        assert_eq!(line.read(1.0), 0.0);
+    }
+
+    #[test]
+    fn test_delay_line_wraps() {
+        let mut line = DelayLine::new(3);
+        for sample in 0..10 {
+            line.write(sample as f64);
+        }
+        assert_eq!(line.read(1.0), 9.0);
+        assert_eq!(line.read(3.0), 7.0);
+        assert_eq!(line.read(100.0), 7.0);
+    }
+}
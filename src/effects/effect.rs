@@ -0,0 +1,32 @@
+//! Effect trait for signal processors
+
+use crate::synth::{Frame, ParamSpec};
+
+/// Trait for effect implementations
+pub trait Effect: Send + Sync {
+    /// Set a parameter value
+    fn set_parameter(&mut self, name: &str, value: f64);
+
+    /// Get a parameter value
+    fn get_parameter(&self, name: &str) -> Option<f64>;
+
+    /// Describe the parameters this effect accepts
+    fn params(&self) -> &'static [ParamSpec] {
+        &[]
+    }
+
+    /// Process the next stereo frame
+    fn process(&mut self, input: Frame) -> Frame;
+
+    /// Follow the transport tempo and beat position
+    ///
+    /// Tempo-synced effects override this; the default ignores the transport.
+    fn sync(&mut self, _bpm: f64, _beat: f64) {}
+
+    /// Clear the effect's memory (tails, delay lines) and jump parameters
+    /// to their targets
+    fn reset(&mut self);
+
+    /// Set the sample rate
+    fn set_sample_rate(&mut self, sample_rate: f64);
+}
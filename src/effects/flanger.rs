@@ -0,0 +1,205 @@
+//! Stereo flanger
+//!
+//! A very short delay swept by an LFO and mixed back with the dry signal
+//! cuts a comb of notches that glides up and down the spectrum. Feedback
+//! deepens the comb into a ringing, metallic sweep; negative feedback
+//! hollows it out instead. The right channel sweeps behind the left.
+
+use super::sweep::Sweep;
+use super::{DelayLine, Effect};
+use crate::synth::{Frame, LfoShape, ParamSpec, Smoother, SmoothingCurve};
+
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec::logarithmic("rate", 0.01, 10.0, 0.15),
+    ParamSpec::continuous("depth", 0.0, 1.0, 0.8),
+    ParamSpec::continuous("delay", 0.5, 10.0, 3.0),
+    ParamSpec::continuous("feedback", -0.95, 0.95, 0.5),
+    ParamSpec::continuous("spread", 0.0, 1.0, 0.25),
+    ParamSpec::stepped("shape", 0.0, 1.0, 1.0).with_options(&["sine", "triangle"]),
+    ParamSpec::continuous("mix", 0.0, 1.0, 0.5).with_aliases(&["wet"]),
+];
+
+/// Shortest delay the sweep reaches in milliseconds
+const MIN_DELAY: f64 = 0.05;
+
+/// Longest sweep delay in milliseconds
+const MAX_DELAY: f64 = 10.0;
+
+/// Glide time for delay, feedback and mix changes
+const GLIDE: f64 = 0.05;
+
+/// Swept comb filter with feedback
+pub struct Flanger {
+    lines: [DelayLine; 2],
+    sweep: Sweep,
+    /// Top of the sweep in milliseconds
+    delay: Smoother,
+    depth: Smoother,
+    feedback: Smoother,
+    mix: Smoother,
+    sample_rate: f64,
+}
+
+impl Flanger {
+    /// Parameters of the flanger
+    pub const PARAMS: &'static [ParamSpec] = PARAMS;
+
+    /// Create a flanger with the default settings
+    pub fn new(sample_rate: f64) -> Self {
+        let max_delay = (MAX_DELAY * 0.001 * sample_rate).ceil() as usize + 1;
+        let mut sweep = Sweep::new(sample_rate, 1);
+        sweep.set_rate(0.15);
+        sweep.set_spread(0.25);
+        sweep.set_shape(LfoShape::Triangle);
+
+        Self {
+            lines: [0, 1].map(|_| DelayLine::new(max_delay)),
+            sweep,
+            delay: Smoother::new(3.0, SmoothingCurve::Exponential, false),
+            depth: Smoother::new(0.8, SmoothingCurve::Exponential, false),
+            feedback: Smoother::new(0.5, SmoothingCurve::Exponential, false),
+            mix: Smoother::new(0.5, SmoothingCurve::Exponential, false),
+            sample_rate,
+        }
+    }
+
+    /// Set the LFO rate in Hz
+    pub fn with_rate(mut self, hz: f64) -> Self {
+        self.set_parameter("rate", hz);
+        self
+    }
+
+    /// Set how much of the range the sweep covers (0.0 still to 1.0 full)
+    pub fn with_depth(mut self, depth: f64) -> Self {
+        self.set_parameter("depth", depth);
+        self.depth.snap();
+        self
+    }
+
+    /// Set the feedback (-0.95 to 0.95)
+    pub fn with_feedback(mut self, feedback: f64) -> Self {
+        self.set_parameter("feedback", feedback);
+        self.feedback.snap();
+        self
+    }
+
+    /// Set the dry/wet mix (0.0 dry to 1.0 wet)
+    pub fn with_mix(mut self, mix: f64) -> Self {
+        self.set_parameter("mix", mix);
+        self.mix.snap();
+        self
+    }
+}
+
+impl Effect for Flanger {
+    fn set_parameter(&mut self, name: &str, value: f64) {
+        let tick = 1.0 / self.sample_rate;
+        match name {
+            "rate" => self.sweep.set_rate(value.clamp(0.01, 10.0)),
+            "depth" => self.depth.set_target(value.clamp(0.0, 1.0), GLIDE, tick),
+            "delay" => self.delay.set_target(value.clamp(0.5, MAX_DELAY), GLIDE, tick),
+            "feedback" => self.feedback.set_target(value.clamp(-0.95, 0.95), GLIDE, tick),
+            "spread" => self.sweep.set_spread(value),
+            "shape" => self.sweep.set_shape(LfoShape::from_index(value.clamp(0.0, 1.0))),
+            "mix" | "wet" => self.mix.set_target(value.clamp(0.0, 1.0), GLIDE, tick),
+            _ => {}
+        }
+    }
+
+    fn get_parameter(&self, name: &str) -> Option<f64> {
+        match name {
+            "rate" => Some(self.sweep.rate()),
+            "depth" => Some(self.depth.target()),
+            "delay" => Some(self.delay.target()),
+            "feedback" => Some(self.feedback.target()),
+            "spread" => Some(self.sweep.spread()),
+            "shape" => Some(self.sweep.shape().index() as f64),
+            "mix" | "wet" => Some(self.mix.target()),
+            _ => None,
+        }
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn process(&mut self, input: Frame) -> Frame {
+        let to_samples = 0.001 * self.sample_rate;
+        let delay = self.delay.advance();
+        let depth = self.depth.advance();
+        let feedback = self.feedback.advance();
+        let [lfo, ..] = self.sweep.process();
+
+        // The sweep swings between just above zero and `delay`
+        let inputs = [input.left, input.right];
+        let wet: [f64; 2] = std::array::from_fn(|i| {
+            let time = MIN_DELAY + (delay - MIN_DELAY) * (0.5 + 0.5 * lfo[i] * depth);
+            let echo = self.lines[i].read(time * to_samples);
+            self.lines[i].write((inputs[i] + echo * feedback).tanh());
+            echo
+        });
+
+        let mix = self.mix.advance();
+        input * (1.0 - mix) + Frame::new(wet[0], wet[1]) * mix
+    }
+
+    fn reset(&mut self) {
+        for line in &mut self.lines {
+            line.clear();
+        }
+        self.sweep.reset();
+        self.delay.snap();
+        self.depth.snap();
+        self.feedback.snap();
+        self.mix.snap();
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        let mut flanger = Flanger::new(sample_rate);
+        for spec in PARAMS {
+            if let Some(value) = self.get_parameter(spec.name) {
+                flanger.set_parameter(spec.name, value);
+            }
+        }
+        flanger.reset();
+        *self = flanger;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flanger_comb() {
+        // Held still in the middle of a 2 ms sweep, the comb repeats every
+        // (0.05 + 2.0) / 2 ms
+        let mut flanger = Flanger::new(44100.0).with_depth(0.0).with_feedback(0.5).with_mix(1.0);
+        flanger.set_parameter("delay", 2.0);
+        flanger.reset();
+        let output: Vec<f64> =
+            (0..400).map(|n| flanger.process(Frame::mono(if n == 0 { 1.0 } else { 0.0 })).left).collect();
+
+        let period: f64 = 1.025 * 44.1;
+        let first = period.round() as usize;
+        let second = (2.0 * period).round() as usize;
+        assert!(output[first - 1..=first + 1].iter().any(|v| v.abs() > 0.3));
+        assert!(output[second - 1..=second + 1].iter().any(|v| v.abs() > 0.1));
+        assert!(output[1..first - 2].iter().all(|v| v.abs() < 1e-9));
+    }
+
+    #[test]
+    fn test_flanger_stays_bounded() {
+        let mut flanger = Flanger::new(44100.0).with_rate(5.0).with_feedback(0.95).with_mix(1.0);
+        for n in 0..100000 {
+            let output = flanger.process(Frame::mono((n as f64 * 0.05).sin()));
+            assert!(output.peak() < 2.0, "sample {}: {:?}", n, output);
+        }
+
+        flanger.set_parameter("feedback", -2.0);
+        assert_eq!(flanger.get_parameter("feedback"), Some(-0.95));
+        for spec in flanger.params() {
+            assert!(flanger.get_parameter(spec.name).is_some(), "{}", spec.name);
+        }
+    }
+}
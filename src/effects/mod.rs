@@ -0,0 +1,24 @@
+//! Audio effects for buses and layers
+//!
+//! Effects process the stereo signal summed on a bus, or a single layer's
+//! output. Like voices, they describe their parameters with `ParamSpec`s, so
+//! configs can set them by name and mappings can drive them from data.
+
+mod chorus;
+mod convolution;
+mod delay;
+mod delay_line;
+mod effect;
+mod flanger;
+mod phaser;
+mod reverb;
+mod sweep;
+
+pub use chorus::Chorus;
+pub use convolution::{Convolver, MAX_IR_SECONDS, PARTITION};
+pub use delay::Delay;
+pub use delay_line::DelayLine;
+pub use effect::Effect;
+pub use flanger::Flanger;
+pub use phaser::Phaser;
+pub use reverb::Reverb;
@@ -0,0 +1,235 @@
+//! Stereo phaser
+//!
+//! A chain of first-order allpass filters shifts the phase of the signal
+//! more and more towards the top of the spectrum; mixed back with the dry
+//! signal, it cancels in a few notches (one per pair of stages). An LFO
+//! swings the allpass frequency over several octaves so the notches sweep,
+//! and feedback sharpens them into resonant peaks. The right channel sweeps
+//! behind the left.
+
+use super::sweep::Sweep;
+use super::Effect;
+use crate::synth::{Frame, LfoShape, ParamSpec, Smoother, SmoothingCurve};
+use std::f64::consts::PI;
+
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec::logarithmic("rate", 0.01, 10.0, 0.2),
+    ParamSpec::continuous("depth", 0.0, 1.0, 0.7),
+    ParamSpec::logarithmic("frequency", 50.0, 5000.0, 600.0),
+    ParamSpec::stepped("stages", 2.0, 12.0, 6.0),
+    ParamSpec::continuous("feedback", -0.95, 0.95, 0.4),
+    ParamSpec::continuous("spread", 0.0, 1.0, 0.25),
+    ParamSpec::stepped("shape", 0.0, 1.0, 0.0).with_options(&["sine", "triangle"]),
+    ParamSpec::continuous("mix", 0.0, 1.0, 0.5).with_aliases(&["wet"]),
+];
+
+/// Most allpass stages per channel
+const MAX_STAGES: usize = 12;
+
+/// Octaves the sweep reaches either side of the centre at full depth
+const OCTAVES: f64 = 2.0;
+
+/// Glide time for frequency, feedback and mix changes
+const GLIDE: f64 = 0.05;
+
+/// Swept allpass cascade with feedback
+pub struct Phaser {
+    /// Allpass memory per channel and stage
+    states: [[f64; MAX_STAGES]; 2],
+    /// Last cascade output per channel, fed back to the input
+    last: [f64; 2],
+    stages: usize,
+    sweep: Sweep,
+    /// Centre of the sweep in Hz
+    frequency: Smoother,
+    depth: Smoother,
+    feedback: Smoother,
+    mix: Smoother,
+    sample_rate: f64,
+}
+
+impl Phaser {
+    /// Parameters of the phaser
+    pub const PARAMS: &'static [ParamSpec] = PARAMS;
+
+    /// Create a phaser with the default settings
+    pub fn new(sample_rate: f64) -> Self {
+        let mut sweep = Sweep::new(sample_rate, 1);
+        sweep.set_rate(0.2);
+        sweep.set_spread(0.25);
+
+        Self {
+            states: [[0.0; MAX_STAGES]; 2],
+            last: [0.0; 2],
+            stages: 6,
+            sweep,
+            frequency: Smoother::new(600.0, SmoothingCurve::Exponential, true),
+            depth: Smoother::new(0.7, SmoothingCurve::Exponential, false),
+            feedback: Smoother::new(0.4, SmoothingCurve::Exponential, false),
+            mix: Smoother::new(0.5, SmoothingCurve::Exponential, false),
+            sample_rate,
+        }
+    }
+
+    /// Set the LFO rate in Hz
+    pub fn with_rate(mut self, hz: f64) -> Self {
+        self.set_parameter("rate", hz);
+        self
+    }
+
+    /// Set how far the sweep swings (0.0 still to 1.0 two octaves each way)
+    pub fn with_depth(mut self, depth: f64) -> Self {
+        self.set_parameter("depth", depth);
+        self.depth.snap();
+        self
+    }
+
+    /// Set the number of allpass stages (2 to 12)
+    pub fn with_stages(mut self, stages: usize) -> Self {
+        self.set_parameter("stages", stages as f64);
+        self
+    }
+
+    /// Set the feedback (-0.95 to 0.95)
+    pub fn with_feedback(mut self, feedback: f64) -> Self {
+        self.set_parameter("feedback", feedback);
+        self.feedback.snap();
+        self
+    }
+
+    /// Set the dry/wet mix (0.0 dry to 1.0 wet)
+    pub fn with_mix(mut self, mix: f64) -> Self {
+        self.set_parameter("mix", mix);
+        self.mix.snap();
+        self
+    }
+}
+
+impl Effect for Phaser {
+    fn set_parameter(&mut self, name: &str, value: f64) {
+        let tick = 1.0 / self.sample_rate;
+        match name {
+            "rate" => self.sweep.set_rate(value.clamp(0.01, 10.0)),
+            "depth" => self.depth.set_target(value.clamp(0.0, 1.0), GLIDE, tick),
+            "frequency" => self.frequency.set_target(value.clamp(50.0, 5000.0), GLIDE, tick),
+            "stages" => self.stages = (value.round() as usize).clamp(2, MAX_STAGES),
+            "feedback" => self.feedback.set_target(value.clamp(-0.95, 0.95), GLIDE, tick),
+            "spread" => self.sweep.set_spread(value),
+            "shape" => self.sweep.set_shape(LfoShape::from_index(value.clamp(0.0, 1.0))),
+            "mix" | "wet" => self.mix.set_target(value.clamp(0.0, 1.0), GLIDE, tick),
+            _ => {}
+        }
+    }
+
+    fn get_parameter(&self, name: &str) -> Option<f64> {
+        match name {
+            "rate" => Some(self.sweep.rate()),
+            "depth" => Some(self.depth.target()),
+            "frequency" => Some(self.frequency.target()),
+            "stages" => Some(self.stages as f64),
+            "feedback" => Some(self.feedback.target()),
+            "spread" => Some(self.sweep.spread()),
+            "shape" => Some(self.sweep.shape().index() as f64),
+            "mix" | "wet" => Some(self.mix.target()),
+            _ => None,
+        }
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn process(&mut self, input: Frame) -> Frame {
+        let frequency = self.frequency.advance();
+        let depth = self.depth.advance();
+        let feedback = self.feedback.advance();
+        let [lfo, ..] = self.sweep.process();
+
+        let inputs = [input.left, input.right];
+        let nyquist = 0.45 * self.sample_rate;
+        for (i, (states, last)) in self.states.iter_mut().zip(&mut self.last).enumerate() {
+            let hz = (frequency * (lfo[i] * depth * OCTAVES).exp2()).clamp(20.0, nyquist);
+            let t = (PI * hz / self.sample_rate).tan();
+            let a = (t - 1.0) / (t + 1.0);
+
+            let mut x = inputs[i] + (*last * feedback).tanh();
+            for z in &mut states[..self.stages] {
+                let y = a * x + *z;
+                *z = x - a * y;
+                x = y;
+            }
+            *last = x;
+        }
+
+        let mix = self.mix.advance();
+        input * (1.0 - mix) + Frame::new(self.last[0], self.last[1]) * mix
+    }
+
+    fn reset(&mut self) {
+        self.states = [[0.0; MAX_STAGES]; 2];
+        self.last = [0.0; 2];
+        self.sweep.reset();
+        self.frequency.snap();
+        self.depth.snap();
+        self.feedback.snap();
+        self.mix.snap();
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        let mut phaser = Phaser::new(sample_rate);
+        for spec in PARAMS {
+            if let Some(value) = self.get_parameter(spec.name) {
+                phaser.set_parameter(spec.name, value);
+            }
+        }
+        phaser.reset();
+        *self = phaser;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mean level of one channel over the second half of a run
+    fn level(output: &[Frame], channel: fn(&Frame) -> f64) -> f64 {
+        let half = &output[output.len() / 2..];
+        (half.iter().map(|f| channel(f).powi(2)).sum::<f64>() / half.len() as f64).sqrt()
+    }
+
+    fn sine(n: usize, hz: f64) -> Frame {
+        Frame::mono((n as f64 * 2.0 * PI * hz / 44100.0).sin())
+    }
+
+    #[test]
+    fn test_phaser_notch() {
+        // Two stages at 1 kHz turn a 1 kHz tone half a cycle around, so it
+        // cancels against the dry signal
+        let mut phaser = Phaser::new(44100.0).with_depth(0.0).with_stages(2).with_feedback(0.0);
+        phaser.set_parameter("frequency", 1000.0);
+        phaser.reset();
+        let notched: Vec<Frame> = (0..8820).map(|n| phaser.process(sine(n, 1000.0) * 0.1)).collect();
+        assert!(level(&notched, |f| f.left) < 0.005, "{}", level(&notched, |f| f.left));
+
+        // Fully wet, the cascade only shifts phase
+        let mut phaser = Phaser::new(44100.0).with_depth(0.0).with_feedback(0.0).with_mix(1.0);
+        let wet: Vec<Frame> = (0..8820).map(|n| phaser.process(sine(n, 300.0) * 0.1)).collect();
+        assert!((level(&wet, |f| f.left) - 0.1 / 2f64.sqrt()).abs() < 0.002);
+    }
+
+    #[test]
+    fn test_phaser_stereo_sweep() {
+        let mut phaser = Phaser::new(44100.0).with_rate(1.0).with_depth(1.0).with_feedback(0.9);
+        phaser.set_parameter("spread", 1.0);
+        let output: Vec<Frame> = (0..44100).map(|n| phaser.process(sine(n, 440.0) * 0.5)).collect();
+
+        assert!(level(&output, |f| f.side()) > 0.01);
+        assert!(output.iter().all(|f| f.peak() < 2.0));
+
+        phaser.set_parameter("stages", 40.0);
+        assert_eq!(phaser.get_parameter("stages"), Some(12.0));
+        for spec in phaser.params() {
+            assert!(phaser.get_parameter(spec.name).is_some(), "{}", spec.name);
+        }
+    }
+}
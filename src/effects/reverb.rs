@@ -0,0 +1,359 @@
+//! Algorithmic reverb
+//!
+//! An eight-line feedback delay network (FDN). The input passes a pre-delay
+//! and a chain of allpass diffusers per channel, then feeds delay lines of
+//! mutually prime lengths that are mixed back into each other through a
+//! Householder matrix. Each line loses energy at the rate that gives the
+//! requested decay time, through a lowpass that makes highs die first
+//! (damping). A slow wobble on each line's length keeps long tails from
+//! ringing metallically.
+
+use super::{DelayLine, Effect};
+use crate::synth::{Frame, ParamSpec, Smoother, SmoothingCurve, CONTROL_INTERVAL};
+use std::f64::consts::TAU;
+
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec::continuous("size", 0.0, 1.0, 0.6).with_aliases(&["room_size"]),
+    ParamSpec::logarithmic("decay", 0.1, 30.0, 4.0).with_aliases(&["time"]),
+    ParamSpec::continuous("damping", 0.0, 1.0, 0.4),
+    ParamSpec::continuous("predelay", 0.0, 0.25, 0.02),
+    ParamSpec::continuous("mix", 0.0, 1.0, 1.0).with_aliases(&["wet"]),
+];
+
+/// Number of feedback delay lines
+const LINES: usize = 8;
+
+/// Feedback line lengths in samples at 44.1 kHz and full size (mutually
+/// prime, so echoes don't pile up on the same samples)
+const LINE_LENGTHS: [f64; LINES] = [2053.0, 2251.0, 2399.0, 2531.0, 2713.0, 2897.0, 3061.0, 3259.0];
+
+/// Smallest room, as a fraction of the full line lengths
+const MIN_SCALE: f64 = 0.25;
+
+/// Rate of each line's length wobble in Hz
+const WOBBLE_RATES: [f64; LINES] = [0.11, 0.17, 0.23, 0.29, 0.37, 0.43, 0.53, 0.61];
+
+/// Depth of the length wobble in samples at 44.1 kHz
+const WOBBLE_DEPTH: f64 = 6.0;
+
+/// Diffuser lengths in samples at 44.1 kHz (left, right)
+const DIFFUSER_LENGTHS: [[f64; 4]; 2] = [[142.0, 107.0, 379.0, 277.0], [149.0, 113.0, 373.0, 283.0]];
+
+/// Allpass gain of the input diffusers
+const DIFFUSION: f64 = 0.6;
+
+/// Output tap signs per line; orthogonal patterns decorrelate the channels
+const LEFT_TAPS: [f64; LINES] = [1.0, -1.0, 1.0, -1.0, 1.0, -1.0, 1.0, -1.0];
+const RIGHT_TAPS: [f64; LINES] = [1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, -1.0];
+
+/// Scale of the input into the network and of the wet output
+const INPUT_GAIN: f64 = 0.5;
+const OUTPUT_GAIN: f64 = 0.35;
+
+/// Glide time for size changes (moving line lengths bends the tail's pitch)
+const SIZE_GLIDE: f64 = 0.5;
+
+/// Glide time for mix changes
+const MIX_GLIDE: f64 = 0.02;
+
+/// Schroeder allpass that smears transients without colouring the tone
+#[derive(Debug, Clone)]
+struct Diffuser {
+    line: DelayLine,
+    delay: f64,
+}
+
+impl Diffuser {
+    fn new(delay: f64) -> Self {
+        Self {
+            line: DelayLine::new(delay.ceil() as usize),
+            delay,
+        }
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let delayed = self.line.read(self.delay);
+        let fed = input + DIFFUSION * delayed;
+        self.line.write(fed);
+        delayed - DIFFUSION * fed
+    }
+}
+
+/// Feedback delay network reverb
+pub struct Reverb {
+    lines: Vec<DelayLine>,
+    /// Lowpass state per line (damping)
+    damping_state: [f64; LINES],
+    /// Current read delay per line in samples
+    delays: [f64; LINES],
+    /// Feedback gain per line for the decay time
+    gains: [f64; LINES],
+    /// Length wobble phase per line (0-1)
+    wobble_phase: [f64; LINES],
+    diffusers: [Vec<Diffuser>; 2],
+    predelay_lines: [DelayLine; 2],
+
+    size: Smoother,
+    mix: Smoother,
+    /// Time for the tail to fall 60 dB in seconds
+    decay: f64,
+    damping: f64,
+    /// Pre-delay in seconds
+    predelay: f64,
+
+    sample_rate: f64,
+    /// Samples until the next control tick
+    countdown: usize,
+}
+
+impl Reverb {
+    /// Parameters of the reverb
+    pub const PARAMS: &'static [ParamSpec] = PARAMS;
+
+    /// Create a reverb with the default settings
+    pub fn new(sample_rate: f64) -> Self {
+        let scale = sample_rate / 44100.0;
+        let max_wobble = WOBBLE_DEPTH * scale;
+        let default = |name: &str| PARAMS.iter().find(|p| p.name == name).map(|p| p.default).unwrap_or(0.0);
+
+        let mut reverb = Self {
+            lines: LINE_LENGTHS
+                .iter()
+                .map(|length| DelayLine::new((length * scale + max_wobble).ceil() as usize + 1))
+                .collect(),
+            damping_state: [0.0; LINES],
+            delays: [0.0; LINES],
+            gains: [0.0; LINES],
+            wobble_phase: std::array::from_fn(|i| i as f64 / LINES as f64),
+            diffusers: DIFFUSER_LENGTHS.map(|lengths| lengths.iter().map(|l| Diffuser::new(l * scale)).collect()),
+            predelay_lines: [0, 1].map(|_| DelayLine::new((0.25 * sample_rate).ceil() as usize + 1)),
+            size: Smoother::new(default("size"), SmoothingCurve::Exponential, false),
+            mix: Smoother::new(default("mix"), SmoothingCurve::Exponential, false),
+            decay: default("decay"),
+            damping: default("damping"),
+            predelay: default("predelay"),
+            sample_rate,
+            countdown: 0,
+        };
+        reverb.update();
+        reverb
+    }
+
+    /// Set the room size (0.0 to 1.0)
+    pub fn with_size(mut self, size: f64) -> Self {
+        self.set_parameter("size", size);
+        self.size.snap();
+        self.update();
+        self
+    }
+
+    /// Set the decay time in seconds
+    pub fn with_decay(mut self, seconds: f64) -> Self {
+        self.set_parameter("decay", seconds);
+        self.update();
+        self
+    }
+
+    /// Set the damping (0.0 bright to 1.0 dark)
+    pub fn with_damping(mut self, damping: f64) -> Self {
+        self.set_parameter("damping", damping);
+        self
+    }
+
+    /// Set the dry/wet mix (0.0 dry to 1.0 wet)
+    pub fn with_mix(mut self, mix: f64) -> Self {
+        self.set_parameter("mix", mix);
+        self.mix.snap();
+        self
+    }
+
+    /// Recompute line lengths and feedback gains (once per control tick)
+    fn update(&mut self) {
+        let scale = self.sample_rate / 44100.0;
+        let size = self.size.advance();
+        let room = scale * (MIN_SCALE + (1.0 - MIN_SCALE) * size);
+        let step = CONTROL_INTERVAL as f64 / self.sample_rate;
+
+        for i in 0..LINES {
+            self.wobble_phase[i] = (self.wobble_phase[i] + WOBBLE_RATES[i] * step).fract();
+            let length = LINE_LENGTHS[i] * room;
+            self.delays[i] = length + WOBBLE_DEPTH * scale * (self.wobble_phase[i] * TAU).sin();
+            // -60 dB after `decay` seconds: each pass through the line loses its share
+            self.gains[i] = 10f64.powf(-3.0 * length / (self.decay * self.sample_rate));
+        }
+    }
+}
+
+impl Effect for Reverb {
+    fn set_parameter(&mut self, name: &str, value: f64) {
+        match name {
+            "size" | "room_size" => {
+                let tick = CONTROL_INTERVAL as f64 / self.sample_rate;
+                self.size.set_target(value.clamp(0.0, 1.0), SIZE_GLIDE, tick);
+            }
+            "decay" | "time" => {
+                self.decay = value.clamp(0.1, 30.0);
+            }
+            "damping" => {
+                self.damping = value.clamp(0.0, 1.0);
+            }
+            "predelay" => {
+                self.predelay = value.clamp(0.0, 0.25);
+            }
+            "mix" | "wet" => {
+                self.mix.set_target(value.clamp(0.0, 1.0), MIX_GLIDE, 1.0 / self.sample_rate);
+            }
+            _ => {}
+        }
+    }
+
+    fn get_parameter(&self, name: &str) -> Option<f64> {
+        match name {
+            "size" | "room_size" => Some(self.size.target()),
+            "decay" | "time" => Some(self.decay),
+            "damping" => Some(self.damping),
+            "predelay" => Some(self.predelay),
+            "mix" | "wet" => Some(self.mix.target()),
+            _ => None,
+        }
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn process(&mut self, input: Frame) -> Frame {
+        if self.countdown == 0 {
+            self.update();
+            self.countdown = CONTROL_INTERVAL;
+        }
+        self.countdown -= 1;
+
+        // Pre-delay and diffuse each channel
+        let predelay = self.predelay * self.sample_rate;
+        let mut diffused = [input.left, input.right];
+        for (channel, sample) in diffused.iter_mut().enumerate() {
+            let line = &mut self.predelay_lines[channel];
+            line.write(*sample);
+            if predelay >= 1.0 {
+                *sample = line.read(predelay);
+            }
+            for diffuser in &mut self.diffusers[channel] {
+                *sample = diffuser.process(*sample);
+            }
+        }
+
+        // Read the lines, damp and scale them, then mix them back in
+        let outputs: [f64; LINES] = std::array::from_fn(|i| self.lines[i].read(self.delays[i]));
+        let damping = self.damping * 0.85;
+        let mut feedback = [0.0; LINES];
+        for i in 0..LINES {
+            self.damping_state[i] = outputs[i] + (self.damping_state[i] - outputs[i]) * damping;
+            feedback[i] = self.damping_state[i] * self.gains[i];
+        }
+        let reflection = feedback.iter().sum::<f64>() * 2.0 / LINES as f64;
+        for (i, line) in self.lines.iter_mut().enumerate() {
+            line.write(feedback[i] - reflection + diffused[i % 2] * INPUT_GAIN);
+        }
+
+        let tap = |taps: &[f64; LINES]| outputs.iter().zip(taps).map(|(o, t)| o * t).sum::<f64>() * OUTPUT_GAIN;
+        let wet = Frame::new(tap(&LEFT_TAPS), tap(&RIGHT_TAPS));
+        let mix = self.mix.advance();
+        input * (1.0 - mix) + wet * mix
+    }
+
+    fn reset(&mut self) {
+        for line in self.lines.iter_mut().chain(&mut self.predelay_lines) {
+            line.clear();
+        }
+        for diffuser in self.diffusers.iter_mut().flatten() {
+            diffuser.line.clear();
+        }
+        self.damping_state = [0.0; LINES];
+        self.size.snap();
+        self.mix.snap();
+        self.update();
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        let mut reverb = Reverb::new(sample_rate)
+            .with_size(self.size.target())
+            .with_decay(self.decay)
+            .with_damping(self.damping)
+            .with_mix(self.mix.target());
+        reverb.predelay = self.predelay;
+        *self = reverb;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Energy of a window of the impulse response
+    fn energy(reverb: &mut Reverb, start: usize, end: usize) -> f64 {
+        let mut total = 0.0;
+        for n in 0..end {
+            let input = if n == 0 { Frame::mono(1.0) } else { Frame::ZERO };
+            let output = reverb.process(input);
+            if n >= start {
+                total += output.left * output.left + output.right * output.right;
+            }
+        }
+        total
+    }
+
+    #[test]
+    fn test_reverb_tail_follows_decay() {
+        let short = energy(&mut Reverb::new(44100.0).with_decay(0.5), 44100, 48000);
+        let long = energy(&mut Reverb::new(44100.0).with_decay(8.0), 44100, 48000);
+        assert!(long > 0.0);
+        assert!(long > short * 100.0);
+        // Still audible well after the impulse, but fading
+        let mut reverb = Reverb::new(44100.0).with_decay(2.0);
+        let early = energy(&mut reverb, 4410, 8820);
+        let late = energy(&mut Reverb::new(44100.0).with_decay(2.0), 44100, 48510);
+        assert!(early > late);
+    }
+
+    #[test]
+    fn test_reverb_stays_stable() {
+        let mut reverb = Reverb::new(44100.0).with_decay(30.0).with_damping(0.0).with_size(1.0);
+        for n in 0..441000 {
+            let input = Frame::mono(if n < 44100 { ((n as f64) * 0.05).sin() } else { 0.0 });
+            let output = reverb.process(input);
+            assert!(output.peak() < 4.0, "sample {}: {:?}", n, output);
+        }
+    }
+
+    #[test]
+    fn test_reverb_is_stereo() {
+        let mut reverb = Reverb::new(44100.0);
+        let mut difference = 0.0;
+        for n in 0..10000 {
+            let input = if n == 0 { Frame::mono(1.0) } else { Frame::ZERO };
+            let output = reverb.process(input);
+            difference += (output.left - output.right).abs();
+        }
+        assert!(difference > 0.1);
+    }
+
+    #[test]
+    fn test_reverb_mix_and_params() {
+        let mut reverb = Reverb::new(44100.0).with_mix(0.0);
+        let input = Frame::new(0.3, -0.2);
+        assert_eq!(reverb.process(input), input);
+
+        reverb.set_parameter("wet", 2.0);
+        assert_eq!(reverb.get_parameter("mix"), Some(1.0));
+        reverb.set_parameter("decay", 100.0);
+        assert_eq!(reverb.get_parameter("decay"), Some(30.0));
+        reverb.set_parameter("damping", 0.7);
+        assert_eq!(reverb.get_parameter("damping"), Some(0.7));
+        assert_eq!(reverb.get_parameter("unknown"), None);
+
+        for spec in reverb.params() {
+            assert!(reverb.get_parameter(spec.name).is_some(), "{}", spec.name);
+        }
+    }
+}
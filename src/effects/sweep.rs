@@ -0,0 +1,133 @@
+//! LFO sweeps for modulation effects
+//!
+//! Chorus, flanger and phaser move their delay times or allpass frequencies
+//! with LFOs. A sweep keeps one LFO per tap and channel at a shared rate and
+//! shape: taps sit evenly around the cycle, and the right channel runs
+//! `spread` half-cycles behind the left, which is what makes a mono source
+//! wide.
+
+use crate::synth::{Lfo, LfoShape};
+
+/// Most taps (chorus voices) a sweep drives
+pub const MAX_TAPS: usize = 4;
+
+/// LFOs for the taps of a modulation effect
+pub struct Sweep {
+    /// Left and right LFO per tap
+    lfos: Vec<[Lfo; 2]>,
+    taps: usize,
+    /// Offset of the right channel in half-cycles (0.0 to 1.0)
+    spread: f64,
+}
+
+impl Sweep {
+    /// Create a sine sweep for `taps` taps
+    pub fn new(sample_rate: f64, taps: usize) -> Self {
+        let mut sweep = Self {
+            lfos: (0..MAX_TAPS).map(|_| [Lfo::new(sample_rate), Lfo::new(sample_rate)]).collect(),
+            taps: 1,
+            spread: 0.0,
+        };
+        sweep.set_taps(taps);
+        sweep
+    }
+
+    /// Get the rate in Hz
+    pub fn rate(&self) -> f64 {
+        self.lfos[0][0].frequency()
+    }
+
+    /// Set the rate in Hz
+    pub fn set_rate(&mut self, hz: f64) {
+        for lfo in self.lfos.iter_mut().flatten() {
+            lfo.set_frequency(hz);
+        }
+    }
+
+    /// Get the LFO shape
+    pub fn shape(&self) -> LfoShape {
+        self.lfos[0][0].shape()
+    }
+
+    /// Set the LFO shape
+    pub fn set_shape(&mut self, shape: LfoShape) {
+        for lfo in self.lfos.iter_mut().flatten() {
+            lfo.set_shape(shape);
+        }
+    }
+
+    /// Get the number of taps in use
+    pub fn taps(&self) -> usize {
+        self.taps
+    }
+
+    /// Set the number of taps (1 to `MAX_TAPS`)
+    pub fn set_taps(&mut self, taps: usize) {
+        self.taps = taps.clamp(1, MAX_TAPS);
+        self.place();
+    }
+
+    /// Get the stereo spread
+    pub fn spread(&self) -> f64 {
+        self.spread
+    }
+
+    /// Set how far the right channel runs behind the left (0.0 in step,
+    /// 1.0 opposite)
+    pub fn set_spread(&mut self, spread: f64) {
+        self.spread = spread.clamp(0.0, 1.0);
+        self.place();
+    }
+
+    /// Spread the LFO phases over the taps and channels
+    fn place(&mut self) {
+        for (tap, [left, right]) in self.lfos.iter_mut().enumerate() {
+            let offset = tap as f64 / self.taps as f64;
+            left.set_phase_offset(offset);
+            right.set_phase_offset(offset + self.spread * 0.5);
+        }
+    }
+
+    /// Advance one sample; returns left and right values (-1.0 to 1.0) for
+    /// each tap (only the first `taps` are meaningful)
+    pub fn process(&mut self) -> [[f64; 2]; MAX_TAPS] {
+        let mut values = [[0.0; 2]; MAX_TAPS];
+        for (value, [left, right]) in values.iter_mut().zip(&mut self.lfos) {
+            *value = [left.process(), right.process()];
+        }
+        values
+    }
+
+    /// Restart the cycle
+    pub fn reset(&mut self) {
+        for lfo in self.lfos.iter_mut().flatten() {
+            lfo.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sweep_phases() {
+        let mut sweep = Sweep::new(1000.0, 2);
+        sweep.set_rate(1.0);
+        sweep.set_spread(1.0);
+
+        // Quarter of the way through a cycle
+        let mut values = [[0.0; 2]; MAX_TAPS];
+        for _ in 0..=250 {
+            values = sweep.process();
+        }
+        let [left, right] = values[0];
+        assert!((left - 1.0).abs() < 1e-3);
+        // Opposite channel, and the second tap half a cycle on
+        assert!((right + 1.0).abs() < 1e-3);
+        assert!((values[1][0] + 1.0).abs() < 1e-3);
+
+        sweep.set_taps(10);
+        assert_eq!(sweep.taps(), MAX_TAPS);
+    }
+}
@@ -0,0 +1,296 @@
+//! Voice allocation
+//!
+//! A polyphonic layer owns several copies of its voice. The allocator hands
+//! each note to a free voice, stealing one when all are busy, and gives the
+//! note a snapshot of the layer's parameters so overlapping notes keep the
+//! values they started with. Voices that sound on their own (drone, texture,
+//! melody) never start notes to take a snapshot, so they follow parameters
+//! live instead.
+
+use crate::config::StealMode;
+use crate::synth::{Frame, Voice, DATA_PREFIX};
+
+/// Decay applied to each voice's level follower (used for quietest stealing)
+const LEVEL_DECAY: f64 = 0.999;
+
+/// A voice and its allocation state
+struct VoiceSlot {
+    voice: Box<dyn Voice>,
+    /// Note-on order (higher is newer)
+    started: u64,
+    /// Recent peak output level
+    level: f64,
+}
+
+/// Assigns notes to a fixed pool of voices
+pub struct VoiceAllocator {
+    slots: Vec<VoiceSlot>,
+    steal: StealMode,
+    /// The voices sound without being triggered
+    sustained: bool,
+    /// Parameter values applied to each voice at note-on, in the order set
+    params: Vec<(String, f64)>,
+    /// Note-on counter
+    clock: u64,
+    /// Slot that received the latest note
+    newest: usize,
+}
+
+impl VoiceAllocator {
+    /// Create an allocator over a pool of voices
+    ///
+    /// The first voice is left as created (so auto-triggered voices like the
+    /// drone still sound on their own); the rest are silenced until a note
+    /// is allocated to them.
+    pub fn new(voices: Vec<Box<dyn Voice>>, steal: StealMode) -> Self {
+        assert!(!voices.is_empty(), "allocator needs at least one voice");
+        let sustained = voices[0].is_active();
+
+        let slots = voices
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut voice)| {
+                if i > 0 {
+                    voice.release();
+                    voice.process();
+                }
+                VoiceSlot {
+                    voice,
+                    started: 0,
+                    level: 0.0,
+                }
+            })
+            .collect();
+
+        Self {
+            slots,
+            steal,
+            sustained,
+            params: Vec::new(),
+            clock: 0,
+            newest: 0,
+        }
+    }
+
+    /// Get the number of voices
+    pub fn polyphony(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Get the number of voices currently sounding
+    pub fn active_voices(&self) -> usize {
+        self.slots.iter().filter(|s| s.voice.is_active()).count()
+    }
+
+    /// Get the stealing strategy
+    pub fn steal_mode(&self) -> StealMode {
+        self.steal
+    }
+
+    /// Set a parameter
+    ///
+    /// A single voice follows parameters live, as do voices that sound
+    /// without being triggered. With several triggered voices the value is
+    /// stored and applied to the next note, leaving sounding notes alone.
+    /// Data modulation (`mod.<source>`) always reaches every voice.
+    pub fn set_parameter(&mut self, name: &str, value: f64) {
+        if self.slots.len() == 1 || self.sustained || name.starts_with(DATA_PREFIX) {
+            for slot in &mut self.slots {
+                slot.voice.set_parameter(name, value);
+            }
+            return;
+        }
+
+        // Keep the order values were set in: some parameters (like a
+        // percussion model) reset others when applied. A known name moves
+        // to the end without allocating (this runs on the audio thread).
+        let entry = match self.params.iter().position(|(n, _)| n == name) {
+            Some(index) => (self.params.remove(index).0, value),
+            None => (name.to_string(), value),
+        };
+        self.params.push(entry);
+    }
+
+    /// Get a parameter (the pending value, or the newest note's)
+    pub fn get_parameter(&self, name: &str) -> Option<f64> {
+        if self.slots.len() > 1 && !self.sustained {
+            if let Some((_, value)) = self.params.iter().find(|(n, _)| n == name) {
+                return Some(*value);
+            }
+        }
+        self.slots[self.newest].voice.get_parameter(name)
+    }
+
+    /// Pick the slot for a new note: a free voice, or one to steal
+    fn allocate(&self) -> usize {
+        if let Some(i) = self.slots.iter().position(|s| !s.voice.is_active()) {
+            return i;
+        }
+
+        let candidates = self.slots.iter().enumerate();
+        match self.steal {
+            StealMode::Oldest => candidates.min_by_key(|(_, s)| s.started),
+            StealMode::Quietest => candidates.min_by(|(_, a), (_, b)| a.level.total_cmp(&b.level)),
+        }
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+    }
+
+    /// Start a note on a free (or stolen) voice
+    pub fn note_on(&mut self) {
+        let index = self.allocate();
+        self.clock += 1;
+
+        let slot = &mut self.slots[index];
+        for (name, value) in &self.params {
+            slot.voice.set_parameter(name, *value);
+        }
+        slot.voice.trigger();
+        slot.started = self.clock;
+        self.newest = index;
+    }
+
+    /// Release every voice
+    pub fn release_all(&mut self) {
+        for slot in &mut self.slots {
+            slot.voice.release();
+        }
+    }
+
+    /// Pass the transport tempo and beat position to every voice
+    pub fn sync(&mut self, bpm: f64, beat: f64) {
+        for slot in &mut self.slots {
+            slot.voice.sync(bpm, beat);
+        }
+    }
+
+    /// Check if any voice is sounding
+    pub fn is_active(&self) -> bool {
+        self.slots.iter().any(|s| s.voice.is_active())
+    }
+
+    /// Generate the next sample (mono mix of all sounding voices)
+    pub fn process(&mut self) -> f64 {
+        self.process_frame().mid()
+    }
+
+    /// Mix every sounding voice into one stereo frame
+    pub fn process_frame(&mut self) -> Frame {
+        let mut sum = Frame::ZERO;
+        for slot in &mut self.slots {
+            let output = if slot.voice.is_active() {
+                slot.voice.process_frame()
+            } else {
+                Frame::ZERO
+            };
+            slot.level = (slot.level * LEVEL_DECAY).max(output.peak());
+            sum += output;
+        }
+        sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synth::{DroneVoice, PercussionModel, PercussionVoice};
+
+    fn allocator(polyphony: usize, steal: StealMode) -> VoiceAllocator {
+        let voices = (0..polyphony)
+            .map(|_| Box::new(PercussionVoice::new(44100.0, PercussionModel::Kick)) as Box<dyn Voice>)
+            .collect();
+        VoiceAllocator::new(voices, steal)
+    }
+
+    fn run(allocator: &mut VoiceAllocator, samples: usize) {
+        for _ in 0..samples {
+            allocator.process();
+        }
+    }
+
+    #[test]
+    fn test_notes_overlap() {
+        let mut voices = allocator(4, StealMode::Oldest);
+        assert_eq!(voices.active_voices(), 0);
+
+        voices.note_on();
+        run(&mut voices, 100);
+        voices.note_on();
+        voices.note_on();
+        assert_eq!(voices.active_voices(), 3);
+    }
+
+    #[test]
+    fn test_steal_oldest() {
+        let mut voices = allocator(2, StealMode::Oldest);
+        voices.set_parameter("pitch", 50.0);
+        voices.note_on();
+        voices.set_parameter("pitch", 60.0);
+        voices.note_on();
+
+        // Both busy: the first note (pitch 50) is replaced
+        voices.set_parameter("pitch", 70.0);
+        voices.note_on();
+        let mut pitches: Vec<f64> = voices
+            .slots
+            .iter()
+            .map(|s| s.voice.get_parameter("pitch").unwrap())
+            .collect();
+        pitches.sort_by(f64::total_cmp);
+        assert_eq!(pitches, vec![60.0, 70.0]);
+    }
+
+    #[test]
+    fn test_steal_quietest() {
+        let mut voices = allocator(2, StealMode::Quietest);
+        voices.set_parameter("amplitude", 1.0);
+        voices.note_on();
+        voices.set_parameter("amplitude", 0.1);
+        voices.note_on();
+        run(&mut voices, 500);
+
+        // The newer but quieter note is the one replaced
+        voices.set_parameter("amplitude", 0.5);
+        voices.note_on();
+        let mut amplitudes: Vec<f64> = voices
+            .slots
+            .iter()
+            .map(|s| s.voice.get_parameter("amplitude").unwrap())
+            .collect();
+        amplitudes.sort_by(f64::total_cmp);
+        assert_eq!(amplitudes, vec![0.5, 1.0]);
+    }
+
+    #[test]
+    fn test_snapshot_leaves_sounding_notes() {
+        let mut voices = allocator(2, StealMode::Oldest);
+        voices.set_parameter("pitch", 50.0);
+        voices.note_on();
+        voices.set_parameter("pitch", 90.0);
+
+        assert_eq!(voices.slots[0].voice.get_parameter("pitch"), Some(50.0));
+        assert_eq!(voices.get_parameter("pitch"), Some(90.0));
+    }
+
+    #[test]
+    fn test_single_voice_follows_parameters() {
+        let mut voices = allocator(1, StealMode::Oldest);
+        voices.set_parameter("pitch", 80.0);
+        assert_eq!(voices.slots[0].voice.get_parameter("pitch"), Some(80.0));
+
+        voices.note_on();
+        voices.note_on();
+        assert_eq!(voices.active_voices(), 1);
+    }
+
+    #[test]
+    fn test_sustained_voices_follow_parameters() {
+        let voices = (0..3).map(|_| Box::new(DroneVoice::new(44100.0)) as Box<dyn Voice>).collect();
+        let mut voices = VoiceAllocator::new(voices, StealMode::Oldest);
+
+        // The drone sounds on its own, so a mapped value reaches it at once
+        voices.set_parameter("pitch", 250.0);
+        assert_eq!(voices.slots[0].voice.get_parameter("pitch"), Some(250.0));
+        assert_eq!(voices.get_parameter("pitch"), Some(250.0));
+    }
+}
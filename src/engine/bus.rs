@@ -0,0 +1,269 @@
+//! Mix buses
+//!
+//! A bus sums the signals sent to it, runs the sum through its effects and
+//! applies its own gain. Layers feed named send buses; every bus (and the
+//! layers' dry signal) then feeds the master bus. Buses carry stereo frames.
+//!
+//! Effect parameters are addressed as `<effect>.<param>` (e.g.
+//! `reverb.size`), so a bus listening to a source can map data onto them.
+
+use super::layer::Layer;
+use crate::config::{BusConfig, EffectConfig, EffectKind, MasterConfig};
+use super::transport::NoteDivision;
+use crate::effects::{Chorus, Convolver, Delay, Effect, Flanger, Phaser, Reverb};
+use crate::mapping::{MappingPipeline, Scale};
+use crate::sources::DataPoint;
+use crate::synth::Frame;
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+
+/// A summing bus with effects and gain
+pub struct Bus {
+    name: String,
+    volume: f32,
+    /// Effects in processing order, by name
+    effects: Vec<(String, Box<dyn Effect>)>,
+    /// Data source for the mappings
+    source: Option<String>,
+    /// Parameter mappings (param_name -> (field_name, pipeline))
+    mappings: HashMap<String, (String, MappingPipeline)>,
+    /// Signal accumulated since the last `process` call
+    input: Frame,
+}
+
+impl Bus {
+    /// Create a new bus
+    pub fn new(name: impl Into<String>, volume: f32) -> Self {
+        Self {
+            name: name.into(),
+            volume,
+            effects: Vec::new(),
+            source: None,
+            mappings: HashMap::new(),
+            input: Frame::ZERO,
+        }
+    }
+
+    /// Create a bus with the effects and mappings from config
+    pub fn from_config(config: &BusConfig, master: &MasterConfig, sample_rate: f64) -> Result<Self> {
+        let scale = Scale::from_name(&master.scale).unwrap_or_else(Scale::minor_pentatonic);
+        let mut bus = Self::new(&config.name, config.volume);
+        for effect in &config.effects {
+            let built = Self::build_effect(effect, master, sample_rate)
+                .with_context(|| format!("failed to create bus '{}'", config.name))?;
+            bus = bus.with_effect(effect.name(), built);
+        }
+        bus.source = config.source.clone();
+        for (param_name, mapping_config) in &config.mappings {
+            let pipeline = Layer::build_pipeline(mapping_config, master, &scale);
+            bus.mappings.insert(param_name.clone(), (mapping_config.field.clone(), pipeline));
+        }
+        Ok(bus)
+    }
+
+    /// Create an effect with its configured starting parameters
+    ///
+    /// Tempo-synced effects start at the master tempo. Layers build their
+    /// effects here too.
+    pub(super) fn build_effect(config: &EffectConfig, master: &MasterConfig, sample_rate: f64) -> Result<Box<dyn Effect>> {
+        let mut effect: Box<dyn Effect> = match config.kind {
+            EffectKind::Reverb => Box::new(Reverb::new(sample_rate)),
+            EffectKind::Convolution => {
+                let path = config
+                    .ir
+                    .as_deref()
+                    .ok_or_else(|| anyhow!("effect '{}' needs an ir", config.name()))?;
+                Box::new(Convolver::load(path, sample_rate)?)
+            }
+            EffectKind::Delay => {
+                let mut delay = Delay::new(sample_rate);
+                if let Some(sync) = &config.sync {
+                    let division = NoteDivision::parse(sync)?;
+                    delay = delay.with_sync(division.beats(master.beats_per_bar));
+                }
+                Box::new(delay)
+            }
+            EffectKind::Chorus => Box::new(Chorus::new(sample_rate)),
+            EffectKind::Flanger => Box::new(Flanger::new(sample_rate)),
+            EffectKind::Phaser => Box::new(Phaser::new(sample_rate)),
+        };
+        for (name, value) in &config.params {
+            let value = value
+                .resolve(effect.params(), name)
+                .with_context(|| format!("effect '{}'", config.name()))?;
+            effect.set_parameter(name, value);
+        }
+        effect.sync(master.bpm as f64, 0.0);
+        effect.reset();
+        Ok(effect)
+    }
+
+    /// Add an effect to the end of the chain
+    pub fn with_effect(mut self, name: impl Into<String>, effect: Box<dyn Effect>) -> Self {
+        self.effects.push((name.into(), effect));
+        self
+    }
+
+    /// Get the bus name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the names of the bus effects in processing order
+    pub fn effect_names(&self) -> impl Iterator<Item = &str> {
+        self.effects.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Get the data source driving the bus mappings
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    /// Get the bus volume
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Set the bus volume (0.0 to 1.0)
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    fn effect_mut(&mut self, name: &str) -> Option<&mut Box<dyn Effect>> {
+        self.effects.iter_mut().find(|(n, _)| n == name).map(|(_, effect)| effect)
+    }
+
+    /// Set an effect parameter by `<effect>.<param>` name
+    pub fn set_parameter(&mut self, name: &str, value: f64) {
+        if let Some((effect, param)) = name.split_once('.') {
+            if let Some(effect) = self.effect_mut(effect) {
+                effect.set_parameter(param, value);
+            }
+        }
+    }
+
+    /// Get an effect parameter by `<effect>.<param>` name
+    pub fn get_parameter(&self, name: &str) -> Option<f64> {
+        let (effect, param) = name.split_once('.')?;
+        let (_, effect) = self.effects.iter().find(|(n, _)| n == effect)?;
+        effect.get_parameter(param)
+    }
+
+    /// Apply mapped values from a data point
+    pub fn process_data(&mut self, data: &DataPoint) {
+        let mut updates = Vec::new();
+        for (param_name, (field_name, pipeline)) in &self.mappings {
+            if let Some(&value) = data.values.get(field_name) {
+                updates.push((param_name.clone(), pipeline.apply(value)));
+            }
+        }
+        for (param_name, value) in updates {
+            self.set_parameter(&param_name, value);
+        }
+    }
+
+    /// Apply the interpolated value of one field between data points
+    /// (allocation-free, for the audio thread)
+    pub fn process_value(&mut self, field: &str, value: f64) {
+        let mappings = std::mem::take(&mut self.mappings);
+        for (param_name, (field_name, pipeline)) in &mappings {
+            if field_name == field {
+                self.set_parameter(param_name, pipeline.apply(value));
+            }
+        }
+        self.mappings = mappings;
+    }
+
+    /// Follow the transport tempo and beat position
+    pub fn sync(&mut self, bpm: f64, beat: f64) {
+        for (_, effect) in &mut self.effects {
+            effect.sync(bpm, beat);
+        }
+    }
+
+    /// Add a signal to the bus input for the current sample
+    pub fn add(&mut self, frame: impl Into<Frame>) {
+        self.input += frame.into();
+    }
+
+    /// Produce the bus output for the current sample and clear its input
+    pub fn process(&mut self) -> Frame {
+        let mut output = self.input;
+        for (_, effect) in &mut self.effects {
+            output = effect.process(output);
+        }
+        self.input = Frame::ZERO;
+        output * self.volume as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{MappingConfig, MappingKind, PatchValue};
+
+    #[test]
+    fn test_bus_sums_inputs() {
+        let mut bus = Bus::new("reverb", 0.5);
+        bus.add(0.4);
+        bus.add(0.2);
+
+        assert!((bus.process().mid() - 0.3).abs() < 1e-12);
+        // Input is cleared after each sample
+        assert_eq!(bus.process(), Frame::ZERO);
+    }
+
+    #[test]
+    fn test_bus_keeps_channels_apart() {
+        let mut bus = Bus::new("reverb", 1.0);
+        bus.add(Frame::new(0.5, 0.0));
+        bus.add(Frame::new(0.0, 0.25));
+
+        assert_eq!(bus.process(), Frame::new(0.5, 0.25));
+    }
+
+    #[test]
+    fn test_bus_volume_clamping() {
+        let mut bus = Bus::new("master", 0.7);
+        assert_eq!(bus.name(), "master");
+        assert_eq!(bus.volume(), 0.7);
+
+        bus.set_volume(2.0);
+        assert_eq!(bus.volume(), 1.0);
+        bus.set_volume(-1.0);
+        assert_eq!(bus.volume(), 0.0);
+    }
+
+    #[test]
+    fn test_bus_effects_and_mappings() {
+        let mut effect = EffectConfig::new(EffectKind::Reverb);
+        effect.params.insert("decay".to_string(), PatchValue::Number(8.0));
+        let mut config = BusConfig::new("space", 1.0);
+        config.effects.push(effect);
+        config.source = Some("weather".to_string());
+        config.mappings.insert(
+            "reverb.size".to_string(),
+            MappingConfig {
+                field: "humidity".to_string(),
+                kind: MappingKind::Linear,
+                in_min: Some(0.0),
+                in_max: Some(100.0),
+                out_min: Some(0.0),
+                out_max: Some(1.0),
+            },
+        );
+
+        let mut bus = Bus::from_config(&config, &MasterConfig::default(), 44100.0).unwrap();
+        assert_eq!(bus.effect_names().collect::<Vec<_>>(), vec!["reverb"]);
+        assert_eq!(bus.source(), Some("weather"));
+        assert_eq!(bus.get_parameter("reverb.decay"), Some(8.0));
+
+        bus.process_data(&DataPoint::new("weather").with_value("humidity", 25.0));
+        assert_eq!(bus.get_parameter("reverb.size"), Some(0.25));
+
+        // An impulse keeps ringing through the reverb
+        bus.add(1.0);
+        let tail: f64 = (0..20000).map(|_| bus.process().peak()).sum();
+        assert!(tail > 0.1);
+    }
+}
@@ -0,0 +1,1232 @@
+//! Sound layers
+//!
+//! A layer is one strand of the audio graph: it listens to a source, applies
+//! mappings to turn data into voice parameters, and feeds its voice output to
+//! the master bus and any send buses. Polyphonic layers play overlapping
+//! notes on a pool of voices. Output is stereo: each layer sets its width
+//! and its place between the speakers, and can run its own effects (a chorus
+//! to widen a static drone, say) before the signal is placed.
+
+use super::allocator::VoiceAllocator;
+use super::bus::Bus;
+use super::transport::NoteDivision;
+use crate::config::{
+    CurveKind, EnvelopeConfig, EnvelopeCurveKind, EnvelopeShapeConfig, FilterConfig, FilterKind, FilterModelKind, LayerConfig, MappingConfig, MappingKind, MasterConfig, ModPolarity, ModSourceKind, ModulationConfig,
+    PatchValue, SmoothingConfig, VoiceKind,
+};
+use crate::mapping::{key_to_hz, ExponentialMapper, LinearMapper, LogarithmicMapper, MappingPipeline, QuantizeMapper, Scale, ThresholdMapper, ThresholdDirection};
+use crate::effects::Effect;
+use crate::sources::DataPoint;
+use crate::synth::{
+    Breakpoint, DroneVoice, Envelope, EnvelopeCurve, Filter, FilterModel, FilterType, FmVoice, LfoShape, MelodyVoice, Frame, ModMatrix, ModSource, ModulatedVoice, ParamKind, PercussionModel,
+    PercussionVoice, Polarity, SampleBuffer, SamplerVoice, SmoothedVoice, Smoother, SmoothingCurve, TextureVoice, Voice,
+    Wavetable, DATA_PREFIX,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+
+/// Decay applied to the level meter each sample (~100ms fall time at 44.1kHz)
+const METER_DECAY: f64 = 0.9995;
+
+/// A send from a layer to a bus
+#[derive(Debug, Clone)]
+pub struct LayerSend {
+    /// Name of the target bus
+    pub bus: String,
+    /// Index of the target bus in the engine
+    pub(crate) index: usize,
+    /// Send level (0.0 to 1.0)
+    pub level: f32,
+}
+
+/// A layer in the audio graph (source -> mappings -> voice)
+pub struct Layer {
+    /// Layer name
+    pub name: String,
+    /// Source name this layer listens to
+    pub source: String,
+    /// Voice type for this layer
+    voice_kind: VoiceKind,
+    /// Voices for this layer
+    voices: VoiceAllocator,
+    /// Parameter mappings (param_name -> (field_name, pipeline))
+    mappings: HashMap<String, (String, MappingPipeline)>,
+    /// Fields feeding data modulation sources (`mod.<source>`, field_name)
+    mod_fields: Vec<(String, String)>,
+    /// Filter on the layer output, left and right (before volume)
+    filter: Option<[Filter; 2]>,
+    /// Effects after the filter in processing order, by name
+    effects: Vec<(String, Box<dyn Effect>)>,
+    /// Stereo position (-1.0 left to 1.0 right)
+    pan: Smoother,
+    /// Stereo spread (0.0 mono, 1.0 as the voice plays, up to 2.0)
+    width: Smoother,
+    /// Glide time for pan and width changes in seconds
+    glide: f64,
+    sample_rate: f64,
+    /// Layer volume
+    volume: f32,
+    /// Sends to buses (post-volume)
+    sends: Vec<LayerSend>,
+    /// Events that trigger the voice
+    triggers: Vec<String>,
+    /// Events that select and play a sample (event, sample index)
+    sample_events: Vec<(String, usize)>,
+    /// Last value of the `trigger` parameter (for edge detection)
+    trigger_level: f64,
+    /// Transport tempo
+    bpm: f64,
+    /// Transport beat position, kept up to date so notes start on the grid
+    beat: f64,
+    /// Peak output level for metering
+    level: f64,
+}
+
+impl Layer {
+    /// Create a new layer from config
+    ///
+    /// The master settings supply the tempo, key and scale for melodic voices
+    /// and quantize mappings. Fails if a sample file can't be loaded.
+    pub fn new(config: &LayerConfig, master: &MasterConfig, sample_rate: f64) -> Result<Self> {
+        let scale = Scale::from_name(&master.scale).unwrap_or_else(Scale::minor_pentatonic);
+        let mut voices = Self::build_voices(config, master, &scale, sample_rate)
+            .with_context(|| format!("failed to create layer '{}'", config.name))?;
+        voices.sync(master.bpm as f64, 0.0);
+        let curve = Self::smoothing_curve(config.smoothing.curve);
+
+        let mut effects = Vec::new();
+        for effect in &config.effects {
+            let built = Bus::build_effect(effect, master, sample_rate)
+                .with_context(|| format!("failed to create layer '{}'", config.name))?;
+            effects.push((effect.name().to_string(), built));
+        }
+
+        // Build mappings
+        let mut mappings = HashMap::new();
+        for (param_name, mapping_config) in &config.mappings {
+            let pipeline = Self::build_pipeline(mapping_config, master, &scale);
+            mappings.insert(
+                param_name.clone(),
+                (mapping_config.field.clone(), pipeline),
+            );
+        }
+
+        Ok(Self {
+            name: config.name.clone(),
+            source: config.source.clone(),
+            voice_kind: config.voice.clone(),
+            voices,
+            mappings,
+            mod_fields: config
+                .modulation
+                .sources
+                .iter()
+                .filter(|(_, source)| source.kind == ModSourceKind::Data)
+                .filter_map(|(name, source)| Some((format!("{}{}", DATA_PREFIX, name), source.field.clone()?)))
+                .collect(),
+            filter: config.filter.as_ref().map(|f| [0, 1].map(|_| Self::build_filter(f, sample_rate))),
+            effects,
+            pan: Smoother::new(config.pan, curve, false),
+            width: Smoother::new(config.width, curve, false),
+            glide: config.smoothing.time,
+            sample_rate,
+            volume: config.volume,
+            sends: Vec::new(),
+            triggers: config.triggers.clone(),
+            sample_events: config
+                .samples
+                .iter()
+                .enumerate()
+                .flat_map(|(index, sample)| sample.events.iter().map(move |e| (e.clone(), index)))
+                .collect(),
+            trigger_level: 0.0,
+            bpm: master.bpm as f64,
+            beat: 0.0,
+            level: 0.0,
+        })
+    }
+
+    /// Create the voice pool for a layer (one voice per note of polyphony)
+    ///
+    /// Sample and wavetable files are loaded once and shared by every voice.
+    fn build_voices(
+        config: &LayerConfig,
+        master: &MasterConfig,
+        scale: &Scale,
+        sample_rate: f64,
+    ) -> Result<VoiceAllocator> {
+        // Texture granulates the first sample; the sampler plays them all
+        let used = match config.voice {
+            VoiceKind::Texture => config.samples.len().min(1),
+            VoiceKind::Sampler => config.samples.len(),
+            _ => 0,
+        };
+        let samples = config.samples[..used]
+            .iter()
+            .map(|sample| SampleBuffer::load(&sample.path))
+            .collect::<Result<Vec<_>>>()?;
+        if config.voice == VoiceKind::Sampler && samples.is_empty() {
+            bail!("sampler voice needs at least one file under samples");
+        }
+        let wavetable = config.wavetable.as_deref().map(Wavetable::load).transpose()?;
+
+        let voices = (0..config.polyphony.max(1))
+            .map(|index| {
+                let mut voice = Self::build_voice(config, master, scale, &samples, wavetable.as_ref(), sample_rate);
+                Self::apply_patch(voice.as_mut(), &config.patch)?;
+                if let Some(envelope) = &config.envelope {
+                    let target = voice
+                        .envelope_mut()
+                        .ok_or_else(|| anyhow!("{:?} voice has no amplitude envelope", config.voice))?;
+                    Self::apply_envelope(target, envelope);
+                }
+                if !config.modulation.routes.is_empty() {
+                    let matrix = Self::build_matrix(&config.modulation, index, master.beats_per_bar, sample_rate);
+                    voice = Box::new(ModulatedVoice::new(voice, matrix, sample_rate)?);
+                }
+                // Smoothing sits outside modulation so it only glides the values the layer sets
+                let smoothed = Self::build_smoothing(voice, &config.smoothing, sample_rate)
+                    .with_portamento(config.portamento);
+                Ok(Box::new(smoothed) as Box<dyn Voice>)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(VoiceAllocator::new(voices, config.steal))
+    }
+
+    /// Wrap a voice with the layer's parameter smoothing
+    fn build_smoothing(voice: Box<dyn Voice>, config: &SmoothingConfig, sample_rate: f64) -> SmoothedVoice {
+        let mut smoothed = SmoothedVoice::new(voice, sample_rate)
+            .with_time(config.time)
+            .with_curve(Self::smoothing_curve(config.curve));
+        for (name, param) in &config.params {
+            smoothed = smoothed.with_param(name.clone(), param.time, param.curve.map(Self::smoothing_curve));
+        }
+        smoothed
+    }
+
+    fn smoothing_curve(kind: CurveKind) -> SmoothingCurve {
+        match kind {
+            CurveKind::Linear => SmoothingCurve::Linear,
+            CurveKind::Exponential => SmoothingCurve::Exponential,
+        }
+    }
+
+    /// Set a voice's starting parameters from the layer's patch
+    ///
+    /// Stepped parameters go first: switching a percussion model, say,
+    /// resets the pitch and decay a patch may also set.
+    fn apply_patch(voice: &mut dyn Voice, patch: &HashMap<String, PatchValue>) -> Result<()> {
+        let params = voice.params();
+        let mut values = patch
+            .iter()
+            .map(|(name, value)| Ok((name.as_str(), value.resolve(params, name).context("invalid patch")?)))
+            .collect::<Result<Vec<_>>>()?;
+        let stepped = |name: &str| params.iter().any(|p| p.matches(name) && p.kind == ParamKind::Stepped);
+        values.sort_by_key(|(name, _)| (!stepped(name), *name));
+        for (name, value) in values {
+            voice.set_parameter(name, value);
+        }
+        Ok(())
+    }
+
+    /// Reshape a voice's amplitude envelope
+    fn apply_envelope(envelope: &mut Envelope, config: &EnvelopeConfig) {
+        if let Some(attack) = config.attack {
+            envelope.set_attack(attack);
+        }
+        if let Some(decay) = config.decay {
+            envelope.set_decay(decay);
+        }
+        if let Some(sustain) = config.sustain {
+            envelope.set_sustain(sustain);
+        }
+        if let Some(release) = config.release {
+            envelope.set_release(release);
+        }
+        Self::apply_envelope_shape(envelope, &config.shape);
+
+        // Voices that start sounding (like the drone) restart with the new shape
+        if envelope.is_active() {
+            envelope.trigger();
+        }
+    }
+
+    /// Apply delay, hold, curves, looping and breakpoints to an envelope
+    fn apply_envelope_shape(envelope: &mut Envelope, config: &EnvelopeShapeConfig) {
+        let curve = |kind: EnvelopeCurveKind| match kind {
+            EnvelopeCurveKind::Linear => EnvelopeCurve::Linear,
+            EnvelopeCurveKind::Exponential => EnvelopeCurve::Exponential,
+            EnvelopeCurveKind::Logarithmic => EnvelopeCurve::Logarithmic,
+        };
+        let segment = |kind: Option<EnvelopeCurveKind>| curve(kind.unwrap_or(config.curve));
+
+        envelope.set_delay(config.delay);
+        envelope.set_hold(config.hold);
+        envelope.set_curves(
+            segment(config.attack_curve),
+            segment(config.decay_curve),
+            segment(config.release_curve),
+        );
+        envelope.set_loop(config.looping);
+        let points = config
+            .points
+            .iter()
+            .map(|p| Breakpoint::new(p.level, p.time, segment(p.curve)))
+            .collect();
+        envelope.set_breakpoints(points, config.sustain_point);
+    }
+
+    /// Build the layer's output filter
+    fn build_filter(config: &FilterConfig, sample_rate: f64) -> Filter {
+        let filter_type = match config.kind {
+            FilterKind::Lowpass => FilterType::LowPass,
+            FilterKind::Highpass => FilterType::HighPass,
+            FilterKind::Bandpass => FilterType::BandPass,
+            FilterKind::Notch => FilterType::Notch,
+            FilterKind::Peaking => FilterType::Peaking,
+            FilterKind::LowShelf => FilterType::LowShelf,
+            FilterKind::HighShelf => FilterType::HighShelf,
+            FilterKind::Allpass => FilterType::AllPass,
+        };
+        let model = match config.model {
+            FilterModelKind::Biquad => FilterModel::Biquad,
+            FilterModelKind::Svf => FilterModel::StateVariable,
+            FilterModelKind::Ladder => FilterModel::Ladder,
+        };
+
+        let mut filter = Filter::with_model(sample_rate, filter_type, model);
+        filter.set_cutoff(config.cutoff);
+        filter.set_resonance(config.resonance);
+        filter.set_gain(config.gain);
+        filter.set_drive(config.drive);
+        filter
+    }
+
+    /// Build the modulation matrix for one voice
+    fn build_matrix(config: &ModulationConfig, voice_index: usize, beats_per_bar: u32, sample_rate: f64) -> ModMatrix {
+        let mut matrix = ModMatrix::new();
+        for (i, (name, source)) in config.sources.iter().enumerate() {
+            let sync = source.sync.as_deref().and_then(|s| NoteDivision::parse(s).ok());
+            let mut modulator = match source.kind {
+                ModSourceKind::Lfo => {
+                    let mut modulator = ModSource::lfo(
+                        sample_rate,
+                        source.rate,
+                        LfoShape::from_name(&source.shape).unwrap_or(LfoShape::Sine),
+                    );
+                    if let ModSource::Lfo(lfo) = &mut modulator {
+                        lfo.set_phase_offset(source.phase);
+                        lfo.set_fade_in(source.fade_in);
+                        lfo.set_retrigger(source.retrigger);
+                        lfo.set_unipolar(source.polarity == Some(ModPolarity::Unipolar));
+                        lfo.set_rate_range(source.rate_source.as_ref().and(source.rate_max));
+                    }
+                    modulator
+                }
+                ModSourceKind::Envelope => ModSource::envelope(
+                    sample_rate,
+                    source.attack,
+                    source.decay,
+                    source.sustain,
+                    source.release,
+                ),
+                ModSourceKind::Data => {
+                    ModSource::data(source.in_min.unwrap_or(0.0), source.in_max.unwrap_or(100.0))
+                }
+                // Seed per voice and source so voices don't wander in step
+                ModSourceKind::Random => ModSource::random(
+                    source.rate,
+                    source.smooth,
+                    ((voice_index * 64 + i + 1) as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15),
+                ),
+            };
+            if let ModSource::Envelope(envelope) = &mut modulator {
+                Self::apply_envelope_shape(envelope, &source.envelope);
+            }
+            if let Some(division) = sync {
+                modulator = modulator.with_sync(division.beats(beats_per_bar));
+            }
+            matrix.add_source(name.clone(), modulator);
+        }
+
+        // Rate sources are wired once every source exists
+        for (name, source) in &config.sources {
+            if let Some(rate_source) = &source.rate_source {
+                matrix.add_rate_route(name, rate_source);
+            }
+        }
+
+        for route in &config.routes {
+            let polarity = route.polarity.map(|p| match p {
+                ModPolarity::Bipolar => Polarity::Bipolar,
+                ModPolarity::Unipolar => Polarity::Unipolar,
+            });
+            matrix.add_route(&route.source, route.target.clone(), route.depth, polarity);
+        }
+        matrix
+    }
+
+    /// Create one voice for a layer
+    fn build_voice(
+        config: &LayerConfig,
+        master: &MasterConfig,
+        scale: &Scale,
+        samples: &[SampleBuffer],
+        wavetable: Option<&Wavetable>,
+        sample_rate: f64,
+    ) -> Box<dyn Voice> {
+        match config.voice {
+            VoiceKind::Drone => match wavetable {
+                Some(table) => Box::new(DroneVoice::with_wavetable(sample_rate, table.clone())),
+                None => Box::new(DroneVoice::new(sample_rate)),
+            },
+            VoiceKind::Percussion => {
+                Box::new(PercussionVoice::new(sample_rate, PercussionModel::Kick))
+            }
+            VoiceKind::Melody => {
+                let mut voice = MelodyVoice::new(sample_rate, master.bpm as f64, Self::root_hz(master, 4), scale.clone());
+                if let Some(step) = config.step.as_deref().and_then(|s| NoteDivision::parse(s).ok()) {
+                    // `division` counts steps per beat
+                    voice.set_parameter("division", 1.0 / step.beats(master.beats_per_bar));
+                }
+                Box::new(voice)
+            }
+            // Granulate the first sample if one is configured, otherwise noise
+            VoiceKind::Texture => match samples.first() {
+                Some(sample) => Box::new(TextureVoice::with_sample(sample_rate, sample.clone())),
+                None => Box::new(TextureVoice::new(sample_rate)),
+            },
+            VoiceKind::Fm => Box::new(FmVoice::new(sample_rate)),
+            VoiceKind::Sampler => Box::new(SamplerVoice::new(sample_rate, samples.to_vec())),
+        }
+    }
+
+    /// Frequency of the master key in the given octave
+    fn root_hz(master: &MasterConfig, octave: i32) -> f64 {
+        key_to_hz(&master.key, octave).unwrap_or(261.63)
+    }
+
+    /// Build a mapping pipeline from config
+    pub(super) fn build_pipeline(config: &MappingConfig, master: &MasterConfig, scale: &Scale) -> MappingPipeline {
+        let in_min = config.in_min.unwrap_or(0.0);
+        let in_max = config.in_max.unwrap_or(100.0);
+        let out_min = config.out_min.unwrap_or(0.0);
+        let out_max = config.out_max.unwrap_or(1.0);
+
+        match config.kind {
+            MappingKind::Linear => {
+                MappingPipeline::new()
+                    .with(LinearMapper::new("linear", in_min, in_max, out_min, out_max))
+            }
+            MappingKind::Logarithmic => {
+                MappingPipeline::new()
+                    .with(LogarithmicMapper::new("logarithmic", in_min, in_max, out_min, out_max))
+            }
+            MappingKind::Exponential => {
+                // True exponential mapper (inverse of logarithmic)
+                // Creates a curve where small input changes at low values
+                // produce large output changes (slow start, fast finish)
+                MappingPipeline::new()
+                    .with(ExponentialMapper::new("exponential", in_min, in_max, out_min, out_max))
+            }
+            MappingKind::Threshold => {
+                // Use midpoint of input range as threshold
+                let threshold = (in_min + in_max) / 2.0;
+                MappingPipeline::new()
+                    .with(ThresholdMapper::new("threshold", threshold)
+                        .with_direction(ThresholdDirection::Rising)
+                        .with_trigger_value(out_max)
+                        .with_rest_value(out_min))
+            }
+            MappingKind::Quantize => {
+                // Map input range to frequency range, then snap to the master key and scale
+                MappingPipeline::new()
+                    .with(LinearMapper::new("range", in_min, in_max, out_min, out_max))
+                    .with(QuantizeMapper::new("quantize", Self::root_hz(master, 3), scale.clone()))
+            }
+        }
+    }
+
+    /// Get the voice type
+    pub fn voice_kind(&self) -> &VoiceKind {
+        &self.voice_kind
+    }
+
+    /// Get the layer volume
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Set the layer volume (0.0 to 1.0)
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    /// Get the bus sends
+    pub fn sends(&self) -> &[LayerSend] {
+        &self.sends
+    }
+
+    /// Add a send to a bus (replaces an existing send to the same bus)
+    pub(crate) fn add_send(&mut self, bus: &str, index: usize, level: f32) {
+        self.sends.retain(|s| s.bus != bus);
+        self.sends.push(LayerSend {
+            bus: bus.to_string(),
+            index,
+            level: level.clamp(0.0, 1.0),
+        });
+    }
+
+    /// Get the current peak output level
+    pub fn level(&self) -> f64 {
+        self.level
+    }
+
+    /// Set a parameter by name
+    ///
+    /// `send.<bus>` sets the send level to that bus, `filter.<param>` sets
+    /// the layer filter's `cutoff`, `resonance`, `gain`, `drive` or `type`
+    /// (a response index), `<effect>.<param>` sets a parameter of a layer
+    /// effect, `pan` and `width` glide the stereo placement, and `trigger`
+    /// fires the voice when it rises above zero (pair it with a threshold
+    /// mapping). Everything else goes to the voice; polyphonic layers hold
+    /// the value for the next note.
+    pub fn set_parameter(&mut self, name: &str, value: f64) {
+        let tick = 1.0 / self.sample_rate;
+        if name == "pan" {
+            self.pan.set_target(value.clamp(-1.0, 1.0), self.glide, tick);
+        } else if name == "width" {
+            self.width.set_target(value.clamp(0.0, 2.0), self.glide, tick);
+        } else if name == "trigger" {
+            if value > 0.0 && self.trigger_level <= 0.0 {
+                self.note_on();
+            }
+            self.trigger_level = value;
+        } else if let Some(bus) = name.strip_prefix("send.") {
+            if let Some(send) = self.sends.iter_mut().find(|s| s.bus == bus) {
+                send.level = (value as f32).clamp(0.0, 1.0);
+            }
+        } else if let Some(param) = name.strip_prefix("filter.") {
+            for filter in self.filter.iter_mut().flatten() {
+                match param {
+                    "cutoff" => filter.set_cutoff(value),
+                    "resonance" => filter.set_resonance(value),
+                    "gain" => filter.set_gain(value),
+                    "drive" => filter.set_drive(value),
+                    "type" => filter.set_type(FilterType::from_index(value)),
+                    _ => {}
+                }
+            }
+        } else if let Some((effect, param)) = self.effect_param_mut(name) {
+            effect.set_parameter(param, value);
+        } else {
+            self.voices.set_parameter(name, value);
+        }
+    }
+
+    /// Get a parameter by name
+    pub fn get_parameter(&self, name: &str) -> Option<f64> {
+        if name == "pan" {
+            Some(self.pan.target())
+        } else if name == "width" {
+            Some(self.width.target())
+        } else if name == "trigger" {
+            Some(self.trigger_level)
+        } else if let Some(bus) = name.strip_prefix("send.") {
+            self.sends
+                .iter()
+                .find(|s| s.bus == bus)
+                .map(|s| s.level as f64)
+        } else if let Some(param) = name.strip_prefix("filter.") {
+            let filter = &self.filter.as_ref()?[0];
+            match param {
+                "cutoff" => Some(filter.cutoff()),
+                "resonance" => Some(filter.resonance()),
+                "gain" => Some(filter.gain()),
+                "drive" => Some(filter.drive()),
+                "type" => Some(filter.filter_type().index() as f64),
+                _ => None,
+            }
+        } else if let Some((effect, param)) = self.effect_param(name) {
+            effect.get_parameter(param)
+        } else {
+            self.voices.get_parameter(name)
+        }
+    }
+
+    /// Split `<effect>.<param>` when it names one of the layer's effects
+    fn effect_param<'a>(&self, name: &'a str) -> Option<(&dyn Effect, &'a str)> {
+        let (effect, param) = name.split_once('.')?;
+        let (_, effect) = self.effects.iter().find(|(n, _)| n == effect)?;
+        Some((effect.as_ref(), param))
+    }
+
+    fn effect_param_mut<'a>(&mut self, name: &'a str) -> Option<(&mut Box<dyn Effect>, &'a str)> {
+        let (effect, param) = name.split_once('.')?;
+        let (_, effect) = self.effects.iter_mut().find(|(n, _)| n == effect)?;
+        Some((effect, param))
+    }
+
+    /// Get the names of the layer effects in processing order
+    pub fn effect_names(&self) -> impl Iterator<Item = &str> {
+        self.effects.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Process a data point and update voice parameters
+    pub fn process_data(&mut self, data: &DataPoint) {
+        self.process_interpolated(data, data);
+    }
+
+    /// Process a data point whose fields have been moved to their
+    /// interpolated positions
+    ///
+    /// `trigger` mappings read the `raw` values instead, so only thresholds
+    /// the data itself crosses fire notes.
+    pub fn process_interpolated(&mut self, data: &DataPoint, raw: &DataPoint) {
+        let mut updates = Vec::new();
+        for (param_name, (field_name, pipeline)) in &self.mappings {
+            let point = if param_name == "trigger" { raw } else { data };
+            if let Some(&value) = point.values.get(field_name) {
+                updates.push((param_name.clone(), pipeline.apply(value)));
+            }
+        }
+        for (param_name, value) in updates {
+            self.set_parameter(&param_name, value);
+        }
+        for (key, field) in &self.mod_fields {
+            if let Some(&value) = data.values.get(field) {
+                self.voices.set_parameter(key, value);
+            }
+        }
+
+        // Sample events pick a sample and play it; other trigger events replay the current one
+        let mut triggered = false;
+        for event in &data.events {
+            for (sample_event, index) in &self.sample_events {
+                if sample_event == event {
+                    self.voices.set_parameter("sample", *index as f64);
+                    self.voices.sync(self.bpm, self.beat);
+                    self.voices.note_on();
+                    triggered = true;
+                }
+            }
+        }
+
+        if !triggered && data.events.iter().any(|e| self.triggers.contains(e)) {
+            self.note_on();
+        }
+    }
+
+    /// Apply the interpolated value of one field between data points
+    ///
+    /// Runs on the audio thread, so nothing is allocated. `trigger` mappings
+    /// are skipped: a ramp between two points must not fire notes.
+    pub fn process_value(&mut self, field: &str, value: f64) {
+        let mappings = std::mem::take(&mut self.mappings);
+        for (param_name, (field_name, pipeline)) in &mappings {
+            if field_name == field && param_name != "trigger" {
+                self.set_parameter(param_name, pipeline.apply(value));
+            }
+        }
+        self.mappings = mappings;
+        for (key, field_name) in &self.mod_fields {
+            if field_name == field {
+                self.voices.set_parameter(key, value);
+            }
+        }
+    }
+
+    /// Generate the next sample from this layer (mono mix, post-volume)
+    pub fn process(&mut self) -> f64 {
+        self.process_frame().mid()
+    }
+
+    /// Generate the next stereo frame from this layer (post-volume)
+    pub fn process_frame(&mut self) -> Frame {
+        let mut output = self.voices.process_frame();
+        if let Some([left, right]) = &mut self.filter {
+            output = Frame::new(left.process(output.left), right.process(output.right));
+        }
+        for (_, effect) in &mut self.effects {
+            output = effect.process(output);
+        }
+        output = output.with_width(self.width.advance()).pan(self.pan.advance()) * self.volume as f64;
+
+        self.level = (self.level * METER_DECAY).max(output.peak());
+        self.beat += self.bpm / (60.0 * self.sample_rate);
+        output
+    }
+
+    /// Trigger a note (on a free voice when polyphonic)
+    pub fn trigger(&mut self) {
+        self.note_on();
+    }
+
+    /// Start a note from the current beat, so sequencers stay on the grid
+    /// even after sitting idle
+    fn note_on(&mut self) {
+        self.voices.sync(self.bpm, self.beat);
+        self.voices.note_on();
+    }
+
+    /// Release all voices
+    pub fn release(&mut self) {
+        self.voices.release_all();
+    }
+
+    /// Follow the transport tempo and beat position
+    pub fn sync(&mut self, bpm: f64, beat: f64) {
+        (self.bpm, self.beat) = (bpm, beat);
+        self.voices.sync(bpm, beat);
+        for (_, effect) in &mut self.effects {
+            effect.sync(bpm, beat);
+        }
+    }
+
+    /// Check if any voice is active
+    pub fn is_active(&self) -> bool {
+        self.voices.is_active()
+    }
+
+    /// Get the number of voices this layer can play at once
+    pub fn polyphony(&self) -> usize {
+        self.voices.polyphony()
+    }
+
+    /// Get the number of voices currently sounding
+    pub fn active_voices(&self) -> usize {
+        self.voices.active_voices()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{MappingConfig, MappingKind, SampleConfig, VoiceKind};
+    use std::collections::HashMap;
+
+    fn test_layer_config() -> LayerConfig {
+        let mut mappings = HashMap::new();
+        mappings.insert(
+            "pitch".to_string(),
+            MappingConfig {
+                field: "temperature".to_string(),
+                kind: MappingKind::Linear,
+                in_min: Some(-20.0),
+                in_max: Some(40.0),
+                out_min: Some(100.0),
+                out_max: Some(400.0),
+            },
+        );
+
+        LayerConfig {
+            mappings,
+            volume: 0.8,
+            ..LayerConfig::new("test_drone", VoiceKind::Drone, "weather")
+        }
+    }
+
+    #[test]
+    fn test_layer_creation() {
+        let config = test_layer_config();
+        let layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+
+        assert_eq!(layer.name, "test_drone");
+        assert_eq!(layer.source, "weather");
+        assert_eq!(layer.voice_kind(), &VoiceKind::Drone);
+        assert_eq!(layer.volume(), 0.8);
+        // DroneVoice starts active by default (for sustained drones)
+        assert!(layer.is_active());
+    }
+
+    #[test]
+    fn test_layer_process_data() {
+        let config = test_layer_config();
+        let mut layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+        layer.trigger();
+
+        let data = DataPoint::new("weather")
+            .with_value("temperature", 10.0)
+            .with_value("humidity", 50.0);
+
+        layer.process_data(&data);
+
+        // -20..40 -> 100..400, so 10 degrees lands at 250 Hz
+        assert_eq!(layer.get_parameter("pitch"), Some(250.0));
+        assert!(layer.is_active());
+    }
+
+    #[test]
+    fn test_layer_send_parameter() {
+        let mut layer = Layer::new(&test_layer_config(), &MasterConfig::default(), 44100.0).unwrap();
+        layer.add_send("reverb", 0, 0.3);
+
+        assert_eq!(layer.get_parameter("send.reverb"), Some(0.3f32 as f64));
+        layer.set_parameter("send.reverb", 0.6);
+        assert_eq!(layer.sends()[0].level, 0.6);
+
+        // Unknown sends are ignored
+        layer.set_parameter("send.delay", 1.0);
+        assert_eq!(layer.sends().len(), 1);
+        assert_eq!(layer.get_parameter("send.delay"), None);
+    }
+
+    #[test]
+    fn test_layer_polyphonic_drone_follows_mappings() {
+        let mut mappings = HashMap::new();
+        mappings.insert(
+            "amplitude".to_string(),
+            MappingConfig {
+                field: "humidity".to_string(),
+                kind: MappingKind::Linear,
+                in_min: Some(0.0),
+                in_max: Some(100.0),
+                out_min: Some(0.0),
+                out_max: Some(1.0),
+            },
+        );
+        let config = LayerConfig {
+            mappings,
+            polyphony: 3,
+            ..LayerConfig::new("pad", VoiceKind::Drone, "weather")
+        };
+        let mut layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+        assert!((0..2000).any(|_| layer.process_frame().peak() > 0.01));
+
+        // The sounding drone hears the mapping; nothing waits for a note
+        layer.process_data(&DataPoint::new("weather").with_value("humidity", 0.0));
+        for _ in 0..22050 {
+            layer.process_frame();
+        }
+        assert!((0..2000).all(|_| layer.process_frame().peak() < 1e-3));
+    }
+
+    #[test]
+    fn test_layer_event_trigger() {
+        let mut config = LayerConfig::new("commits", VoiceKind::Percussion, "git");
+        config.triggers.push("commit".to_string());
+        let mut layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+        assert!(!layer.is_active());
+
+        layer.process_data(&DataPoint::new("git").with_event("staged"));
+        assert!(!layer.is_active());
+
+        layer.process_data(&DataPoint::new("git").with_event("commit"));
+        assert!(layer.is_active());
+    }
+
+    #[test]
+    fn test_layer_threshold_trigger() {
+        let mut mappings = HashMap::new();
+        mappings.insert(
+            "trigger".to_string(),
+            MappingConfig {
+                field: "activity".to_string(),
+                kind: MappingKind::Threshold,
+                in_min: Some(0.0),
+                in_max: Some(100.0),
+                out_min: Some(0.0),
+                out_max: Some(1.0),
+            },
+        );
+        let config = LayerConfig {
+            mappings,
+            ..LayerConfig::new("hits", VoiceKind::Percussion, "git")
+        };
+        let mut layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+
+        let point = |activity: f64| DataPoint::new("git").with_value("activity", activity);
+
+        layer.process_data(&point(20.0));
+        assert!(!layer.is_active());
+
+        // Crossing the midpoint fires once
+        layer.process_data(&point(80.0));
+        assert!(layer.is_active());
+        while layer.is_active() {
+            layer.process();
+        }
+
+        // Staying above the threshold does not retrigger
+        layer.process_data(&point(90.0));
+        assert!(!layer.is_active());
+
+        layer.process_data(&point(10.0));
+        layer.process_data(&point(70.0));
+        assert!(layer.is_active());
+    }
+
+    #[test]
+    fn test_layer_melody_uses_master_key() {
+        let master = MasterConfig {
+            key: "A".to_string(),
+            ..MasterConfig::default()
+        };
+        let config = LayerConfig::new("tune", VoiceKind::Melody, "price");
+        let mut layer = Layer::new(&config, &master, 44100.0).unwrap();
+        assert!(layer.is_active());
+
+        // The first note of a phrase is the root: A4
+        layer.process();
+        assert!((layer.get_parameter("note").unwrap() - 440.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_layer_melody_retrigger_stays_on_grid() {
+        // 120 BPM at two steps per beat: a step every 11025 samples
+        let master = MasterConfig {
+            bpm: 120.0,
+            ..MasterConfig::default()
+        };
+        let config = LayerConfig::new("tune", VoiceKind::Melody, "price");
+        let mut layer = Layer::new(&config, &master, 44100.0).unwrap();
+        for _ in 0..1000 {
+            layer.process();
+        }
+        layer.release();
+        for _ in 1000..40000 {
+            layer.process();
+        }
+        assert!(!layer.is_active());
+
+        // Retriggered between steps, the first note waits for step 4
+        layer.trigger();
+        for _ in 40000..44100 {
+            assert_eq!(layer.process(), 0.0);
+        }
+        let peak = (0..200).map(|_| layer.process().abs()).fold(0.0, f64::max);
+        assert!(peak > 0.0);
+    }
+
+    #[test]
+    fn test_layer_quantize_uses_master_scale() {
+        let mut mappings = HashMap::new();
+        mappings.insert(
+            "pitch".to_string(),
+            MappingConfig {
+                field: "price".to_string(),
+                kind: MappingKind::Quantize,
+                in_min: Some(0.0),
+                in_max: Some(100.0),
+                out_min: Some(100.0),
+                out_max: Some(400.0),
+            },
+        );
+        let config = LayerConfig {
+            mappings,
+            ..LayerConfig::new("drone", VoiceKind::Drone, "price")
+        };
+        let master = MasterConfig {
+            key: "D".to_string(),
+            scale: "major".to_string(),
+            ..MasterConfig::default()
+        };
+        let mut layer = Layer::new(&config, &master, 44100.0).unwrap();
+
+        // 50 -> 250 Hz, which snaps to B3 in D major (246.94 Hz)
+        layer.process_data(&DataPoint::new("price").with_value("price", 50.0));
+        let pitch = layer.get_parameter("pitch").unwrap();
+        assert!((pitch - 246.94).abs() < 0.01, "got {}", pitch);
+    }
+
+    #[test]
+    fn test_layer_texture_voice() {
+        let config = LayerConfig::new("grains", VoiceKind::Texture, "system");
+        let layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+        assert_eq!(layer.get_parameter("grain_size"), Some(0.08));
+
+        let mut config = LayerConfig::new("grains", VoiceKind::Texture, "system");
+        config.samples.push(SampleConfig {
+            path: "/nonexistent/rain.wav".into(),
+            events: vec![],
+        });
+        let err = Layer::new(&config, &MasterConfig::default(), 44100.0).err().unwrap();
+        assert!(format!("{:#}", err).contains("failed to create layer 'grains'"));
+    }
+
+    #[test]
+    fn test_layer_sample_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 44100,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut config = LayerConfig::new("hits", VoiceKind::Sampler, "price");
+        for (name, events) in [("pump.wav", vec!["pump"]), ("dump.wav", vec!["dump"])] {
+            let path = dir.path().join(name);
+            let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+            for _ in 0..100 {
+                writer.write_sample(0.5f32).unwrap();
+            }
+            writer.finalize().unwrap();
+            config.samples.push(SampleConfig {
+                path,
+                events: events.into_iter().map(String::from).collect(),
+            });
+        }
+        let mut layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+        assert!(!layer.is_active());
+
+        layer.process_data(&DataPoint::new("price").with_event("dump"));
+        assert!(layer.is_active());
+        assert_eq!(layer.get_parameter("sample"), Some(1.0));
+
+        layer.process_data(&DataPoint::new("price").with_event("pump"));
+        assert_eq!(layer.get_parameter("sample"), Some(0.0));
+    }
+
+    #[test]
+    fn test_layer_level_meter() {
+        let mut layer = Layer::new(&test_layer_config(), &MasterConfig::default(), 44100.0).unwrap();
+        assert_eq!(layer.level(), 0.0);
+
+        for _ in 0..2000 {
+            layer.process();
+        }
+        assert!(layer.level() > 0.0);
+    }
+
+    #[test]
+    fn test_layer_modulation() {
+        let yaml = r#"
+name: pad
+voice: drone
+source: weather
+modulation:
+  sources:
+    wind: {kind: data, field: wind_speed, in_max: 20}
+    wobble: {kind: lfo, rate: 2, shape: triangle}
+  routes:
+    - {source: wind, target: amplitude, depth: -0.5}
+    - {source: wobble, target: filter, depth: 400}
+"#;
+        let config: LayerConfig = serde_yaml::from_str(yaml).unwrap();
+        let mut layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+        let rms = |layer: &mut Layer| {
+            let sum: f64 = (0..22050).map(|_| layer.process().powi(2)).sum();
+            (sum / 22050.0).sqrt()
+        };
+        // Past the attack
+        rms(&mut layer);
+        let calm = rms(&mut layer);
+
+        // Full wind pulls the amplitude down by 0.5; the base value is untouched
+        layer.process_data(&DataPoint::new("weather").with_value("wind_speed", 20.0));
+        let windy = rms(&mut layer);
+        assert!(windy < calm * 0.5, "{} vs {}", windy, calm);
+        assert_eq!(layer.get_parameter("amplitude"), Some(0.7));
+
+        let mut bad = config.clone();
+        bad.modulation.routes[0].target = "wobble".to_string();
+        let err = Layer::new(&bad, &MasterConfig::default(), 44100.0).err().unwrap();
+        assert!(format!("{:#}", err).contains("unknown modulation target 'wobble'"));
+    }
+
+    #[test]
+    fn test_layer_smoothing() {
+        let config = LayerConfig {
+            portamento: 0.3,
+            ..test_layer_config()
+        };
+        let mut layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+        assert_eq!(layer.get_parameter("portamento"), Some(0.3));
+
+        // A data update ramps the output instead of stepping it
+        layer.set_parameter("amplitude", 0.0);
+        for _ in 0..44100 {
+            layer.process();
+        }
+        layer.set_parameter("amplitude", 1.0);
+        let first = (0..64).map(|_| layer.process().abs()).fold(0.0, f64::max);
+        assert!(first < 0.1, "{}", first);
+        assert_eq!(layer.get_parameter("amplitude"), Some(1.0));
+    }
+
+    #[test]
+    fn test_layer_filter() {
+        let peak = |layer: &mut Layer| {
+            (0..44100).map(|_| layer.process().abs()).fold(0.0, f64::max)
+        };
+        let mut open = Layer::new(&test_layer_config(), &MasterConfig::default(), 44100.0).unwrap();
+        assert_eq!(open.get_parameter("filter.cutoff"), None);
+
+        let config = LayerConfig {
+            filter: Some(FilterConfig {
+                kind: FilterKind::Highpass,
+                model: FilterModelKind::Ladder,
+                cutoff: 8000.0,
+                resonance: 0.707,
+                gain: 0.0,
+                drive: 2.0,
+            }),
+            ..test_layer_config()
+        };
+        let mut filtered = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+        assert!(peak(&mut filtered) < peak(&mut open) * 0.5);
+
+        filtered.set_parameter("filter.cutoff", 500.0);
+        filtered.set_parameter("filter.type", 3.0);
+        assert_eq!(filtered.get_parameter("filter.cutoff"), Some(500.0));
+        assert_eq!(filtered.get_parameter("filter.type"), Some(3.0));
+        assert_eq!(filtered.get_parameter("filter.drive"), Some(2.0));
+    }
+
+    #[test]
+    fn test_layer_patch() {
+        let mut config = LayerConfig::new("hits", VoiceKind::Percussion, "git");
+        config.patch = serde_yaml::from_str("{tone: 0.9, model: snare, decay: 0.15}").unwrap();
+        let layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+        assert_eq!(layer.get_parameter("model"), Some(1.0));
+        // Set after the model, so the snare's own decay doesn't win
+        assert_eq!(layer.get_parameter("decay"), Some(0.15));
+        assert_eq!(layer.get_parameter("tone"), Some(0.9));
+
+        let mut config = LayerConfig::new("pad", VoiceKind::Drone, "weather");
+        config.patch = serde_yaml::from_str("{waveform: triangle, detune: 12, filter_type: bandpass}").unwrap();
+        let mut layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+        assert_eq!(layer.get_parameter("waveform"), Some(3.0));
+        assert_eq!(layer.get_parameter("detune"), Some(12.0));
+        assert_eq!(layer.get_parameter("filter_type"), Some(2.0));
+        assert!((0..1000).map(|_| layer.process()).any(|s| s != 0.0));
+
+        config.patch = serde_yaml::from_str("{model: snare}").unwrap();
+        assert!(Layer::new(&config, &MasterConfig::default(), 44100.0).is_err());
+    }
+
+    #[test]
+    fn test_layer_envelope() {
+        let yaml = "release: 0.05\npoints:\n  - {level: 1.0, time: 0.01}\n  - {level: 0.5, time: 0.2, curve: exponential}\nsustain_point: 1\n";
+        let config = LayerConfig {
+            envelope: Some(serde_yaml::from_str(yaml).unwrap()),
+            ..LayerConfig::new("hits", VoiceKind::Sampler, "git")
+        };
+        assert!(Layer::new(&config, &MasterConfig::default(), 44100.0).is_err());
+
+        let config = LayerConfig {
+            voice: VoiceKind::Drone,
+            ..config
+        };
+        let mut layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+        assert!(layer.is_active());
+
+        // Held at the sustain point, then gone shortly after release
+        for _ in 0..44100 {
+            layer.process();
+        }
+        assert!(layer.is_active());
+        layer.release();
+        for _ in 0..4410 {
+            layer.process();
+        }
+        assert!(!layer.is_active());
+
+        let fm = LayerConfig {
+            voice: VoiceKind::Fm,
+            ..config
+        };
+        assert!(Layer::new(&fm, &MasterConfig::default(), 44100.0).is_err());
+    }
+
+    #[test]
+    fn test_layer_polyphonic_events_overlap() {
+        let mut mappings = HashMap::new();
+        mappings.insert(
+            "pitch".to_string(),
+            MappingConfig {
+                field: "price".to_string(),
+                kind: MappingKind::Linear,
+                in_min: Some(0.0),
+                in_max: Some(100.0),
+                out_min: Some(50.0),
+                out_max: Some(150.0),
+            },
+        );
+        let config = LayerConfig {
+            mappings,
+            triggers: vec!["tick".to_string()],
+            polyphony: 4,
+            ..LayerConfig::new("ticks", VoiceKind::Percussion, "price")
+        };
+        let mut layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+        assert_eq!(layer.polyphony(), 4);
+
+        let tick = |price: f64| DataPoint::new("price").with_value("price", price).with_event("tick");
+        layer.process_data(&tick(10.0));
+        for _ in 0..100 {
+            layer.process();
+        }
+        layer.process_data(&tick(90.0));
+        layer.process_data(&tick(50.0));
+
+        // Each tick rings on its own voice with its own pitch
+        assert_eq!(layer.active_voices(), 3);
+        assert_eq!(layer.get_parameter("pitch"), Some(100.0));
+    }
+
+    #[test]
+    fn test_layer_polyphonic_drone_starts_with_one_voice() {
+        let config = LayerConfig {
+            polyphony: 3,
+            ..LayerConfig::new("pad", VoiceKind::Drone, "weather")
+        };
+        let mut layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+        layer.process();
+        assert_eq!(layer.active_voices(), 1);
+
+        layer.trigger();
+        assert_eq!(layer.active_voices(), 2);
+    }
+
+    #[test]
+    fn test_layer_pan_and_width() {
+        let config = LayerConfig {
+            pan: -1.0,
+            ..LayerConfig::new("pad", VoiceKind::Drone, "weather")
+        };
+        let mut layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+        let frames: Vec<Frame> = (0..2000).map(|_| layer.process_frame()).collect();
+        assert!(frames.iter().any(|f| f.left.abs() > 0.01));
+        assert!(frames.iter().all(|f| f.right.abs() < 1e-9));
+
+        // Width 0 folds the drone's stereo stack to mono
+        let config = LayerConfig {
+            width: 0.0,
+            ..LayerConfig::new("pad", VoiceKind::Drone, "weather")
+        };
+        let mut layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+        for _ in 0..2000 {
+            let frame = layer.process_frame();
+            assert!((frame.left - frame.right).abs() < 1e-9);
+        }
+
+        // Pan is a mappable layer parameter
+        layer.set_parameter("pan", 0.5);
+        assert_eq!(layer.get_parameter("pan"), Some(0.5));
+        layer.set_parameter("width", 5.0);
+        assert_eq!(layer.get_parameter("width"), Some(2.0));
+    }
+
+    #[test]
+    fn test_layer_effects() {
+        let side = |layer: &mut Layer| (0..20000).map(|_| layer.process_frame().side().abs()).fold(0.0, f64::max);
+
+        // The melody voice is mono; a chorus spreads it
+        let config = LayerConfig::new("tune", VoiceKind::Melody, "price");
+        let mut layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+        assert!(side(&mut layer) < 1e-9);
+
+        let mut config = LayerConfig::new("tune", VoiceKind::Melody, "price");
+        config.effects = serde_yaml::from_str("[{kind: chorus, rate: 2.0, depth: 5.0}]").unwrap();
+        let mut layer = Layer::new(&config, &MasterConfig::default(), 44100.0).unwrap();
+        assert_eq!(layer.effect_names().collect::<Vec<_>>(), vec!["chorus"]);
+        assert!(side(&mut layer) > 0.01);
+
+        // Effect parameters are mappable as `<effect>.<param>`
+        assert_eq!(layer.get_parameter("chorus.depth"), Some(5.0));
+        layer.set_parameter("chorus.rate", 0.5);
+        assert_eq!(layer.get_parameter("chorus.rate"), Some(0.5));
+        assert_eq!(layer.get_parameter("flanger.rate"), None);
+    }
+}
@@ -111,7 +111,9 @@ impl MidiPlayer {
         };
 
         let port_name_actual = midi_out.port_name(&port)?;
-        let conn = midi_out.connect(&port, "drift-output")?;
+        let conn = midi_out
+            .connect(&port, "drift-output")
+            .map_err(|e| anyhow!("Failed to connect MIDI port: {}", e))?;
 
         let (sender, receiver) = mpsc::channel::<MidiPlayerCommand>();
 
@@ -1,98 +1,411 @@
 //! Audio engine for Drift
 //!
-//! Manages audio output and voice mixing.
+//! The engine is the audio graph: it owns the layers (source -> mappings ->
+//! voice), the send buses they feed (with their effects), and the master bus. Player, Recorder
+//! and the visualization all consume it.
 
+mod allocator;
+mod bus;
+mod layer;
 mod midi;
-mod mixer;
 mod player;
 mod recorder;
+mod series;
+mod transport;
 
+pub use allocator::VoiceAllocator;
+pub use bus::Bus;
+pub use layer::{Layer, LayerSend};
 pub use midi::{default_port_name, list_midi_ports, MidiConfig, MidiMessage, MidiPlayer};
-pub use mixer::{Mixer, MixerLayer};
 pub use player::{default_device_name, list_output_devices, Player};
 pub use recorder::Recorder;
+pub use series::{DataSeries, Series, INTERPOLATION_INTERVAL};
+pub use transport::{NoteDivision, Position, Transport, TICKS_PER_BEAT};
 
-use crate::config::DriftConfig;
-use crate::synth::{DroneVoice, Voice};
-use anyhow::Result;
+use crate::config::{BusConfig, DriftConfig, LayerConfig};
+use crate::sources::DataPoint;
+use crate::synth::Frame;
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
 
 /// The main audio engine
 pub struct Engine {
     config: DriftConfig,
-    voices: Vec<Box<dyn Voice>>,
+    /// Layers in configuration order
+    layers: Vec<Layer>,
+    /// Send buses
+    buses: Vec<Bus>,
+    /// Master bus (all layers and buses end up here)
+    master: Bus,
+    /// Latest data from each source
+    latest_data: HashMap<String, DataPoint>,
+    /// Interpolated source fields
+    series: DataSeries,
+    /// Samples generated so far
+    clock: u64,
+    /// Musical clock (bars and beats at the master tempo)
+    transport: Transport,
     sample_rate: f64,
     running: bool,
 }
 
 impl Engine {
-    /// Create a new engine with the given configuration
-    pub fn new(config: DriftConfig) -> Self {
+    /// Create an engine with the buses and layers from the configuration
+    pub fn new(config: DriftConfig) -> Result<Self> {
         let sample_rate = config.audio.sample_rate as f64;
-        
-        Self {
+        let buses = config
+            .buses
+            .iter()
+            .map(|b| Bus::from_config(b, &config.master, sample_rate))
+            .collect::<Result<Vec<_>>>()?;
+        let master = BusConfig {
+            effects: config.master.effects.clone(),
+            ..BusConfig::new("master", config.master.volume)
+        };
+        let master = Bus::from_config(&master, &config.master, sample_rate)?;
+        let layers = config.layers.clone();
+        let series = DataSeries::new(&config.sources);
+        let transport = Transport::new(sample_rate, config.master.bpm as f64, config.master.beats_per_bar);
+
+        let mut engine = Self {
             config,
-            voices: Vec::new(),
+            layers: Vec::new(),
+            buses,
+            master,
+            latest_data: HashMap::new(),
+            series,
+            clock: 0,
+            transport,
             sample_rate,
             running: false,
+        };
+
+        for layer in &layers {
+            engine.add_layer(layer)?;
         }
+
+        Ok(engine)
     }
-    
+
     /// Get the sample rate
     pub fn sample_rate(&self) -> f64 {
         self.sample_rate
     }
-    
-    /// Add a voice to the engine
-    pub fn add_voice(&mut self, voice: Box<dyn Voice>) {
-        self.voices.push(voice);
+
+    /// Get the configuration the engine was built from
+    pub fn config(&self) -> &DriftConfig {
+        &self.config
+    }
+
+    /// Add a layer to the graph
+    pub fn add_layer(&mut self, config: &LayerConfig) -> Result<()> {
+        if self.layer(&config.name).is_some() {
+            bail!("Duplicate layer name '{}'", config.name);
+        }
+
+        let mut layer = Layer::new(config, &self.config.master, self.sample_rate)?;
+        layer.sync(self.transport.bpm(), self.transport.beats());
+
+        for (bus_name, level) in &config.sends {
+            let index = self
+                .buses
+                .iter()
+                .position(|b| b.name() == bus_name)
+                .ok_or_else(|| {
+                    anyhow!("Layer '{}' sends to unknown bus '{}'", config.name, bus_name)
+                })?;
+            layer.add_send(bus_name, index, *level);
+        }
+
+        self.layers.push(layer);
+        Ok(())
+    }
+
+    /// Get the number of layers
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Get all layers in order
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// Get a layer by name
+    pub fn layer(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|l| l.name == name)
     }
-    
-    /// Add a drone voice
-    pub fn add_drone(&mut self) -> usize {
-        let voice = DroneVoice::new(self.sample_rate);
-        self.voices.push(Box::new(voice));
-        self.voices.len() - 1
+
+    fn layer_mut(&mut self, name: &str) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|l| l.name == name)
     }
-    
-    /// Set a parameter on a voice
-    pub fn set_voice_parameter(&mut self, voice_index: usize, name: &str, value: f64) {
-        if let Some(voice) = self.voices.get_mut(voice_index) {
-            voice.set_parameter(name, value);
+
+    /// Set a parameter on a layer
+    ///
+    /// Returns false if no layer has that name.
+    pub fn set_layer_parameter(&mut self, layer: &str, name: &str, value: f64) -> bool {
+        match self.layer_mut(layer) {
+            Some(layer) => {
+                layer.set_parameter(name, value);
+                true
+            }
+            None => false,
         }
     }
-    
-    /// Generate the next sample (mix of all voices)
+
+    /// Get a parameter from a layer
+    pub fn get_layer_parameter(&self, layer: &str, name: &str) -> Option<f64> {
+        self.layer(layer).and_then(|l| l.get_parameter(name))
+    }
+
+    /// Set a layer's volume
+    ///
+    /// Returns false if no layer has that name.
+    pub fn set_layer_volume(&mut self, layer: &str, volume: f32) -> bool {
+        match self.layer_mut(layer) {
+            Some(layer) => {
+                layer.set_volume(volume);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Trigger a layer's voice
+    pub fn trigger_layer(&mut self, layer: &str) -> bool {
+        match self.layer_mut(layer) {
+            Some(layer) => {
+                layer.trigger();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Release a layer's voice
+    pub fn release_layer(&mut self, layer: &str) -> bool {
+        match self.layer_mut(layer) {
+            Some(layer) => {
+                layer.release();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Trigger all layers
+    pub fn trigger_all(&mut self) {
+        for layer in &mut self.layers {
+            layer.trigger();
+        }
+    }
+
+    /// Release all layers
+    pub fn release_all(&mut self) {
+        for layer in &mut self.layers {
+            layer.release();
+        }
+    }
+
+    /// Check if any layer is active
+    pub fn has_active_layers(&self) -> bool {
+        self.layers.iter().any(|l| l.is_active())
+    }
+
+    /// Get the send buses
+    pub fn buses(&self) -> &[Bus] {
+        &self.buses
+    }
+
+    /// Set a send bus volume
+    ///
+    /// Returns false if no bus has that name.
+    pub fn set_bus_volume(&mut self, bus: &str, volume: f32) -> bool {
+        match self.buses.iter_mut().find(|b| b.name() == bus) {
+            Some(bus) => {
+                bus.set_volume(volume);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bus_mut(&mut self, name: &str) -> Option<&mut Bus> {
+        if name == "master" {
+            return Some(&mut self.master);
+        }
+        self.buses.iter_mut().find(|b| b.name() == name)
+    }
+
+    /// Set an effect parameter on a send bus or `master` (`<effect>.<param>`)
+    ///
+    /// Returns false if no bus has that name.
+    pub fn set_bus_parameter(&mut self, bus: &str, name: &str, value: f64) -> bool {
+        match self.bus_mut(bus) {
+            Some(bus) => {
+                bus.set_parameter(name, value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get an effect parameter from a send bus or `master`
+    pub fn get_bus_parameter(&self, bus: &str, name: &str) -> Option<f64> {
+        if bus == "master" {
+            return self.master.get_parameter(name);
+        }
+        self.buses.iter().find(|b| b.name() == bus).and_then(|b| b.get_parameter(name))
+    }
+
+    /// Get the master volume
+    pub fn master_volume(&self) -> f32 {
+        self.master.volume()
+    }
+
+    /// Set the master volume
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.master.set_volume(volume);
+    }
+
+    /// Get the transport
+    pub fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    /// Get the tempo
+    pub fn bpm(&self) -> f64 {
+        self.transport.bpm()
+    }
+
+    /// Change the tempo; synced LFOs and sequencers follow from the current beat
+    pub fn set_bpm(&mut self, bpm: f64) {
+        self.transport.set_bpm(bpm);
+        let (bpm, beat) = (self.transport.bpm(), self.transport.beats());
+        for layer in &mut self.layers {
+            layer.sync(bpm, beat);
+        }
+        for bus in self.buses.iter_mut().chain([&mut self.master]) {
+            bus.sync(bpm, beat);
+        }
+    }
+
+    /// Get the engine time in seconds
+    pub fn time(&self) -> f64 {
+        self.clock as f64 / self.sample_rate
+    }
+
+    /// Route a data point from a source to the layers and buses listening to it
+    ///
+    /// Interpolated fields reach the layers at their current position and
+    /// keep moving toward the new value as the engine runs.
+    pub fn receive_data(&mut self, data: DataPoint) {
+        self.latest_data.insert(data.source.clone(), data.clone());
+
+        let interpolated = self.series.push(data.clone(), self.time());
+        self.route_data(&interpolated, &data);
+    }
+
+    fn route_data(&mut self, data: &DataPoint, raw: &DataPoint) {
+        for layer in &mut self.layers {
+            if layer.source == data.source {
+                layer.process_interpolated(data, raw);
+            }
+        }
+        for bus in &mut self.buses {
+            if bus.source() == Some(data.source.as_str()) {
+                bus.process_data(data);
+            }
+        }
+    }
+
+    /// Set how much faster than real time data arrives (when replaying a
+    /// log), so fixed interpolation intervals keep pace with it
+    pub fn set_data_speed(&mut self, speed: f64) {
+        self.series.set_speed(speed);
+    }
+
+    /// Get the latest data value for a source and field
+    pub fn get_latest(&self, source: &str, field: &str) -> Option<f64> {
+        self.latest_data
+            .get(source)
+            .and_then(|data| data.values.get(field).copied())
+    }
+
+    /// Get the current interpolated value for a source and field
+    ///
+    /// Falls back to the latest value for fields that are not interpolated.
+    pub fn get_interpolated(&self, source: &str, field: &str) -> Option<f64> {
+        self.series
+            .value(source, field, self.time())
+            .or_else(|| self.get_latest(source, field))
+    }
+
+    /// Generate the next sample as a mono mix of the stereo output
     pub fn process(&mut self) -> f64 {
-        let mut output = 0.0;
-        
-        for voice in &mut self.voices {
-            if voice.is_active() {
-                output += voice.process();
+        self.process_frame().mid()
+    }
+
+    /// Generate the next stereo frame (layers -> buses -> master)
+    pub fn process_frame(&mut self) -> Frame {
+        if self.clock.is_multiple_of(INTERPOLATION_INTERVAL as u64) {
+            let time = self.time();
+            let (layers, buses) = (&mut self.layers, &mut self.buses);
+            self.series.updates(time, |source, field, value| {
+                for layer in layers.iter_mut().filter(|l| l.source == source) {
+                    layer.process_value(field, value);
+                }
+                for bus in buses.iter_mut().filter(|b| b.source() == Some(source)) {
+                    bus.process_value(field, value);
+                }
+            });
+        }
+        self.clock += 1;
+        self.transport.advance();
+
+        for layer in &mut self.layers {
+            let output = layer.process_frame();
+            self.master.add(output);
+            for send in layer.sends() {
+                self.buses[send.index].add(output * send.level as f64);
             }
         }
-        
-        // Apply master volume
-        output * self.config.master.volume as f64
+
+        for bus in &mut self.buses {
+            self.master.add(bus.process());
+        }
+
+        self.master.process()
     }
-    
-    /// Fill a buffer with samples
+
+    /// Fill a buffer with mono samples
     pub fn fill_buffer(&mut self, buffer: &mut [f32]) {
         for sample in buffer.iter_mut() {
             *sample = self.process() as f32;
         }
     }
-    
+
+    /// Fill a buffer with interleaved frames of `channels` samples each
+    pub fn fill_interleaved(&mut self, buffer: &mut [f32], channels: usize) {
+        for frame in buffer.chunks_mut(channels) {
+            let output = self.process_frame();
+            for (index, sample) in frame.iter_mut().enumerate() {
+                *sample = output.channel(index, channels) as f32;
+            }
+        }
+    }
+
     /// Check if the engine is running
     pub fn is_running(&self) -> bool {
         self.running
     }
-    
+
     /// Start the engine
     pub fn start(&mut self) -> Result<()> {
         self.running = true;
         Ok(())
     }
-    
+
     /// Stop the engine
     pub fn stop(&mut self) {
         self.running = false;
@@ -102,8 +415,7 @@ impl Engine {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{AudioConfig, MasterConfig};
-    
+    use crate::config::{AudioConfig, BusConfig, EffectConfig, EffectKind, MappingConfig, MappingKind, MasterConfig, VoiceKind};
 
     fn test_config() -> DriftConfig {
         DriftConfig {
@@ -112,70 +424,522 @@ mod tests {
                 buffer_size: 512,
                 device: None,
                 output_file: None,
+                channels: 2,
             },
             master: MasterConfig {
                 bpm: 60.0,
                 key: "C".to_string(),
                 scale: "minor_pentatonic".to_string(),
                 volume: 0.7,
+                beats_per_bar: 4,
+                effects: Vec::new(),
             },
             sources: vec![],
             layers: vec![],
+            buses: vec![],
+        }
+    }
+
+    fn test_layer_config() -> LayerConfig {
+        let mut mappings = HashMap::new();
+        mappings.insert(
+            "pitch".to_string(),
+            MappingConfig {
+                field: "temperature".to_string(),
+                kind: MappingKind::Linear,
+                in_min: Some(-20.0),
+                in_max: Some(40.0),
+                out_min: Some(100.0),
+                out_max: Some(400.0),
+            },
+        );
+        mappings.insert(
+            "filter".to_string(),
+            MappingConfig {
+                field: "humidity".to_string(),
+                kind: MappingKind::Linear,
+                in_min: Some(0.0),
+                in_max: Some(100.0),
+                out_min: Some(200.0),
+                out_max: Some(2000.0),
+            },
+        );
+
+        LayerConfig {
+            mappings,
+            volume: 0.8,
+            ..LayerConfig::new("test_drone", VoiceKind::Drone, "weather")
         }
     }
 
+    fn engine_with_layer() -> Engine {
+        let mut config = test_config();
+        config.layers.push(test_layer_config());
+        Engine::new(config).unwrap()
+    }
+
     #[test]
     fn test_engine_creation() {
         let config = test_config();
-        let engine = Engine::new(config);
-        
+        let engine = Engine::new(config).unwrap();
+
         assert_eq!(engine.sample_rate(), 44100.0);
+        assert_eq!(engine.layer_count(), 0);
+        assert_eq!(engine.master_volume(), 0.7);
         assert!(!engine.is_running());
     }
 
     #[test]
-    fn test_engine_add_drone() {
-        let config = test_config();
-        let mut engine = Engine::new(config);
-        
-        let idx = engine.add_drone();
-        assert_eq!(idx, 0);
-        
+    fn test_engine_builds_layers_from_config() {
+        let mut engine = engine_with_layer();
+        assert_eq!(engine.layer_count(), 1);
+        assert!(engine.layer("test_drone").is_some());
+
         // Generate multiple samples and check for non-zero output
         // (filter may attenuate initial samples)
         let mut max_sample = 0.0f64;
         for _ in 0..1000 {
-            let sample = engine.process();
-            max_sample = max_sample.max(sample.abs());
+            max_sample = max_sample.max(engine.process().abs());
         }
         assert!(max_sample > 0.0, "Expected non-zero audio output");
     }
 
+    #[test]
+    fn test_engine_add_layer() {
+        let mut engine = Engine::new(test_config()).unwrap();
+        engine.add_layer(&test_layer_config()).unwrap();
+        assert_eq!(engine.layer_count(), 1);
+
+        // Layer names address parameters, so they must be unique
+        assert!(engine.add_layer(&test_layer_config()).is_err());
+    }
+
     #[test]
     fn test_engine_fill_buffer() {
-        let config = test_config();
-        let mut engine = Engine::new(config);
-        engine.add_drone();
-        
+        let mut engine = engine_with_layer();
+
         let mut buffer = vec![0.0f32; 512];
         engine.fill_buffer(&mut buffer);
-        
+
         // Buffer should have non-zero samples
         let has_audio = buffer.iter().any(|&s| s.abs() > 0.0);
         assert!(has_audio);
     }
 
     #[test]
-    fn test_engine_parameter_setting() {
-        let config = test_config();
-        let mut engine = Engine::new(config);
-        let idx = engine.add_drone();
-        
-        engine.set_voice_parameter(idx, "pitch", 440.0);
-        
-        // Generate some samples - should work without panicking
+    fn test_engine_layer_parameters() {
+        let mut engine = engine_with_layer();
+
+        assert!(engine.set_layer_parameter("test_drone", "pitch", 440.0));
+        assert_eq!(engine.get_layer_parameter("test_drone", "pitch"), Some(440.0));
+
+        assert!(!engine.set_layer_parameter("missing", "pitch", 440.0));
+        assert_eq!(engine.get_layer_parameter("missing", "pitch"), None);
+    }
+
+    #[test]
+    fn test_engine_receive_data() {
+        let mut engine = engine_with_layer();
+
+        let data = DataPoint::new("weather")
+            .with_value("temperature", 22.5)
+            .with_value("humidity", 65.0);
+        engine.receive_data(data);
+
+        // Check latest data is stored
+        assert_eq!(engine.get_latest("weather", "temperature"), Some(22.5));
+        assert_eq!(engine.get_latest("weather", "humidity"), Some(65.0));
+
+        // -20..40 -> 100..400 Hz
+        let pitch = engine.get_layer_parameter("test_drone", "pitch").unwrap();
+        assert!((pitch - 312.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_engine_interpolates_data() {
+        let mut config = test_config();
+        config.audio.sample_rate = 1000;
+        config.sources = serde_yaml::from_str(
+            "- name: weather\n  kind: weather\n  interpolation:\n    mode: linear\n    interval: 2.0\n",
+        )
+        .unwrap();
+        config.layers.push(test_layer_config());
+        let mut engine = Engine::new(config).unwrap();
+
+        engine.receive_data(DataPoint::new("weather").with_value("temperature", -20.0));
+        assert_eq!(engine.get_layer_parameter("test_drone", "pitch"), Some(100.0));
+
+        // The new value is approached over the 2 s interval instead of jumped to
+        engine.receive_data(DataPoint::new("weather").with_value("temperature", 40.0));
+        assert_eq!(engine.get_latest("weather", "temperature"), Some(40.0));
+        assert_eq!(engine.get_layer_parameter("test_drone", "pitch"), Some(100.0));
+
+        for _ in 0..1000 {
+            engine.process();
+        }
+        let temperature = engine.get_interpolated("weather", "temperature").unwrap();
+        assert!((temperature - 10.0).abs() < 1e-9);
+        let pitch = engine.get_layer_parameter("test_drone", "pitch").unwrap();
+        assert!(pitch > 200.0 && pitch < 300.0, "pitch {}", pitch);
+
+        for _ in 0..1500 {
+            engine.process();
+        }
+        assert_eq!(engine.get_layer_parameter("test_drone", "pitch"), Some(400.0));
+    }
+
+    #[test]
+    fn test_engine_interpolates_replay_at_speed() {
+        // A 60 s interval, replayed at 10x: points 60 data seconds apart
+        // arrive every 6 engine seconds, and each curve must finish in time
+        let mut config = test_config();
+        config.audio.sample_rate = 1000;
+        config.sources = serde_yaml::from_str(
+            "- name: weather\n  kind: weather\n  interpolation:\n    mode: linear\n    interval: 60.0\n",
+        )
+        .unwrap();
+        config.layers.push(test_layer_config());
+        let mut engine = Engine::new(config).unwrap();
+        engine.set_data_speed(10.0);
+
+        for (i, temperature) in [-20.0, 40.0, -20.0].into_iter().enumerate() {
+            assert_eq!(engine.time(), i as f64 * 6.0);
+            engine.receive_data(DataPoint::new("weather").with_value("temperature", temperature));
+            for _ in 0..3000 {
+                engine.process();
+            }
+            if i > 0 {
+                let halfway = engine.get_interpolated("weather", "temperature").unwrap();
+                assert!((halfway - 10.0).abs() < 1e-9, "{}", halfway);
+            }
+            for _ in 0..3000 {
+                engine.process();
+            }
+            assert_eq!(engine.get_interpolated("weather", "temperature"), Some(temperature));
+        }
+        // Layers get the settled value on the next update
+        for _ in 0..INTERPOLATION_INTERVAL {
+            engine.process();
+        }
+        assert_eq!(engine.get_layer_parameter("test_drone", "pitch"), Some(100.0));
+    }
+
+    #[test]
+    fn test_engine_interpolation_does_not_trigger() {
+        let mut config = test_config();
+        config.audio.sample_rate = 1000;
+        config.sources = serde_yaml::from_str("- name: git\n  kind: git\n  interpolation:\n    mode: extrapolate\n").unwrap();
+        let mut mappings = HashMap::new();
+        mappings.insert(
+            "trigger".to_string(),
+            MappingConfig {
+                field: "activity".to_string(),
+                kind: MappingKind::Threshold,
+                in_min: Some(0.0),
+                in_max: Some(100.0),
+                out_min: Some(0.0),
+                out_max: Some(1.0),
+            },
+        );
+        mappings.insert(
+            "pitch".to_string(),
+            MappingConfig {
+                field: "activity".to_string(),
+                kind: MappingKind::Linear,
+                in_min: Some(0.0),
+                in_max: Some(100.0),
+                out_min: Some(100.0),
+                out_max: Some(200.0),
+            },
+        );
+        config.layers.push(LayerConfig {
+            mappings,
+            ..LayerConfig::new("hits", VoiceKind::Percussion, "git")
+        });
+        let mut engine = Engine::new(config).unwrap();
+
+        // The trend heads past the threshold (0, 40, then toward 80), but the
+        // data never crosses it
+        engine.receive_data(DataPoint::new("git").with_value("activity", 0.0));
+        for _ in 0..1000 {
+            engine.process();
+        }
+        engine.receive_data(DataPoint::new("git").with_value("activity", 40.0));
+        for _ in 0..1000 {
+            engine.process();
+            assert!(!engine.layer("hits").unwrap().is_active());
+        }
+        let pitch = engine.get_layer_parameter("hits", "pitch").unwrap();
+        assert!(pitch > 150.0, "pitch {}", pitch);
+
+        // A raw crossing still fires
+        engine.receive_data(DataPoint::new("git").with_value("activity", 90.0));
+        assert!(engine.layer("hits").unwrap().is_active());
+    }
+
+    #[test]
+    fn test_engine_multiple_layers() {
+        let mut mappings = HashMap::new();
+        mappings.insert(
+            "pitch".to_string(),
+            MappingConfig {
+                field: "load".to_string(),
+                kind: MappingKind::Linear,
+                in_min: Some(0.0),
+                in_max: Some(1.0),
+                out_min: Some(500.0),
+                out_max: Some(1000.0),
+            },
+        );
+        let pad = LayerConfig {
+            mappings,
+            volume: 0.5,
+            ..LayerConfig::new("pad", VoiceKind::Drone, "system")
+        };
+        let engine = |layers: Vec<LayerConfig>| {
+            let mut config = test_config();
+            config.layers = layers;
+            Engine::new(config).unwrap()
+        };
+        let mut both = engine(vec![test_layer_config(), pad.clone()]);
+        let mut drone = engine(vec![test_layer_config()]);
+        let mut pad = engine(vec![pad]);
+
+        // Data only reaches the layer listening to its source
+        for engine in [&mut both, &mut drone] {
+            engine.receive_data(DataPoint::new("weather").with_value("temperature", 40.0));
+        }
+        assert_eq!(both.get_layer_parameter("test_drone", "pitch"), Some(400.0));
+        assert_eq!(both.get_layer_parameter("pad", "pitch"), Some(220.0));
+        for engine in [&mut both, &mut pad] {
+            engine.receive_data(DataPoint::new("system").with_value("load", 1.0));
+        }
+        assert_eq!(both.get_layer_parameter("pad", "pitch"), Some(1000.0));
+        assert_eq!(both.get_layer_parameter("test_drone", "pitch"), Some(400.0));
+
+        // The layers are summed into the master
+        let mut peaks = [0.0f64; 2];
+        for _ in 0..2000 {
+            let (low, high) = (drone.process_frame(), pad.process_frame());
+            let output = both.process_frame();
+            let expected = low + high;
+            assert!((output.left - expected.left).abs() < 1e-12);
+            assert!((output.right - expected.right).abs() < 1e-12);
+            peaks = [peaks[0].max(low.peak()), peaks[1].max(high.peak())];
+        }
+        assert!(peaks.iter().all(|&peak| peak > 0.01), "{:?}", peaks);
+    }
+
+    #[test]
+    fn test_engine_transport() {
+        let mut config = test_config();
+        config.audio.sample_rate = 1000;
+        config.master.bpm = 120.0;
+        config.layers.push(LayerConfig {
+            step: Some("1/16".to_string()),
+            ..LayerConfig::new("tune", VoiceKind::Melody, "price")
+        });
+        let mut engine = Engine::new(config).unwrap();
+        assert_eq!(engine.get_layer_parameter("tune", "division"), Some(4.0));
+
+        for _ in 0..2250 {
+            engine.process();
+        }
+        let position = engine.transport().position();
+        assert_eq!((position.bar, position.beat, position.tick), (1, 0, 480));
+
+        // The sequencer follows tempo changes
+        engine.set_bpm(90.0);
+        assert_eq!(engine.bpm(), 90.0);
+        assert_eq!(engine.get_layer_parameter("tune", "bpm"), Some(90.0));
+    }
+
+    #[test]
+    fn test_engine_ignores_other_sources() {
+        let mut engine = engine_with_layer();
+        engine.receive_data(DataPoint::new("system").with_value("temperature", 40.0));
+
+        assert_eq!(engine.get_layer_parameter("test_drone", "pitch"), Some(220.0));
+        assert!(engine.get_latest("system", "temperature").is_some());
+    }
+
+    #[test]
+    fn test_engine_layer_volume() {
+        let mut config = test_config();
+        config.master.volume = 1.0;
+        let mut layer = test_layer_config();
+        layer.volume = 0.0;
+        config.layers.push(layer);
+        let mut engine = Engine::new(config).unwrap();
+
+        // Output should be silent
         for _ in 0..100 {
+            assert_eq!(engine.process(), 0.0);
+        }
+
+        assert!(engine.set_layer_volume("test_drone", 0.5));
+        let max = (0..1000).map(|_| engine.process().abs()).fold(0.0, f64::max);
+        assert!(max > 0.0);
+    }
+
+    #[test]
+    fn test_engine_send_bus() {
+        let mut config = test_config();
+        config.master.volume = 1.0;
+        config.buses.push(BusConfig::new("reverb", 1.0));
+        let mut layer = test_layer_config();
+        layer.sends.insert("reverb".to_string(), 1.0);
+        config.layers.push(layer);
+
+        let mut dry = engine_with_layer();
+        dry.set_master_volume(1.0);
+        let mut wet = Engine::new(config).unwrap();
+        assert_eq!(wet.buses().len(), 1);
+        assert_eq!(wet.layers()[0].sends()[0].bus, "reverb");
+
+        // A full send to a unity bus doubles the signal
+        for _ in 0..1000 {
+            let d = dry.process();
+            let w = wet.process();
+            assert!((w - 2.0 * d).abs() < 1e-9);
+        }
+
+        assert!(wet.set_bus_volume("reverb", 0.0));
+        assert!(!wet.set_bus_volume("delay", 0.0));
+    }
+
+    #[test]
+    fn test_engine_bus_effects() {
+        let mut config = test_config();
+        let mut bus = BusConfig::new("space", 1.0);
+        bus.effects.push(EffectConfig::new(EffectKind::Reverb));
+        bus.source = Some("weather".to_string());
+        bus.mappings.insert(
+            "reverb.damping".to_string(),
+            MappingConfig {
+                field: "humidity".to_string(),
+                kind: MappingKind::Linear,
+                in_min: Some(0.0),
+                in_max: Some(100.0),
+                out_min: Some(0.0),
+                out_max: Some(1.0),
+            },
+        );
+        config.buses.push(bus);
+        let mut layer = test_layer_config();
+        layer.sends.insert("space".to_string(), 1.0);
+        config.layers.push(layer);
+
+        let mut engine = Engine::new(config).unwrap();
+        engine.receive_data(DataPoint::new("weather").with_value("humidity", 80.0));
+        assert_eq!(engine.get_bus_parameter("space", "reverb.damping"), Some(0.8));
+
+        assert!(engine.set_bus_parameter("space", "reverb.decay", 12.0));
+        assert_eq!(engine.get_bus_parameter("space", "reverb.decay"), Some(12.0));
+        assert!(!engine.set_bus_parameter("delay", "reverb.decay", 1.0));
+        assert_eq!(engine.get_bus_parameter("space", "chorus.rate"), None);
+
+        // The wet bus decorrelates the channels of a mono drone
+        let side = (0..20000).map(|_| engine.process_frame().side().abs()).fold(0.0, f64::max);
+        assert!(side > 0.0);
+    }
+
+    #[test]
+    fn test_engine_synced_delay() {
+        let mut config = test_config();
+        config.master.bpm = 120.0;
+        let mut effect = EffectConfig::new(EffectKind::Delay);
+        effect.sync = Some("1/8".to_string());
+        let mut bus = BusConfig::new("echo", 1.0);
+        bus.effects.push(effect);
+        bus.source = Some("git".to_string());
+        bus.mappings.insert(
+            "delay.feedback".to_string(),
+            MappingConfig {
+                field: "commits".to_string(),
+                kind: MappingKind::Linear,
+                in_min: Some(0.0),
+                in_max: Some(10.0),
+                out_min: Some(0.2),
+                out_max: Some(0.9),
+            },
+        );
+        config.buses.push(bus);
+
+        let mut engine = Engine::new(config).unwrap();
+        assert_eq!(engine.get_bus_parameter("echo", "delay.time"), Some(250.0));
+        engine.set_bpm(60.0);
+        assert_eq!(engine.get_bus_parameter("echo", "delay.time"), Some(500.0));
+
+        // More commits, longer trails
+        engine.receive_data(DataPoint::new("git").with_value("commits", 10.0));
+        assert!((engine.get_bus_parameter("echo", "delay.feedback").unwrap() - 0.9).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_engine_master_convolution() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        {
+            let mut writer = hound::WavWriter::create(file.path(), spec).unwrap();
+            for n in 0..4000 {
+                let tail = (-(n as f32) / 500.0).exp();
+                writer.write_sample(tail * if n % 7 == 0 { 1.0 } else { -0.3 }).unwrap();
+                writer.write_sample(tail * if n % 5 == 0 { 1.0 } else { -0.2 }).unwrap();
+            }
+            writer.finalize().unwrap();
+        }
+
+        let mut config = test_config();
+        let mut effect = EffectConfig::new(EffectKind::Convolution);
+        effect.ir = Some(file.path().to_path_buf());
+        config.master.effects.push(effect);
+        config.layers.push(test_layer_config());
+
+        let mut engine = Engine::new(config.clone()).unwrap();
+        assert!(engine.set_bus_parameter("master", "convolution.mix", 0.5));
+        assert_eq!(engine.get_bus_parameter("master", "convolution.mix"), Some(0.5));
+        // The stereo IR spreads the mono drone
+        let side = (0..5000).map(|_| engine.process_frame().side().abs()).fold(0.0, f64::max);
+        assert!(side > 0.0);
+
+        config.master.effects[0].ir = Some("/nonexistent/hall.wav".into());
+        assert!(Engine::new(config).is_err());
+    }
+
+    #[test]
+    fn test_engine_unknown_send_bus() {
+        let mut config = test_config();
+        let mut layer = test_layer_config();
+        layer.sends.insert("delay".to_string(), 0.5);
+        config.layers.push(layer);
+
+        assert!(Engine::new(config).is_err());
+    }
+
+    #[test]
+    fn test_engine_trigger_release() {
+        let mut engine = engine_with_layer();
+
+        // DroneVoice starts active by default (for sustained drones)
+        assert!(engine.has_active_layers());
+
+        engine.release_all();
+
+        // With ADSR envelope, voice stays active during release phase
+        // Process samples to let release complete (1s release at 44100 Hz)
+        for _ in 0..50000 {
             engine.process();
         }
+        assert!(!engine.has_active_layers());
+
+        assert!(engine.trigger_layer("test_drone"));
+        assert!(engine.has_active_layers());
+        assert!(!engine.release_layer("missing"));
     }
 }
@@ -94,17 +94,17 @@ impl Player {
 
                 if let Ok(mut eng) = engine.try_lock() {
                     for frame in data.chunks_mut(channels) {
-                        let sample = eng.process() as f32;
+                        let output = eng.process_frame();
                         
-                        // Push sample to visualization buffer if available
+                        // Push the mono mix to visualization buffer if available
                         if let Some(ref viz) = viz_buffer {
                             if let Ok(mut buf) = viz.try_lock() {
-                                buf.push(sample);
+                                buf.push(output.mid() as f32);
                             }
                         }
                         
-                        for channel_sample in frame.iter_mut() {
-                            *channel_sample = T::from_sample(sample);
+                        for (index, channel_sample) in frame.iter_mut().enumerate() {
+                            *channel_sample = T::from_sample(output.channel(index, channels) as f32);
                         }
                     }
                 } else {
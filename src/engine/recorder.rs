@@ -1,7 +1,9 @@
 //! WAV file recorder
 //!
-//! Records audio output to WAV files.
+//! Records audio output to WAV files, mono or with interleaved stereo
+//! frames.
 
+use crate::synth::Frame;
 use anyhow::{Context, Result};
 use hound::{SampleFormat, WavSpec, WavWriter};
 use std::fs::File;
@@ -12,18 +14,24 @@ use std::path::Path;
 pub struct Recorder {
     writer: WavWriter<BufWriter<File>>,
     sample_rate: u32,
+    channels: u16,
     samples_written: u64,
 }
 
 impl Recorder {
-    /// Create a new recorder
+    /// Create a new mono recorder
     ///
     /// # Arguments
     /// * `path` - Output file path
     /// * `sample_rate` - Sample rate in Hz
     pub fn new(path: &Path, sample_rate: u32) -> Result<Self> {
+        Self::with_channels(path, sample_rate, 1)
+    }
+
+    /// Create a recorder for interleaved samples in `channels` channels
+    pub fn with_channels(path: &Path, sample_rate: u32, channels: u16) -> Result<Self> {
         let spec = WavSpec {
-            channels: 1,
+            channels,
             sample_rate,
             bits_per_sample: 32,
             sample_format: SampleFormat::Float,
@@ -35,6 +43,7 @@ impl Recorder {
         Ok(Self {
             writer,
             sample_rate,
+            channels,
             samples_written: 0,
         })
     }
@@ -44,14 +53,19 @@ impl Recorder {
         self.sample_rate
     }
 
-    /// Get the number of samples written
+    /// Get the number of channels
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Get the number of samples written (across all channels)
     pub fn samples_written(&self) -> u64 {
         self.samples_written
     }
 
     /// Get the duration recorded in seconds
     pub fn duration_secs(&self) -> f64 {
-        self.samples_written as f64 / self.sample_rate as f64
+        self.samples_written as f64 / self.channels as f64 / self.sample_rate as f64
     }
 
     /// Write a single sample
@@ -63,7 +77,15 @@ impl Recorder {
         Ok(())
     }
 
-    /// Write a buffer of samples
+    /// Write a stereo frame (its mono mix in a one-channel file)
+    pub fn write_frame(&mut self, frame: Frame) -> Result<()> {
+        for index in 0..self.channels as usize {
+            self.write_sample(frame.channel(index, self.channels as usize) as f32)?;
+        }
+        Ok(())
+    }
+
+    /// Write a buffer of (interleaved) samples
     pub fn write_buffer(&mut self, buffer: &[f32]) -> Result<()> {
         for &sample in buffer {
             self.writer
@@ -173,4 +195,26 @@ mod tests {
         let samples: Vec<f32> = reader.into_samples().map(|s| s.unwrap()).collect();
         assert_eq!(samples.len(), 1000);
     }
+
+    #[test]
+    fn test_recorder_stereo() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_path_buf();
+
+        {
+            let mut recorder = Recorder::with_channels(&path, 1000, 2).unwrap();
+            assert_eq!(recorder.channels(), 2);
+            for _ in 0..1000 {
+                recorder.write_frame(Frame::new(0.5, -0.25)).unwrap();
+            }
+            assert!((recorder.duration_secs() - 1.0).abs() < 1e-9);
+            recorder.finalize().unwrap();
+        }
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        let samples: Vec<f32> = reader.into_samples().map(|s| s.unwrap()).collect();
+        assert_eq!(samples.len(), 2000);
+        assert_eq!(&samples[..2], &[0.5, -0.25]);
+    }
 }
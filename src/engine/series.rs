@@ -0,0 +1,398 @@
+//! Data interpolation between polls
+//!
+//! Slow sources (weather every few minutes, prices every minute) would
+//! otherwise hold a value and then jump. Each numeric field is kept as a
+//! short time series: when a new point arrives, the field starts a curve
+//! from wherever it currently is toward the new value (or toward the value
+//! the trend predicts) that lasts one poll interval, so the music keeps
+//! moving between polls without ever jumping.
+
+use crate::config::{InterpolationConfig, InterpolationMode, SourceConfig};
+use crate::sources::DataPoint;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+/// Samples between interpolated updates sent to layers
+pub const INTERPOLATION_INTERVAL: usize = 256;
+
+/// One numeric field over time
+#[derive(Debug, Clone)]
+pub struct Series {
+    mode: InterpolationMode,
+    /// Fixed segment length in seconds (None: the measured gap)
+    interval: Option<f64>,
+    /// Current segment
+    start_time: f64,
+    start_value: f64,
+    end_value: f64,
+    duration: f64,
+    /// Slope at the segment start, scaled to the segment length (spline)
+    start_tangent: f64,
+    /// Last raw point received
+    last: Option<(f64, f64)>,
+}
+
+impl Series {
+    /// Create an empty series
+    pub fn new(mode: InterpolationMode, interval: Option<f64>) -> Self {
+        Self {
+            mode,
+            interval,
+            start_time: 0.0,
+            start_value: 0.0,
+            end_value: 0.0,
+            duration: 0.0,
+            start_tangent: 0.0,
+            last: None,
+        }
+    }
+
+    /// Get the interpolation mode
+    pub fn mode(&self) -> InterpolationMode {
+        self.mode
+    }
+
+    /// Add a data point (times in seconds)
+    ///
+    /// The first point (and every point in step mode) is taken as is.
+    pub fn push(&mut self, time: f64, value: f64) {
+        let previous = self.last.replace((time, value));
+        let (last_time, last_value) = match previous {
+            Some(point) if self.mode != InterpolationMode::Step => point,
+            _ => {
+                self.snap(time, value);
+                return;
+            }
+        };
+
+        let gap = time - last_time;
+        let duration = self.interval.unwrap_or(gap);
+        if duration <= 0.0 {
+            self.snap(time, value);
+            return;
+        }
+
+        let current = self.value_at(time);
+        let slope = self.slope_at(time);
+        let end_value = match self.mode {
+            InterpolationMode::Extrapolate if gap > 0.0 => {
+                value + (value - last_value) / gap * duration
+            }
+            _ => value,
+        };
+
+        self.start_time = time;
+        self.start_value = current;
+        self.end_value = end_value;
+        self.duration = duration;
+        self.start_tangent = slope * duration;
+    }
+
+    /// Jump straight to a value
+    fn snap(&mut self, time: f64, value: f64) {
+        self.start_time = time;
+        self.start_value = value;
+        self.end_value = value;
+        self.duration = 0.0;
+        self.start_tangent = 0.0;
+    }
+
+    /// Position within the current segment (0-1)
+    fn progress(&self, time: f64) -> f64 {
+        if self.duration <= 0.0 {
+            1.0
+        } else {
+            ((time - self.start_time) / self.duration).clamp(0.0, 1.0)
+        }
+    }
+
+    /// Get the value at a time
+    pub fn value_at(&self, time: f64) -> f64 {
+        let u = self.progress(time);
+        let (start, end) = (self.start_value, self.end_value);
+        match self.mode {
+            InterpolationMode::Step => end,
+            InterpolationMode::Linear | InterpolationMode::Extrapolate => start + (end - start) * u,
+            InterpolationMode::Cosine => start + (end - start) * (1.0 - (PI * u).cos()) / 2.0,
+            InterpolationMode::Spline => {
+                // Cubic Hermite: leaves with the incoming slope, arrives with
+                // the segment's average slope
+                let (u2, u3) = (u * u, u * u * u);
+                (2.0 * u3 - 3.0 * u2 + 1.0) * start
+                    + (u3 - 2.0 * u2 + u) * self.start_tangent
+                    + (-2.0 * u3 + 3.0 * u2) * end
+                    + (u3 - u2) * (end - start)
+            }
+        }
+    }
+
+    /// Get the rate of change (per second) at a time
+    fn slope_at(&self, time: f64) -> f64 {
+        if self.is_settled(time) {
+            return 0.0;
+        }
+
+        let u = self.progress(time);
+        let delta = self.end_value - self.start_value;
+        let per_unit = match self.mode {
+            InterpolationMode::Step => 0.0,
+            InterpolationMode::Linear | InterpolationMode::Extrapolate => delta,
+            InterpolationMode::Cosine => delta * PI / 2.0 * (PI * u).sin(),
+            InterpolationMode::Spline => {
+                let u2 = u * u;
+                (6.0 * u2 - 6.0 * u) * self.start_value
+                    + (3.0 * u2 - 4.0 * u + 1.0) * self.start_tangent
+                    + (-6.0 * u2 + 6.0 * u) * self.end_value
+                    + (3.0 * u2 - 2.0 * u) * delta
+            }
+        };
+        per_unit / self.duration
+    }
+
+    /// Check if the series has reached its target
+    pub fn is_settled(&self, time: f64) -> bool {
+        self.duration <= 0.0 || time >= self.start_time + self.duration
+    }
+}
+
+/// A source's interpolation settings and field series
+struct SourceSeries {
+    config: InterpolationConfig,
+    fields: HashMap<String, Series>,
+    /// Whether layers still need updates (stays set for one final update
+    /// once every field has settled)
+    moving: bool,
+}
+
+/// Interpolated fields for every configured source
+pub struct DataSeries {
+    sources: HashMap<String, SourceSeries>,
+    /// Data seconds per engine second (above 1.0 when replaying a log fast)
+    speed: f64,
+}
+
+impl Default for DataSeries {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+impl DataSeries {
+    /// Create series for the sources that interpolate any field
+    pub fn new(sources: &[SourceConfig]) -> Self {
+        let sources = sources
+            .iter()
+            .filter(|s| {
+                let interpolation = &s.interpolation;
+                interpolation.mode != InterpolationMode::Step
+                    || interpolation.fields.values().any(|m| *m != InterpolationMode::Step)
+            })
+            .map(|s| {
+                let series = SourceSeries {
+                    config: s.interpolation.clone(),
+                    fields: HashMap::new(),
+                    moving: false,
+                };
+                (s.name.clone(), series)
+            })
+            .collect();
+        Self { sources, speed: 1.0 }
+    }
+
+    /// Set how many data seconds pass per engine second
+    ///
+    /// Replaying a log at `speed` times real time shortens fixed
+    /// interpolation intervals to match, so each curve still ends before
+    /// the next point arrives. (Measured gaps already shrink on their own.)
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+        for source in self.sources.values_mut() {
+            let interval = source.config.interval.map(|i| i / speed);
+            for series in source.fields.values_mut() {
+                series.interval = interval;
+            }
+        }
+    }
+
+    /// Record a data point and return it with values at their interpolated
+    /// positions (events are kept)
+    pub fn push(&mut self, data: DataPoint, time: f64) -> DataPoint {
+        let Some(source) = self.sources.get_mut(&data.source) else {
+            return data;
+        };
+
+        let mut data = data;
+        let interval = source.config.interval.map(|i| i / self.speed);
+        for (field, value) in data.values.iter_mut() {
+            let series = source.fields.entry(field.clone()).or_insert_with(|| {
+                Series::new(source.config.mode_for(field), interval)
+            });
+            series.push(time, *value);
+            *value = series.value_at(time);
+        }
+        source.moving = true;
+        data
+    }
+
+    /// Pass the current values of the interpolated fields of sources that
+    /// are moving toward a target (including one last update with the
+    /// settled values) to `apply` as (source, field, value)
+    ///
+    /// Step fields are left out: they only change when a point arrives.
+    /// This runs on the audio thread, so nothing is allocated.
+    pub fn updates(&mut self, time: f64, mut apply: impl FnMut(&str, &str, f64)) {
+        for (name, source) in self.sources.iter_mut().filter(|(_, s)| s.moving) {
+            for (field, series) in &source.fields {
+                if series.mode() != InterpolationMode::Step {
+                    apply(name, field, series.value_at(time));
+                }
+            }
+            source.moving = source.fields.values().any(|f| !f.is_settled(time));
+        }
+    }
+
+    /// Get the interpolated value of a field
+    pub fn value(&self, source: &str, field: &str, time: f64) -> Option<f64> {
+        self.sources
+            .get(source)
+            .and_then(|s| s.fields.get(field))
+            .map(|series| series.value_at(time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(mode: InterpolationMode) -> Series {
+        let mut series = Series::new(mode, None);
+        series.push(0.0, 0.0);
+        series.push(10.0, 10.0);
+        series
+    }
+
+    #[test]
+    fn test_step() {
+        let series = series(InterpolationMode::Step);
+        assert_eq!(series.value_at(10.0), 10.0);
+        assert!(series.is_settled(10.0));
+    }
+
+    #[test]
+    fn test_first_point_snaps() {
+        let mut series = Series::new(InterpolationMode::Linear, None);
+        series.push(5.0, 3.0);
+        assert_eq!(series.value_at(5.0), 3.0);
+        assert!(series.is_settled(5.0));
+    }
+
+    #[test]
+    fn test_linear_and_cosine() {
+        // Each new point starts a 10 s segment from the old value
+        let linear = series(InterpolationMode::Linear);
+        assert_eq!(linear.value_at(10.0), 0.0);
+        assert!((linear.value_at(12.5) - 2.5).abs() < 1e-9);
+        assert_eq!(linear.value_at(20.0), 10.0);
+        assert_eq!(linear.value_at(30.0), 10.0);
+
+        let cosine = series(InterpolationMode::Cosine);
+        assert!(cosine.value_at(12.5) < 2.5);
+        assert!((cosine.value_at(15.0) - 5.0).abs() < 1e-9);
+        assert!(cosine.value_at(17.5) > 7.5);
+    }
+
+    #[test]
+    fn test_fixed_interval() {
+        let mut series = Series::new(InterpolationMode::Linear, Some(2.0));
+        series.push(0.0, 0.0);
+        series.push(10.0, 10.0);
+        assert!((series.value_at(11.0) - 5.0).abs() < 1e-9);
+        assert!(series.is_settled(12.0));
+    }
+
+    #[test]
+    fn test_no_jump_on_early_point() {
+        // A point arriving mid-segment starts from where the field is now
+        for mode in [
+            InterpolationMode::Linear,
+            InterpolationMode::Cosine,
+            InterpolationMode::Spline,
+            InterpolationMode::Extrapolate,
+        ] {
+            let mut series = series(mode);
+            let before = series.value_at(14.0);
+            series.push(14.0, -20.0);
+            assert!((series.value_at(14.0) - before).abs() < 1e-9, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn test_spline_keeps_slope() {
+        let mut series = series(InterpolationMode::Spline);
+        let dt = 1e-4;
+        let before = (series.value_at(15.0) - series.value_at(15.0 - dt)) / dt;
+        series.push(15.0, 30.0);
+        let after = (series.value_at(15.0 + dt) - series.value_at(15.0)) / dt;
+        assert!((before - after).abs() < 0.01, "{} vs {}", before, after);
+        assert!((series.value_at(25.0) - 30.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_extrapolate_follows_trend() {
+        let mut series = Series::new(InterpolationMode::Extrapolate, None);
+        series.push(0.0, 0.0);
+        series.push(10.0, 10.0);
+        series.push(20.0, 20.0);
+
+        // Rising 1 per second, so it heads for 30 by the next poll
+        assert!(series.value_at(25.0) > 20.0);
+        assert!((series.value_at(30.0) - 30.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_data_series() {
+        let yaml = r#"
+- name: weather
+  kind: weather
+  interpolation:
+    mode: linear
+    fields:
+      condition: step
+- name: system
+  kind: system
+"#;
+        let sources: Vec<SourceConfig> = serde_yaml::from_str(yaml).unwrap();
+        let mut series = DataSeries::new(&sources);
+
+        let point = |temperature: f64| {
+            DataPoint::new("weather")
+                .with_value("temperature", temperature)
+                .with_value("condition", temperature)
+                .with_event("rain")
+        };
+        series.push(point(10.0), 0.0);
+        let data = series.push(point(20.0), 60.0);
+        assert_eq!(data.values["temperature"], 10.0);
+        assert_eq!(data.values["condition"], 20.0);
+        assert_eq!(data.events, vec!["rain".to_string()]);
+
+        let mut updates = |time: f64| {
+            let mut values = Vec::new();
+            series.updates(time, |source, field, value| values.push((source.to_string(), field.to_string(), value)));
+            values
+        };
+        // Only the interpolated field is resent
+        let moving = updates(90.0);
+        assert_eq!(moving.len(), 1);
+        assert_eq!((moving[0].0.as_str(), moving[0].1.as_str()), ("weather", "temperature"));
+        assert!((moving[0].2 - 15.0).abs() < 1e-9);
+        assert_eq!(updates(120.0)[0].2, 20.0);
+        assert!(updates(130.0).is_empty());
+        assert_eq!(series.value("weather", "temperature", 120.0), Some(20.0));
+
+        // Step-only sources pass through untouched
+        let system = series.push(DataPoint::new("system").with_value("cpu", 50.0), 0.0);
+        assert_eq!(system.values["cpu"], 50.0);
+        assert_eq!(series.value("system", "cpu", 0.0), None);
+    }
+}
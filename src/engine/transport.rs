@@ -0,0 +1,230 @@
+//! Musical transport
+//!
+//! The transport is the engine's shared clock: it turns samples into bars,
+//! beats and ticks at the master tempo. Tempo-synced LFOs, sequencer steps
+//! and delay times are written as note divisions ("1/4", "1/8T", "2 bars")
+//! and converted through the transport, so they follow tempo changes.
+
+use anyhow::{bail, Result};
+
+/// Ticks per beat (quarter note)
+pub const TICKS_PER_BEAT: u32 = 960;
+
+/// A position in bars, beats and ticks (all counted from zero)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub bar: u64,
+    pub beat: u32,
+    pub tick: u32,
+}
+
+/// Sample-accurate musical clock
+#[derive(Debug, Clone)]
+pub struct Transport {
+    bpm: f64,
+    beats_per_bar: u32,
+    sample_rate: f64,
+    /// Beats elapsed at the last tempo change
+    anchor: f64,
+    /// Samples since the last tempo change (counting keeps the position
+    /// exact where summing per-sample increments would drift)
+    elapsed: u64,
+    /// Beats advanced per sample at the current tempo
+    beats_per_sample: f64,
+}
+
+impl Transport {
+    /// Create a transport at a tempo and meter
+    pub fn new(sample_rate: f64, bpm: f64, beats_per_bar: u32) -> Self {
+        let mut transport = Self {
+            bpm: 0.0,
+            beats_per_bar: beats_per_bar.max(1),
+            sample_rate,
+            anchor: 0.0,
+            elapsed: 0,
+            beats_per_sample: 0.0,
+        };
+        transport.set_bpm(bpm);
+        transport
+    }
+
+    /// Get the tempo
+    pub fn bpm(&self) -> f64 {
+        self.bpm
+    }
+
+    /// Set the tempo (20 to 300 BPM); the position is kept
+    pub fn set_bpm(&mut self, bpm: f64) {
+        self.anchor = self.beats();
+        self.elapsed = 0;
+        self.bpm = bpm.clamp(20.0, 300.0);
+        self.beats_per_sample = self.bpm / 60.0 / self.sample_rate;
+    }
+
+    /// Get the beats per bar
+    pub fn beats_per_bar(&self) -> u32 {
+        self.beats_per_bar
+    }
+
+    /// Get the beats elapsed since the start
+    pub fn beats(&self) -> f64 {
+        self.anchor + self.elapsed as f64 * self.beats_per_sample
+    }
+
+    /// Get the current bar, beat and tick
+    pub fn position(&self) -> Position {
+        let ticks = (self.beats() * TICKS_PER_BEAT as f64 + 1e-6) as u64;
+        let beat = ticks / TICKS_PER_BEAT as u64;
+        Position {
+            bar: beat / self.beats_per_bar as u64,
+            beat: (beat % self.beats_per_bar as u64) as u32,
+            tick: (ticks % TICKS_PER_BEAT as u64) as u32,
+        }
+    }
+
+    /// Get the length of a beat in samples
+    pub fn samples_per_beat(&self) -> f64 {
+        1.0 / self.beats_per_sample
+    }
+
+    /// Get the length of a division in seconds at the current tempo
+    pub fn seconds(&self, division: NoteDivision) -> f64 {
+        division.seconds(self.bpm, self.beats_per_bar)
+    }
+
+    /// Advance one sample
+    pub fn advance(&mut self) {
+        self.elapsed += 1;
+    }
+
+    /// Go back to the start
+    pub fn rewind(&mut self) {
+        self.anchor = 0.0;
+        self.elapsed = 0;
+    }
+}
+
+/// A musical length: a note value ("1/8", "1/4T", "1/2.") or a number of
+/// beats or bars ("3 beats", "2 bars")
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoteDivision {
+    /// Length in beats (quarter notes)
+    Beats(f64),
+    /// Length in bars (depends on the meter)
+    Bars(f64),
+}
+
+impl NoteDivision {
+    /// Parse a division
+    ///
+    /// Note values are fractions of a whole note, optionally followed by `T`
+    /// (triplet, 2/3 the length) or `.` (dotted, 1.5 times the length).
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        let lower = text.to_lowercase();
+
+        if let Some((count, unit)) = lower.split_once(char::is_whitespace) {
+            let count: f64 = match count.parse() {
+                Ok(count) if count > 0.0 => count,
+                _ => bail!("Invalid note division '{}'", text),
+            };
+            return match unit.trim() {
+                "bar" | "bars" => Ok(NoteDivision::Bars(count)),
+                "beat" | "beats" => Ok(NoteDivision::Beats(count)),
+                _ => bail!("Invalid note division '{}' (expected bars or beats)", text),
+            };
+        }
+
+        let (fraction, scale) = if let Some(rest) = lower.strip_suffix('t') {
+            (rest, 2.0 / 3.0)
+        } else if let Some(rest) = lower.strip_suffix('.') {
+            (rest, 1.5)
+        } else {
+            (lower.as_str(), 1.0)
+        };
+        let (numerator, denominator) = fraction.split_once('/').unwrap_or((fraction, "1"));
+        match (numerator.parse::<f64>(), denominator.parse::<f64>()) {
+            (Ok(n), Ok(d)) if n > 0.0 && d > 0.0 => Ok(NoteDivision::Beats(4.0 * n / d * scale)),
+            _ => bail!("Invalid note division '{}' (expected e.g. 1/4, 1/8T, 1/2. or 2 bars)", text),
+        }
+    }
+
+    /// Get the length in beats
+    pub fn beats(&self, beats_per_bar: u32) -> f64 {
+        match self {
+            NoteDivision::Beats(beats) => *beats,
+            NoteDivision::Bars(bars) => bars * beats_per_bar as f64,
+        }
+    }
+
+    /// Get the length in seconds at a tempo
+    pub fn seconds(&self, bpm: f64, beats_per_bar: u32) -> f64 {
+        self.beats(beats_per_bar) * 60.0 / bpm
+    }
+
+    /// Get the rate (repeats per second) at a tempo
+    pub fn hz(&self, bpm: f64, beats_per_bar: u32) -> f64 {
+        1.0 / self.seconds(bpm, beats_per_bar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_divisions() {
+        let beats = |text: &str| NoteDivision::parse(text).unwrap().beats(4);
+        assert_eq!(beats("1/4"), 1.0);
+        assert_eq!(beats("1/16"), 0.25);
+        assert_eq!(beats("1"), 4.0);
+        assert_eq!(beats("3/4"), 3.0);
+        assert!((beats("1/8T") - 1.0 / 3.0).abs() < 1e-12);
+        assert_eq!(beats("1/4."), 1.5);
+        assert_eq!(beats("2 bars"), 8.0);
+        assert_eq!(beats("1 bar"), 4.0);
+        assert_eq!(beats("3 beats"), 3.0);
+        assert_eq!(NoteDivision::parse("2 bars").unwrap().beats(3), 6.0);
+
+        for bad in ["", "1/0", "quarter", "-1/4", "2 weeks", "0 bars"] {
+            assert!(NoteDivision::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_division_timing() {
+        let quarter = NoteDivision::parse("1/4").unwrap();
+        assert_eq!(quarter.seconds(120.0, 4), 0.5);
+        assert_eq!(quarter.hz(120.0, 4), 2.0);
+    }
+
+    #[test]
+    fn test_transport_position() {
+        let mut transport = Transport::new(1000.0, 120.0, 3);
+        assert_eq!(transport.samples_per_beat(), 500.0);
+
+        // 3.5 bars of 3/4 at 120 BPM is 10.5 beats, or 5250 samples
+        for _ in 0..5250 {
+            transport.advance();
+        }
+        let position = transport.position();
+        assert_eq!(position, Position { bar: 3, beat: 1, tick: 480 });
+
+        transport.rewind();
+        assert_eq!(transport.position(), Position { bar: 0, beat: 0, tick: 0 });
+    }
+
+    #[test]
+    fn test_transport_tempo_change() {
+        let mut transport = Transport::new(1000.0, 60.0, 4);
+        for _ in 0..1000 {
+            transport.advance();
+        }
+        transport.set_bpm(120.0);
+        for _ in 0..1000 {
+            transport.advance();
+        }
+        assert!((transport.beats() - 3.0).abs() < 1e-9);
+        assert_eq!(transport.seconds(NoteDivision::Bars(1.0)), 2.0);
+    }
+}
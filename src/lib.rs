@@ -7,6 +7,7 @@ pub mod config;
 pub mod sources;
 pub mod mapping;
 pub mod synth;
+pub mod effects;
 pub mod engine;
 pub mod viz;
 
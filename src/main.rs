@@ -3,9 +3,11 @@
 use anyhow::Result;
 use clap::Parser;
 use cpal::traits::{DeviceTrait, HostTrait};
-use drift::config::{self, SourceKind};
+use drift::config::{self, DriftConfig, LayerConfig, VoiceKind};
 use drift::engine::{list_midi_ports, Engine, MidiConfig, MidiPlayer, Player, Recorder};
-use drift::sources::{GitConfig, GitSource, PriceConfig, PriceSource, Source, SystemSource, WeatherConfig, WeatherSource};
+use drift::sources::{DataLog, DataLogWriter, DataPoint, Source, SourceRegistry};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
 
 mod cli;
 
@@ -22,8 +24,6 @@ fn main() -> Result<()> {
             midi_channel,
             viz,
         } => {
-            use std::sync::{Arc, Mutex};
-
             println!("Loading configuration from {:?}...", config_path);
             let cfg = config::load_config(&config_path)?;
 
@@ -31,11 +31,12 @@ fn main() -> Result<()> {
             println!("  Sample rate: {} Hz", cfg.audio.sample_rate);
             println!("  Master volume: {:.0}%", cfg.master.volume * 100.0);
 
-            let mut engine = Engine::new(cfg);
-            let drone_idx = engine.add_drone();
+            let engine = Arc::new(Mutex::new(build_engine(&cfg)?));
 
-            // Set initial pitch
-            engine.set_voice_parameter(drone_idx, "pitch", 220.0);
+            // Sources poll on the tokio runtime and push data into the engine
+            let rt = tokio::runtime::Runtime::new()?;
+            let _guard = rt.enter();
+            let mut sources = start_sources(&cfg, &engine, None);
 
             if midi {
                 // MIDI output mode
@@ -62,7 +63,7 @@ fn main() -> Result<()> {
                         let mut last_value = 0.0;
                         while running.load(std::sync::atomic::Ordering::SeqCst) {
                             // Get a sample value as a control signal
-                            let sample = engine.process();
+                            let sample = engine.lock().map(|mut eng| eng.process()).unwrap_or(0.0);
                             let normalized = (sample + 1.0) / 2.0; // -1..1 to 0..1
 
                             // Send CC for continuous control
@@ -94,113 +95,182 @@ fn main() -> Result<()> {
                         }
                     }
                 }
+            } else if viz {
+                // Visualization mode
+                use drift::viz::{run_viz, VizState};
+
+                let viz_state = Arc::new(Mutex::new(VizState::new(1024)));
+                let sample_buffer = {
+                    let state = viz_state.lock().unwrap();
+                    state.sample_buffer.clone()
+                };
+
+                let mut player = Player::new();
+                match player.start_with_viz(engine.clone(), Some(sample_buffer)) {
+                    Ok(()) => {
+                        // Run TUI - it handles its own event loop and cleanup
+                        if let Err(e) = run_viz(engine.clone(), viz_state.clone()) {
+                            eprintln!("Visualization error: {}", e);
+                        }
+                        player.stop();
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to start audio: {}", e);
+                    }
+                }
             } else {
-                // Audio output mode
-                let engine = Arc::new(Mutex::new(engine));
+                // Normal audio mode
+                let mut player = Player::new();
+                match player.start(engine.clone()) {
+                    Ok(()) => {
+                        println!("\nPlaying ambient audio... Press Ctrl+C to stop.\n");
 
-                if viz {
-                    // Visualization mode
-                    use drift::viz::{run_viz, VizState};
+                        let running = Arc::new(std::sync::atomic::AtomicBool::new(true));
+                        let r = running.clone();
 
-                    let viz_state = Arc::new(Mutex::new(VizState::new(1024)));
-                    let sample_buffer = {
-                        let state = viz_state.lock().unwrap();
-                        state.sample_buffer.clone()
-                    };
+                        ctrlc::set_handler(move || {
+                            r.store(false, std::sync::atomic::Ordering::SeqCst);
+                        })?;
 
-                    let mut player = Player::new();
-                    match player.start_with_viz(engine.clone(), Some(sample_buffer)) {
-                        Ok(()) => {
-                            // Run TUI - it handles its own event loop and cleanup
-                            if let Err(e) = run_viz(engine.clone(), viz_state.clone()) {
-                                eprintln!("Visualization error: {}", e);
-                            }
-                            player.stop();
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to start audio: {}", e);
+                        while running.load(std::sync::atomic::Ordering::SeqCst) {
+                            std::thread::sleep(std::time::Duration::from_millis(100));
                         }
+
+                        player.stop();
+                        println!("\nStopped.");
                     }
-                } else {
-                    // Normal audio mode
-                    let mut player = Player::new();
-                    match player.start(engine.clone()) {
-                        Ok(()) => {
-                            println!("\nPlaying ambient audio... Press Ctrl+C to stop.\n");
-
-                            let running = Arc::new(std::sync::atomic::AtomicBool::new(true));
-                            let r = running.clone();
-
-                            ctrlc::set_handler(move || {
-                                r.store(false, std::sync::atomic::Ordering::SeqCst);
-                            })?;
-
-                            while running.load(std::sync::atomic::Ordering::SeqCst) {
-                                std::thread::sleep(std::time::Duration::from_millis(100));
-                            }
+                    Err(e) => {
+                        eprintln!("Failed to start audio playback: {}", e);
+                        eprintln!("\nFalling back to preview mode...");
 
-                            player.stop();
-                            println!("\nStopped.");
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to start audio playback: {}", e);
-                            eprintln!("\nFalling back to preview mode...");
-
-                            if let Ok(mut eng) = engine.lock() {
-                                for i in 0..5 {
-                                    let sample = eng.process();
-                                    println!("  Sample {}: {:.6}", i, sample);
-                                }
+                        if let Ok(mut eng) = engine.lock() {
+                            for i in 0..5 {
+                                let sample = eng.process();
+                                println!("  Sample {}: {:.6}", i, sample);
                             }
-
-                            println!("\nTo generate audio, use the record command:");
-                            println!(
-                                "  drift record --config {:?} --output ambient.wav --duration 60",
-                                config_path
-                            );
                         }
+
+                        println!("\nTo generate audio, use the record command:");
+                        println!(
+                            "  drift record --config {:?} --output ambient.wav --duration 60",
+                            config_path
+                        );
                     }
                 }
             }
+
+            for source in &mut sources {
+                source.stop();
+            }
         }
 
         Commands::Record {
             config: config_path,
             output,
             duration,
+            data,
+            speed,
+            capture,
         } => {
+            use std::sync::atomic::{AtomicBool, Ordering};
+            use std::time::{Duration, Instant};
+
             println!("Loading configuration from {:?}...", config_path);
             let cfg = config::load_config(&config_path)?;
 
-            println!("Recording {} seconds to {:?}...", duration, output);
+            let sample_rate = cfg.audio.sample_rate;
+            let total_samples = sample_rate as u64 * duration;
+            let block_size = cfg.audio.buffer_size;
+            let channels = cfg.audio.channels as usize;
+            let mut buffer = vec![0.0f32; block_size * channels];
+            let mut rendered = 0u64;
+
+            let mut recorder = Recorder::with_channels(&output, sample_rate, cfg.audio.channels)?;
+
+            // Stop early on Ctrl+C but still finalize the WAV header
+            let running = Arc::new(AtomicBool::new(true));
+            let r = running.clone();
+            ctrlc::set_handler(move || {
+                r.store(false, Ordering::SeqCst);
+            })?;
+
+            if let Some(data_path) = data {
+                // Offline: replay the data log as fast as we can render
+                let mut log = DataLog::load(&data_path)?;
+                println!(
+                    "Rendering {} data points from {:?} at {}x speed...",
+                    log.len(),
+                    data_path,
+                    speed
+                );
+                println!("Recording {} seconds to {:?}...", duration, output);
+
+                let mut engine = build_engine(&cfg)?;
+                engine.set_data_speed(speed);
+
+                while rendered < total_samples && running.load(Ordering::SeqCst) {
+                    let data_time = rendered as f64 / sample_rate as f64 * speed;
+                    for point in log.advance(data_time) {
+                        engine.receive_data(point.to_data_point());
+                    }
+
+                    let n = block_size.min((total_samples - rendered) as usize);
+                    engine.fill_interleaved(&mut buffer[..n * channels], channels);
+                    recorder.write_buffer(&buffer[..n * channels])?;
+                    print_progress(rendered, rendered + n as u64, sample_rate, duration)?;
+                    rendered += n as u64;
+                }
+            } else {
+                // Live: sources deliver data on their own schedule, so render in real time
+                let engine = Arc::new(Mutex::new(build_engine(&cfg)?));
 
-            let mut engine = Engine::new(cfg.clone());
-            engine.add_drone();
+                let rt = tokio::runtime::Runtime::new()?;
+                let _guard = rt.enter();
 
-            let sample_rate = cfg.audio.sample_rate;
-            let total_samples = (sample_rate as u64 * duration) as usize;
+                let capture = match capture {
+                    Some(path) => {
+                        println!("Capturing source data to {:?}", path);
+                        Some(Arc::new(Mutex::new(DataLogWriter::create(&path)?)))
+                    }
+                    None => None,
+                };
+                let mut sources = start_sources(&cfg, &engine, capture.clone());
 
-            // Create recorder
-            let mut recorder = Recorder::new(&output, sample_rate)?;
+                // Without sources there's nothing to wait for
+                let realtime = !sources.is_empty();
+                println!("Recording {} seconds to {:?}...", duration, output);
 
-            for i in 0..total_samples {
-                let sample = engine.process() as f32;
-                recorder.write_sample(sample)?;
+                let started = Instant::now();
+                while rendered < total_samples && running.load(Ordering::SeqCst) {
+                    let n = block_size.min((total_samples - rendered) as usize);
+                    if let Ok(mut eng) = engine.lock() {
+                        eng.fill_interleaved(&mut buffer[..n * channels], channels);
+                    }
+                    recorder.write_buffer(&buffer[..n * channels])?;
+                    print_progress(rendered, rendered + n as u64, sample_rate, duration)?;
+                    rendered += n as u64;
+
+                    if realtime {
+                        let target = Duration::from_secs_f64(rendered as f64 / sample_rate as f64);
+                        if let Some(wait) = target.checked_sub(started.elapsed()) {
+                            std::thread::sleep(wait);
+                        }
+                    }
+                }
 
-                // Progress update every second
-                if i % (sample_rate as usize) == 0 {
-                    print!(
-                        "\r  Progress: {}s / {}s",
-                        i / sample_rate as usize,
-                        duration
-                    );
-                    use std::io::Write;
-                    std::io::stdout().flush()?;
+                for source in &mut sources {
+                    source.stop();
+                }
+                if let Some(capture) = capture {
+                    if let Ok(mut writer) = capture.lock() {
+                        writer.flush()?;
+                    }
                 }
             }
 
+            let recorded_secs = recorder.duration_secs();
             recorder.finalize()?;
-            println!("\nRecorded to {:?}", output);
+            println!("\nRecorded {:.1}s to {:?}", recorded_secs, output);
         }
 
         Commands::MidiPorts => {
@@ -298,6 +368,8 @@ fn main() -> Result<()> {
 
             println!("Monitoring {} sources...\n", cfg.sources.len());
 
+            let registry = SourceRegistry::with_builtins();
+
             // Create and start sources
             let rt = tokio::runtime::Runtime::new()?;
             rt.block_on(async {
@@ -308,111 +380,48 @@ fn main() -> Result<()> {
                     }
 
                     println!("{}:", source_config.name);
-                    println!("  Type: {:?}", source_config.kind);
-
-                    match source_config.kind {
-                        SourceKind::Weather => {
-                            match WeatherConfig::from_settings(&source_config.settings) {
-                                Ok(wc) => {
-                                    println!("  Location: {}", wc.location);
-                                    println!("  Interval: {:?}", wc.interval);
-                                    println!("  API key: {}...", &wc.api_key.chars().take(8).collect::<String>());
-                                    
-                                    // Try to fetch current data
-                                    let mut source = WeatherSource::new(&source_config.name, wc);
-                                    let mut rx = source.subscribe();
-                                    source.start().ok();
-                                    
-                                    // Wait briefly for data
-                                    tokio::select! {
-                                        Ok(data) = rx.recv() => {
-                                            println!("  Current readings:");
-                                            for (key, value) in &data.values {
-                                                println!("    {}: {:.2}", key, value);
-                                            }
-                                        }
-                                        _ = tokio::time::sleep(std::time::Duration::from_secs(5)) => {
-                                            println!("  (Timeout waiting for data)");
-                                        }
-                                    }
-                                    source.stop();
-                                }
-                                Err(e) => println!("  Error: {}", e),
-                            }
+                    println!("  Type: {}", source_config.kind.name());
+
+                    let mut keys: Vec<&String> = source_config.settings.keys().collect();
+                    keys.sort();
+                    for key in keys {
+                        println!("  {}: {}", key, display_setting(key, &source_config.settings[key]));
+                    }
+
+                    let mut source = match registry.build(source_config) {
+                        Ok(source) => source,
+                        Err(e) => {
+                            println!("  Error: {:#}", e);
+                            println!();
+                            continue;
                         }
-                        SourceKind::System => {
-                            let mut source = SystemSource::new(&source_config.name);
-                            let mut rx = source.subscribe();
-                            source.start().ok();
-                            
-                            tokio::select! {
-                                Ok(data) = rx.recv() => {
-                                    println!("  Current readings:");
-                                    for (key, value) in &data.values {
-                                        println!("    {}: {:.2}", key, value);
-                                    }
-                                }
-                                _ = tokio::time::sleep(std::time::Duration::from_secs(2)) => {
-                                    println!("  (Timeout waiting for data)");
-                                }
+                    };
+
+                    let mut rx = source.subscribe();
+                    if let Err(e) = source.start() {
+                        println!("  Failed to start: {:#}", e);
+                        println!();
+                        continue;
+                    }
+
+                    // Wait briefly for data
+                    tokio::select! {
+                        Ok(data) = rx.recv() => {
+                            println!("  Current readings:");
+                            let mut values: Vec<_> = data.values.iter().collect();
+                            values.sort_by(|a, b| a.0.cmp(b.0));
+                            for (key, value) in values {
+                                println!("    {}: {:.2}", key, value);
                             }
-                            source.stop();
-                        }
-                        SourceKind::Git => {
-                            match GitConfig::from_settings(&source_config.settings) {
-                                Ok(gc) => {
-                                    println!("  Path: {:?}", gc.path);
-                                    println!("  Interval: {:?}", gc.interval);
-                                    
-                                    let mut source = GitSource::new(&source_config.name, gc);
-                                    let mut rx = source.subscribe();
-                                    if source.start().is_ok() {
-                                        tokio::select! {
-                                            Ok(data) = rx.recv() => {
-                                                println!("  Current readings:");
-                                                for (key, value) in &data.values {
-                                                    println!("    {}: {:.2}", key, value);
-                                                }
-                                            }
-                                            _ = tokio::time::sleep(std::time::Duration::from_secs(2)) => {
-                                                println!("  (Timeout waiting for data)");
-                                            }
-                                        }
-                                        source.stop();
-                                    } else {
-                                        println!("  (Failed to start - check path)");
-                                    }
-                                }
-                                Err(e) => println!("  Error: {}", e),
+                            if !data.events.is_empty() {
+                                println!("  Events: {}", data.events.join(", "));
                             }
                         }
-                        SourceKind::Price => {
-                            match PriceConfig::from_settings(&source_config.settings) {
-                                Ok(pc) => {
-                                    println!("  Symbols: {:?}", pc.symbols);
-                                    println!("  Interval: {:?}", pc.interval);
-                                    
-                                    let mut source = PriceSource::new(&source_config.name, pc);
-                                    let mut rx = source.subscribe();
-                                    source.start().ok();
-                                    
-                                    tokio::select! {
-                                        Ok(data) = rx.recv() => {
-                                            println!("  Current readings:");
-                                            for (key, value) in &data.values {
-                                                println!("    {}: {:.2}", key, value);
-                                            }
-                                        }
-                                        _ = tokio::time::sleep(std::time::Duration::from_secs(5)) => {
-                                            println!("  (Timeout waiting for data)");
-                                        }
-                                    }
-                                    source.stop();
-                                }
-                                Err(e) => println!("  Error: {}", e),
-                            }
+                        _ = tokio::time::sleep(std::time::Duration::from_secs(5)) => {
+                            println!("  (Timeout waiting for data)");
                         }
                     }
+                    source.stop();
                     println!();
                 }
             });
@@ -426,17 +435,23 @@ fn main() -> Result<()> {
                     println!("Configuration is valid!");
                     println!("  Sample rate: {} Hz", cfg.audio.sample_rate);
                     println!("  Buffer size: {}", cfg.audio.buffer_size);
+                    println!("  Channels: {}", cfg.audio.channels);
                     println!("  Master volume: {:.0}%", cfg.master.volume * 100.0);
                     println!("  BPM: {}", cfg.master.bpm);
                     println!("  Key: {}", cfg.master.key);
                     println!("  Scale: {}", cfg.master.scale);
+                    let registry = SourceRegistry::with_builtins();
+                    let mut unknown_kinds = false;
                     println!("  Sources: {}", cfg.sources.len());
                     for source in &cfg.sources {
+                        let known = registry.contains(source.kind.name());
+                        unknown_kinds |= !known;
                         println!(
-                            "    - {} ({:?}) {}",
+                            "    - {} ({}) {}{}",
                             source.name,
-                            source.kind,
-                            if source.enabled { "[enabled]" } else { "[disabled]" }
+                            source.kind.name(),
+                            if source.enabled { "[enabled]" } else { "[disabled]" },
+                            if known { "" } else { " [unknown kind]" }
                         );
                     }
                     println!("  Layers: {}", cfg.layers.len());
@@ -445,6 +460,19 @@ fn main() -> Result<()> {
                             "    - {} ({:?}) -> {}",
                             layer.name, layer.voice, layer.source
                         );
+                        if !layer.patch.is_empty() {
+                            let mut names: Vec<&str> = layer.patch.keys().map(String::as_str).collect();
+                            names.sort_unstable();
+                            println!("      patch: {}", names.join(", "));
+                        }
+                    }
+
+                    if unknown_kinds {
+                        println!(
+                            "\nUnknown source kinds (available: {})",
+                            registry.kinds().join(", ")
+                        );
+                        std::process::exit(1);
                     }
                 }
                 Err(e) => {
@@ -469,3 +497,125 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Build an engine with every configured layer
+///
+/// Falls back to a static drone when no layers are configured so there's
+/// always something to hear.
+fn build_engine(cfg: &DriftConfig) -> Result<Engine> {
+    let mut engine = Engine::new(cfg.clone())?;
+    if engine.layer_count() == 0 {
+        println!("  No layers configured, using a static drone");
+        engine.add_layer(&LayerConfig::new("drone", VoiceKind::Drone, ""))?;
+        engine.set_layer_parameter("drone", "pitch", 220.0);
+    } else {
+        println!("  Layers: {}", engine.layer_count());
+        if !engine.buses().is_empty() {
+            println!("  Buses: {}", engine.buses().len());
+        }
+    }
+    Ok(engine)
+}
+
+/// Print recording progress whenever a rendered block crosses a second boundary
+fn print_progress(before: u64, after: u64, sample_rate: u32, duration: u64) -> Result<()> {
+    use std::io::Write;
+
+    let second = after / sample_rate as u64;
+    if second != before / sample_rate as u64 {
+        print!("\r  Progress: {}s / {}s", second, duration);
+        std::io::stdout().flush()?;
+    }
+    Ok(())
+}
+
+/// Format a source setting for display, hiding most of anything secret
+fn display_setting(key: &str, value: &serde_yaml::Value) -> String {
+    let text = match value {
+        serde_yaml::Value::String(s) => s.clone(),
+        other => serde_yaml::to_string(other)
+            .map(|s| s.trim().replace('\n', " "))
+            .unwrap_or_default(),
+    };
+
+    if key.contains("key") || key.contains("token") || key.contains("secret") {
+        format!("{}...", text.chars().take(8).collect::<String>())
+    } else {
+        text
+    }
+}
+
+/// Start every enabled source and forward its data points into the engine
+///
+/// Must be called from within a tokio runtime. Sources that fail to build or
+/// start are reported and skipped so one bad source doesn't silence the rest.
+/// When `capture` is set, every forwarded point is also written to the data log.
+fn start_sources(
+    cfg: &DriftConfig,
+    engine: &Arc<Mutex<Engine>>,
+    capture: Option<Arc<Mutex<DataLogWriter>>>,
+) -> Vec<Box<dyn Source>> {
+    let registry = SourceRegistry::with_builtins();
+    let mut sources = Vec::new();
+
+    for source_config in cfg.sources.iter().filter(|s| s.enabled) {
+        let mut source = match registry.build(source_config) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("  Source '{}' skipped: {:#}", source_config.name, e);
+                continue;
+            }
+        };
+
+        // Subscribe before starting so the first reading isn't missed
+        let receiver = source.subscribe();
+        if let Err(e) = source.start() {
+            eprintln!("  Source '{}' failed to start: {}", source_config.name, e);
+            continue;
+        }
+
+        println!("  Source: {} ({})", source_config.name, source_config.kind.name());
+        tokio::spawn(forward_data(
+            source_config.name.clone(),
+            receiver,
+            engine.clone(),
+            capture.clone(),
+        ));
+        sources.push(source);
+    }
+
+    sources
+}
+
+/// Route data points from a source subscription into the engine
+///
+/// Points dropped because the engine fell behind are reported by count.
+async fn forward_data(
+    name: String,
+    mut receiver: broadcast::Receiver<DataPoint>,
+    engine: Arc<Mutex<Engine>>,
+    capture: Option<Arc<Mutex<DataLogWriter>>>,
+) {
+    loop {
+        match receiver.recv().await {
+            Ok(data) => {
+                if let Some(writer) = &capture {
+                    if let Ok(mut writer) = writer.lock() {
+                        if let Err(e) = writer.write(&data) {
+                            eprintln!("Data capture error: {}", e);
+                        }
+                    }
+                }
+                if let Ok(mut eng) = engine.lock() {
+                    eng.receive_data(data);
+                }
+            }
+            // Only the latest values matter, but a backlog means the engine
+            // lock is held too long and triggers may have missed events
+            Err(RecvError::Lagged(skipped)) => {
+                eprintln!("Source '{}' lagging: dropped {} data points", name, skipped);
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
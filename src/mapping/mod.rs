@@ -15,5 +15,5 @@ pub use linear::LinearMapper;
 pub use logarithmic::LogarithmicMapper;
 pub use mapper::{Mapper, MappingPipeline};
 pub use pattern::{EuclideanPattern, PatternMapper};
-pub use quantize::{QuantizeMapper, Scale};
+pub use quantize::{key_to_hz, parse_key, QuantizeMapper, Scale};
 pub use threshold::{EdgeThresholdMapper, ThresholdDirection, ThresholdMapper};
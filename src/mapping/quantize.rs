@@ -70,6 +70,50 @@ impl Scale {
     pub fn intervals(&self) -> &[u8] {
         &self.intervals
     }
+    
+    /// Convert a scale degree to semitones from the root
+    ///
+    /// Degrees wrap into neighbouring octaves, so with a 5-note scale degree 5
+    /// is the root an octave up and degree -1 is the top note an octave down.
+    pub fn degree_to_semitones(&self, degree: i32) -> i32 {
+        let len = self.intervals.len() as i32;
+        if len == 0 {
+            return 0;
+        }
+        let octave = degree.div_euclid(len);
+        let index = degree.rem_euclid(len) as usize;
+        octave * 12 + self.intervals[index] as i32
+    }
+}
+
+/// Parse a key name ("C", "F#", "Bb") into semitones above C
+pub fn parse_key(name: &str) -> Option<i32> {
+    let mut chars = name.trim().chars();
+    let base: i32 = match chars.next()?.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+    let accidental = match chars.as_str() {
+        "" => 0,
+        "#" | "s" | "sharp" => 1,
+        "b" | "flat" => -1,
+        _ => return None,
+    };
+    Some((base + accidental).rem_euclid(12))
+}
+
+/// Frequency of a key in a given octave (A4 = 440 Hz)
+pub fn key_to_hz(name: &str, octave: i32) -> Option<f64> {
+    let semitone = parse_key(name)?;
+    // MIDI note number, where C4 = 60 and A4 = 69
+    let note = (octave + 1) * 12 + semitone;
+    Some(440.0 * 2.0_f64.powf((note - 69) as f64 / 12.0))
 }
 
 /// A mapper that quantizes frequencies to a musical scale
@@ -245,6 +289,31 @@ mod tests {
         assert!((result - 293.66).abs() < 1.0, "Expected ~294 Hz, got {}", result);
     }
 
+    #[test]
+    fn test_scale_degrees() {
+        let scale = Scale::minor_pentatonic();
+        assert_eq!(scale.degree_to_semitones(0), 0);
+        assert_eq!(scale.degree_to_semitones(2), 5);
+        assert_eq!(scale.degree_to_semitones(5), 12);
+        assert_eq!(scale.degree_to_semitones(-1), -2);
+        assert_eq!(scale.degree_to_semitones(-5), -12);
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key("C"), Some(0));
+        assert_eq!(parse_key("a"), Some(9));
+        assert_eq!(parse_key("F#"), Some(6));
+        assert_eq!(parse_key("Bb"), Some(10));
+        assert_eq!(parse_key("Cb"), Some(11));
+        assert_eq!(parse_key("H"), None);
+        assert_eq!(parse_key(""), None);
+
+        assert!((key_to_hz("A", 4).unwrap() - 440.0).abs() < 1e-9);
+        assert!((key_to_hz("C", 4).unwrap() - 261.63).abs() < 0.01);
+        assert!((key_to_hz("A", 3).unwrap() - 220.0).abs() < 1e-9);
+    }
+
     #[test]
     fn test_quantize_handles_zero() {
         let mapper = QuantizeMapper::new(
@@ -5,12 +5,16 @@
 
 mod git;
 mod price;
+mod registry;
+mod replay;
 mod source;
 mod system;
 mod weather;
 
 pub use git::{GitConfig, GitSource};
 pub use price::{PriceConfig, PriceSource};
+pub use registry::{SourceConstructor, SourceRegistry};
+pub use replay::{DataLog, DataLogWriter, RecordedPoint};
 pub use source::{DataPoint, Source};
 pub use system::{SystemConfig, SystemSource};
 pub use weather::{WeatherConfig, WeatherSource};
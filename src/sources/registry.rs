@@ -0,0 +1,199 @@
+//! Source registry
+//!
+//! Maps source kind names to constructors so every command builds sources the
+//! same way, and library users can add their own kinds without touching
+//! `SourceKind`.
+
+use super::{
+    GitConfig, GitSource, PriceConfig, PriceSource, Source, SystemConfig, SystemSource,
+    WeatherConfig, WeatherSource,
+};
+use crate::config::SourceConfig;
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+
+/// Constructor that builds a source from its configuration
+pub type SourceConstructor = Box<dyn Fn(&SourceConfig) -> Result<Box<dyn Source>> + Send + Sync>;
+
+/// Registry of source constructors keyed by kind name
+pub struct SourceRegistry {
+    constructors: HashMap<String, SourceConstructor>,
+}
+
+impl SourceRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self {
+            constructors: HashMap::new(),
+        }
+    }
+
+    /// Create a registry with the built-in sources (weather, system, git, price)
+    pub fn with_builtins() -> Self {
+        Self::new()
+            .with("weather", |config| {
+                let settings = WeatherConfig::from_settings(&config.settings)?;
+                Ok(Box::new(WeatherSource::new(&config.name, settings)))
+            })
+            .with("system", |config| {
+                let settings = SystemConfig::from_settings(&config.settings)?;
+                Ok(Box::new(SystemSource::with_config(&config.name, settings)))
+            })
+            .with("git", |config| {
+                let settings = GitConfig::from_settings(&config.settings)?;
+                Ok(Box::new(GitSource::new(&config.name, settings)))
+            })
+            .with("price", |config| {
+                let settings = PriceConfig::from_settings(&config.settings)?;
+                Ok(Box::new(PriceSource::new(&config.name, settings)))
+            })
+    }
+
+    /// Register a constructor for a source kind (builder pattern)
+    pub fn with<F>(mut self, kind: impl Into<String>, constructor: F) -> Self
+    where
+        F: Fn(&SourceConfig) -> Result<Box<dyn Source>> + Send + Sync + 'static,
+    {
+        self.register(kind, constructor);
+        self
+    }
+
+    /// Register a constructor for a source kind, replacing any existing one
+    pub fn register<F>(&mut self, kind: impl Into<String>, constructor: F)
+    where
+        F: Fn(&SourceConfig) -> Result<Box<dyn Source>> + Send + Sync + 'static,
+    {
+        self.constructors.insert(kind.into(), Box::new(constructor));
+    }
+
+    /// Check if a kind has a registered constructor
+    pub fn contains(&self, kind: &str) -> bool {
+        self.constructors.contains_key(kind)
+    }
+
+    /// Get the registered kind names, sorted
+    pub fn kinds(&self) -> Vec<&str> {
+        let mut kinds: Vec<&str> = self.constructors.keys().map(|k| k.as_str()).collect();
+        kinds.sort_unstable();
+        kinds
+    }
+
+    /// Build a source from its configuration
+    pub fn build(&self, config: &SourceConfig) -> Result<Box<dyn Source>> {
+        let kind = config.kind.name();
+        let Some(constructor) = self.constructors.get(kind) else {
+            bail!(
+                "unknown source kind '{}' (available: {})",
+                kind,
+                self.kinds().join(", ")
+            );
+        };
+
+        constructor(config).with_context(|| format!("failed to create source '{}'", config.name))
+    }
+}
+
+impl Default for SourceRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SourceKind;
+    use crate::sources::DataPoint;
+    use tokio::sync::broadcast;
+
+    fn source_config(name: &str, kind: SourceKind) -> SourceConfig {
+        SourceConfig {
+            name: name.to_string(),
+            kind,
+            enabled: true,
+            settings: HashMap::new(),
+            interpolation: Default::default(),
+        }
+    }
+
+    /// Minimal source used to test custom registration
+    struct StaticSource {
+        name: String,
+        sender: broadcast::Sender<DataPoint>,
+    }
+
+    impl Source for StaticSource {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn start(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn stop(&mut self) {}
+
+        fn is_running(&self) -> bool {
+            false
+        }
+
+        fn subscribe(&self) -> broadcast::Receiver<DataPoint> {
+            self.sender.subscribe()
+        }
+    }
+
+    #[test]
+    fn test_builtin_kinds() {
+        let registry = SourceRegistry::with_builtins();
+        assert_eq!(registry.kinds(), vec!["git", "price", "system", "weather"]);
+        assert!(registry.contains("weather"));
+        assert!(!SourceRegistry::new().contains("weather"));
+        assert!(SourceRegistry::default().kinds().is_empty());
+    }
+
+    #[test]
+    fn test_build_builtin_source() {
+        let registry = SourceRegistry::with_builtins();
+        let source = registry
+            .build(&source_config("cpu", SourceKind::System))
+            .unwrap();
+        assert_eq!(source.name(), "cpu");
+        assert!(!source.is_running());
+    }
+
+    #[test]
+    fn test_build_reports_settings_errors() {
+        let registry = SourceRegistry::with_builtins();
+        // Weather requires an api_key setting
+        let err = registry
+            .build(&source_config("weather", SourceKind::Weather))
+            .err()
+            .unwrap();
+        let message = format!("{:#}", err);
+        assert!(message.contains("failed to create source 'weather'"));
+        assert!(message.contains("api_key"));
+    }
+
+    #[test]
+    fn test_build_unknown_kind() {
+        let registry = SourceRegistry::with_builtins();
+        let config = source_config("sensor", SourceKind::Custom("air_quality".to_string()));
+        let err = registry.build(&config).err().unwrap();
+        assert!(err.to_string().contains("unknown source kind 'air_quality'"));
+    }
+
+    #[test]
+    fn test_register_custom_kind() {
+        let registry = SourceRegistry::with_builtins().with("static", |config| {
+            let (sender, _) = broadcast::channel(1);
+            Ok(Box::new(StaticSource {
+                name: config.name.clone(),
+                sender,
+            }))
+        });
+
+        let config = source_config("fixed", SourceKind::Custom("static".to_string()));
+        let source = registry.build(&config).unwrap();
+        assert_eq!(source.name(), "fixed");
+    }
+}
@@ -0,0 +1,262 @@
+//! Recorded data logs
+//!
+//! Stores data points as JSON Lines so a recording can be rendered from a
+//! captured data set instead of live sources. Each line is one point:
+//!
+//! ```text
+//! {"time": 12.5, "source": "weather", "values": {"temperature": 21.3}, "events": ["Clouds"]}
+//! ```
+//!
+//! `time` is in seconds from the start of the log. Blank lines and lines
+//! starting with `#` are ignored.
+
+use super::DataPoint;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+/// A data point with a time offset, as stored in a data log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedPoint {
+    /// Seconds since the start of the log
+    pub time: f64,
+
+    /// Name of the source that emitted this
+    pub source: String,
+
+    /// Numeric values
+    #[serde(default)]
+    pub values: HashMap<String, f64>,
+
+    /// Discrete events
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+impl RecordedPoint {
+    /// Create a recorded point from a live data point
+    pub fn from_data_point(time: f64, point: &DataPoint) -> Self {
+        Self {
+            time,
+            source: point.source.clone(),
+            values: point.values.clone(),
+            events: point.events.clone(),
+        }
+    }
+
+    /// Convert back into a data point (timestamped now)
+    pub fn to_data_point(&self) -> DataPoint {
+        let mut point = DataPoint::new(&self.source);
+        point.values = self.values.clone();
+        point.events = self.events.clone();
+        point
+    }
+}
+
+/// A time-ordered log of recorded data points
+#[derive(Debug, Clone, Default)]
+pub struct DataLog {
+    points: Vec<RecordedPoint>,
+    /// Index of the next point not yet returned by `advance`
+    position: usize,
+}
+
+impl DataLog {
+    /// Load a data log from a JSON Lines file
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read data log: {:?}", path))?;
+        Self::parse(&contents).with_context(|| format!("invalid data log: {:?}", path))
+    }
+
+    /// Parse a data log from JSON Lines text
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut points = Vec::new();
+
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let point: RecordedPoint = serde_json::from_str(line)
+                .with_context(|| format!("line {}", line_number + 1))?;
+            points.push(point);
+        }
+
+        // Stable sort keeps the file order for points with equal times
+        points.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        Ok(Self {
+            points,
+            position: 0,
+        })
+    }
+
+    /// Get the number of points in the log
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Check if the log has no points
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Get the time of the last point in seconds
+    pub fn duration(&self) -> f64 {
+        self.points.last().map(|p| p.time).unwrap_or(0.0)
+    }
+
+    /// Get all points in time order
+    pub fn points(&self) -> &[RecordedPoint] {
+        &self.points
+    }
+
+    /// Return the points that became due up to and including `time`
+    ///
+    /// Each point is returned once; call with increasing times while rendering.
+    pub fn advance(&mut self, time: f64) -> &[RecordedPoint] {
+        let start = self.position;
+        while self.position < self.points.len() && self.points[self.position].time <= time {
+            self.position += 1;
+        }
+        &self.points[start..self.position]
+    }
+
+    /// Rewind to the beginning of the log
+    pub fn reset(&mut self) {
+        self.position = 0;
+    }
+}
+
+/// Writes live data points to a JSON Lines data log
+pub struct DataLogWriter {
+    writer: BufWriter<File>,
+    started: Instant,
+}
+
+impl DataLogWriter {
+    /// Create a new data log file, timing points from now
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("failed to create data log: {:?}", path))?;
+
+        Ok(Self {
+            writer: BufWriter::new(file),
+            started: Instant::now(),
+        })
+    }
+
+    /// Append a data point, timed relative to when the log was created
+    pub fn write(&mut self, point: &DataPoint) -> Result<()> {
+        let time = point
+            .timestamp
+            .saturating_duration_since(self.started)
+            .as_secs_f64();
+        self.write_recorded(&RecordedPoint::from_data_point(time, point))
+    }
+
+    /// Append an already-timed point
+    pub fn write_recorded(&mut self, point: &RecordedPoint) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, point).context("failed to encode data point")?;
+        self.writer
+            .write_all(b"\n")
+            .context("failed to write data log")?;
+        Ok(())
+    }
+
+    /// Flush buffered points to disk
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush().context("failed to flush data log")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    const LOG: &str = r#"
+# captured from a test run
+{"time": 0.0, "source": "weather", "values": {"temperature": 20.0}}
+{"time": 2.5, "source": "git", "events": ["commit"]}
+
+{"time": 1.0, "source": "weather", "values": {"temperature": 21.0}}
+"#;
+
+    #[test]
+    fn test_parse_skips_comments_and_sorts() {
+        let log = DataLog::parse(LOG).unwrap();
+        assert_eq!(log.len(), 3);
+        assert_eq!(log.duration(), 2.5);
+
+        let times: Vec<f64> = log.points().iter().map(|p| p.time).collect();
+        assert_eq!(times, vec![0.0, 1.0, 2.5]);
+    }
+
+    #[test]
+    fn test_parse_defaults_values_and_events() {
+        let log = DataLog::parse(LOG).unwrap();
+        let commit = &log.points()[2];
+        assert_eq!(commit.source, "git");
+        assert!(commit.values.is_empty());
+        assert_eq!(commit.events, vec!["commit".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_reports_line_number() {
+        let err = DataLog::parse("{\"time\": 0, \"source\": \"a\"}\nnot json").unwrap_err();
+        assert!(format!("{:#}", err).contains("line 2"));
+    }
+
+    #[test]
+    fn test_advance_returns_each_point_once() {
+        let mut log = DataLog::parse(LOG).unwrap();
+
+        assert_eq!(log.advance(0.5).len(), 1);
+        assert_eq!(log.advance(0.5).len(), 0);
+        assert_eq!(log.advance(1.0).len(), 1);
+        assert_eq!(log.advance(10.0).len(), 1);
+        assert!(log.advance(20.0).is_empty());
+
+        log.reset();
+        assert_eq!(log.advance(10.0).len(), 3);
+    }
+
+    #[test]
+    fn test_recorded_point_to_data_point() {
+        let log = DataLog::parse(LOG).unwrap();
+        let point = log.points()[1].to_data_point();
+        assert_eq!(point.source, "weather");
+        assert_eq!(point.values.get("temperature"), Some(&21.0));
+    }
+
+    #[test]
+    fn test_writer_round_trip() {
+        let file = NamedTempFile::new().unwrap();
+
+        {
+            let mut writer = DataLogWriter::create(file.path()).unwrap();
+            let point = DataPoint::new("system")
+                .with_value("cpu_percent", 42.0)
+                .with_event("spike");
+            writer.write(&point).unwrap();
+            writer
+                .write_recorded(&RecordedPoint::from_data_point(5.0, &point))
+                .unwrap();
+            writer.flush().unwrap();
+        }
+
+        let log = DataLog::load(file.path()).unwrap();
+        assert_eq!(log.len(), 2);
+        assert!(log.points()[0].time < 1.0);
+        assert_eq!(log.points()[1].time, 5.0);
+        assert_eq!(log.points()[1].values.get("cpu_percent"), Some(&42.0));
+        assert_eq!(log.points()[1].events, vec!["spike".to_string()]);
+    }
+}
@@ -1,9 +1,52 @@
 //! Drone voice implementation
 //!
 //! A sustained tone generator with multiple detuned oscillators,
-//! ADSR envelope, biquad filter, and LFO modulation.
+//! ADSR envelope, biquad filter, and LFO modulation. A wavetable oscillator
+//! can be blended in (or replace the stack) for morphing timbres. The
+//! detuned oscillators are spread across the stereo field.
 
-use super::{Envelope, Filter, FilterType, Lfo, LfoShape, Oscillator, Voice, Waveform};
+use super::{
+    Envelope, Filter, FilterType, Frame, Lfo, LfoShape, Oscillator, ParamSpec, Voice, Waveform, Wavetable,
+    WavetableOscillator,
+};
+
+/// Drone parameters
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec::logarithmic("pitch", 20.0, 20000.0, 220.0).with_aliases(&["frequency"]),
+    ParamSpec::continuous("amplitude", 0.0, 1.0, 0.7).with_aliases(&["volume"]),
+    ParamSpec::logarithmic("filter", 20.0, 20000.0, 2000.0).with_aliases(&["filter_cutoff", "cutoff"]),
+    ParamSpec::continuous("filter_resonance", 0.1, 20.0, 1.5).with_aliases(&["resonance", "q"]),
+    ParamSpec::logarithmic("filter_lfo_rate", 0.01, 20.0, 0.1).with_aliases(&["filter_lfo_freq"]),
+    ParamSpec::continuous("filter_lfo_depth", 0.0, 5000.0, 500.0),
+    ParamSpec::logarithmic("vibrato_rate", 0.1, 20.0, 4.0).with_aliases(&["pitch_lfo_rate"]),
+    ParamSpec::continuous("vibrato_depth", 0.0, 100.0, 5.0).with_aliases(&["pitch_lfo_depth"]),
+    ParamSpec::continuous("noise_mix", 0.0, 1.0, 0.02).with_aliases(&["noise"]),
+    ParamSpec::continuous("sub_mix", 0.0, 1.0, 0.3).with_aliases(&["sub"]),
+    ParamSpec::continuous("wavetable_mix", 0.0, 1.0, 0.0),
+    ParamSpec::continuous("position", 0.0, 1.0, 0.0).with_aliases(&["wavetable_position"]),
+    ParamSpec::continuous("attack", 0.001, 10.0, 0.5),
+    ParamSpec::continuous("decay", 0.001, 10.0, 0.3),
+    ParamSpec::continuous("sustain", 0.0, 1.0, 0.8),
+    ParamSpec::continuous("release", 0.001, 30.0, 1.0),
+    ParamSpec::stepped("waveform", 0.0, 3.0, 0.0).with_options(&["saw", "square", "sine", "triangle"]),
+    ParamSpec::continuous("detune", 0.0, 50.0, 5.0),
+    ParamSpec::stepped("filter_type", 0.0, 7.0, 0.0).with_options(&[
+        "lowpass", "highpass", "bandpass", "notch", "peaking", "low_shelf", "high_shelf", "allpass",
+    ]),
+    ParamSpec::stepped("filter_lfo_shape", 0.0, 6.0, 0.0).with_options(&[
+        "sine", "triangle", "saw", "square", "sample_and_hold", "smooth_random", "drift",
+    ]),
+];
+
+/// Waveforms for the oscillator stack, in `waveform` parameter order
+const STACK_WAVEFORMS: [Waveform; 4] = [Waveform::Saw, Waveform::Square, Waveform::Sine, Waveform::Triangle];
+
+/// Detune of each stack oscillator as a multiple of `detune` cents (the
+/// fourth is always a square, a third of the spread above the root)
+const STACK_SPREAD: [f64; 4] = [0.0, 1.0, -1.0, 1.0 / 3.0];
+
+/// Stereo position of each stack oscillator (-1.0 left to 1.0 right)
+const STACK_PAN: [f64; 4] = [0.0, -0.6, 0.6, 0.0];
 
 /// A drone voice with multiple detuned oscillators and full modulation
 pub struct DroneVoice {
@@ -13,10 +56,12 @@ pub struct DroneVoice {
     sub_oscillator: Oscillator,
     /// Noise oscillator for texture
     noise_oscillator: Oscillator,
+    /// Wavetable oscillator (blended with the main oscillators)
+    wavetable: WavetableOscillator,
     /// ADSR amplitude envelope
     envelope: Envelope,
-    /// Biquad low-pass filter
-    filter: Filter,
+    /// Biquad low-pass filter (left and right)
+    filters: [Filter; 2],
     /// LFO for filter modulation
     filter_lfo: Lfo,
     /// LFO for pitch modulation (vibrato)
@@ -37,32 +82,40 @@ pub struct DroneVoice {
     noise_mix: f64,
     /// Sub oscillator mix level
     sub_mix: f64,
+    /// Wavetable level (0.0 = oscillator stack only, 1.0 = wavetable only)
+    wavetable_mix: f64,
+    /// Index into `STACK_WAVEFORMS` for the detuned oscillators
+    waveform: usize,
+    /// Oscillator spread in cents
+    detune: f64,
     
     active: bool,
 }
 
 impl DroneVoice {
+    /// Parameters every drone voice accepts (also returned by `params`)
+    pub const PARAMS: &'static [ParamSpec] = PARAMS;
+    
     /// Create a new drone voice
     pub fn new(sample_rate: f64) -> Self {
         // Create multiple detuned oscillators for rich sound
-        let oscillators = vec![
-            Oscillator::new(Waveform::Saw, 220.0, sample_rate),
-            Oscillator::new(Waveform::Saw, 220.0 * 1.003, sample_rate), // +5 cents
-            Oscillator::new(Waveform::Saw, 220.0 * 0.997, sample_rate), // -5 cents
-            Oscillator::new(Waveform::Square, 220.0 * 1.001, sample_rate), // +1.7 cents
-        ];
+        let oscillators = Self::build_stack(0, 220.0, 5.0, sample_rate);
         
         let sub_oscillator = Oscillator::new(Waveform::Sine, 110.0, sample_rate);
         let noise_oscillator = Oscillator::new(Waveform::PinkNoise, 1.0, sample_rate);
+        let wavetable = WavetableOscillator::new(Wavetable::basic(), 220.0, sample_rate);
         
         // Configure envelope for drone (slow attack, long sustain)
         let mut envelope = Envelope::new(sample_rate);
         envelope.configure(0.5, 0.3, 0.8, 1.0); // 500ms attack, 300ms decay, 80% sustain, 1s release
         
         // Configure filter
-        let mut filter = Filter::with_type(sample_rate, FilterType::LowPass);
-        filter.set_cutoff(2000.0);
-        filter.set_resonance(1.5); // Slight resonance for character
+        let filters = [0, 1].map(|_| {
+            let mut filter = Filter::with_type(sample_rate, FilterType::LowPass);
+            filter.set_cutoff(2000.0);
+            filter.set_resonance(1.5); // Slight resonance for character
+            filter
+        });
         
         // Configure filter LFO (slow, subtle)
         let mut filter_lfo = Lfo::new(sample_rate);
@@ -80,8 +133,9 @@ impl DroneVoice {
             oscillators,
             sub_oscillator,
             noise_oscillator,
+            wavetable,
             envelope,
-            filter,
+            filters,
             filter_lfo,
             pitch_lfo,
             sample_rate,
@@ -93,6 +147,9 @@ impl DroneVoice {
             pitch_lfo_depth: 5.0,    // 5 cents vibrato
             noise_mix: 0.02,         // Subtle noise
             sub_mix: 0.3,            // 30% sub
+            wavetable_mix: 0.0,      // Oscillator stack only
+            waveform: 0,             // Saw stack
+            detune: 5.0,             // +/-5 cents
             active: true,
         };
         
@@ -102,6 +159,27 @@ impl DroneVoice {
         voice
     }
     
+    /// Create a drone voice that plays a wavetable instead of the oscillator stack
+    pub fn with_wavetable(sample_rate: f64, table: Wavetable) -> Self {
+        let mut voice = Self::new(sample_rate);
+        voice.wavetable.set_table(table);
+        voice.wavetable_mix = 1.0;
+        voice
+    }
+    
+    /// Create the detuned oscillator stack: three oscillators of the chosen
+    /// waveform plus a square for edge
+    fn build_stack(waveform: usize, pitch: f64, detune: f64, sample_rate: f64) -> Vec<Oscillator> {
+        STACK_SPREAD
+            .iter()
+            .enumerate()
+            .map(|(i, spread)| {
+                let shape = if i == 3 { Waveform::Square } else { STACK_WAVEFORMS[waveform] };
+                Oscillator::new(shape, pitch * 2.0_f64.powf(spread * detune / 1200.0), sample_rate)
+            })
+            .collect()
+    }
+    
     /// Update all oscillator frequencies based on pitch + LFO
     fn update_oscillator_frequencies(&mut self, pitch_mod: f64) {
         // Convert cents to frequency multiplier
@@ -109,13 +187,14 @@ impl DroneVoice {
         let modulated_pitch = self.pitch * cents_mult;
         
         // Main oscillators with detuning
-        self.oscillators[0].set_frequency(modulated_pitch);
-        self.oscillators[1].set_frequency(modulated_pitch * 1.003);
-        self.oscillators[2].set_frequency(modulated_pitch * 0.997);
-        self.oscillators[3].set_frequency(modulated_pitch * 1.001);
+        for (osc, spread) in self.oscillators.iter_mut().zip(STACK_SPREAD) {
+            osc.set_frequency(modulated_pitch * 2.0_f64.powf(spread * self.detune / 1200.0));
+        }
         
         // Sub one octave down
         self.sub_oscillator.set_frequency(modulated_pitch * 0.5);
+        
+        self.wavetable.set_frequency(modulated_pitch);
     }
 }
 
@@ -131,11 +210,15 @@ impl Voice for DroneVoice {
             }
             "filter" | "filter_cutoff" | "cutoff" => {
                 self.filter_cutoff = value.clamp(20.0, 20000.0);
-                self.filter.set_cutoff(self.filter_cutoff);
+                for filter in &mut self.filters {
+                    filter.set_cutoff(self.filter_cutoff);
+                }
             }
             "filter_resonance" | "resonance" | "q" => {
                 self.filter_resonance = value.clamp(0.1, 20.0);
-                self.filter.set_resonance(self.filter_resonance);
+                for filter in &mut self.filters {
+                    filter.set_resonance(self.filter_resonance);
+                }
             }
             "filter_lfo_rate" | "filter_lfo_freq" => {
                 self.filter_lfo.set_frequency(value.clamp(0.01, 20.0));
@@ -155,6 +238,12 @@ impl Voice for DroneVoice {
             "sub_mix" | "sub" => {
                 self.sub_mix = value.clamp(0.0, 1.0);
             }
+            "wavetable_mix" => {
+                self.wavetable_mix = value.clamp(0.0, 1.0);
+            }
+            "position" | "wavetable_position" => {
+                self.wavetable.set_position(value);
+            }
             "attack" => {
                 self.envelope.set_attack(value.clamp(0.001, 10.0));
             }
@@ -167,6 +256,24 @@ impl Voice for DroneVoice {
             "release" => {
                 self.envelope.set_release(value.clamp(0.001, 30.0));
             }
+            "waveform" => {
+                self.waveform = value.round().clamp(0.0, (STACK_WAVEFORMS.len() - 1) as f64) as usize;
+                for osc in &mut self.oscillators[..3] {
+                    osc.set_waveform(STACK_WAVEFORMS[self.waveform]);
+                }
+            }
+            "detune" => {
+                self.detune = value.clamp(0.0, 50.0);
+                self.update_oscillator_frequencies(0.0);
+            }
+            "filter_type" => {
+                for filter in &mut self.filters {
+                    filter.set_type(FilterType::from_index(value));
+                }
+            }
+            "filter_lfo_shape" => {
+                self.filter_lfo.set_shape(LfoShape::from_index(value));
+            }
             _ => {}
         }
     }
@@ -183,10 +290,24 @@ impl Voice for DroneVoice {
             "vibrato_depth" | "pitch_lfo_depth" => Some(self.pitch_lfo_depth),
             "noise_mix" | "noise" => Some(self.noise_mix),
             "sub_mix" | "sub" => Some(self.sub_mix),
+            "wavetable_mix" => Some(self.wavetable_mix),
+            "position" | "wavetable_position" => Some(self.wavetable.position()),
+            "waveform" => Some(self.waveform as f64),
+            "detune" => Some(self.detune),
+            "filter_type" => Some(self.filters[0].filter_type().index() as f64),
+            "filter_lfo_shape" => Some(self.filter_lfo.shape().index() as f64),
+            "attack" => Some(self.envelope.attack_time()),
+            "decay" => Some(self.envelope.decay_time()),
+            "sustain" => Some(self.envelope.sustain_level()),
+            "release" => Some(self.envelope.release_time()),
             _ => None,
         }
     }
     
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+    
     fn trigger(&mut self) {
         self.active = true;
         self.envelope.trigger();
@@ -201,8 +322,12 @@ impl Voice for DroneVoice {
     }
     
     fn process(&mut self) -> f64 {
+        self.process_frame().mid()
+    }
+    
+    fn process_frame(&mut self) -> Frame {
         if !self.active {
-            return 0.0;
+            return Frame::ZERO;
         }
         
         // Get LFO values
@@ -214,23 +339,31 @@ impl Voice for DroneVoice {
         
         // Update filter cutoff with LFO
         let modulated_cutoff = (self.filter_cutoff + filter_mod).clamp(20.0, 20000.0);
-        self.filter.set_cutoff(modulated_cutoff);
+        for filter in &mut self.filters {
+            filter.set_cutoff(modulated_cutoff);
+        }
         
-        // Sum main oscillators
-        let mut sum = 0.0;
-        for osc in &mut self.oscillators {
-            sum += osc.generate();
+        // Sum main oscillators, panned linearly so the mono mix is unchanged
+        let mut sum = Frame::ZERO;
+        for (osc, pan) in self.oscillators.iter_mut().zip(STACK_PAN) {
+            let sample = osc.generate();
+            sum += Frame::new(sample * (1.0 - pan), sample * (1.0 + pan));
         }
-        sum /= self.oscillators.len() as f64;
+        sum = sum * (1.0 / self.oscillators.len() as f64);
+        
+        // Blend in the wavetable
+        let table = self.wavetable.generate();
+        sum = sum * (1.0 - self.wavetable_mix) + Frame::mono(table * self.wavetable_mix);
         
         // Add sub oscillator
-        sum += self.sub_oscillator.generate() * self.sub_mix;
+        sum += Frame::mono(self.sub_oscillator.generate() * self.sub_mix);
         
         // Add noise
-        sum += self.noise_oscillator.generate() * self.noise_mix;
+        sum += Frame::mono(self.noise_oscillator.generate() * self.noise_mix);
         
         // Apply filter
-        let filtered = self.filter.process(sum);
+        let [left, right] = &mut self.filters;
+        let filtered = Frame::new(left.process(sum.left), right.process(sum.right));
         
         // Apply envelope
         let env_level = self.envelope.process();
@@ -246,29 +379,37 @@ impl Voice for DroneVoice {
         output
     }
     
+    fn envelope_mut(&mut self) -> Option<&mut Envelope> {
+        Some(&mut self.envelope)
+    }
+
     fn set_sample_rate(&mut self, sample_rate: f64) {
         self.sample_rate = sample_rate;
         
         // Recreate oscillators at new sample rate
-        self.oscillators = vec![
-            Oscillator::new(Waveform::Saw, self.pitch, sample_rate),
-            Oscillator::new(Waveform::Saw, self.pitch * 1.003, sample_rate),
-            Oscillator::new(Waveform::Saw, self.pitch * 0.997, sample_rate),
-            Oscillator::new(Waveform::Square, self.pitch * 1.001, sample_rate),
-        ];
+        self.oscillators = Self::build_stack(self.waveform, self.pitch, self.detune, sample_rate);
         self.sub_oscillator = Oscillator::new(Waveform::Sine, self.pitch * 0.5, sample_rate);
         self.noise_oscillator = Oscillator::new(Waveform::PinkNoise, 1.0, sample_rate);
+        let position = self.wavetable.position();
+        self.wavetable = WavetableOscillator::new(self.wavetable.table().clone(), self.pitch, sample_rate);
+        self.wavetable.set_position(position);
         
         // Recreate other components
         self.envelope = Envelope::new(sample_rate);
         self.envelope.configure(0.5, 0.3, 0.8, 1.0);
         
-        self.filter = Filter::with_type(sample_rate, FilterType::LowPass);
-        self.filter.set_cutoff(self.filter_cutoff);
-        self.filter.set_resonance(self.filter_resonance);
+        let filter_type = self.filters[0].filter_type();
+        self.filters = [0, 1].map(|_| {
+            let mut filter = Filter::with_type(sample_rate, filter_type);
+            filter.set_cutoff(self.filter_cutoff);
+            filter.set_resonance(self.filter_resonance);
+            filter
+        });
         
+        let shape = self.filter_lfo.shape();
         self.filter_lfo = Lfo::new(sample_rate);
         self.filter_lfo.set_frequency(0.1);
+        self.filter_lfo.set_shape(shape);
         
         self.pitch_lfo = Lfo::new(sample_rate);
         self.pitch_lfo.set_frequency(4.0);
@@ -342,6 +483,23 @@ mod tests {
         assert!(!voice.is_active());
     }
 
+    #[test]
+    fn test_drone_voice_wavetable() {
+        let mut voice = DroneVoice::with_wavetable(44100.0, Wavetable::harmonics());
+        assert_eq!(voice.get_parameter("wavetable_mix"), Some(1.0));
+        
+        voice.set_parameter("position", 0.75);
+        assert_eq!(voice.get_parameter("position"), Some(0.75));
+        voice.set_parameter("position", 3.0);
+        assert_eq!(voice.get_parameter("position"), Some(1.0));
+        
+        let max = (0..4410).map(|_| voice.process().abs()).fold(0.0, f64::max);
+        assert!(max > 0.0);
+        
+        // The default drone doesn't use the wavetable
+        assert_eq!(DroneVoice::new(44100.0).get_parameter("wavetable_mix"), Some(0.0));
+    }
+
     #[test]
     fn test_drone_voice_lfo_modulation() {
         let mut voice = DroneVoice::new(44100.0);
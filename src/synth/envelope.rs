@@ -1,18 +1,71 @@
-//! ADSR envelope generator
+//! Envelope generator
 //!
-//! Attack-Decay-Sustain-Release envelope for amplitude shaping.
+//! Delay-Attack-Hold-Decay-Sustain-Release envelope for amplitude shaping,
+//! with linear, exponential or logarithmic segments. An envelope can loop
+//! (for slow modulation) or follow a list of breakpoints instead of ADSR.
 
 /// Envelope stage
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum EnvelopeStage {
     Idle,
+    Delay,
     Attack,
+    Hold,
     Decay,
     Sustain,
     Release,
+    /// Moving toward a breakpoint (by index)
+    Segment(usize),
 }
 
-/// ADSR envelope generator
+/// Steepness of curved segments
+const CURVE_STEEPNESS: f64 = 5.0;
+
+/// Shape of an envelope segment
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum EnvelopeCurve {
+    /// Constant rate
+    #[default]
+    Linear,
+    /// Fast at first, easing into the target (natural decays and releases)
+    Exponential,
+    /// Slow at first, speeding up toward the target (swelling attacks)
+    Logarithmic,
+}
+
+impl EnvelopeCurve {
+    /// Map progress through a segment (0-1) to progress toward its target (0-1)
+    pub fn apply(&self, progress: f64) -> f64 {
+        let t = progress.clamp(0.0, 1.0);
+        let k = CURVE_STEEPNESS;
+        match self {
+            EnvelopeCurve::Linear => t,
+            EnvelopeCurve::Exponential => (1.0 - (-k * t).exp()) / (1.0 - (-k).exp()),
+            EnvelopeCurve::Logarithmic => ((k * t).exp() - 1.0) / (k.exp() - 1.0),
+        }
+    }
+}
+
+/// A point in a multi-stage envelope: move to `level` over `time` seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Breakpoint {
+    pub level: f64,
+    pub time: f64,
+    pub curve: EnvelopeCurve,
+}
+
+impl Breakpoint {
+    /// Create a breakpoint
+    pub fn new(level: f64, time: f64, curve: EnvelopeCurve) -> Self {
+        Self {
+            level: level.clamp(0.0, 1.0),
+            time: time.max(0.0),
+            curve,
+        }
+    }
+}
+
+/// DAHDSR / multi-stage envelope generator
 pub struct Envelope {
     sample_rate: f64,
     
@@ -21,12 +74,29 @@ pub struct Envelope {
     decay: f64,
     sustain: f64,  // Level (0.0-1.0)
     release: f64,
+    delay: f64,
+    hold: f64,
+    
+    // Segment shapes
+    attack_curve: EnvelopeCurve,
+    decay_curve: EnvelopeCurve,
+    release_curve: EnvelopeCurve,
+    
+    /// Repeat attack-hold-decay (or the breakpoints) until released
+    looping: bool,
+    /// Breakpoints used instead of ADSR when not empty
+    points: Vec<Breakpoint>,
+    /// Breakpoint held while the note is on
+    sustain_point: Option<usize>,
     
     // State
     stage: EnvelopeStage,
     level: f64,
     time_in_stage: f64,
-    release_start_level: f64,
+    /// Level when the current stage started
+    stage_start_level: f64,
+    /// Whether a breakpoint envelope is playing its release points
+    releasing: bool,
 }
 
 impl Envelope {
@@ -38,10 +108,19 @@ impl Envelope {
             decay: 0.1,      // 100ms
             sustain: 0.7,    // 70% level
             release: 0.3,    // 300ms
+            delay: 0.0,
+            hold: 0.0,
+            attack_curve: EnvelopeCurve::Linear,
+            decay_curve: EnvelopeCurve::Linear,
+            release_curve: EnvelopeCurve::Linear,
+            looping: false,
+            points: Vec::new(),
+            sustain_point: None,
             stage: EnvelopeStage::Idle,
             level: 0.0,
             time_in_stage: 0.0,
-            release_start_level: 0.0,
+            stage_start_level: 0.0,
+            releasing: false,
         }
     }
     
@@ -65,6 +144,36 @@ impl Envelope {
         self.release = seconds.max(0.001);
     }
     
+    /// Get the attack time in seconds
+    pub fn attack_time(&self) -> f64 {
+        self.attack
+    }
+    
+    /// Get the decay time in seconds
+    pub fn decay_time(&self) -> f64 {
+        self.decay
+    }
+    
+    /// Get the sustain level
+    pub fn sustain_level(&self) -> f64 {
+        self.sustain
+    }
+    
+    /// Get the release time in seconds
+    pub fn release_time(&self) -> f64 {
+        self.release
+    }
+    
+    /// Set the delay before the attack in seconds
+    pub fn set_delay(&mut self, seconds: f64) {
+        self.delay = seconds.max(0.0);
+    }
+    
+    /// Set how long the peak is held before the decay in seconds
+    pub fn set_hold(&mut self, seconds: f64) {
+        self.hold = seconds.max(0.0);
+    }
+    
     /// Configure all ADSR parameters at once
     pub fn configure(&mut self, attack: f64, decay: f64, sustain: f64, release: f64) {
         self.set_attack(attack);
@@ -73,19 +182,72 @@ impl Envelope {
         self.set_release(release);
     }
     
-    /// Trigger the envelope (start attack phase)
+    /// Set the attack, decay and release curves
+    pub fn set_curves(&mut self, attack: EnvelopeCurve, decay: EnvelopeCurve, release: EnvelopeCurve) {
+        self.attack_curve = attack;
+        self.decay_curve = decay;
+        self.release_curve = release;
+    }
+    
+    /// Repeat the envelope until released
+    ///
+    /// An ADSR envelope cycles attack, hold and decay (from the sustain
+    /// level back to the peak); a breakpoint envelope starts over when it
+    /// reaches its sustain point or last point.
+    pub fn set_loop(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+    
+    /// Check if the envelope loops
+    pub fn is_looping(&self) -> bool {
+        self.looping
+    }
+    
+    /// Follow breakpoints instead of ADSR (an empty list returns to ADSR)
+    ///
+    /// The envelope moves through the points from the current level. With a
+    /// sustain point it holds that point's level until released, then plays
+    /// the points after it; without one it plays through and finishes on
+    /// its own. Anything left above zero at the end fades out over the
+    /// release time.
+    pub fn set_breakpoints(&mut self, points: Vec<Breakpoint>, sustain_point: Option<usize>) {
+        self.sustain_point = sustain_point.filter(|i| *i < points.len());
+        self.points = points;
+    }
+    
+    /// Get the breakpoints (empty for ADSR)
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.points
+    }
+    
+    /// Trigger the envelope (start from the first stage)
     pub fn trigger(&mut self) {
-        self.stage = EnvelopeStage::Attack;
-        self.time_in_stage = 0.0;
+        self.releasing = false;
+        let stage = if !self.points.is_empty() {
+            EnvelopeStage::Segment(0)
+        } else if self.delay > 0.0 {
+            EnvelopeStage::Delay
+        } else {
+            EnvelopeStage::Attack
+        };
         // Don't reset level - allows retriggering from current position
+        self.enter(stage);
     }
     
     /// Release the envelope (start release phase)
     pub fn release(&mut self) {
-        if self.stage != EnvelopeStage::Idle && self.stage != EnvelopeStage::Release {
-            self.release_start_level = self.level;
-            self.stage = EnvelopeStage::Release;
-            self.time_in_stage = 0.0;
+        if self.stage == EnvelopeStage::Idle || self.stage == EnvelopeStage::Release || self.releasing {
+            return;
+        }
+        
+        match self.sustain_point {
+            Some(point) if point + 1 < self.points.len() => {
+                self.releasing = true;
+                self.enter(EnvelopeStage::Segment(point + 1));
+            }
+            // Nothing to fade out yet
+            _ if self.level <= 0.0 => self.enter(EnvelopeStage::Idle),
+            _ => self.enter(EnvelopeStage::Release),
         }
     }
     
@@ -94,6 +256,7 @@ impl Envelope {
         self.stage = EnvelopeStage::Idle;
         self.level = 0.0;
         self.time_in_stage = 0.0;
+        self.releasing = false;
     }
     
     /// Get current stage
@@ -106,56 +269,82 @@ impl Envelope {
         self.stage != EnvelopeStage::Idle
     }
     
+    /// Start a stage from the current level
+    fn enter(&mut self, stage: EnvelopeStage) {
+        self.stage = stage;
+        self.time_in_stage = 0.0;
+        self.stage_start_level = self.level;
+    }
+    
+    /// Target level, length and curve of the current stage (None when static)
+    fn segment(&self) -> Option<(f64, f64, EnvelopeCurve)> {
+        let start = self.stage_start_level;
+        match self.stage {
+            EnvelopeStage::Idle | EnvelopeStage::Sustain => None,
+            EnvelopeStage::Delay => Some((start, self.delay, EnvelopeCurve::Linear)),
+            // Retriggers rise from the current level at the same rate
+            EnvelopeStage::Attack => Some((1.0, self.attack * (1.0 - start).max(0.0), self.attack_curve)),
+            EnvelopeStage::Hold => Some((start, self.hold, EnvelopeCurve::Linear)),
+            EnvelopeStage::Decay => Some((self.sustain, self.decay, self.decay_curve)),
+            EnvelopeStage::Release => Some((0.0, self.release, self.release_curve)),
+            EnvelopeStage::Segment(i) => {
+                let point = self.points[i];
+                Some((point.level, point.time, point.curve))
+            }
+        }
+    }
+    
+    /// Move on from a finished stage
+    fn next_stage(&mut self) {
+        let next = match self.stage {
+            EnvelopeStage::Delay => EnvelopeStage::Attack,
+            EnvelopeStage::Attack if self.hold > 0.0 => EnvelopeStage::Hold,
+            EnvelopeStage::Attack | EnvelopeStage::Hold => EnvelopeStage::Decay,
+            EnvelopeStage::Decay if self.looping => EnvelopeStage::Attack,
+            EnvelopeStage::Decay => EnvelopeStage::Sustain,
+            EnvelopeStage::Segment(i) => {
+                let at_sustain = !self.releasing && self.sustain_point == Some(i);
+                if i + 1 < self.points.len() && !at_sustain {
+                    EnvelopeStage::Segment(i + 1)
+                } else if self.looping && !self.releasing {
+                    EnvelopeStage::Segment(0)
+                } else if at_sustain {
+                    EnvelopeStage::Sustain
+                } else if self.level > 0.0 {
+                    EnvelopeStage::Release
+                } else {
+                    EnvelopeStage::Idle
+                }
+            }
+            EnvelopeStage::Release | EnvelopeStage::Idle | EnvelopeStage::Sustain => EnvelopeStage::Idle,
+        };
+        self.enter(next);
+    }
+    
     /// Generate the next envelope sample
     pub fn process(&mut self) -> f64 {
         let dt = 1.0 / self.sample_rate;
         
-        match self.stage {
-            EnvelopeStage::Idle => {
-                self.level = 0.0;
+        match self.segment() {
+            None => {
+                self.level = match self.stage {
+                    // Hold at sustain level (breakpoints hold their sustain point)
+                    EnvelopeStage::Sustain if self.points.is_empty() => self.sustain,
+                    EnvelopeStage::Sustain => self.level,
+                    _ => 0.0,
+                };
             }
-            
-            EnvelopeStage::Attack => {
-                // Linear attack from current level to 1.0
-                self.level += dt / self.attack;
+            Some((target, duration, curve)) => {
                 self.time_in_stage += dt;
-                
-                if self.level >= 1.0 {
-                    self.level = 1.0;
-                    self.stage = EnvelopeStage::Decay;
-                    self.time_in_stage = 0.0;
-                }
-            }
-            
-            EnvelopeStage::Decay => {
-                // Exponential decay from 1.0 to sustain level
-                let target = self.sustain;
-                let rate = (1.0 - target) / self.decay;
-                self.level -= rate * dt;
-                self.time_in_stage += dt;
-                
-                if self.level <= target {
+                if self.time_in_stage >= duration {
                     self.level = target;
-                    self.stage = EnvelopeStage::Sustain;
-                    self.time_in_stage = 0.0;
-                }
-            }
-            
-            EnvelopeStage::Sustain => {
-                // Hold at sustain level
-                self.level = self.sustain;
-            }
-            
-            EnvelopeStage::Release => {
-                // Linear release from release_start_level to 0.0
-                let rate = self.release_start_level / self.release;
-                self.level -= rate * dt;
-                self.time_in_stage += dt;
-                
-                if self.level <= 0.0 {
-                    self.level = 0.0;
-                    self.stage = EnvelopeStage::Idle;
-                    self.time_in_stage = 0.0;
+                    self.next_stage();
+                    if self.stage == EnvelopeStage::Idle {
+                        self.level = 0.0;
+                    }
+                } else {
+                    let progress = curve.apply(self.time_in_stage / duration);
+                    self.level = self.stage_start_level + (target - self.stage_start_level) * progress;
                 }
             }
         }
@@ -269,4 +458,122 @@ mod tests {
         env.trigger();
         assert_eq!(env.stage(), EnvelopeStage::Attack);
     }
+
+    /// Run an envelope for a number of seconds, returning every sample
+    fn run(env: &mut Envelope, seconds: f64) -> Vec<f64> {
+        (0..(seconds * 1000.0) as usize).map(|_| env.process()).collect()
+    }
+
+    #[test]
+    fn test_curves() {
+        for curve in [EnvelopeCurve::Linear, EnvelopeCurve::Exponential, EnvelopeCurve::Logarithmic] {
+            assert!(curve.apply(0.0).abs() < 1e-12);
+            assert!((curve.apply(1.0) - 1.0).abs() < 1e-12);
+        }
+        assert!(EnvelopeCurve::Exponential.apply(0.25) > 0.5);
+        assert!(EnvelopeCurve::Logarithmic.apply(0.25) < 0.1);
+    }
+
+    #[test]
+    fn test_curved_release() {
+        let mut linear = Envelope::new(1000.0);
+        let mut curved = Envelope::new(1000.0);
+        curved.set_curves(EnvelopeCurve::Linear, EnvelopeCurve::Linear, EnvelopeCurve::Exponential);
+        for env in [&mut linear, &mut curved] {
+            env.configure(0.001, 0.001, 1.0, 1.0);
+            env.trigger();
+            run(env, 0.1);
+            env.release();
+        }
+
+        // A quarter of the way through the release
+        let linear = run(&mut linear, 0.25);
+        let curved = run(&mut curved, 0.25);
+        assert!((linear[249] - 0.75).abs() < 0.01);
+        assert!(curved[249] < 0.3, "{}", curved[249]);
+    }
+
+    #[test]
+    fn test_delay_and_hold() {
+        let mut env = Envelope::new(1000.0);
+        env.configure(0.01, 0.01, 0.5, 0.1);
+        env.set_delay(0.1);
+        env.set_hold(0.2);
+        env.trigger();
+        assert_eq!(env.stage(), EnvelopeStage::Delay);
+
+        let levels = run(&mut env, 0.5);
+        assert_eq!(levels[50], 0.0);
+        assert!((levels[200] - 1.0).abs() < 1e-9);
+        assert!((levels[450] - 0.5).abs() < 1e-9);
+        assert_eq!(env.stage(), EnvelopeStage::Sustain);
+    }
+
+    #[test]
+    fn test_looping_envelope() {
+        let mut env = Envelope::new(1000.0);
+        env.configure(0.1, 0.1, 0.0, 0.1);
+        env.set_loop(true);
+        env.trigger();
+
+        // Attack and decay repeat every 200 ms without settling
+        let levels = run(&mut env, 1.0);
+        let peaks = levels.windows(2).filter(|w| w[0] >= 1.0 - 1e-9 && w[1] < w[0]).count();
+        assert_eq!(peaks, 5);
+        assert!(env.is_active());
+
+        env.release();
+        run(&mut env, 0.2);
+        assert!(!env.is_active());
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut env = Envelope::new(1000.0);
+        env.set_breakpoints(
+            vec![
+                Breakpoint::new(1.0, 0.1, EnvelopeCurve::Linear),
+                Breakpoint::new(0.3, 0.1, EnvelopeCurve::Exponential),
+                Breakpoint::new(0.6, 0.1, EnvelopeCurve::Linear),
+                Breakpoint::new(0.0, 0.1, EnvelopeCurve::Linear),
+            ],
+            Some(2),
+        );
+        env.trigger();
+        assert_eq!(env.stage(), EnvelopeStage::Segment(0));
+
+        let levels = run(&mut env, 0.5);
+        assert!((levels[99] - 1.0).abs() < 1e-9);
+        assert!((levels[199] - 0.3).abs() < 1e-9);
+        assert!((levels[450] - 0.6).abs() < 1e-9);
+        assert_eq!(env.stage(), EnvelopeStage::Sustain);
+
+        // Release plays the points after the sustain point
+        env.release();
+        assert_eq!(env.stage(), EnvelopeStage::Segment(3));
+        run(&mut env, 0.11);
+        assert!(!env.is_active());
+    }
+
+    #[test]
+    fn test_one_shot_breakpoints_finish() {
+        let mut env = Envelope::new(1000.0);
+        env.set_breakpoints(
+            vec![
+                Breakpoint::new(1.0, 0.01, EnvelopeCurve::Linear),
+                Breakpoint::new(0.5, 0.1, EnvelopeCurve::Linear),
+            ],
+            None,
+        );
+        env.set_release(0.05);
+        env.trigger();
+
+        // No sustain point: the last level fades out over the release time
+        run(&mut env, 0.12);
+        assert_eq!(env.stage(), EnvelopeStage::Release);
+        run(&mut env, 0.06);
+        assert!(!env.is_active());
+        assert_eq!(env.level(), 0.0);
+    }
 }
+
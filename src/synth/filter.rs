@@ -1,15 +1,94 @@
-//! Biquad filter implementation
+//! Filter implementation
 //!
-//! Digital biquad filter for audio processing.
+//! Digital filters for audio processing: RBJ biquads, a state-variable filter
+//! (stays stable under fast cutoff sweeps) and a Moog-style ladder with
+//! saturating drive.
 
 use std::f64::consts::PI;
 
-/// Filter type
+/// Filter response
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FilterType {
     LowPass,
     HighPass,
     BandPass,
+    /// Cuts a narrow band around the cutoff
+    Notch,
+    /// Boosts or cuts around the cutoff by the filter gain
+    Peaking,
+    /// Boosts or cuts below the cutoff by the filter gain
+    LowShelf,
+    /// Boosts or cuts above the cutoff by the filter gain
+    HighShelf,
+    /// Flat magnitude, shifts phase around the cutoff
+    AllPass,
+}
+
+impl FilterType {
+    /// All responses, in parameter order
+    pub const ALL: [FilterType; 8] = [
+        FilterType::LowPass,
+        FilterType::HighPass,
+        FilterType::BandPass,
+        FilterType::Notch,
+        FilterType::Peaking,
+        FilterType::LowShelf,
+        FilterType::HighShelf,
+        FilterType::AllPass,
+    ];
+
+    /// Get a response from its parameter value (rounded and clamped)
+    pub fn from_index(value: f64) -> Self {
+        Self::ALL[(value.round().max(0.0) as usize).min(Self::ALL.len() - 1)]
+    }
+
+    /// Get the parameter value for this response
+    pub fn index(&self) -> usize {
+        Self::ALL.iter().position(|t| t == self).unwrap_or(0)
+    }
+
+    /// Check if the response uses the filter gain
+    pub fn uses_gain(&self) -> bool {
+        matches!(self, FilterType::Peaking | FilterType::LowShelf | FilterType::HighShelf)
+    }
+}
+
+/// Filter circuit
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FilterModel {
+    /// RBJ biquad (default)
+    #[default]
+    Biquad,
+    /// Trapezoidal state-variable filter, smooth under modulation
+    StateVariable,
+    /// Four-pole transistor ladder with drive (low, high and band pass only)
+    Ladder,
+}
+
+impl FilterModel {
+    /// All models, in parameter order
+    pub const ALL: [FilterModel; 3] = [FilterModel::Biquad, FilterModel::StateVariable, FilterModel::Ladder];
+
+    /// Get a model from its parameter value (rounded and clamped)
+    pub fn from_index(value: f64) -> Self {
+        Self::ALL[(value.round().max(0.0) as usize).min(Self::ALL.len() - 1)]
+    }
+
+    /// Get the parameter value for this model
+    pub fn index(&self) -> usize {
+        Self::ALL.iter().position(|m| m == self).unwrap_or(0)
+    }
+
+    /// Check if the model can produce a response
+    pub fn supports(&self, filter_type: FilterType) -> bool {
+        match self {
+            FilterModel::Ladder => matches!(
+                filter_type,
+                FilterType::LowPass | FilterType::HighPass | FilterType::BandPass
+            ),
+            _ => true,
+        }
+    }
 }
 
 /// Biquad filter coefficients
@@ -34,51 +113,82 @@ impl Default for Coefficients {
     }
 }
 
-/// Biquad filter for audio processing
+/// State-variable filter coefficients (tick gains and output mix)
+#[derive(Debug, Clone, Copy, Default)]
+struct SvfCoefficients {
+    a1: f64,
+    a2: f64,
+    a3: f64,
+    m0: f64,
+    m1: f64,
+    m2: f64,
+}
+
+/// Filter for audio processing
 pub struct Filter {
     filter_type: FilterType,
+    model: FilterModel,
     sample_rate: f64,
     cutoff: f64,
     resonance: f64, // Q factor
+    /// Boost or cut in dB (peaking and shelving responses)
+    gain: f64,
+    /// Input gain into the ladder's saturation
+    drive: f64,
     
     coeffs: Coefficients,
+    svf: SvfCoefficients,
+    /// Ladder one-pole coefficient and feedback amount
+    ladder_g: f64,
+    ladder_k: f64,
     
-    // Filter state (Direct Form II transposed)
+    // Biquad state (Direct Form II transposed)
     z1: f64,
     z2: f64,
+    // State-variable integrator state
+    ic1: f64,
+    ic2: f64,
+    // Ladder stage outputs
+    stages: [f64; 4],
 }
 
 impl Filter {
     /// Create a new low-pass filter
     pub fn new(sample_rate: f64) -> Self {
-        let mut filter = Self {
-            filter_type: FilterType::LowPass,
-            sample_rate,
-            cutoff: 1000.0,
-            resonance: 0.707, // Butterworth Q
-            coeffs: Coefficients::default(),
-            z1: 0.0,
-            z2: 0.0,
-        };
-        filter.calculate_coefficients();
-        filter
+        Self::with_type(sample_rate, FilterType::LowPass)
     }
     
     /// Create a filter with specific type
     pub fn with_type(sample_rate: f64, filter_type: FilterType) -> Self {
         let mut filter = Self {
             filter_type,
+            model: FilterModel::Biquad,
             sample_rate,
             cutoff: 1000.0,
-            resonance: 0.707,
+            resonance: 0.707, // Butterworth Q
+            gain: 0.0,
+            drive: 1.0,
             coeffs: Coefficients::default(),
+            svf: SvfCoefficients::default(),
+            ladder_g: 0.0,
+            ladder_k: 0.0,
             z1: 0.0,
             z2: 0.0,
+            ic1: 0.0,
+            ic2: 0.0,
+            stages: [0.0; 4],
         };
         filter.calculate_coefficients();
         filter
     }
     
+    /// Create a filter with a specific type and model
+    pub fn with_model(sample_rate: f64, filter_type: FilterType, model: FilterModel) -> Self {
+        let mut filter = Self::with_type(sample_rate, filter_type);
+        filter.set_model(model);
+        filter
+    }
+    
     /// Set cutoff frequency in Hz
     pub fn set_cutoff(&mut self, hz: f64) {
         // Clamp to valid range (20 Hz to Nyquist - margin)
@@ -112,18 +222,75 @@ impl Filter {
         self.calculate_coefficients();
     }
     
+    /// Get filter type
+    pub fn filter_type(&self) -> FilterType {
+        self.filter_type
+    }
+    
+    /// Set filter model
+    ///
+    /// The ladder only has low, high and band pass responses; other types
+    /// fall back to its low pass.
+    pub fn set_model(&mut self, model: FilterModel) {
+        if model != self.model {
+            self.model = model;
+            self.reset();
+            self.calculate_coefficients();
+        }
+    }
+    
+    /// Get filter model
+    pub fn model(&self) -> FilterModel {
+        self.model
+    }
+    
+    /// Set the peaking/shelf gain in dB (-24 to 24)
+    pub fn set_gain(&mut self, db: f64) {
+        self.gain = db.clamp(-24.0, 24.0);
+        self.calculate_coefficients();
+    }
+    
+    /// Get the peaking/shelf gain in dB
+    pub fn gain(&self) -> f64 {
+        self.gain
+    }
+    
+    /// Set the ladder drive (1 = clean, up to 10)
+    pub fn set_drive(&mut self, drive: f64) {
+        self.drive = drive.clamp(1.0, 10.0);
+    }
+    
+    /// Get the ladder drive
+    pub fn drive(&self) -> f64 {
+        self.drive
+    }
+    
     /// Reset filter state (clear history)
     pub fn reset(&mut self) {
         self.z1 = 0.0;
         self.z2 = 0.0;
+        self.ic1 = 0.0;
+        self.ic2 = 0.0;
+        self.stages = [0.0; 4];
     }
     
-    /// Calculate biquad coefficients based on current parameters
+    /// Calculate coefficients for the current model and parameters
     fn calculate_coefficients(&mut self) {
+        match self.model {
+            FilterModel::Biquad => self.calculate_biquad(),
+            FilterModel::StateVariable => self.calculate_svf(),
+            FilterModel::Ladder => self.calculate_ladder(),
+        }
+    }
+    
+    /// Calculate biquad coefficients (RBJ audio EQ cookbook)
+    fn calculate_biquad(&mut self) {
         let omega = 2.0 * PI * self.cutoff / self.sample_rate;
         let sin_omega = omega.sin();
         let cos_omega = omega.cos();
         let alpha = sin_omega / (2.0 * self.resonance);
+        let a = 10f64.powf(self.gain / 40.0);
+        let shelf = 2.0 * a.sqrt() * alpha;
         
         let (b0, b1, b2, a0, a1, a2) = match self.filter_type {
             FilterType::LowPass => {
@@ -153,6 +320,51 @@ impl Filter {
                 let a2 = 1.0 - alpha;
                 (b0, b1, b2, a0, a1, a2)
             }
+            FilterType::Notch => {
+                let b0 = 1.0;
+                let b1 = -2.0 * cos_omega;
+                let b2 = 1.0;
+                let a0 = 1.0 + alpha;
+                let a1 = -2.0 * cos_omega;
+                let a2 = 1.0 - alpha;
+                (b0, b1, b2, a0, a1, a2)
+            }
+            FilterType::Peaking => {
+                let b0 = 1.0 + alpha * a;
+                let b1 = -2.0 * cos_omega;
+                let b2 = 1.0 - alpha * a;
+                let a0 = 1.0 + alpha / a;
+                let a1 = -2.0 * cos_omega;
+                let a2 = 1.0 - alpha / a;
+                (b0, b1, b2, a0, a1, a2)
+            }
+            FilterType::LowShelf => {
+                let b0 = a * ((a + 1.0) - (a - 1.0) * cos_omega + shelf);
+                let b1 = 2.0 * a * ((a - 1.0) - (a + 1.0) * cos_omega);
+                let b2 = a * ((a + 1.0) - (a - 1.0) * cos_omega - shelf);
+                let a0 = (a + 1.0) + (a - 1.0) * cos_omega + shelf;
+                let a1 = -2.0 * ((a - 1.0) + (a + 1.0) * cos_omega);
+                let a2 = (a + 1.0) + (a - 1.0) * cos_omega - shelf;
+                (b0, b1, b2, a0, a1, a2)
+            }
+            FilterType::HighShelf => {
+                let b0 = a * ((a + 1.0) + (a - 1.0) * cos_omega + shelf);
+                let b1 = -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_omega);
+                let b2 = a * ((a + 1.0) + (a - 1.0) * cos_omega - shelf);
+                let a0 = (a + 1.0) - (a - 1.0) * cos_omega + shelf;
+                let a1 = 2.0 * ((a - 1.0) - (a + 1.0) * cos_omega);
+                let a2 = (a + 1.0) - (a - 1.0) * cos_omega - shelf;
+                (b0, b1, b2, a0, a1, a2)
+            }
+            FilterType::AllPass => {
+                let b0 = 1.0 - alpha;
+                let b1 = -2.0 * cos_omega;
+                let b2 = 1.0 + alpha;
+                let a0 = 1.0 + alpha;
+                let a1 = -2.0 * cos_omega;
+                let a2 = 1.0 - alpha;
+                (b0, b1, b2, a0, a1, a2)
+            }
         };
         
         // Normalize by a0
@@ -165,8 +377,62 @@ impl Filter {
         };
     }
     
+    /// Calculate state-variable coefficients (Simper's trapezoidal SVF)
+    fn calculate_svf(&mut self) {
+        let a = 10f64.powf(self.gain / 40.0);
+        let mut g = (PI * self.cutoff / self.sample_rate).tan();
+        let mut k = 1.0 / self.resonance;
+        
+        // Output mix of input, band pass and low pass
+        let (m0, m1, m2) = match self.filter_type {
+            FilterType::LowPass => (0.0, 0.0, 1.0),
+            FilterType::HighPass => (1.0, -k, -1.0),
+            FilterType::BandPass => (0.0, 1.0, 0.0),
+            FilterType::Notch => (1.0, -k, 0.0),
+            FilterType::AllPass => (1.0, -2.0 * k, 0.0),
+            FilterType::Peaking => {
+                k = 1.0 / (self.resonance * a);
+                (1.0, k * (a * a - 1.0), 0.0)
+            }
+            FilterType::LowShelf => {
+                g /= a.sqrt();
+                (1.0, k * (a - 1.0), a * a - 1.0)
+            }
+            FilterType::HighShelf => {
+                g *= a.sqrt();
+                (a * a, k * (1.0 - a) * a, 1.0 - a * a)
+            }
+        };
+        
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        self.svf = SvfCoefficients {
+            a1,
+            a2,
+            a3: g * a2,
+            m0,
+            m1,
+            m2,
+        };
+    }
+    
+    /// Calculate ladder coefficients
+    fn calculate_ladder(&mut self) {
+        self.ladder_g = 1.0 - (-2.0 * PI * self.cutoff / self.sample_rate).exp();
+        // Q 0.707 is gentle; self-oscillation sets in around Q 5
+        self.ladder_k = (self.resonance / 5.0).min(1.0) * 3.9;
+    }
+    
     /// Process a single sample through the filter
     pub fn process(&mut self, input: f64) -> f64 {
+        match self.model {
+            FilterModel::Biquad => self.process_biquad(input),
+            FilterModel::StateVariable => self.process_svf(input),
+            FilterModel::Ladder => self.process_ladder(input),
+        }
+    }
+    
+    fn process_biquad(&mut self, input: f64) -> f64 {
         // Direct Form II Transposed
         let output = self.coeffs.b0 * input + self.z1;
         
@@ -176,6 +442,37 @@ impl Filter {
         output
     }
     
+    fn process_svf(&mut self, input: f64) -> f64 {
+        let c = self.svf;
+        let v3 = input - self.ic2;
+        let v1 = c.a1 * self.ic1 + c.a2 * v3;
+        let v2 = self.ic2 + c.a2 * self.ic1 + c.a3 * v3;
+        self.ic1 = 2.0 * v1 - self.ic1;
+        self.ic2 = 2.0 * v2 - self.ic2;
+        
+        c.m0 * input + c.m1 * v1 + c.m2 * v2
+    }
+    
+    fn process_ladder(&mut self, input: f64) -> f64 {
+        let (g, k) = (self.ladder_g, self.ladder_k);
+        
+        // Resonance feedback from the last stage; the (1 + k) input gain
+        // keeps the pass band level as resonance rises
+        let x = (self.drive * input * (1.0 + k) - k * self.stages[3]).tanh();
+        let mut previous = x;
+        for stage in self.stages.iter_mut() {
+            *stage += g * (previous.tanh() - stage.tanh());
+            previous = *stage;
+        }
+        
+        let [s1, s2, s3, s4] = self.stages;
+        match self.filter_type {
+            FilterType::HighPass => x - 4.0 * s1 + 6.0 * s2 - 4.0 * s3 + s4,
+            FilterType::BandPass => 4.0 * (s2 - 2.0 * s3 + s4),
+            _ => s4,
+        }
+    }
+    
     /// Process a buffer of samples in place
     pub fn process_buffer(&mut self, buffer: &mut [f64]) {
         for sample in buffer.iter_mut() {
@@ -334,4 +631,110 @@ mod tests {
         let max = buffer.iter().fold(0.0f64, |a, &b| a.max(b.abs()));
         assert!(max < 0.2);
     }
+
+    /// Steady-state gain for a sine at `freq`
+    fn gain_at(filter: &mut Filter, freq: f64) -> f64 {
+        let mut input_sq = 0.0;
+        let mut output_sq = 0.0;
+        for i in 0..8820 {
+            let input = (2.0 * PI * freq * i as f64 / 44100.0).sin() * 0.1;
+            let output = filter.process(input);
+            if i >= 4410 {
+                input_sq += input * input;
+                output_sq += output * output;
+            }
+        }
+        (output_sq / input_sq).sqrt()
+    }
+
+    #[test]
+    fn test_type_index_roundtrip() {
+        for filter_type in FilterType::ALL {
+            assert_eq!(FilterType::from_index(filter_type.index() as f64), filter_type);
+        }
+        assert_eq!(FilterType::from_index(-3.0), FilterType::LowPass);
+        assert_eq!(FilterType::from_index(99.0), FilterType::AllPass);
+        assert_eq!(FilterModel::from_index(2.2), FilterModel::Ladder);
+        assert!(!FilterModel::Ladder.supports(FilterType::Notch));
+    }
+
+    #[test]
+    fn test_notch_and_allpass() {
+        for model in [FilterModel::Biquad, FilterModel::StateVariable] {
+            let mut notch = Filter::with_model(44100.0, FilterType::Notch, model);
+            assert!(gain_at(&mut notch, 1000.0) < 0.05, "{:?}", model);
+            assert!(gain_at(&mut notch, 100.0) > 0.9, "{:?}", model);
+
+            let mut allpass = Filter::with_model(44100.0, FilterType::AllPass, model);
+            for freq in [100.0, 1000.0, 8000.0] {
+                assert!((gain_at(&mut allpass, freq) - 1.0).abs() < 0.02, "{:?}", model);
+            }
+        }
+    }
+
+    #[test]
+    fn test_peaking_and_shelves() {
+        // +12 dB is a gain of ~3.98
+        for model in [FilterModel::Biquad, FilterModel::StateVariable] {
+            let mut peak = Filter::with_model(44100.0, FilterType::Peaking, model);
+            peak.set_gain(12.0);
+            assert!((gain_at(&mut peak, 1000.0) - 3.98).abs() < 0.1, "{:?}", model);
+            assert!((gain_at(&mut peak, 50.0) - 1.0).abs() < 0.1, "{:?}", model);
+
+            let mut low = Filter::with_model(44100.0, FilterType::LowShelf, model);
+            low.set_gain(12.0);
+            assert!((gain_at(&mut low, 50.0) - 3.98).abs() < 0.2, "{:?}", model);
+            assert!((gain_at(&mut low, 10000.0) - 1.0).abs() < 0.1, "{:?}", model);
+
+            let mut high = Filter::with_model(44100.0, FilterType::HighShelf, model);
+            high.set_gain(-12.0);
+            assert!((gain_at(&mut high, 10000.0) - 0.251).abs() < 0.05, "{:?}", model);
+            assert!((gain_at(&mut high, 50.0) - 1.0).abs() < 0.1, "{:?}", model);
+        }
+    }
+
+    #[test]
+    fn test_state_variable_matches_biquad() {
+        let mut svf = Filter::with_model(44100.0, FilterType::LowPass, FilterModel::StateVariable);
+        svf.set_cutoff(500.0);
+        assert!(gain_at(&mut svf, 5000.0) < 0.02);
+        assert!(gain_at(&mut svf, 50.0) > 0.95);
+
+        let mut svf = Filter::with_model(44100.0, FilterType::HighPass, FilterModel::StateVariable);
+        svf.set_cutoff(2000.0);
+        assert!(gain_at(&mut svf, 100.0) < 0.01);
+        assert!(gain_at(&mut svf, 15000.0) > 0.95);
+    }
+
+    #[test]
+    fn test_ladder() {
+        let mut ladder = Filter::with_model(44100.0, FilterType::LowPass, FilterModel::Ladder);
+        ladder.set_cutoff(500.0);
+        assert!(gain_at(&mut ladder, 50.0) > 0.9);
+        assert!(gain_at(&mut ladder, 5000.0) < 0.01);
+
+        // Resonance peaks near the cutoff
+        let mut resonant = Filter::with_model(44100.0, FilterType::LowPass, FilterModel::Ladder);
+        resonant.set_cutoff(1000.0);
+        resonant.set_resonance(4.0);
+        assert!(gain_at(&mut resonant, 1000.0) > 1.5);
+
+        let mut highpass = Filter::with_model(44100.0, FilterType::HighPass, FilterModel::Ladder);
+        highpass.set_cutoff(1000.0);
+        assert!(gain_at(&mut highpass, 50.0) < 0.01);
+        assert!(gain_at(&mut highpass, 10000.0) > 0.5);
+    }
+
+    #[test]
+    fn test_ladder_drive_saturates() {
+        let mut ladder = Filter::with_model(44100.0, FilterType::LowPass, FilterModel::Ladder);
+        ladder.set_cutoff(5000.0);
+        ladder.set_drive(10.0);
+        assert_eq!(ladder.drive(), 10.0);
+
+        let max = (0..4410)
+            .map(|i| ladder.process((2.0 * PI * 100.0 * i as f64 / 44100.0).sin()))
+            .fold(0.0f64, |a, b| a.max(b.abs()));
+        assert!(max <= 1.0 + 1e-9 && max > 0.9, "max {}", max);
+    }
 }
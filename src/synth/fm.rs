@@ -0,0 +1,386 @@
+//! FM voice implementation
+//!
+//! Four sine operators in phase-modulation arrangements (algorithms), each
+//! with its own frequency ratio, level and envelope. The modulation index
+//! scales how hard modulators drive their targets, which is the main
+//! brightness control.
+
+use super::{Envelope, ParamSpec, Voice};
+use std::f64::consts::PI;
+
+/// Number of operators
+pub const OPERATORS: usize = 4;
+
+/// Operator routings: for each algorithm, the modulators of each operator and
+/// whether it is a carrier. Operators only modulate lower-numbered operators
+/// (op4 is always processed first and is the one with feedback).
+///
+/// ```text
+/// 0: 4 -> 3 -> 2 -> 1          serial stack
+/// 1: (3 + 4) -> 2 -> 1         two modulators into a stack
+/// 2: (2 + (4 -> 3)) -> 1       branch into one carrier
+/// 3: (2 -> 1) + (4 -> 3)       two pairs
+/// 4: 4 -> (1, 2, 3)            one modulator, three carriers
+/// 5: (4 -> 3) + 2 + 1          one pair plus two sines
+/// 6: 1 + 2 + 3 + 4             additive
+/// ```
+const ALGORITHMS: [[(&[usize], bool); OPERATORS]; 7] = [
+    [(&[1], true), (&[2], false), (&[3], false), (&[], false)],
+    [(&[1], true), (&[2, 3], false), (&[], false), (&[], false)],
+    [(&[1, 2], true), (&[], false), (&[3], false), (&[], false)],
+    [(&[1], true), (&[], false), (&[3], true), (&[], false)],
+    [(&[3], true), (&[3], true), (&[3], true), (&[], false)],
+    [(&[], true), (&[], true), (&[3], true), (&[], false)],
+    [(&[], true), (&[], true), (&[], true), (&[], true)],
+];
+
+/// FM parameters
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec::logarithmic("pitch", 20.0, 20000.0, 220.0).with_aliases(&["frequency"]),
+    ParamSpec::stepped("algorithm", 0.0, 6.0, 1.0),
+    ParamSpec::continuous("index", 0.0, 20.0, 2.0).with_aliases(&["mod_index"]),
+    ParamSpec::continuous("feedback", 0.0, 1.0, 0.0),
+    ParamSpec::continuous("amplitude", 0.0, 1.0, 0.6).with_aliases(&["volume"]),
+    ParamSpec::logarithmic("op1_ratio", 0.0625, 32.0, 1.0),
+    ParamSpec::continuous("op1_level", 0.0, 1.0, 1.0),
+    ParamSpec::continuous("op1_attack", 0.001, 10.0, 0.01),
+    ParamSpec::continuous("op1_decay", 0.001, 10.0, 0.3),
+    ParamSpec::continuous("op1_sustain", 0.0, 1.0, 0.7),
+    ParamSpec::continuous("op1_release", 0.001, 30.0, 0.5),
+    ParamSpec::logarithmic("op2_ratio", 0.0625, 32.0, 2.0),
+    ParamSpec::continuous("op2_level", 0.0, 1.0, 0.6),
+    ParamSpec::continuous("op2_attack", 0.001, 10.0, 0.01),
+    ParamSpec::continuous("op2_decay", 0.001, 10.0, 0.3),
+    ParamSpec::continuous("op2_sustain", 0.0, 1.0, 0.7),
+    ParamSpec::continuous("op2_release", 0.001, 30.0, 0.5),
+    ParamSpec::logarithmic("op3_ratio", 0.0625, 32.0, 1.0),
+    ParamSpec::continuous("op3_level", 0.0, 1.0, 0.5),
+    ParamSpec::continuous("op3_attack", 0.001, 10.0, 0.01),
+    ParamSpec::continuous("op3_decay", 0.001, 10.0, 0.3),
+    ParamSpec::continuous("op3_sustain", 0.0, 1.0, 0.7),
+    ParamSpec::continuous("op3_release", 0.001, 30.0, 0.5),
+    ParamSpec::logarithmic("op4_ratio", 0.0625, 32.0, 3.0),
+    ParamSpec::continuous("op4_level", 0.0, 1.0, 0.3),
+    ParamSpec::continuous("op4_attack", 0.001, 10.0, 0.01),
+    ParamSpec::continuous("op4_decay", 0.001, 10.0, 0.3),
+    ParamSpec::continuous("op4_sustain", 0.0, 1.0, 0.7),
+    ParamSpec::continuous("op4_release", 0.001, 30.0, 0.5),
+];
+
+/// A single sine operator
+struct Operator {
+    phase: f64,
+    /// Frequency as a multiple of the voice pitch
+    ratio: f64,
+    /// Output level (0.0 to 1.0)
+    level: f64,
+    envelope: Envelope,
+    /// Last output (used for feedback and modulation)
+    output: f64,
+}
+
+impl Operator {
+    fn new(sample_rate: f64, ratio: f64, level: f64) -> Self {
+        let mut envelope = Envelope::new(sample_rate);
+        envelope.configure(0.01, 0.3, 0.7, 0.5);
+        Self {
+            phase: 0.0,
+            ratio,
+            level,
+            envelope,
+            output: 0.0,
+        }
+    }
+}
+
+/// A four-operator FM voice
+pub struct FmVoice {
+    operators: Vec<Operator>,
+    sample_rate: f64,
+
+    // Parameters
+    pitch: f64,
+    algorithm: usize,
+    /// Modulation index in radians at full modulator level
+    index: f64,
+    /// Self-modulation of operator 4 (0.0 to 1.0)
+    feedback: f64,
+    amplitude: f64,
+
+    active: bool,
+}
+
+impl FmVoice {
+    /// Parameters every FM voice accepts (also returned by `params`)
+    pub const PARAMS: &'static [ParamSpec] = PARAMS;
+
+    /// Create a new FM voice
+    pub fn new(sample_rate: f64) -> Self {
+        // A soft electric-piano-like default: 1:1 carrier, 2:1 and 1:1 modulators
+        let operators = vec![
+            Operator::new(sample_rate, 1.0, 1.0),
+            Operator::new(sample_rate, 2.0, 0.6),
+            Operator::new(sample_rate, 1.0, 0.5),
+            Operator::new(sample_rate, 3.0, 0.3),
+        ];
+
+        let mut voice = Self {
+            operators,
+            sample_rate,
+            pitch: 220.0,
+            algorithm: 1,
+            index: 2.0,
+            feedback: 0.0,
+            amplitude: 0.6,
+            active: true,
+        };
+
+        // Auto-trigger like the drone so the layer sounds straight away
+        voice.trigger();
+        voice
+    }
+
+    /// Get the current algorithm number
+    pub fn algorithm(&self) -> usize {
+        self.algorithm
+    }
+
+    /// Parse an operator parameter name like `op2_ratio` into (index, field)
+    fn operator_parameter(name: &str) -> Option<(usize, &str)> {
+        let rest = name.strip_prefix("op")?;
+        let (number, field) = rest.split_once('_')?;
+        let number: usize = number.parse().ok()?;
+        if (1..=OPERATORS).contains(&number) {
+            Some((number - 1, field))
+        } else {
+            None
+        }
+    }
+}
+
+impl Voice for FmVoice {
+    fn set_parameter(&mut self, name: &str, value: f64) {
+        if let Some((i, field)) = Self::operator_parameter(name) {
+            let op = &mut self.operators[i];
+            match field {
+                "ratio" => op.ratio = value.clamp(0.0625, 32.0),
+                "level" => op.level = value.clamp(0.0, 1.0),
+                "attack" => op.envelope.set_attack(value.clamp(0.001, 10.0)),
+                "decay" => op.envelope.set_decay(value.clamp(0.001, 10.0)),
+                "sustain" => op.envelope.set_sustain(value.clamp(0.0, 1.0)),
+                "release" => op.envelope.set_release(value.clamp(0.001, 30.0)),
+                _ => {}
+            }
+            return;
+        }
+
+        match name {
+            "pitch" | "frequency" => {
+                self.pitch = value.clamp(20.0, 20000.0);
+            }
+            "algorithm" => {
+                self.algorithm = value.round().clamp(0.0, (ALGORITHMS.len() - 1) as f64) as usize;
+            }
+            "index" | "mod_index" => {
+                self.index = value.clamp(0.0, 20.0);
+            }
+            "feedback" => {
+                self.feedback = value.clamp(0.0, 1.0);
+            }
+            "amplitude" | "volume" => {
+                self.amplitude = value.clamp(0.0, 1.0);
+            }
+            _ => {}
+        }
+    }
+
+    fn get_parameter(&self, name: &str) -> Option<f64> {
+        if let Some((i, field)) = Self::operator_parameter(name) {
+            let op = &self.operators[i];
+            return match field {
+                "ratio" => Some(op.ratio),
+                "level" => Some(op.level),
+                "attack" => Some(op.envelope.attack_time()),
+                "decay" => Some(op.envelope.decay_time()),
+                "sustain" => Some(op.envelope.sustain_level()),
+                "release" => Some(op.envelope.release_time()),
+                _ => None,
+            };
+        }
+
+        match name {
+            "pitch" | "frequency" => Some(self.pitch),
+            "algorithm" => Some(self.algorithm as f64),
+            "index" | "mod_index" => Some(self.index),
+            "feedback" => Some(self.feedback),
+            "amplitude" | "volume" => Some(self.amplitude),
+            _ => None,
+        }
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn trigger(&mut self) {
+        self.active = true;
+        for op in &mut self.operators {
+            op.envelope.trigger();
+        }
+    }
+
+    fn release(&mut self) {
+        for op in &mut self.operators {
+            op.envelope.release();
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        let routing = &ALGORITHMS[self.algorithm];
+        self.active
+            && self
+                .operators
+                .iter()
+                .zip(routing.iter())
+                .any(|(op, (_, carrier))| *carrier && op.envelope.is_active())
+    }
+
+    fn process(&mut self) -> f64 {
+        if !self.active {
+            return 0.0;
+        }
+
+        let routing = &ALGORITHMS[self.algorithm];
+        let mut sum = 0.0;
+        let mut carriers = 0;
+
+        // Modulators always have higher numbers, so run from op4 down
+        for i in (0..OPERATORS).rev() {
+            let (modulators, carrier) = routing[i];
+
+            let mut modulation: f64 = modulators.iter().map(|&m| self.operators[m].output).sum();
+            modulation *= self.index;
+            if i == OPERATORS - 1 {
+                modulation += self.feedback * PI * self.operators[i].output;
+            }
+
+            let op = &mut self.operators[i];
+            let env = op.envelope.process();
+            op.output = (2.0 * PI * op.phase + modulation).sin() * op.level * env;
+
+            op.phase += self.pitch * op.ratio / self.sample_rate;
+            op.phase -= op.phase.floor();
+
+            if carrier {
+                sum += op.output;
+                carriers += 1;
+            }
+        }
+
+        if !self.is_active() {
+            self.active = false;
+        }
+
+        sum / carriers.max(1) as f64 * self.amplitude
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+        for op in &mut self.operators {
+            op.envelope = Envelope::new(sample_rate);
+            op.envelope.configure(0.01, 0.3, 0.7, 0.5);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fraction of spectral energy above the 2nd harmonic (crude brightness)
+    fn brightness(voice: &mut FmVoice) -> f64 {
+        // Skip the attack
+        for _ in 0..4410 {
+            voice.process();
+        }
+        let samples: Vec<f64> = (0..4410).map(|_| voice.process()).collect();
+
+        // First difference emphasises high frequencies
+        let total: f64 = samples.iter().map(|s| s * s).sum();
+        let diff: f64 = samples.windows(2).map(|w| (w[1] - w[0]).powi(2)).sum();
+        diff / total
+    }
+
+    #[test]
+    fn test_fm_voice_output() {
+        let mut voice = FmVoice::new(44100.0);
+        assert!(voice.is_active());
+        let max = (0..4410).map(|_| voice.process().abs()).fold(0.0, f64::max);
+        assert!(max > 0.1);
+        assert!(max <= 1.0);
+    }
+
+    #[test]
+    fn test_fm_index_controls_brightness() {
+        let mut dull = FmVoice::new(44100.0);
+        dull.set_parameter("index", 0.0);
+        let mut bright = FmVoice::new(44100.0);
+        bright.set_parameter("index", 8.0);
+
+        assert!(brightness(&mut bright) > brightness(&mut dull) * 2.0);
+    }
+
+    #[test]
+    fn test_fm_zero_index_is_pure_sine() {
+        // Algorithm 0 with no modulation is just op1: a sine at the pitch
+        let mut voice = FmVoice::new(44100.0);
+        voice.set_parameter("algorithm", 0.0);
+        voice.set_parameter("index", 0.0);
+        voice.set_parameter("op1_ratio", 1.0);
+        voice.set_parameter("pitch", 441.0);
+        voice.set_parameter("op1_attack", 0.001);
+
+        for _ in 0..44100 {
+            voice.process();
+        }
+        // 441 Hz at 44.1 kHz repeats every 100 samples
+        let a: Vec<f64> = (0..100).map(|_| voice.process()).collect();
+        let b: Vec<f64> = (0..100).map(|_| voice.process()).collect();
+        for (x, y) in a.iter().zip(b.iter()) {
+            assert!((x - y).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_fm_operator_parameters() {
+        let mut voice = FmVoice::new(44100.0);
+        voice.set_parameter("op3_ratio", 3.5);
+        voice.set_parameter("op4_level", 0.2);
+        assert_eq!(voice.get_parameter("op3_ratio"), Some(3.5));
+        assert_eq!(voice.get_parameter("op4_level"), Some(0.2));
+        assert_eq!(voice.get_parameter("op5_ratio"), None);
+
+        // Operator envelopes read back too, so they can be modulated
+        voice.set_parameter("op2_decay", 1.5);
+        voice.set_parameter("op1_sustain", 0.25);
+        assert_eq!(voice.get_parameter("op2_decay"), Some(1.5));
+        assert_eq!(voice.get_parameter("op1_sustain"), Some(0.25));
+        assert_eq!(voice.get_parameter("op4_release"), Some(0.5));
+
+        voice.set_parameter("algorithm", 42.0);
+        assert_eq!(voice.algorithm(), 6);
+    }
+
+    #[test]
+    fn test_fm_release() {
+        let mut voice = FmVoice::new(44100.0);
+        for n in 1..=OPERATORS {
+            voice.set_parameter(&format!("op{}_release", n), 0.1);
+        }
+        voice.release();
+        for _ in 0..10000 {
+            voice.process();
+        }
+        assert!(!voice.is_active());
+    }
+}
@@ -0,0 +1,161 @@
+//! Stereo sample frames
+//!
+//! The signal path runs in stereo from voices through layers and buses to
+//! the master. Mono voices produce frames with the same value in both
+//! channels; `mid` folds a frame back to mono, so mono output (MIDI control,
+//! visualization, one-channel devices) hears the same mix as before.
+
+use std::f64::consts::{FRAC_PI_4, SQRT_2};
+use std::ops::{Add, AddAssign, Mul};
+
+/// One stereo sample
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Frame {
+    pub left: f64,
+    pub right: f64,
+}
+
+impl Frame {
+    /// Silence
+    pub const ZERO: Frame = Frame { left: 0.0, right: 0.0 };
+
+    /// Create a frame from both channels
+    pub fn new(left: f64, right: f64) -> Self {
+        Self { left, right }
+    }
+
+    /// Create a frame with the same value in both channels
+    pub fn mono(sample: f64) -> Self {
+        Self::new(sample, sample)
+    }
+
+    /// Create a frame from mid (sum) and side (difference) signals
+    pub fn from_mid_side(mid: f64, side: f64) -> Self {
+        Self::new(mid + side, mid - side)
+    }
+
+    /// Average of the channels (the mono mix)
+    pub fn mid(&self) -> f64 {
+        (self.left + self.right) * 0.5
+    }
+
+    /// Half the difference between the channels (zero for mono)
+    pub fn side(&self) -> f64 {
+        (self.left - self.right) * 0.5
+    }
+
+    /// Largest absolute value of either channel
+    pub fn peak(&self) -> f64 {
+        self.left.abs().max(self.right.abs())
+    }
+
+    /// Scale the stereo spread: 0.0 folds to mono, 1.0 keeps the frame,
+    /// above 1.0 widens it
+    pub fn with_width(self, width: f64) -> Self {
+        Self::from_mid_side(self.mid(), self.side() * width)
+    }
+
+    /// Place the frame between the speakers (-1.0 left to 1.0 right)
+    ///
+    /// Equal-power panning, scaled so the centre leaves both channels
+    /// untouched.
+    pub fn pan(self, pan: f64) -> Self {
+        let angle = (pan.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+        Self::new(self.left * angle.cos() * SQRT_2, self.right * angle.sin() * SQRT_2)
+    }
+
+    /// Apply a function to both channels
+    pub fn map(self, mut f: impl FnMut(f64) -> f64) -> Self {
+        Self::new(f(self.left), f(self.right))
+    }
+
+    /// Get the sample for one of `channels` output channels
+    ///
+    /// One channel gets the mono mix; with more, the first two get left and
+    /// right and the rest stay silent.
+    pub fn channel(&self, index: usize, channels: usize) -> f64 {
+        match (channels, index) {
+            (1, _) => self.mid(),
+            (_, 0) => self.left,
+            (_, 1) => self.right,
+            _ => 0.0,
+        }
+    }
+}
+
+impl From<f64> for Frame {
+    fn from(sample: f64) -> Self {
+        Self::mono(sample)
+    }
+}
+
+impl Add for Frame {
+    type Output = Frame;
+
+    fn add(self, other: Frame) -> Frame {
+        Frame::new(self.left + other.left, self.right + other.right)
+    }
+}
+
+impl AddAssign for Frame {
+    fn add_assign(&mut self, other: Frame) {
+        self.left += other.left;
+        self.right += other.right;
+    }
+}
+
+impl Mul<f64> for Frame {
+    type Output = Frame;
+
+    fn mul(self, gain: f64) -> Frame {
+        Frame::new(self.left * gain, self.right * gain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mid_side() {
+        let frame = Frame::new(0.8, 0.2);
+        assert!((frame.mid() - 0.5).abs() < 1e-12);
+        assert!((frame.side() - 0.3).abs() < 1e-12);
+        let back = Frame::from_mid_side(frame.mid(), frame.side());
+        assert!((back.left - 0.8).abs() < 1e-12 && (back.right - 0.2).abs() < 1e-12);
+        assert_eq!(Frame::mono(0.4).side(), 0.0);
+    }
+
+    #[test]
+    fn test_width() {
+        let frame = Frame::new(1.0, 0.0);
+        assert_eq!(frame.with_width(0.0), Frame::mono(0.5));
+        assert_eq!(frame.with_width(1.0), frame);
+        assert_eq!(frame.with_width(2.0), Frame::new(1.5, -0.5));
+    }
+
+    #[test]
+    fn test_pan() {
+        let centre = Frame::mono(0.5).pan(0.0);
+        assert!((centre.left - 0.5).abs() < 1e-12 && (centre.right - 0.5).abs() < 1e-12);
+
+        let left = Frame::mono(0.5).pan(-1.0);
+        assert!(left.right.abs() < 1e-12);
+        assert!((left.left - 0.5 * SQRT_2).abs() < 1e-12);
+
+        // Equal power across the field
+        let power = |f: Frame| f.left * f.left + f.right * f.right;
+        assert!((power(Frame::mono(1.0).pan(0.3)) - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_channels() {
+        let frame = Frame::new(0.6, 0.2);
+        assert!((frame.channel(0, 1) - 0.4).abs() < 1e-12);
+        assert_eq!(frame.channel(0, 2), 0.6);
+        assert_eq!(frame.channel(1, 2), 0.2);
+        assert_eq!(frame.channel(3, 6), 0.0);
+        assert_eq!(frame + Frame::mono(1.0), Frame::new(1.6, 1.2));
+        assert_eq!(frame * 2.0, Frame::new(1.2, 0.4));
+    }
+}
@@ -1,6 +1,9 @@
 //! Low Frequency Oscillator for modulation
 //!
-//! Provides slow modulation for pitch, filter, amplitude, etc.
+//! Provides slow modulation for pitch, filter, amplitude, etc. Besides the
+//! periodic shapes there are two non-periodic ones for organic motion:
+//! smooth random (sample-and-hold that glides between values) and drift
+//! (Perlin-style gradient noise).
 
 use std::f64::consts::PI;
 
@@ -12,6 +15,48 @@ pub enum LfoShape {
     Saw,
     Square,
     SampleAndHold,
+    /// A new random value each cycle, glided to on a cosine curve
+    SmoothRandom,
+    /// Perlin-style gradient noise: wanders without repeating
+    Drift,
+}
+
+impl LfoShape {
+    /// All shapes, in parameter order
+    pub const ALL: [LfoShape; 7] = [
+        Self::Sine,
+        Self::Triangle,
+        Self::Saw,
+        Self::Square,
+        Self::SampleAndHold,
+        Self::SmoothRandom,
+        Self::Drift,
+    ];
+
+    /// Get a shape from a parameter value (rounded and clamped)
+    pub fn from_index(value: f64) -> Self {
+        Self::ALL[value.round().clamp(0.0, (Self::ALL.len() - 1) as f64) as usize]
+    }
+
+    /// Get the parameter value of a shape
+    pub fn index(&self) -> usize {
+        Self::ALL.iter().position(|s| s == self).unwrap_or(0)
+    }
+
+    /// Get a shape by name (sine, triangle, saw, square, sample_and_hold,
+    /// smooth_random, drift)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "sine" => Some(LfoShape::Sine),
+            "triangle" => Some(LfoShape::Triangle),
+            "saw" => Some(LfoShape::Saw),
+            "square" => Some(LfoShape::Square),
+            "sample_and_hold" | "s&h" => Some(LfoShape::SampleAndHold),
+            "smooth_random" => Some(LfoShape::SmoothRandom),
+            "drift" | "perlin" => Some(LfoShape::Drift),
+            _ => None,
+        }
+    }
 }
 
 /// Low Frequency Oscillator
@@ -22,9 +67,28 @@ pub struct Lfo {
     sample_rate: f64,
     /// Depth of modulation (0.0 to 1.0)
     depth: f64,
-    /// Last sample-and-hold value
+    /// Beats per cycle when locked to the tempo
+    sync: Option<f64>,
+    /// Phase offset (0.0 to 1.0)
+    phase_offset: f64,
+    /// Fade-in time in seconds (0 = none)
+    fade_in: f64,
+    /// Fade-in progress (0.0 to 1.0)
+    fade: f64,
+    /// Restart the cycle on each trigger
+    retrigger: bool,
+    /// Output 0.0 to 1.0 instead of -1.0 to 1.0
+    unipolar: bool,
+    /// Highest rate reachable through rate modulation (None = fixed rate)
+    rate_max: Option<f64>,
+    /// Position between the set frequency and `rate_max` (0.0 to 1.0)
+    rate_position: f64,
+    /// Random value (or gradient) at the start of the current cycle
+    previous_value: f64,
+    /// Random value (or gradient) at the end of the current cycle; the
+    /// held value for sample-and-hold
     sh_value: f64,
-    /// RNG state for S&H
+    /// RNG state for the random shapes
     rng_state: u64,
 }
 
@@ -37,6 +101,15 @@ impl Lfo {
             phase: 0.0,
             sample_rate,
             depth: 1.0,
+            sync: None,
+            phase_offset: 0.0,
+            fade_in: 0.0,
+            fade: 1.0,
+            retrigger: false,
+            unipolar: false,
+            rate_max: None,
+            rate_position: 0.0,
+            previous_value: 0.0,
             sh_value: 0.0,
             rng_state: 12345,
         }
@@ -52,6 +125,27 @@ impl Lfo {
         self.frequency
     }
     
+    /// Let the rate be swept from the set frequency up to `max` Hz
+    ///
+    /// `None` fixes the rate at the set frequency.
+    pub fn set_rate_range(&mut self, max: Option<f64>) {
+        self.rate_max = max.map(|m| m.clamp(0.01, 100.0));
+    }
+    
+    /// Move the rate within its range (0.0 = set frequency, 1.0 = maximum),
+    /// on an exponential scale so slow rates get as much travel as fast ones
+    pub fn set_rate_position(&mut self, position: f64) {
+        self.rate_position = position.clamp(0.0, 1.0);
+    }
+    
+    /// Get the rate in Hz after rate modulation
+    pub fn rate(&self) -> f64 {
+        match self.rate_max {
+            Some(max) => self.frequency * (max / self.frequency).powf(self.rate_position),
+            None => self.frequency,
+        }
+    }
+    
     /// Set modulation depth (0.0 to 1.0)
     pub fn set_depth(&mut self, depth: f64) {
         self.depth = depth.clamp(0.0, 1.0);
@@ -67,47 +161,147 @@ impl Lfo {
         self.shape = shape;
     }
     
+    /// Get LFO shape
+    pub fn shape(&self) -> LfoShape {
+        self.shape
+    }
+    
+    /// Shift the waveform by part of a cycle (0.0 to 1.0)
+    pub fn set_phase_offset(&mut self, offset: f64) {
+        self.phase_offset = offset.rem_euclid(1.0);
+    }
+    
+    /// Get the phase offset
+    pub fn phase_offset(&self) -> f64 {
+        self.phase_offset
+    }
+    
+    /// Fade the output in over `seconds` (restarts the fade; 0 disables)
+    pub fn set_fade_in(&mut self, seconds: f64) {
+        self.fade_in = seconds.max(0.0);
+        self.fade = if self.fade_in > 0.0 { 0.0 } else { 1.0 };
+    }
+    
+    /// Restart the cycle from the phase offset on each trigger
+    pub fn set_retrigger(&mut self, retrigger: bool) {
+        self.retrigger = retrigger;
+    }
+    
+    /// Output 0.0 to 1.0 (unipolar) instead of -1.0 to 1.0
+    pub fn set_unipolar(&mut self, unipolar: bool) {
+        self.unipolar = unipolar;
+    }
+    
+    /// Check if the output is unipolar
+    pub fn is_unipolar(&self) -> bool {
+        self.unipolar
+    }
+    
+    /// Start a note or event: restarts the fade-in, and the cycle when
+    /// retriggering
+    pub fn trigger(&mut self) {
+        if self.retrigger {
+            self.phase = 0.0;
+        }
+        if self.fade_in > 0.0 {
+            self.fade = 0.0;
+        }
+    }
+    
+    /// Lock the rate to the tempo: one cycle every `beats` beats
+    ///
+    /// `None` returns to the free-running frequency. The rate takes effect
+    /// on the next `sync`.
+    pub fn set_sync(&mut self, beats: Option<f64>) {
+        self.sync = beats.filter(|b| *b > 0.0);
+    }
+    
+    /// Get the beats per cycle when tempo-synced
+    pub fn sync_beats(&self) -> Option<f64> {
+        self.sync
+    }
+    
+    /// Follow the transport: set the rate from the tempo and line the phase
+    /// up with the beat position (no effect unless tempo-synced)
+    pub fn sync(&mut self, bpm: f64, beat: f64) {
+        if let Some(beats) = self.sync {
+            self.frequency = bpm / 60.0 / beats;
+            self.phase = (beat / beats).rem_euclid(1.0);
+        }
+    }
+    
     /// Reset phase
     pub fn reset(&mut self) {
         self.phase = 0.0;
     }
     
-    /// Generate next sample (-1.0 to 1.0, scaled by depth)
+    /// Generate next sample (-1.0 to 1.0, or 0.0 to 1.0 when unipolar,
+    /// scaled by depth and the fade-in)
     pub fn process(&mut self) -> f64 {
+        let value = self.next() * self.depth * self.fade;
+        if self.unipolar {
+            (value + 1.0) * 0.5
+        } else {
+            value
+        }
+    }
+    
+    /// Generate unipolar output (0.0 to 1.0, scaled by depth)
+    pub fn process_unipolar(&mut self) -> f64 {
+        (self.next() * self.depth * self.fade + 1.0) * 0.5
+    }
+    
+    /// Advance one sample and return the bipolar waveform
+    fn next(&mut self) -> f64 {
+        let increment = self.rate() / self.sample_rate;
+        
+        // Draw the random shapes' next value at the start of each cycle
+        if self.phase < increment {
+            self.previous_value = self.sh_value;
+            self.sh_value = self.random();
+        }
+        
+        let phase = (self.phase + self.phase_offset).rem_euclid(1.0);
         let raw = match self.shape {
-            LfoShape::Sine => (self.phase * 2.0 * PI).sin(),
+            LfoShape::Sine => (phase * 2.0 * PI).sin(),
             LfoShape::Triangle => {
-                if self.phase < 0.25 {
-                    4.0 * self.phase
-                } else if self.phase < 0.75 {
-                    2.0 - 4.0 * self.phase
+                if phase < 0.25 {
+                    4.0 * phase
+                } else if phase < 0.75 {
+                    2.0 - 4.0 * phase
                 } else {
-                    4.0 * self.phase - 4.0
+                    4.0 * phase - 4.0
                 }
             }
-            LfoShape::Saw => 2.0 * self.phase - 1.0,
-            LfoShape::Square => if self.phase < 0.5 { 1.0 } else { -1.0 },
-            LfoShape::SampleAndHold => {
-                // Update value at phase wrap
-                if self.phase < self.frequency / self.sample_rate {
-                    self.sh_value = self.random();
-                }
-                self.sh_value
+            LfoShape::Saw => 2.0 * phase - 1.0,
+            LfoShape::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            LfoShape::SampleAndHold => self.sh_value,
+            LfoShape::SmoothRandom => {
+                let glide = (1.0 - (self.phase * PI).cos()) / 2.0;
+                self.previous_value + (self.sh_value - self.previous_value) * glide
+            }
+            LfoShape::Drift => {
+                // 1D gradient noise: the random values are slopes at each
+                // cycle boundary, blended with a quintic fade (peaks at 0.5,
+                // so doubled to fill the range)
+                let t = self.phase;
+                let fade = t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+                let from = self.previous_value * t;
+                let to = self.sh_value * (t - 1.0);
+                (2.0 * (from + (to - from) * fade)).clamp(-1.0, 1.0)
             }
         };
         
         // Advance phase
-        self.phase += self.frequency / self.sample_rate;
+        self.phase += increment;
         if self.phase >= 1.0 {
             self.phase -= 1.0;
         }
+        if self.fade < 1.0 {
+            self.fade = (self.fade + 1.0 / (self.fade_in * self.sample_rate)).min(1.0);
+        }
         
-        raw * self.depth
-    }
-    
-    /// Generate unipolar output (0.0 to 1.0, scaled by depth)
-    pub fn process_unipolar(&mut self) -> f64 {
-        (self.process() + 1.0) * 0.5
+        raw
     }
     
     /// Simple RNG for sample-and-hold
@@ -175,4 +369,117 @@ mod tests {
         lfo.set_frequency(200.0);
         assert_eq!(lfo.frequency(), 100.0);
     }
+
+    #[test]
+    fn test_lfo_tempo_sync() {
+        let mut lfo = Lfo::new(1000.0);
+        lfo.set_frequency(5.0);
+        lfo.sync(120.0, 0.0);
+        assert_eq!(lfo.frequency(), 5.0);
+
+        // One cycle per bar of 4/4 at 120 BPM is 0.5 Hz
+        lfo.set_sync(Some(4.0));
+        lfo.sync(120.0, 0.0);
+        assert_eq!(lfo.frequency(), 0.5);
+        lfo.sync(60.0, 1.0);
+        assert_eq!(lfo.frequency(), 0.25);
+
+        // A beat into the bar is a quarter of the way through the cycle
+        lfo.set_shape(LfoShape::Saw);
+        assert!((lfo.process() + 0.5).abs() < 1e-9);
+
+        lfo.set_sync(None);
+        assert_eq!(lfo.sync_beats(), None);
+    }
+
+    #[test]
+    fn test_lfo_random_shapes() {
+        for shape in [LfoShape::SmoothRandom, LfoShape::Drift] {
+            let mut lfo = Lfo::new(1000.0);
+            lfo.set_shape(shape);
+            lfo.set_frequency(2.0);
+
+            let mut last = lfo.process();
+            let mut peak: f64 = 0.0;
+            for _ in 0..10000 {
+                let sample = lfo.process();
+                assert!((-1.0..=1.0).contains(&sample));
+                // No steps: each sample stays close to the one before
+                assert!((sample - last).abs() < 0.02, "{:?} jumped", shape);
+                peak = peak.max(sample.abs());
+                last = sample;
+            }
+            assert!(peak > 0.1, "{:?} barely moved", shape);
+        }
+        assert_eq!(LfoShape::from_name("perlin"), Some(LfoShape::Drift));
+        assert_eq!(LfoShape::from_name("smooth_random"), Some(LfoShape::SmoothRandom));
+    }
+
+    #[test]
+    fn test_lfo_phase_offset_and_retrigger() {
+        let mut lfo = Lfo::new(1000.0);
+        lfo.set_shape(LfoShape::Sine);
+        lfo.set_phase_offset(0.25);
+        assert!((lfo.process() - 1.0).abs() < 1e-9);
+
+        for _ in 0..300 {
+            lfo.process();
+        }
+        lfo.trigger();
+        assert!(lfo.process() < 0.99);
+
+        lfo.set_retrigger(true);
+        lfo.trigger();
+        assert!((lfo.process() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_lfo_fade_in() {
+        let mut lfo = Lfo::new(1000.0);
+        lfo.set_shape(LfoShape::Square);
+        lfo.set_fade_in(1.0);
+        assert!(lfo.process().abs() < 0.01);
+        for _ in 0..499 {
+            lfo.process();
+        }
+        assert!((lfo.process().abs() - 0.5).abs() < 0.01);
+        for _ in 0..1000 {
+            lfo.process();
+        }
+        assert_eq!(lfo.process().abs(), 1.0);
+
+        // Each trigger fades in again
+        lfo.trigger();
+        assert!(lfo.process().abs() < 0.01);
+    }
+
+    #[test]
+    fn test_lfo_polarity() {
+        let mut lfo = Lfo::new(1000.0);
+        lfo.set_shape(LfoShape::Square);
+        lfo.set_frequency(1.0);
+        lfo.set_unipolar(true);
+        assert!(lfo.is_unipolar());
+        assert_eq!(lfo.process(), 1.0);
+        for _ in 0..600 {
+            lfo.process();
+        }
+        assert_eq!(lfo.process(), 0.0);
+    }
+
+    #[test]
+    fn test_lfo_rate_range() {
+        let mut lfo = Lfo::new(1000.0);
+        lfo.set_frequency(0.1);
+        lfo.set_rate_position(1.0);
+        assert_eq!(lfo.rate(), 0.1);
+
+        lfo.set_rate_range(Some(10.0));
+        assert!((lfo.rate() - 10.0).abs() < 1e-9);
+        lfo.set_rate_position(0.5);
+        assert!((lfo.rate() - 1.0).abs() < 1e-9);
+        lfo.set_rate_position(0.0);
+        assert_eq!(lfo.rate(), 0.1);
+        assert_eq!(lfo.frequency(), 0.1);
+    }
 }
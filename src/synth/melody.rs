@@ -0,0 +1,578 @@
+//! Melody voice implementation
+//!
+//! A monophonic voice driven by its own step sequencer. The clock runs from
+//! the master tempo; on each step the voice either rests or plays the next
+//! note of a random walk over the degrees of the master scale. Data shapes
+//! the walk (density, register, contour, range) rather than the pitch itself,
+//! so changing values turn into phrases instead of glides.
+
+use super::{Envelope, Filter, FilterType, Oscillator, ParamSpec, Smoother, SmoothingCurve, Voice, Waveform};
+use crate::mapping::Scale;
+
+/// Melody parameters (`note` reads back the current note)
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec::continuous("bpm", 20.0, 300.0, 60.0).with_aliases(&["tempo"]),
+    ParamSpec::logarithmic("division", 0.25, 8.0, 2.0),
+    ParamSpec::continuous("density", 0.0, 1.0, 0.6),
+    ParamSpec::continuous("register", -3.0, 3.0, 0.0).with_aliases(&["octave"]),
+    ParamSpec::stepped("pitch", -24.0, 24.0, 0.0).with_aliases(&["transpose"]),
+    ParamSpec::continuous("contour", -1.0, 1.0, 0.0),
+    ParamSpec::stepped("range", 1.0, 24.0, 7.0),
+    ParamSpec::continuous("gate", 0.05, 1.0, 0.6),
+    ParamSpec::stepped("phrase", 0.0, 64.0, 16.0),
+    ParamSpec::continuous("brightness", 0.0, 1.0, 0.4),
+    ParamSpec::continuous("amplitude", 0.0, 1.0, 0.6).with_aliases(&["volume"]),
+    ParamSpec::continuous("attack", 0.001, 2.0, 0.005),
+    ParamSpec::continuous("release", 0.001, 5.0, 0.25),
+    ParamSpec::stepped("portamento", 0.0, 10.0, 0.0).with_aliases(&["glide"]),
+];
+
+/// A sequenced melodic voice
+pub struct MelodyVoice {
+    /// Main tone
+    oscillator: Oscillator,
+    /// Per-note amplitude envelope
+    envelope: Envelope,
+    /// Low-pass filter tracking the note pitch
+    filter: Filter,
+
+    /// Scale the walk moves over
+    scale: Scale,
+    /// Frequency of scale degree 0 at register 0
+    root_hz: f64,
+
+    sample_rate: f64,
+
+    // Parameters
+    bpm: f64,
+    /// Steps per beat
+    division: f64,
+    /// Probability of playing a note on each step (0.0 to 1.0)
+    density: f64,
+    /// Octave offset from the root
+    register: f64,
+    /// Transposition in semitones
+    pitch: f64,
+    /// Tendency of the walk to move up (1.0) or down (-1.0)
+    contour: f64,
+    /// Largest distance from the root, in scale degrees
+    range: i32,
+    /// Note length as a fraction of a step
+    gate: f64,
+    /// Steps per phrase; each phrase starts on the root (0 disables)
+    phrase: u32,
+    /// Filter brightness (0.0 to 1.0)
+    brightness: f64,
+    amplitude: f64,
+    /// Glide time between notes in seconds
+    portamento: f64,
+
+    // Sequencer state
+    /// Whether the sequencer is running
+    running: bool,
+    /// Samples left until the next step
+    samples_to_step: f64,
+    /// Samples left until the current note is released
+    gate_remaining: f64,
+    /// Steps since beat 0 of the transport
+    step_index: u64,
+    /// Transport beat position at the next sample
+    beat: f64,
+    /// Current scale degree of the walk
+    degree: i32,
+    /// Frequency of the current (or last) note
+    note_hz: f64,
+    /// Sounding frequency, gliding to `note_hz` with portamento
+    glide: Smoother,
+    /// RNG state (xorshift)
+    rng_state: u64,
+}
+
+impl MelodyVoice {
+    /// Parameters every melody voice accepts (also returned by `params`)
+    pub const PARAMS: &'static [ParamSpec] = PARAMS;
+
+    /// Create a new melody voice
+    ///
+    /// # Arguments
+    /// * `sample_rate` - Sample rate in Hz
+    /// * `bpm` - Tempo of the step clock
+    /// * `root_hz` - Frequency of the scale root at register 0
+    /// * `scale` - Scale to pick notes from
+    pub fn new(sample_rate: f64, bpm: f64, root_hz: f64, scale: Scale) -> Self {
+        let mut envelope = Envelope::new(sample_rate);
+        envelope.configure(0.005, 0.15, 0.6, 0.25);
+
+        let mut filter = Filter::with_type(sample_rate, FilterType::LowPass);
+        filter.set_resonance(1.0);
+
+        let mut voice = Self {
+            oscillator: Oscillator::new(Waveform::Triangle, root_hz, sample_rate),
+            envelope,
+            filter,
+            scale,
+            root_hz,
+            sample_rate,
+            bpm: bpm.clamp(20.0, 300.0),
+            division: 2.0,
+            density: 0.6,
+            register: 0.0,
+            pitch: 0.0,
+            contour: 0.0,
+            range: 7,
+            gate: 0.6,
+            phrase: 16,
+            brightness: 0.4,
+            amplitude: 0.6,
+            portamento: 0.0,
+            running: true,
+            samples_to_step: 0.0,
+            gate_remaining: 0.0,
+            step_index: 0,
+            beat: 0.0,
+            degree: 0,
+            note_hz: root_hz,
+            glide: Smoother::new(root_hz, SmoothingCurve::Exponential, true),
+            rng_state: 0x2545_F491_4F6C_DD1D,
+        };
+        voice.update_filter();
+        voice
+    }
+
+    /// Length of one step in samples
+    fn step_samples(&self) -> f64 {
+        self.sample_rate * 60.0 / (self.bpm * self.division)
+    }
+
+    /// Xorshift RNG returning 0.0..1.0
+    fn random(&mut self) -> f64 {
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng_state = x;
+        x as f64 / u64::MAX as f64
+    }
+
+    /// Frequency of a scale degree in the current register
+    fn degree_to_hz(&self, degree: i32) -> f64 {
+        let semitones = self.scale.degree_to_semitones(degree) as f64 + self.register.round() * 12.0 + self.pitch;
+        self.root_hz * 2.0_f64.powf(semitones / 12.0)
+    }
+
+    /// Cutoff follows the note so brightness is consistent across registers
+    fn update_filter(&mut self) {
+        let cutoff = self.note_hz * (1.5 + self.brightness * 14.0);
+        self.filter.set_cutoff(cutoff);
+    }
+
+    /// Lock the step clock to the transport: step n falls on beat
+    /// n / division, so the next one is the first at or after `beat`
+    fn align(&mut self) {
+        let steps = self.beat.max(0.0) * self.division;
+        let next = (steps - 1e-9).ceil().max(0.0);
+        self.step_index = next as u64;
+        self.samples_to_step = (next - steps).max(0.0) * self.step_samples();
+    }
+
+    /// Advance the sequencer by one step
+    fn step(&mut self) {
+        let position = if self.phrase > 0 {
+            self.step_index % self.phrase as u64
+        } else {
+            1
+        };
+        self.step_index += 1;
+
+        if self.density <= 0.0 {
+            return;
+        }
+
+        if position == 0 {
+            // Phrases open on the root
+            self.degree = 0;
+        } else {
+            if self.random() >= self.density {
+                return; // Rest
+            }
+
+            // Mostly stepwise motion, with occasional leaps and repeats
+            let r = self.random();
+            let size = if r < 0.1 {
+                0
+            } else if r < 0.7 {
+                1
+            } else if r < 0.9 {
+                2
+            } else {
+                3
+            };
+            let up = self.random() < (1.0 + self.contour) / 2.0;
+            let mut next = if up { self.degree + size } else { self.degree - size };
+
+            // Bounce off the edges of the range
+            if next.abs() > self.range {
+                next = if up { self.degree - size } else { self.degree + size };
+            }
+            self.degree = next.clamp(-self.range, self.range);
+        }
+
+        self.note_hz = self.degree_to_hz(self.degree);
+        self.glide.set_target(self.note_hz, self.portamento, 1.0 / self.sample_rate);
+        self.oscillator.set_frequency(self.glide.value());
+        self.update_filter();
+        self.envelope.trigger();
+        self.gate_remaining = (self.step_samples() * self.gate).max(1.0);
+    }
+}
+
+impl Voice for MelodyVoice {
+    fn set_parameter(&mut self, name: &str, value: f64) {
+        match name {
+            "bpm" | "tempo" => {
+                self.bpm = value.clamp(20.0, 300.0);
+            }
+            "division" => {
+                self.division = value.clamp(0.25, 8.0);
+            }
+            "density" => {
+                self.density = value.clamp(0.0, 1.0);
+            }
+            "register" | "octave" => {
+                self.register = value.clamp(-3.0, 3.0);
+            }
+            "pitch" | "transpose" => {
+                self.pitch = value.round().clamp(-24.0, 24.0);
+            }
+            "contour" => {
+                self.contour = value.clamp(-1.0, 1.0);
+            }
+            "range" => {
+                self.range = value.round().clamp(1.0, 24.0) as i32;
+            }
+            "gate" => {
+                self.gate = value.clamp(0.05, 1.0);
+            }
+            "phrase" => {
+                self.phrase = value.round().clamp(0.0, 64.0) as u32;
+            }
+            "brightness" => {
+                self.brightness = value.clamp(0.0, 1.0);
+                self.update_filter();
+            }
+            "amplitude" | "volume" => {
+                self.amplitude = value.clamp(0.0, 1.0);
+            }
+            "attack" => {
+                self.envelope.set_attack(value.clamp(0.001, 2.0));
+            }
+            "release" => {
+                self.envelope.set_release(value.clamp(0.001, 5.0));
+            }
+            "portamento" | "glide" => {
+                self.portamento = value.clamp(0.0, 10.0);
+            }
+            _ => {}
+        }
+    }
+
+    fn get_parameter(&self, name: &str) -> Option<f64> {
+        match name {
+            "bpm" | "tempo" => Some(self.bpm),
+            "division" => Some(self.division),
+            "density" => Some(self.density),
+            "register" | "octave" => Some(self.register),
+            "pitch" | "transpose" => Some(self.pitch),
+            "contour" => Some(self.contour),
+            "range" => Some(self.range as f64),
+            "gate" => Some(self.gate),
+            "phrase" => Some(self.phrase as f64),
+            "brightness" => Some(self.brightness),
+            "amplitude" | "volume" => Some(self.amplitude),
+            "portamento" | "glide" => Some(self.portamento),
+            "attack" => Some(self.envelope.attack_time()),
+            "release" => Some(self.envelope.release_time()),
+            "note" => Some(self.note_hz),
+            _ => None,
+        }
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn trigger(&mut self) {
+        // (Re)start the sequencer on the next step of the grid
+        self.running = true;
+        self.align();
+    }
+
+    fn release(&mut self) {
+        self.running = false;
+        self.envelope.release();
+    }
+
+    fn is_active(&self) -> bool {
+        self.running || self.envelope.is_active()
+    }
+
+    fn process(&mut self) -> f64 {
+        self.beat += self.bpm / (60.0 * self.sample_rate);
+        if !self.is_active() {
+            return 0.0;
+        }
+
+        if self.running {
+            if self.samples_to_step <= 0.0 {
+                self.samples_to_step += self.step_samples();
+                self.step();
+            }
+            self.samples_to_step -= 1.0;
+        }
+
+        if self.gate_remaining > 0.0 {
+            self.gate_remaining -= 1.0;
+            if self.gate_remaining <= 0.0 {
+                self.envelope.release();
+            }
+        }
+
+        if !self.glide.is_settled() {
+            self.oscillator.set_frequency(self.glide.advance());
+        }
+
+        let tone = self.filter.process(self.oscillator.generate());
+        tone * self.envelope.process() * self.amplitude
+    }
+
+    fn sync(&mut self, bpm: f64, beat: f64) {
+        self.bpm = bpm.clamp(20.0, 300.0);
+        self.beat = beat;
+        self.align();
+    }
+
+    fn envelope_mut(&mut self) -> Option<&mut Envelope> {
+        Some(&mut self.envelope)
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+
+        self.oscillator = Oscillator::new(Waveform::Triangle, self.note_hz, sample_rate);
+        self.envelope = Envelope::new(sample_rate);
+        self.envelope.configure(0.005, 0.15, 0.6, 0.25);
+        self.filter = Filter::with_type(sample_rate, FilterType::LowPass);
+        self.filter.set_resonance(1.0);
+        self.update_filter();
+        self.glide = Smoother::new(self.note_hz, SmoothingCurve::Exponential, true);
+        self.samples_to_step = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the voice and collect the frequency of every note it starts
+    fn collect_notes(voice: &mut MelodyVoice, samples: usize) -> Vec<f64> {
+        let mut notes = Vec::new();
+        let mut last_step = voice.step_index;
+        for _ in 0..samples {
+            voice.process();
+            if voice.step_index != last_step {
+                last_step = voice.step_index;
+                if voice.gate_remaining > 0.0 && voice.envelope.is_active() {
+                    notes.push(voice.note_hz);
+                }
+            }
+        }
+        notes
+    }
+
+    fn voice() -> MelodyVoice {
+        // 120 BPM, 2 steps per beat -> one step every 11025 samples
+        MelodyVoice::new(44100.0, 120.0, 220.0, Scale::minor_pentatonic())
+    }
+
+    #[test]
+    fn test_melody_steps_follow_tempo() {
+        let mut voice = voice();
+        voice.process();
+        assert_eq!(voice.step_index, 1);
+
+        for _ in 0..11025 {
+            voice.process();
+        }
+        assert_eq!(voice.step_index, 2);
+
+        voice.set_parameter("bpm", 60.0);
+        for _ in 0..22050 {
+            voice.process();
+        }
+        assert_eq!(voice.step_index, 3);
+    }
+
+    #[test]
+    fn test_melody_notes_stay_in_scale() {
+        let mut voice = voice();
+        voice.set_parameter("density", 1.0);
+        let notes = collect_notes(&mut voice, 44100 * 8);
+        assert!(notes.len() >= 15);
+
+        for hz in notes {
+            let semitones = 12.0 * (hz / 220.0).log2();
+            let rounded = semitones.round();
+            assert!((semitones - rounded).abs() < 1e-6);
+            let pitch_class = (rounded as i32).rem_euclid(12) as u8;
+            assert!(
+                Scale::minor_pentatonic().intervals().contains(&pitch_class),
+                "{} Hz is outside the scale",
+                hz
+            );
+        }
+    }
+
+    #[test]
+    fn test_melody_density_controls_rests() {
+        let mut sparse = voice();
+        sparse.set_parameter("density", 0.2);
+        let mut dense = voice();
+        dense.set_parameter("density", 1.0);
+
+        let sparse_notes = collect_notes(&mut sparse, 44100 * 16).len();
+        let dense_notes = collect_notes(&mut dense, 44100 * 16).len();
+        assert!(sparse_notes < dense_notes / 2);
+
+        let mut silent = voice();
+        silent.set_parameter("density", 0.0);
+        let max = (0..44100).map(|_| silent.process().abs()).fold(0.0, f64::max);
+        assert_eq!(max, 0.0);
+    }
+
+    #[test]
+    fn test_melody_contour_and_range() {
+        // Average walk position over many steps
+        let mean_degree = |contour: f64| {
+            let mut voice = voice();
+            voice.set_parameter("density", 1.0);
+            voice.set_parameter("contour", contour);
+            voice.set_parameter("range", 5.0);
+            voice.set_parameter("phrase", 0.0);
+            let mut sum = 0;
+            for _ in 0..64 {
+                voice.step();
+                assert!(voice.degree.abs() <= 5);
+                sum += voice.degree;
+            }
+            sum as f64 / 64.0
+        };
+
+        assert!(mean_degree(1.0) > 2.0);
+        assert!(mean_degree(-1.0) < -2.0);
+    }
+
+    #[test]
+    fn test_melody_register_shifts_octave() {
+        let mut voice = voice();
+        voice.set_parameter("register", 1.0);
+        voice.process();
+        // The first step of a phrase is the root
+        assert!((voice.get_parameter("note").unwrap() - 440.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_melody_transposes() {
+        let mut voice = voice();
+        voice.set_parameter("pitch", 7.0);
+        assert_eq!(voice.get_parameter("transpose"), Some(7.0));
+        voice.process();
+        assert!((voice.get_parameter("note").unwrap() - 220.0 * 2f64.powf(7.0 / 12.0)).abs() < 1e-9);
+
+        voice.set_parameter("pitch", 40.0);
+        assert_eq!(voice.get_parameter("pitch"), Some(24.0));
+    }
+
+    #[test]
+    fn test_melody_sync_locks_to_beat() {
+        // Two steps per beat at 120 BPM: 11025 samples each
+        let mut voice = voice();
+        voice.set_parameter("phrase", 4.0);
+
+        // Joining at beat 2.25 waits half a step for step 5, which is step 1
+        // of the second phrase
+        voice.sync(120.0, 2.25);
+        assert_eq!(voice.step_index, 5);
+        for _ in 0..5513 {
+            voice.process();
+        }
+        assert_eq!(voice.step_index, 5);
+        voice.process();
+        assert_eq!(voice.step_index, 6);
+
+        // On a step boundary the step plays straight away
+        voice.sync(120.0, 4.0);
+        voice.process();
+        assert_eq!(voice.step_index, 9);
+
+        // A tempo change keeps the place in the bar
+        for _ in 0..5512 {
+            voice.process();
+        }
+        voice.sync(60.0, 4.25);
+        assert_eq!(voice.step_index, 9);
+        assert!((voice.samples_to_step - 11025.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_melody_trigger_keeps_grid() {
+        // 11025 samples per step; retrigger a quarter of the way into step 2
+        let mut voice = voice();
+        voice.sync(120.0, 0.0);
+        for _ in 0..24806 {
+            voice.process();
+        }
+        assert_eq!(voice.step_index, 3);
+        voice.trigger();
+
+        // The sequencer does not restart: step 3 still falls on beat 1.5
+        assert_eq!(voice.step_index, 3);
+        for _ in 24806..33075 {
+            voice.process();
+        }
+        assert_eq!(voice.step_index, 3);
+        voice.process();
+        assert_eq!(voice.step_index, 4);
+    }
+
+    #[test]
+    fn test_melody_portamento() {
+        let mut voice = voice();
+        voice.set_parameter("portamento", 0.1);
+        voice.set_parameter("register", 1.0);
+        voice.process();
+
+        // The first note glides up an octave from the root instead of jumping
+        let start = voice.oscillator.frequency();
+        assert!(start < 300.0, "{}", start);
+        for _ in 0..4410 {
+            voice.process();
+        }
+        assert!((voice.oscillator.frequency() - 440.0).abs() < 5.0);
+    }
+
+    #[test]
+    fn test_melody_release_stops_sequencer() {
+        let mut voice = voice();
+        assert!(voice.is_active());
+        voice.process();
+        voice.release();
+
+        for _ in 0..44100 {
+            voice.process();
+        }
+        assert!(!voice.is_active());
+
+        voice.trigger();
+        assert!(voice.is_active());
+    }
+}
@@ -7,11 +7,31 @@ mod voice;
 mod drone;
 mod envelope;
 mod filter;
+mod frame;
+mod fm;
 mod lfo;
+mod melody;
+mod modmatrix;
+mod param;
+mod percussion;
+mod sample;
+mod sampler;
+mod texture;
+mod wavetable;
 
 pub use oscillator::{Oscillator, Waveform};
 pub use voice::Voice;
 pub use drone::DroneVoice;
-pub use envelope::{Envelope, EnvelopeStage};
-pub use filter::{Filter, FilterType};
+pub use envelope::{Breakpoint, Envelope, EnvelopeCurve, EnvelopeStage};
+pub use filter::{Filter, FilterModel, FilterType};
+pub use frame::Frame;
+pub use fm::FmVoice;
 pub use lfo::{Lfo, LfoShape};
+pub use melody::MelodyVoice;
+pub use modmatrix::{ModMatrix, ModRoute, ModSource, ModulatedVoice, Polarity, CONTROL_INTERVAL, DATA_PREFIX};
+pub use param::{find_param, ParamKind, ParamSpec, SmoothedVoice, Smoother, SmoothingCurve};
+pub use percussion::{PercussionModel, PercussionVoice};
+pub use sample::SampleBuffer;
+pub use sampler::SamplerVoice;
+pub use texture::TextureVoice;
+pub use wavetable::{Wavetable, WavetableOscillator, BUILTIN_TABLES, DEFAULT_FRAME_SIZE};
@@ -0,0 +1,706 @@
+//! Modulation matrix
+//!
+//! Named modulation sources (LFOs, envelopes, data values, random walks)
+//! routed to any voice parameter with a depth and polarity. A
+//! `ModulatedVoice` wraps a voice and, at control rate, writes each target
+//! parameter as its base value plus the sum of the routes feeding it.
+
+use super::{find_param, Envelope, Frame, Lfo, LfoShape, ParamSpec, Voice};
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+
+/// Samples between modulation updates
+pub const CONTROL_INTERVAL: usize = 32;
+
+/// Parameter prefix that feeds a data source (`mod.<source>`)
+pub const DATA_PREFIX: &str = "mod.";
+
+/// Range a route maps its source into
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Polarity {
+    /// -1.0 to 1.0
+    Bipolar,
+    /// 0.0 to 1.0
+    Unipolar,
+}
+
+/// A modulation source
+pub enum ModSource {
+    /// Low frequency oscillator (bipolar unless set unipolar)
+    Lfo(Lfo),
+    /// Envelope started by each note (unipolar)
+    Envelope(Envelope),
+    /// A value fed from outside, normalized from an input range (unipolar)
+    Data {
+        in_min: f64,
+        in_max: f64,
+        value: f64,
+    },
+    /// A new random target `rate` times per second, stepped or glided (bipolar)
+    Random {
+        rate: f64,
+        /// Beats per new target when tempo-synced
+        sync: Option<f64>,
+        smooth: bool,
+        /// Control ticks until the next target
+        countdown: f64,
+        from: f64,
+        to: f64,
+        /// Progress from `from` to `to` (0.0 to 1.0)
+        progress: f64,
+        rng_state: u64,
+    },
+}
+
+impl ModSource {
+    /// Create an LFO source running at the control rate
+    pub fn lfo(sample_rate: f64, rate: f64, shape: LfoShape) -> Self {
+        let mut lfo = Lfo::new(sample_rate / CONTROL_INTERVAL as f64);
+        lfo.set_frequency(rate);
+        lfo.set_shape(shape);
+        ModSource::Lfo(lfo)
+    }
+
+    /// Create an ADSR envelope source running at the control rate
+    pub fn envelope(sample_rate: f64, attack: f64, decay: f64, sustain: f64, release: f64) -> Self {
+        let mut envelope = Envelope::new(sample_rate / CONTROL_INTERVAL as f64);
+        envelope.configure(attack, decay, sustain, release);
+        ModSource::Envelope(envelope)
+    }
+
+    /// Create a data source that normalizes values from `in_min..in_max`
+    pub fn data(in_min: f64, in_max: f64) -> Self {
+        ModSource::Data {
+            in_min,
+            in_max,
+            value: 0.0,
+        }
+    }
+
+    /// Create a random source
+    pub fn random(rate: f64, smooth: bool, seed: u64) -> Self {
+        ModSource::Random {
+            rate: rate.clamp(0.01, 100.0),
+            sync: None,
+            smooth,
+            countdown: 0.0,
+            from: 0.0,
+            to: 0.0,
+            progress: 1.0,
+            // Xorshift state must be non-zero
+            rng_state: seed | 1,
+        }
+    }
+
+    /// Lock an LFO or random source to the tempo: one cycle (or new target)
+    /// every `beats` beats
+    pub fn with_sync(mut self, beats: f64) -> Self {
+        match &mut self {
+            ModSource::Lfo(lfo) => lfo.set_sync(Some(beats)),
+            ModSource::Random { sync, .. } => *sync = Some(beats).filter(|b| *b > 0.0),
+            _ => {}
+        }
+        self
+    }
+
+    /// Follow the transport tempo and beat position
+    fn sync(&mut self, bpm: f64, beat: f64) {
+        match self {
+            ModSource::Lfo(lfo) => lfo.sync(bpm, beat),
+            ModSource::Random { rate, sync: Some(beats), .. } => {
+                *rate = (bpm / 60.0 / *beats).clamp(0.01, 100.0);
+            }
+            _ => {}
+        }
+    }
+
+    /// Native polarity of the source's output
+    fn polarity(&self) -> Polarity {
+        match self {
+            ModSource::Lfo(lfo) if lfo.is_unipolar() => Polarity::Unipolar,
+            ModSource::Lfo(_) | ModSource::Random { .. } => Polarity::Bipolar,
+            ModSource::Envelope(_) | ModSource::Data { .. } => Polarity::Unipolar,
+        }
+    }
+
+    /// Advance one control tick and return the output
+    fn tick(&mut self, control_rate: f64) -> f64 {
+        match self {
+            ModSource::Lfo(lfo) => lfo.process(),
+            ModSource::Envelope(envelope) => envelope.process(),
+            ModSource::Data { value, .. } => *value,
+            ModSource::Random {
+                rate,
+                smooth,
+                countdown,
+                from,
+                to,
+                progress,
+                rng_state,
+                ..
+            } => {
+                let ticks = control_rate / *rate;
+                *countdown -= 1.0;
+                if *countdown <= 0.0 {
+                    let mut x = *rng_state;
+                    x ^= x << 13;
+                    x ^= x >> 7;
+                    x ^= x << 17;
+                    *rng_state = x;
+
+                    *from = if *smooth { *from + (*to - *from) * *progress } else { *to };
+                    *to = (x as f64 / u64::MAX as f64) * 2.0 - 1.0;
+                    *progress = 0.0;
+                    *countdown += ticks;
+                }
+
+                if *smooth {
+                    *progress = (*progress + 1.0 / ticks).min(1.0);
+                    *from + (*to - *from) * *progress
+                } else {
+                    *to
+                }
+            }
+        }
+    }
+
+    fn trigger(&mut self) {
+        match self {
+            ModSource::Envelope(envelope) => envelope.trigger(),
+            ModSource::Lfo(lfo) => lfo.trigger(),
+            _ => {}
+        }
+    }
+
+    fn release(&mut self) {
+        if let ModSource::Envelope(envelope) = self {
+            envelope.release();
+        }
+    }
+
+    /// Feed a raw value to a data source
+    fn set_value(&mut self, raw: f64) {
+        if let ModSource::Data { in_min, in_max, value } = self {
+            let span = *in_max - *in_min;
+            *value = if span.abs() < f64::EPSILON {
+                0.0
+            } else {
+                ((raw - *in_min) / span).clamp(0.0, 1.0)
+            };
+        }
+    }
+}
+
+/// A route from a source to a parameter
+#[derive(Debug, Clone)]
+pub struct ModRoute {
+    /// Source index in the matrix
+    source: usize,
+    /// Target parameter name
+    pub target: String,
+    /// Amount in parameter units at full source output
+    pub depth: f64,
+    /// Range the source is mapped into (None = the source's own)
+    pub polarity: Option<Polarity>,
+}
+
+/// Named sources and the routes from them to parameters
+#[derive(Default)]
+pub struct ModMatrix {
+    sources: Vec<(String, ModSource)>,
+    /// Last output of each source
+    outputs: Vec<f64>,
+    routes: Vec<ModRoute>,
+    /// LFOs whose rate follows another source (LFO index, source index)
+    rate_routes: Vec<(usize, usize)>,
+}
+
+impl ModMatrix {
+    /// Create an empty matrix
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a named source (replaces a source with the same name)
+    pub fn add_source(&mut self, name: impl Into<String>, source: ModSource) {
+        let name = name.into();
+        match self.sources.iter().position(|(n, _)| *n == name) {
+            Some(i) => self.sources[i].1 = source,
+            None => {
+                self.sources.push((name, source));
+                self.outputs.push(0.0);
+            }
+        }
+    }
+
+    /// Route a source to a parameter
+    ///
+    /// Returns false if the source doesn't exist.
+    pub fn add_route(
+        &mut self,
+        source: &str,
+        target: impl Into<String>,
+        depth: f64,
+        polarity: Option<Polarity>,
+    ) -> bool {
+        let Some(index) = self.sources.iter().position(|(n, _)| n == source) else {
+            return false;
+        };
+        self.routes.push(ModRoute {
+            source: index,
+            target: target.into(),
+            depth,
+            polarity,
+        });
+        true
+    }
+
+    /// Let a source sweep an LFO's rate across its rate range
+    ///
+    /// The source's output, taken as 0.0 to 1.0, sets the LFO's position
+    /// between its frequency and its maximum rate. Returns false unless
+    /// `lfo` names an LFO and `source` another existing source.
+    pub fn add_rate_route(&mut self, lfo: &str, source: &str) -> bool {
+        let find = |name: &str| self.sources.iter().position(|(n, _)| n == name);
+        let (Some(lfo_index), Some(source_index)) = (find(lfo), find(source)) else {
+            return false;
+        };
+        if lfo_index == source_index || !matches!(self.sources[lfo_index].1, ModSource::Lfo(_)) {
+            return false;
+        }
+        self.rate_routes.push((lfo_index, source_index));
+        true
+    }
+
+    /// Check if the matrix has no routes
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// Get the routes
+    pub fn routes(&self) -> &[ModRoute] {
+        &self.routes
+    }
+
+    /// Get the distinct parameters the routes modulate
+    pub fn targets(&self) -> Vec<String> {
+        let mut targets: Vec<String> = Vec::new();
+        for route in &self.routes {
+            if !targets.contains(&route.target) {
+                targets.push(route.target.clone());
+            }
+        }
+        targets
+    }
+
+    /// Feed a raw value to a data source by name
+    pub fn set_data(&mut self, source: &str, value: f64) {
+        if let Some((_, s)) = self.sources.iter_mut().find(|(n, _)| n == source) {
+            s.set_value(value);
+        }
+    }
+
+    /// Start envelope sources and restart retriggered LFOs
+    pub fn trigger(&mut self) {
+        for (_, source) in &mut self.sources {
+            source.trigger();
+        }
+    }
+
+    /// Release envelope sources
+    pub fn release(&mut self) {
+        for (_, source) in &mut self.sources {
+            source.release();
+        }
+    }
+
+    /// Follow the transport tempo and beat position (tempo-synced sources)
+    pub fn sync(&mut self, bpm: f64, beat: f64) {
+        for (_, source) in &mut self.sources {
+            source.sync(bpm, beat);
+        }
+    }
+
+    /// Advance every source one control tick
+    pub fn tick(&mut self, control_rate: f64) {
+        // Rates follow their sources' previous outputs
+        for &(lfo, source) in &self.rate_routes {
+            let output = self.outputs[source];
+            let position = match self.sources[source].1.polarity() {
+                Polarity::Bipolar => (output + 1.0) * 0.5,
+                Polarity::Unipolar => output,
+            };
+            if let ModSource::Lfo(lfo) = &mut self.sources[lfo].1 {
+                lfo.set_rate_position(position);
+            }
+        }
+
+        for ((_, source), output) in self.sources.iter_mut().zip(self.outputs.iter_mut()) {
+            *output = source.tick(control_rate);
+        }
+    }
+
+    /// Summed modulation for a parameter from the last tick
+    pub fn offset(&self, target: &str) -> f64 {
+        self.routes
+            .iter()
+            .filter(|r| r.target == target)
+            .map(|r| {
+                let native = self.sources[r.source].1.polarity();
+                let value = match (native, r.polarity.unwrap_or(native)) {
+                    (Polarity::Bipolar, Polarity::Unipolar) => (self.outputs[r.source] + 1.0) * 0.5,
+                    (Polarity::Unipolar, Polarity::Bipolar) => self.outputs[r.source] * 2.0 - 1.0,
+                    _ => self.outputs[r.source],
+                };
+                value * r.depth
+            })
+            .sum()
+    }
+}
+
+/// A voice with a modulation matrix applied to its parameters
+pub struct ModulatedVoice {
+    voice: Box<dyn Voice>,
+    matrix: ModMatrix,
+    /// Unmodulated value of each target parameter
+    base: HashMap<String, f64>,
+    sample_rate: f64,
+    /// Samples until the next control tick
+    countdown: usize,
+}
+
+impl ModulatedVoice {
+    /// Wrap a voice with a matrix
+    ///
+    /// Base values start from the voice's current parameters, so every
+    /// target must be a parameter the voice can report. Targets written as
+    /// aliases are keyed by the canonical name, which is the one smoothing
+    /// and patches forward.
+    pub fn new(voice: Box<dyn Voice>, mut matrix: ModMatrix, sample_rate: f64) -> Result<Self> {
+        for route in &mut matrix.routes {
+            if let Some(spec) = find_param(voice.params(), &route.target) {
+                route.target = spec.name.to_string();
+            }
+        }
+        let mut base = HashMap::new();
+        for target in matrix.targets() {
+            let Some(value) = voice.get_parameter(&target) else {
+                bail!("unknown modulation target '{}'", target);
+            };
+            base.insert(target, value);
+        }
+
+        // Voices that start sounding start their envelopes too
+        if voice.is_active() {
+            matrix.trigger();
+        }
+
+        Ok(Self {
+            voice,
+            matrix,
+            base,
+            sample_rate,
+            countdown: 0,
+        })
+    }
+
+    /// Get the matrix
+    pub fn matrix(&self) -> &ModMatrix {
+        &self.matrix
+    }
+
+    /// Resolve an alias to the voice's canonical parameter name
+    fn canonical<'a>(&self, name: &'a str) -> &'a str {
+        match find_param(self.voice.params(), name) {
+            Some(spec) => spec.name,
+            None => name,
+        }
+    }
+
+    /// Write every target as base plus modulation
+    fn apply(&mut self) {
+        for (target, base) in &self.base {
+            self.voice.set_parameter(target, base + self.matrix.offset(target));
+        }
+    }
+
+    /// Advance the matrix on control ticks
+    fn step(&mut self) {
+        if self.countdown == 0 {
+            self.matrix.tick(self.sample_rate / CONTROL_INTERVAL as f64);
+            self.apply();
+            self.countdown = CONTROL_INTERVAL;
+        }
+        self.countdown -= 1;
+    }
+}
+
+impl Voice for ModulatedVoice {
+    fn set_parameter(&mut self, name: &str, value: f64) {
+        let name = self.canonical(name);
+        if let Some(source) = name.strip_prefix(DATA_PREFIX) {
+            self.matrix.set_data(source, value);
+        } else if let Some(base) = self.base.get_mut(name) {
+            *base = value;
+            self.voice.set_parameter(name, value + self.matrix.offset(name));
+        } else {
+            self.voice.set_parameter(name, value);
+        }
+    }
+
+    fn get_parameter(&self, name: &str) -> Option<f64> {
+        match self.base.get(self.canonical(name)) {
+            Some(&value) => Some(value),
+            None => self.voice.get_parameter(name),
+        }
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        self.voice.params()
+    }
+
+    fn trigger(&mut self) {
+        self.matrix.trigger();
+        self.voice.trigger();
+    }
+
+    fn release(&mut self) {
+        self.matrix.release();
+        self.voice.release();
+    }
+
+    fn is_active(&self) -> bool {
+        self.voice.is_active()
+    }
+
+    fn process(&mut self) -> f64 {
+        self.step();
+        self.voice.process()
+    }
+
+    fn process_frame(&mut self) -> Frame {
+        self.step();
+        self.voice.process_frame()
+    }
+
+    fn envelope_mut(&mut self) -> Option<&mut Envelope> {
+        self.voice.envelope_mut()
+    }
+
+    fn sync(&mut self, bpm: f64, beat: f64) {
+        self.matrix.sync(bpm, beat);
+        self.voice.sync(bpm, beat);
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+        self.voice.set_sample_rate(sample_rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synth::{DroneVoice, FmVoice};
+
+    const CONTROL_RATE: f64 = 44100.0 / CONTROL_INTERVAL as f64;
+
+    #[test]
+    fn test_route_polarity() {
+        let mut matrix = ModMatrix::new();
+        matrix.add_source("level", ModSource::data(0.0, 10.0));
+        assert!(matrix.add_route("level", "filter", 100.0, None));
+        assert!(matrix.add_route("level", "pitch", 10.0, Some(Polarity::Bipolar)));
+        assert!(!matrix.add_route("missing", "pitch", 1.0, None));
+
+        matrix.set_data("level", 2.5);
+        matrix.tick(CONTROL_RATE);
+        assert_eq!(matrix.offset("filter"), 25.0);
+        // 0.25 unipolar is -0.5 bipolar
+        assert_eq!(matrix.offset("pitch"), -5.0);
+        assert_eq!(matrix.offset("amplitude"), 0.0);
+        assert_eq!(matrix.targets(), vec!["filter", "pitch"]);
+    }
+
+    #[test]
+    fn test_lfo_rate_follows_data() {
+        let mut lfo = Lfo::new(CONTROL_RATE);
+        lfo.set_frequency(0.1);
+        lfo.set_rate_range(Some(10.0));
+        lfo.set_unipolar(true);
+
+        let mut matrix = ModMatrix::new();
+        matrix.add_source("wind", ModSource::data(0.0, 20.0));
+        matrix.add_source("sway", ModSource::Lfo(lfo));
+        assert!(matrix.add_rate_route("sway", "wind"));
+        assert!(!matrix.add_rate_route("wind", "sway"));
+        assert!(!matrix.add_rate_route("sway", "sway"));
+        matrix.add_route("sway", "filter", 100.0, None);
+
+        matrix.set_data("wind", 10.0);
+        matrix.tick(CONTROL_RATE);
+        matrix.tick(CONTROL_RATE);
+        match &matrix.sources[1].1 {
+            ModSource::Lfo(lfo) => assert!((lfo.rate() - 1.0).abs() < 1e-9),
+            _ => unreachable!(),
+        }
+        // Unipolar LFOs only push the target up
+        assert!(matrix.offset("filter") >= 0.0);
+    }
+
+    #[test]
+    fn test_routes_sum() {
+        let mut matrix = ModMatrix::new();
+        matrix.add_source("a", ModSource::data(0.0, 1.0));
+        matrix.add_source("b", ModSource::data(0.0, 1.0));
+        matrix.add_route("a", "filter", 100.0, None);
+        matrix.add_route("b", "filter", -40.0, None);
+        matrix.set_data("a", 1.0);
+        matrix.set_data("b", 1.0);
+        matrix.tick(CONTROL_RATE);
+        assert_eq!(matrix.offset("filter"), 60.0);
+    }
+
+    #[test]
+    fn test_lfo_source_range() {
+        let mut matrix = ModMatrix::new();
+        matrix.add_source("wobble", ModSource::lfo(44100.0, 5.0, LfoShape::Sine));
+        matrix.add_route("wobble", "bipolar", 1.0, None);
+        matrix.add_route("wobble", "unipolar", 1.0, Some(Polarity::Unipolar));
+
+        let (mut min, mut max) = (f64::MAX, f64::MIN);
+        for _ in 0..CONTROL_RATE as usize {
+            matrix.tick(CONTROL_RATE);
+            min = min.min(matrix.offset("bipolar"));
+            max = max.max(matrix.offset("bipolar"));
+            assert!((0.0..=1.0).contains(&matrix.offset("unipolar")));
+        }
+        assert!(min < -0.99 && max > 0.99);
+    }
+
+    #[test]
+    fn test_random_source() {
+        let mut stepped = ModSource::random(10.0, false, 7);
+        let mut smooth = ModSource::random(10.0, true, 7);
+
+        let steps: Vec<f64> = (0..CONTROL_RATE as usize).map(|_| stepped.tick(CONTROL_RATE)).collect();
+        let glides: Vec<f64> = (0..CONTROL_RATE as usize).map(|_| smooth.tick(CONTROL_RATE)).collect();
+        assert!(steps.iter().all(|v| (-1.0..=1.0).contains(v)));
+
+        // Stepped jumps between held values, smooth moves in small increments
+        let max_jump = |v: &[f64]| v.windows(2).map(|w| (w[1] - w[0]).abs()).fold(0.0, f64::max);
+        assert!(max_jump(&steps) > 0.1);
+        assert!(max_jump(&glides) < 0.05);
+    }
+
+    #[test]
+    fn test_sources_follow_tempo() {
+        let mut matrix = ModMatrix::new();
+        matrix.add_source("bar", ModSource::lfo(44100.0, 3.0, LfoShape::Saw).with_sync(4.0));
+        matrix.add_source("free", ModSource::lfo(44100.0, 3.0, LfoShape::Saw));
+        matrix.add_source("steps", ModSource::random(3.0, false, 1).with_sync(0.5));
+        matrix.sync(120.0, 2.0);
+
+        let frequency = |name: &str| match &matrix.sources.iter().find(|(n, _)| n == name).unwrap().1 {
+            ModSource::Lfo(lfo) => lfo.frequency(),
+            ModSource::Random { rate, .. } => *rate,
+            _ => unreachable!(),
+        };
+        assert_eq!(frequency("bar"), 0.5);
+        assert_eq!(frequency("free"), 3.0);
+        assert_eq!(frequency("steps"), 4.0);
+
+        // Half way through the bar, the synced saw is at its midpoint
+        matrix.add_route("bar", "pitch", 1.0, None);
+        matrix.tick(CONTROL_RATE);
+        assert!(matrix.offset("pitch").abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_envelope_follows_notes() {
+        let mut matrix = ModMatrix::new();
+        matrix.add_source("swell", ModSource::envelope(44100.0, 0.01, 0.01, 1.0, 0.01));
+        matrix.add_route("swell", "filter", 1000.0, None);
+
+        matrix.tick(CONTROL_RATE);
+        assert_eq!(matrix.offset("filter"), 0.0);
+
+        matrix.trigger();
+        for _ in 0..100 {
+            matrix.tick(CONTROL_RATE);
+        }
+        assert_eq!(matrix.offset("filter"), 1000.0);
+
+        matrix.release();
+        for _ in 0..100 {
+            matrix.tick(CONTROL_RATE);
+        }
+        assert_eq!(matrix.offset("filter"), 0.0);
+    }
+
+    #[test]
+    fn test_modulated_voice() {
+        let mut matrix = ModMatrix::new();
+        matrix.add_source("brightness", ModSource::data(0.0, 100.0));
+        matrix.add_route("brightness", "filter", 1000.0, None);
+
+        let voice = Box::new(DroneVoice::new(44100.0));
+        let mut voice = ModulatedVoice::new(voice, matrix, 44100.0).unwrap();
+        voice.set_parameter("filter", 500.0);
+        voice.set_parameter("mod.brightness", 50.0);
+        voice.process();
+
+        // The base stays put; the voice hears base + modulation
+        assert_eq!(voice.get_parameter("filter"), Some(500.0));
+        assert_eq!(voice.voice.get_parameter("filter"), Some(1000.0));
+    }
+
+    #[test]
+    fn test_modulated_voice_alias_target() {
+        let mut matrix = ModMatrix::new();
+        matrix.add_source("brightness", ModSource::data(0.0, 100.0));
+        matrix.add_route("brightness", "cutoff", 1000.0, None);
+
+        let voice = Box::new(DroneVoice::new(44100.0));
+        let mut voice = ModulatedVoice::new(voice, matrix, 44100.0).unwrap();
+        assert_eq!(voice.matrix().targets(), vec!["filter"]);
+
+        // Mapping the canonical name moves the base the route adds to
+        voice.set_parameter("filter", 300.0);
+        for _ in 0..44100 {
+            voice.process();
+        }
+        assert_eq!(voice.get_parameter("filter"), Some(300.0));
+        assert_eq!(voice.get_parameter("cutoff"), Some(300.0));
+        assert_eq!(voice.voice.get_parameter("filter"), Some(300.0));
+
+        voice.set_parameter("mod.brightness", 20.0);
+        for _ in 0..CONTROL_INTERVAL {
+            voice.process();
+        }
+        assert_eq!(voice.voice.get_parameter("filter"), Some(500.0));
+    }
+
+    #[test]
+    fn test_modulated_voice_every_param() {
+        // Every listed parameter is a valid target
+        let voice = FmVoice::new(44100.0);
+        for spec in voice.params() {
+            let mut matrix = ModMatrix::new();
+            matrix.add_source("lfo", ModSource::lfo(44100.0, 1.0, LfoShape::Sine));
+            matrix.add_route("lfo", spec.name, 0.1, None);
+            assert!(ModulatedVoice::new(Box::new(FmVoice::new(44100.0)), matrix, 44100.0).is_ok(), "{}", spec.name);
+        }
+    }
+
+    #[test]
+    fn test_modulated_voice_unknown_target() {
+        let mut matrix = ModMatrix::new();
+        matrix.add_source("lfo", ModSource::lfo(44100.0, 1.0, LfoShape::Sine));
+        matrix.add_route("lfo", "wobble", 1.0, None);
+
+        let voice = Box::new(DroneVoice::new(44100.0));
+        let err = ModulatedVoice::new(voice, matrix, 44100.0).err().unwrap();
+        assert!(err.to_string().contains("'wobble'"));
+    }
+}
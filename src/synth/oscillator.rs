@@ -1,4 +1,8 @@
 //! Basic oscillator implementation
+//!
+//! Saw, square and triangle are band-limited by default: PolyBLEP smooths the
+//! saw and square discontinuities and PolyBLAMP the triangle corners, which
+//! removes most of the aliasing a naive waveform produces at high pitches.
 
 use std::f64::consts::PI;
 
@@ -29,6 +33,8 @@ pub struct Oscillator {
     brown_state: f64,
     /// Simple RNG state (xorshift)
     rng_state: u64,
+    /// Apply PolyBLEP/PolyBLAMP corrections to saw, square and triangle
+    band_limited: bool,
 }
 
 impl Oscillator {
@@ -43,9 +49,28 @@ impl Oscillator {
             brown_state: 0.0,
             // Initialize RNG with a non-zero seed based on frequency
             rng_state: ((frequency * 1000.0) as u64).max(1),
+            band_limited: true,
         }
     }
     
+    /// Enable or disable band limiting (builder pattern)
+    ///
+    /// Naive waveforms are exact textbook shapes, useful for modulation and tests.
+    pub fn with_band_limited(mut self, band_limited: bool) -> Self {
+        self.band_limited = band_limited;
+        self
+    }
+    
+    /// Enable or disable band limiting
+    pub fn set_band_limited(&mut self, band_limited: bool) {
+        self.band_limited = band_limited;
+    }
+    
+    /// Check if band limiting is enabled
+    pub fn is_band_limited(&self) -> bool {
+        self.band_limited
+    }
+    
     /// Set the frequency
     pub fn set_frequency(&mut self, frequency: f64) {
         self.frequency = frequency;
@@ -91,23 +116,45 @@ impl Oscillator {
         (self.phase * 2.0 * PI).sin()
     }
     
+    /// Phase increment per sample (0 when band limiting is off)
+    fn blep_width(&self) -> f64 {
+        if self.band_limited {
+            (self.frequency / self.sample_rate).abs().min(0.5)
+        } else {
+            0.0
+        }
+    }
+    
     fn triangle(&self) -> f64 {
         let p = self.phase;
-        if p < 0.25 {
+        let naive = if p < 0.25 {
             4.0 * p
         } else if p < 0.75 {
             2.0 - 4.0 * p
         } else {
             4.0 * p - 4.0
+        };
+        
+        let dt = self.blep_width();
+        if dt == 0.0 {
+            return naive;
         }
+        
+        // Slope changes by +/-8 at the trough (0.75) and peak (0.25)
+        let trough = (p + 0.25).fract();
+        let peak = (p + 0.75).fract();
+        naive + 4.0 * dt * (poly_blamp(trough, dt) - poly_blamp(peak, dt))
     }
     
     fn saw(&self) -> f64 {
-        2.0 * self.phase - 1.0
+        let naive = 2.0 * self.phase - 1.0;
+        naive - poly_blep(self.phase, self.blep_width())
     }
     
     fn square(&self) -> f64 {
-        if self.phase < 0.5 { 1.0 } else { -1.0 }
+        let naive = if self.phase < 0.5 { 1.0 } else { -1.0 };
+        let dt = self.blep_width();
+        naive + poly_blep(self.phase, dt) - poly_blep((self.phase + 0.5).fract(), dt)
     }
     
     /// Xorshift RNG for noise generation
@@ -163,6 +210,41 @@ impl Oscillator {
     }
 }
 
+/// Two-sample polynomial band-limited step residual
+///
+/// `t` is the phase (0.0 to 1.0) relative to a unit step at phase 0 and `dt`
+/// the phase increment per sample. Returns 0 away from the step.
+fn poly_blep(t: f64, dt: f64) -> f64 {
+    if dt <= 0.0 {
+        0.0
+    } else if t < dt {
+        let t = t / dt;
+        2.0 * t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        t * t + 2.0 * t + 1.0
+    } else {
+        0.0
+    }
+}
+
+/// Two-sample polynomial band-limited ramp residual (integrated PolyBLEP)
+///
+/// Corrects slope discontinuities such as the corners of a triangle.
+fn poly_blamp(t: f64, dt: f64) -> f64 {
+    if dt <= 0.0 {
+        0.0
+    } else if t < dt {
+        let t = t / dt - 1.0;
+        -t * t * t / 3.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt + 1.0;
+        t * t * t / 3.0
+    } else {
+        0.0
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,7 +260,7 @@ mod tests {
 
     #[test]
     fn test_square_oscillator() {
-        let mut osc = Oscillator::new(Waveform::Square, 1.0, 4.0);
+        let mut osc = Oscillator::new(Waveform::Square, 1.0, 4.0).with_band_limited(false);
         
         // 4 samples per cycle at 1 Hz, 4 Hz sample rate
         assert_eq!(osc.generate(), 1.0);  // phase 0.0
@@ -189,7 +271,7 @@ mod tests {
 
     #[test]
     fn test_saw_oscillator() {
-        let mut osc = Oscillator::new(Waveform::Saw, 1.0, 4.0);
+        let mut osc = Oscillator::new(Waveform::Saw, 1.0, 4.0).with_band_limited(false);
         
         // Saw goes from -1 to 1 linearly
         assert_eq!(osc.generate(), -1.0); // phase 0.0
@@ -245,4 +327,81 @@ mod tests {
             assert!((-1.0..=1.0).contains(&sample), "Sample out of range: {}", sample);
         }
     }
+
+    /// Fraction of signal energy that is not at a harmonic of `frequency`
+    ///
+    /// 4410 samples at 44.1 kHz gives 10 Hz bins. With a fundamental on a
+    /// multiple of 10 Hz the signal is exactly periodic in the window, so true
+    /// harmonics and their aliases all land on bins and nothing leaks.
+    fn aliasing_ratio(waveform: Waveform, frequency: f64, band_limited: bool) -> f64 {
+        const N: usize = 4410;
+        const SAMPLE_RATE: f64 = 44100.0;
+        let mut osc = Oscillator::new(waveform, frequency, SAMPLE_RATE).with_band_limited(band_limited);
+        let signal: Vec<f64> = (0..N).map(|_| osc.generate()).collect();
+        
+        let bin_hz = SAMPLE_RATE / N as f64;
+        let harmonic_step = (frequency / bin_hz).round() as usize;
+        
+        let mut harmonic = 0.0;
+        let mut total = 0.0;
+        for bin in 1..N / 2 {
+            // Goertzel algorithm for one DFT bin
+            let coeff = 2.0 * (2.0 * PI * bin as f64 / N as f64).cos();
+            let (mut s1, mut s2) = (0.0, 0.0);
+            for &x in &signal {
+                let s0 = x + coeff * s1 - s2;
+                s2 = s1;
+                s1 = s0;
+            }
+            let power = s1 * s1 + s2 * s2 - coeff * s1 * s2;
+            
+            total += power;
+            if bin % harmonic_step == 0 {
+                harmonic += power;
+            }
+        }
+        
+        (total - harmonic) / total
+    }
+
+    #[test]
+    fn test_band_limited_saw_reduces_aliasing() {
+        let naive = aliasing_ratio(Waveform::Saw, 3110.0, false);
+        let blep = aliasing_ratio(Waveform::Saw, 3110.0, true);
+        assert!(blep < naive / 10.0, "naive {:.5}, band-limited {:.5}", naive, blep);
+        assert!(blep < 0.01, "band-limited {:.5}", blep);
+    }
+
+    #[test]
+    fn test_band_limited_square_reduces_aliasing() {
+        let naive = aliasing_ratio(Waveform::Square, 3110.0, false);
+        let blep = aliasing_ratio(Waveform::Square, 3110.0, true);
+        assert!(blep < naive / 10.0, "naive {:.5}, band-limited {:.5}", naive, blep);
+        assert!(blep < 0.01, "band-limited {:.5}", blep);
+    }
+
+    #[test]
+    fn test_band_limited_triangle_reduces_aliasing() {
+        let naive = aliasing_ratio(Waveform::Triangle, 3110.0, false);
+        let blamp = aliasing_ratio(Waveform::Triangle, 3110.0, true);
+        assert!(blamp < naive / 10.0, "naive {:.6}, band-limited {:.6}", naive, blamp);
+    }
+
+    #[test]
+    fn test_band_limited_matches_naive_at_low_pitch() {
+        // Away from the discontinuities the corrections vanish
+        let mut naive = Oscillator::new(Waveform::Saw, 100.0, 44100.0).with_band_limited(false);
+        let mut blep = Oscillator::new(Waveform::Saw, 100.0, 44100.0);
+        assert!(blep.is_band_limited());
+        
+        let mut max_diff = 0.0f64;
+        for i in 0..441 {
+            let d = (naive.generate() - blep.generate()).abs();
+            // Skip the samples next to the wrap
+            if i > 2 && i < 439 {
+                max_diff = max_diff.max(d);
+            }
+        }
+        assert!(max_diff < 1e-12);
+    }
 }
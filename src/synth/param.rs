@@ -0,0 +1,579 @@
+//! Voice parameters
+//!
+//! Each voice describes its parameters with `ParamSpec`s (range, default and
+//! how the value moves). `SmoothedVoice` uses them to glide continuous
+//! parameters toward new values instead of jumping, which removes the
+//! zipper noise and clicks of data updates landing mid-note.
+
+use super::{Envelope, Frame, Voice, CONTROL_INTERVAL};
+use std::collections::HashMap;
+
+/// How a parameter's value moves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamKind {
+    /// Smoothed on a linear scale
+    Continuous,
+    /// Positive values smoothed on a log scale (frequencies, ratios)
+    Logarithmic,
+    /// Whole steps or switches that apply immediately
+    Stepped,
+}
+
+/// Description of a voice parameter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParamSpec {
+    /// Canonical name
+    pub name: &'static str,
+    /// Other names the voice accepts
+    pub aliases: &'static [&'static str],
+    pub min: f64,
+    pub max: f64,
+    /// Value of a newly created voice
+    pub default: f64,
+    pub kind: ParamKind,
+    /// Names for a stepped parameter's values (index 0 = `min`)
+    pub options: &'static [&'static str],
+}
+
+impl ParamSpec {
+    /// A parameter smoothed on a linear scale
+    pub const fn continuous(name: &'static str, min: f64, max: f64, default: f64) -> Self {
+        Self {
+            name,
+            aliases: &[],
+            min,
+            max,
+            default,
+            kind: ParamKind::Continuous,
+            options: &[],
+        }
+    }
+
+    /// A parameter smoothed on a log scale
+    pub const fn logarithmic(name: &'static str, min: f64, max: f64, default: f64) -> Self {
+        Self {
+            kind: ParamKind::Logarithmic,
+            ..Self::continuous(name, min, max, default)
+        }
+    }
+
+    /// A parameter that applies immediately
+    pub const fn stepped(name: &'static str, min: f64, max: f64, default: f64) -> Self {
+        Self {
+            kind: ParamKind::Stepped,
+            ..Self::continuous(name, min, max, default)
+        }
+    }
+
+    /// Add alternative names
+    pub const fn with_aliases(self, aliases: &'static [&'static str]) -> Self {
+        Self { aliases, ..self }
+    }
+
+    /// Name the values of a stepped parameter, in order from `min`
+    pub const fn with_options(self, options: &'static [&'static str]) -> Self {
+        Self { options, ..self }
+    }
+
+    /// Get the value of a named option
+    pub fn option_value(&self, option: &str) -> Option<f64> {
+        let option = option.to_lowercase();
+        self.options
+            .iter()
+            .position(|o| *o == option)
+            .map(|i| self.min + i as f64)
+    }
+
+    /// Check if a name refers to this parameter
+    pub fn matches(&self, name: &str) -> bool {
+        self.name == name || self.aliases.contains(&name)
+    }
+}
+
+/// Find a parameter by name or alias
+pub fn find_param<'a>(params: &'a [ParamSpec], name: &str) -> Option<&'a ParamSpec> {
+    params.iter().find(|p| p.matches(name))
+}
+
+/// Shape of a smoothing glide
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SmoothingCurve {
+    /// Constant rate, arriving exactly after the smoothing time
+    Linear,
+    /// Fast start, slow finish (one-pole), within 1% after the smoothing time
+    #[default]
+    Exponential,
+}
+
+/// Time constants per smoothing time: a one-pole filter is within 1% after ~4.6
+const EXPONENTIAL_SETTLE: f64 = 4.6;
+
+/// Glides a value toward a target
+#[derive(Debug, Clone)]
+pub struct Smoother {
+    current: f64,
+    target: f64,
+    curve: SmoothingCurve,
+    /// Smooth in log space
+    logarithmic: bool,
+    /// Distance covered per tick (linear) or fraction per tick (exponential)
+    rate: f64,
+}
+
+impl Smoother {
+    /// Create a smoother resting at a value
+    pub fn new(value: f64, curve: SmoothingCurve, logarithmic: bool) -> Self {
+        Self {
+            current: value,
+            target: value,
+            curve,
+            logarithmic,
+            rate: 1.0,
+        }
+    }
+
+    /// Log space only works for positive values
+    fn in_log(&self) -> bool {
+        self.logarithmic && self.current > 0.0 && self.target > 0.0
+    }
+
+    fn to_domain(&self, value: f64) -> f64 {
+        if self.in_log() { value.ln() } else { value }
+    }
+
+    fn to_value(&self, value: f64) -> f64 {
+        if self.in_log() { value.exp() } else { value }
+    }
+
+    /// Set a new target reached over `time` seconds of `tick` second steps
+    pub fn set_target(&mut self, target: f64, time: f64, tick: f64) {
+        self.target = target;
+        if time <= 0.0 {
+            self.current = target;
+            return;
+        }
+
+        let ticks = (time / tick).max(1.0);
+        self.rate = match self.curve {
+            SmoothingCurve::Linear => {
+                (self.to_domain(self.target) - self.to_domain(self.current)).abs() / ticks
+            }
+            SmoothingCurve::Exponential => 1.0 - (-EXPONENTIAL_SETTLE / ticks).exp(),
+        };
+    }
+
+    /// Get the current value
+    pub fn value(&self) -> f64 {
+        self.current
+    }
+
+    /// Get the target value
+    pub fn target(&self) -> f64 {
+        self.target
+    }
+
+    /// Check if the value has reached the target
+    pub fn is_settled(&self) -> bool {
+        self.current == self.target
+    }
+
+    /// Jump to the target
+    pub fn snap(&mut self) {
+        self.current = self.target;
+    }
+
+    /// Advance one tick and return the new value
+    pub fn advance(&mut self) -> f64 {
+        if self.is_settled() {
+            return self.current;
+        }
+
+        let current = self.to_domain(self.current);
+        let target = self.to_domain(self.target);
+        let next = match self.curve {
+            SmoothingCurve::Linear => {
+                let step = self.rate.min((target - current).abs());
+                current + step * (target - current).signum()
+            }
+            SmoothingCurve::Exponential => current + (target - current) * self.rate,
+        };
+
+        // Land exactly once close enough
+        if (target - next).abs() < 1e-6 * target.abs().max(1.0) {
+            self.current = self.target;
+        } else {
+            self.current = self.to_value(next);
+        }
+        self.current
+    }
+}
+
+/// A voice whose continuous parameters glide to new values
+pub struct SmoothedVoice {
+    voice: Box<dyn Voice>,
+    /// Smoothers for every continuous parameter by canonical name (all
+    /// created up front, so setting a parameter never allocates)
+    smoothers: HashMap<&'static str, Smoother>,
+    /// Default smoothing time in seconds
+    time: f64,
+    curve: SmoothingCurve,
+    /// Per-parameter (time, curve) overrides
+    overrides: HashMap<String, (Option<f64>, Option<SmoothingCurve>)>,
+    /// Pitch glide time in seconds (0 = use the smoothing time)
+    portamento: f64,
+    sample_rate: f64,
+    /// Samples until the next control tick
+    countdown: usize,
+}
+
+impl SmoothedVoice {
+    /// Wrap a voice with the default smoothing (20 ms, exponential)
+    pub fn new(voice: Box<dyn Voice>, sample_rate: f64) -> Self {
+        let smoothers = voice
+            .params()
+            .iter()
+            .filter(|spec| spec.kind != ParamKind::Stepped)
+            .map(|spec| {
+                let value = voice.get_parameter(spec.name).unwrap_or(spec.default);
+                (spec.name, Smoother::new(value, SmoothingCurve::default(), spec.kind == ParamKind::Logarithmic))
+            })
+            .collect();
+        Self {
+            voice,
+            smoothers,
+            time: 0.02,
+            curve: SmoothingCurve::default(),
+            overrides: HashMap::new(),
+            portamento: 0.0,
+            sample_rate,
+            countdown: 0,
+        }
+    }
+
+    /// Set the default smoothing time in seconds (0 disables smoothing)
+    pub fn with_time(mut self, seconds: f64) -> Self {
+        self.time = seconds.max(0.0);
+        self
+    }
+
+    /// Set the default smoothing curve
+    pub fn with_curve(mut self, curve: SmoothingCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    /// Override the time and/or curve for one parameter
+    pub fn with_param(mut self, name: impl Into<String>, time: Option<f64>, curve: Option<SmoothingCurve>) -> Self {
+        self.overrides.insert(name.into(), (time, curve));
+        self
+    }
+
+    /// Set the pitch glide time in seconds
+    pub fn with_portamento(mut self, seconds: f64) -> Self {
+        self.set_parameter("portamento", seconds);
+        self
+    }
+
+    /// Get the pitch glide time
+    pub fn portamento(&self) -> f64 {
+        self.portamento
+    }
+
+    /// Seconds per control tick
+    fn tick(&self) -> f64 {
+        CONTROL_INTERVAL as f64 / self.sample_rate
+    }
+
+    /// Advance the smoothers on control ticks
+    fn step(&mut self) {
+        if self.countdown == 0 {
+            for (name, smoother) in &mut self.smoothers {
+                if !smoother.is_settled() {
+                    self.voice.set_parameter(name, smoother.advance());
+                }
+            }
+            self.countdown = CONTROL_INTERVAL;
+        }
+        self.countdown -= 1;
+    }
+
+    /// Smoothing time and curve for a parameter
+    fn settings(&self, spec: &ParamSpec) -> (f64, SmoothingCurve) {
+        let (time, curve) = spec
+            .aliases
+            .iter()
+            .chain(std::iter::once(&spec.name))
+            .find_map(|n| self.overrides.get(*n))
+            .copied()
+            .unwrap_or((None, None));
+        let default_time = if spec.name == "pitch" && self.portamento > 0.0 {
+            self.portamento
+        } else {
+            self.time
+        };
+        (time.unwrap_or(default_time), curve.unwrap_or(self.curve))
+    }
+}
+
+impl Voice for SmoothedVoice {
+    fn set_parameter(&mut self, name: &str, value: f64) {
+        if name == "portamento" {
+            // Voices that glide internally (melody) take it too
+            self.portamento = value.max(0.0);
+            self.voice.set_parameter(name, value);
+            return;
+        }
+
+        let spec = match find_param(self.voice.params(), name) {
+            Some(spec) if spec.kind != ParamKind::Stepped => *spec,
+            _ => {
+                self.voice.set_parameter(name, value);
+                return;
+            }
+        };
+
+        let (time, curve) = self.settings(&spec);
+        let value = value.clamp(spec.min, spec.max);
+        let tick = self.tick();
+        let start = self.voice.get_parameter(spec.name).unwrap_or(value);
+        let Some(smoother) = self.smoothers.get_mut(spec.name) else {
+            self.voice.set_parameter(spec.name, value);
+            return;
+        };
+        if smoother.is_settled() {
+            // Glide from where the voice is now, even if it was changed
+            // around this wrapper (envelope settings, sample rate)
+            *smoother = Smoother::new(start, curve, spec.kind == ParamKind::Logarithmic);
+        }
+        smoother.curve = curve;
+        smoother.set_target(value, time, tick);
+        if time <= 0.0 {
+            self.voice.set_parameter(spec.name, value);
+        }
+    }
+
+    fn get_parameter(&self, name: &str) -> Option<f64> {
+        if name == "portamento" {
+            return Some(self.portamento);
+        }
+        find_param(self.voice.params(), name)
+            .and_then(|spec| self.smoothers.get(spec.name))
+            .filter(|s| !s.is_settled())
+            .map(|s| s.target())
+            .or_else(|| self.voice.get_parameter(name))
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        self.voice.params()
+    }
+
+    fn trigger(&mut self) {
+        // New notes start at their targets; only pitch glides into them
+        let glide = self.portamento > 0.0;
+        for (name, smoother) in &mut self.smoothers {
+            if smoother.is_settled() || (glide && *name == "pitch") {
+                continue;
+            }
+            smoother.snap();
+            self.voice.set_parameter(name, smoother.value());
+        }
+        self.voice.trigger();
+    }
+
+    fn release(&mut self) {
+        self.voice.release();
+    }
+
+    fn is_active(&self) -> bool {
+        self.voice.is_active()
+    }
+
+    fn process(&mut self) -> f64 {
+        self.step();
+        self.voice.process()
+    }
+
+    fn process_frame(&mut self) -> Frame {
+        self.step();
+        self.voice.process_frame()
+    }
+
+    fn envelope_mut(&mut self) -> Option<&mut Envelope> {
+        self.voice.envelope_mut()
+    }
+
+    fn sync(&mut self, bpm: f64, beat: f64) {
+        self.voice.sync(bpm, beat);
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+        self.voice.set_sample_rate(sample_rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapping::Scale;
+    use crate::synth::{
+        DroneVoice, FmVoice, MelodyVoice, PercussionModel, PercussionVoice, SampleBuffer, SamplerVoice,
+        TextureVoice,
+    };
+
+    fn voices() -> Vec<Box<dyn Voice>> {
+        vec![
+            Box::new(DroneVoice::new(44100.0)),
+            Box::new(PercussionVoice::new(44100.0, PercussionModel::Kick)),
+            Box::new(MelodyVoice::new(44100.0, 60.0, 261.63, Scale::minor_pentatonic())),
+            Box::new(TextureVoice::new(44100.0)),
+            Box::new(SamplerVoice::new(44100.0, vec![SampleBuffer::from_samples(vec![0.0; 10], 44100.0)])),
+            Box::new(FmVoice::new(44100.0)),
+        ]
+    }
+
+    #[test]
+    fn test_param_specs_match_voices() {
+        for voice in voices() {
+            assert!(!voice.params().is_empty());
+            for spec in voice.params() {
+                assert!(spec.min <= spec.default && spec.default <= spec.max, "{}", spec.name);
+                let value = voice.get_parameter(spec.name);
+                assert!(value.is_some(), "{} can't be read back", spec.name);
+                if let Some(value) = value {
+                    assert!((value - spec.default).abs() < 1e-9, "{}: {} != {}", spec.name, value, spec.default);
+                }
+                for alias in spec.aliases {
+                    assert_eq!(voice.get_parameter(alias), voice.get_parameter(spec.name), "{}", alias);
+                }
+                if !spec.options.is_empty() {
+                    assert_eq!(spec.kind, ParamKind::Stepped, "{}", spec.name);
+                    assert_eq!(spec.options.len() as f64, spec.max - spec.min + 1.0, "{}", spec.name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_param_options() {
+        let spec = ParamSpec::stepped("model", 0.0, 2.0, 0.0).with_options(&["kick", "snare", "hat"]);
+        assert_eq!(spec.option_value("snare"), Some(1.0));
+        assert_eq!(spec.option_value("HAT"), Some(2.0));
+        assert_eq!(spec.option_value("cowbell"), None);
+    }
+
+    #[test]
+    fn test_smoother_linear() {
+        let mut smoother = Smoother::new(0.0, SmoothingCurve::Linear, false);
+        smoother.set_target(1.0, 4.0, 1.0);
+        let values: Vec<f64> = (0..5).map(|_| smoother.advance()).collect();
+        assert_eq!(values, vec![0.25, 0.5, 0.75, 1.0, 1.0]);
+        assert!(smoother.is_settled());
+    }
+
+    #[test]
+    fn test_smoother_exponential() {
+        let mut smoother = Smoother::new(0.0, SmoothingCurve::Exponential, false);
+        smoother.set_target(1.0, 100.0, 1.0);
+        let first = smoother.advance();
+        let second = smoother.advance() - first;
+        // Decelerates toward the target and is within 1% after the smoothing time
+        assert!(second < first);
+        for _ in 2..100 {
+            smoother.advance();
+        }
+        assert!((smoother.value() - 1.0).abs() < 0.011);
+    }
+
+    #[test]
+    fn test_smoother_logarithmic() {
+        // Halfway in log space between 100 and 400 Hz is 200 Hz
+        let mut smoother = Smoother::new(100.0, SmoothingCurve::Linear, true);
+        smoother.set_target(400.0, 2.0, 1.0);
+        assert!((smoother.advance() - 200.0).abs() < 1e-9);
+        assert_eq!(smoother.advance(), 400.0);
+    }
+
+    #[test]
+    fn test_smoothed_voice_glides() {
+        let drone = Box::new(DroneVoice::new(44100.0));
+        let mut voice = SmoothedVoice::new(drone, 44100.0).with_time(0.1);
+        voice.set_parameter("cutoff", 4000.0);
+
+        // Reads back the target, while the voice moves there gradually
+        assert_eq!(voice.get_parameter("filter"), Some(4000.0));
+        assert_eq!(voice.voice.get_parameter("filter"), Some(2000.0));
+        for _ in 0..2205 {
+            voice.process();
+        }
+        let halfway = voice.voice.get_parameter("filter").unwrap();
+        assert!(halfway > 2000.0 && halfway < 4000.0, "{}", halfway);
+        for _ in 0..22050 {
+            voice.process();
+        }
+        assert_eq!(voice.voice.get_parameter("filter"), Some(4000.0));
+    }
+
+    #[test]
+    fn test_smoothed_voice_smoothers_made_up_front() {
+        let drone = Box::new(DroneVoice::new(44100.0));
+        let mut voice = SmoothedVoice::new(drone, 44100.0).with_time(0.1);
+        let continuous = DroneVoice::PARAMS.iter().filter(|s| s.kind != ParamKind::Stepped).count();
+        assert_eq!(voice.smoothers.len(), continuous);
+
+        // Setting parameters only retargets them
+        let capacity = voice.smoothers.capacity();
+        for spec in DroneVoice::PARAMS {
+            voice.set_parameter(spec.name, spec.max);
+        }
+        assert_eq!(voice.smoothers.len(), continuous);
+        assert_eq!(voice.smoothers.capacity(), capacity);
+
+        // Changes made to the voice directly are picked up by the next glide
+        let drone = Box::new(DroneVoice::new(44100.0));
+        let mut voice = SmoothedVoice::new(drone, 44100.0).with_time(0.1);
+        voice.voice.set_parameter("filter", 800.0);
+        assert_eq!(voice.get_parameter("filter"), Some(800.0));
+        voice.set_parameter("filter", 1600.0);
+        for _ in 0..2205 {
+            voice.process();
+        }
+        let halfway = voice.voice.get_parameter("filter").unwrap();
+        assert!(halfway > 800.0 && halfway < 1600.0, "{}", halfway);
+    }
+
+    #[test]
+    fn test_smoothed_voice_settings() {
+        let drone = Box::new(DroneVoice::new(44100.0));
+        let mut voice = SmoothedVoice::new(drone, 44100.0)
+            .with_time(0.0)
+            .with_param("amplitude", Some(1.0), Some(SmoothingCurve::Linear));
+
+        // Smoothing off: applies immediately
+        voice.set_parameter("filter", 500.0);
+        assert_eq!(voice.voice.get_parameter("filter"), Some(500.0));
+
+        voice.set_parameter("volume", 0.1);
+        assert_eq!(voice.voice.get_parameter("amplitude"), Some(0.7));
+    }
+
+    #[test]
+    fn test_trigger_snaps_except_portamento() {
+        let kick = Box::new(PercussionVoice::new(44100.0, PercussionModel::Kick));
+        let mut voice = SmoothedVoice::new(kick, 44100.0).with_time(1.0);
+        voice.set_parameter("decay", 1.0);
+        voice.set_parameter("pitch", 110.0);
+        voice.trigger();
+        assert_eq!(voice.voice.get_parameter("decay"), Some(1.0));
+        assert_eq!(voice.voice.get_parameter("pitch"), Some(110.0));
+
+        let mut voice = voice.with_portamento(0.5);
+        voice.set_parameter("pitch", 220.0);
+        voice.trigger();
+        assert_eq!(voice.voice.get_parameter("pitch"), Some(110.0));
+        for _ in 0..88200 {
+            voice.process();
+        }
+        assert_eq!(voice.voice.get_parameter("pitch"), Some(220.0));
+    }
+}
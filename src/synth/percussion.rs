@@ -0,0 +1,396 @@
+//! Percussion voice implementation
+//!
+//! Synthesized one-shot hits (kick, snare, hat, click) built from a body
+//! oscillator, a noise oscillator, a filter and two envelopes. The voice is
+//! silent until triggered and goes inactive once the hit has decayed.
+
+use super::{Envelope, EnvelopeStage, Filter, FilterType, Oscillator, ParamSpec, Voice, Waveform};
+
+/// Percussion sound models
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PercussionModel {
+    /// Sine body with a fast downward pitch sweep
+    Kick,
+    /// Short tonal body plus band-passed noise
+    Snare,
+    /// High-passed noise
+    Hat,
+    /// Very short band-passed noise burst
+    Click,
+}
+
+impl PercussionModel {
+    /// All models, in parameter order (`model` 0 = kick, 1 = snare, ...)
+    pub const ALL: [PercussionModel; 4] = [Self::Kick, Self::Snare, Self::Hat, Self::Click];
+
+    /// Get a model by name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "kick" => Some(Self::Kick),
+            "snare" => Some(Self::Snare),
+            "hat" | "hihat" | "hi_hat" => Some(Self::Hat),
+            "click" => Some(Self::Click),
+            _ => None,
+        }
+    }
+
+    /// Get a model from a numeric parameter value (rounded, clamped)
+    pub fn from_index(value: f64) -> Self {
+        let index = value.round().clamp(0.0, (Self::ALL.len() - 1) as f64) as usize;
+        Self::ALL[index]
+    }
+
+    /// Get the numeric parameter value for this model
+    pub fn index(&self) -> usize {
+        Self::ALL.iter().position(|m| m == self).unwrap_or(0)
+    }
+
+    /// Default (pitch Hz, decay seconds, tone 0-1) for this model
+    fn defaults(&self) -> (f64, f64, f64) {
+        match self {
+            Self::Kick => (55.0, 0.4, 0.3),
+            Self::Snare => (180.0, 0.2, 0.6),
+            Self::Hat => (8000.0, 0.06, 0.7),
+            Self::Click => (2500.0, 0.01, 0.5),
+        }
+    }
+}
+
+/// Percussion parameters
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec::stepped("model", 0.0, 3.0, 0.0).with_options(&["kick", "snare", "hat", "click"]),
+    ParamSpec::logarithmic("pitch", 20.0, 16000.0, 55.0).with_aliases(&["frequency"]),
+    ParamSpec::logarithmic("decay", 0.005, 5.0, 0.4),
+    ParamSpec::continuous("tone", 0.0, 1.0, 0.3),
+    ParamSpec::continuous("sweep", 0.0, 6.0, 2.0),
+    ParamSpec::continuous("amplitude", 0.0, 1.0, 0.8).with_aliases(&["volume"]),
+    ParamSpec::continuous("velocity", 0.0, 1.0, 1.0),
+];
+
+/// A one-shot percussion voice
+pub struct PercussionVoice {
+    model: PercussionModel,
+    /// Tonal body (kick, snare)
+    body: Oscillator,
+    /// Noise source (snare, hat, click)
+    noise: Oscillator,
+    /// Filter shaping the noise
+    filter: Filter,
+    /// Amplitude envelope
+    amp_envelope: Envelope,
+    /// Pitch sweep envelope (kick, snare)
+    pitch_envelope: Envelope,
+
+    sample_rate: f64,
+
+    // Parameters
+    /// Body frequency in Hz (filter center for hat and click)
+    pitch: f64,
+    /// Decay time in seconds
+    decay: f64,
+    /// Brightness / noise balance (0.0 to 1.0)
+    tone: f64,
+    /// Pitch sweep depth in octaves
+    sweep: f64,
+    amplitude: f64,
+    /// Velocity of the next hit (0.0 to 1.0)
+    velocity: f64,
+
+    /// Velocity latched when the current hit was triggered
+    hit_velocity: f64,
+    active: bool,
+}
+
+impl PercussionVoice {
+    /// Parameters every percussion voice accepts (also returned by `params`)
+    pub const PARAMS: &'static [ParamSpec] = PARAMS;
+
+    /// Create a new percussion voice with the given model
+    pub fn new(sample_rate: f64, model: PercussionModel) -> Self {
+        let mut voice = Self {
+            model,
+            body: Oscillator::new(Waveform::Sine, 55.0, sample_rate),
+            noise: Oscillator::new(Waveform::WhiteNoise, 1.0, sample_rate),
+            filter: Filter::new(sample_rate),
+            amp_envelope: Envelope::new(sample_rate),
+            pitch_envelope: Envelope::new(sample_rate),
+            sample_rate,
+            pitch: 55.0,
+            decay: 0.4,
+            tone: 0.3,
+            sweep: 2.0,
+            amplitude: 0.8,
+            velocity: 1.0,
+            hit_velocity: 1.0,
+            active: false,
+        };
+        voice.shape_envelopes();
+        voice.set_model(model);
+        voice
+    }
+
+    /// Get the current model
+    pub fn model(&self) -> PercussionModel {
+        self.model
+    }
+
+    /// Switch model and load its default pitch, decay and tone
+    pub fn set_model(&mut self, model: PercussionModel) {
+        self.model = model;
+        let (pitch, decay, tone) = model.defaults();
+        self.pitch = pitch;
+        self.decay = decay;
+        self.tone = tone;
+        self.configure();
+    }
+
+    /// Give fresh envelopes the percussive shape: instant attack, decay to
+    /// silence, no sustain
+    ///
+    /// Only done when the envelopes are created, so a layer's `envelope:`
+    /// overrides survive later parameter changes.
+    fn shape_envelopes(&mut self) {
+        self.amp_envelope.configure(0.001, self.decay, 0.0, 0.01);
+        self.pitch_envelope.configure(0.001, (self.decay * 0.15).max(0.005), 0.0, 0.01);
+    }
+
+    /// Apply the current parameters to the envelope decays and filter
+    fn configure(&mut self) {
+        self.amp_envelope.set_decay(self.decay);
+        self.pitch_envelope.set_decay((self.decay * 0.15).max(0.005));
+
+        let (filter_type, cutoff, q) = match self.model {
+            PercussionModel::Kick => (FilterType::LowPass, 200.0 + self.tone * 4000.0, 0.707),
+            PercussionModel::Snare => (FilterType::BandPass, 1000.0 + self.tone * 5000.0, 0.8),
+            PercussionModel::Hat => (FilterType::HighPass, self.pitch, 1.2),
+            PercussionModel::Click => (FilterType::BandPass, self.pitch, 2.0 + self.tone * 6.0),
+        };
+        self.filter.set_type(filter_type);
+        self.filter.set_cutoff(cutoff);
+        self.filter.set_resonance(q);
+    }
+}
+
+impl Voice for PercussionVoice {
+    fn set_parameter(&mut self, name: &str, value: f64) {
+        match name {
+            "model" => {
+                let model = PercussionModel::from_index(value);
+                if model != self.model {
+                    self.set_model(model);
+                }
+            }
+            "pitch" | "frequency" => {
+                self.pitch = value.clamp(20.0, 16000.0);
+                self.configure();
+            }
+            "decay" => {
+                self.decay = value.clamp(0.005, 5.0);
+                self.configure();
+            }
+            "tone" => {
+                self.tone = value.clamp(0.0, 1.0);
+                self.configure();
+            }
+            "sweep" => {
+                self.sweep = value.clamp(0.0, 6.0);
+            }
+            "amplitude" | "volume" => {
+                self.amplitude = value.clamp(0.0, 1.0);
+            }
+            "velocity" => {
+                self.velocity = value.clamp(0.0, 1.0);
+            }
+            _ => {}
+        }
+    }
+
+    fn get_parameter(&self, name: &str) -> Option<f64> {
+        match name {
+            "model" => Some(self.model.index() as f64),
+            "pitch" | "frequency" => Some(self.pitch),
+            "decay" => Some(self.decay),
+            "tone" => Some(self.tone),
+            "sweep" => Some(self.sweep),
+            "amplitude" | "volume" => Some(self.amplitude),
+            "velocity" => Some(self.velocity),
+            _ => None,
+        }
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn trigger(&mut self) {
+        self.active = true;
+        self.hit_velocity = self.velocity;
+        // Hits always restart from silence so retriggers get a clean transient
+        self.amp_envelope.reset();
+        self.pitch_envelope.reset();
+        self.amp_envelope.trigger();
+        self.pitch_envelope.trigger();
+        self.body.reset();
+        self.filter.reset();
+    }
+
+    fn release(&mut self) {
+        // One-shot: the hit always plays out its decay
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn process(&mut self) -> f64 {
+        if !self.active {
+            return 0.0;
+        }
+
+        let env = self.amp_envelope.process();
+        let pitch_env = self.pitch_envelope.process();
+        // Square the linear decay for a more natural exponential-like tail
+        let amp = env * env;
+
+        let sample = match self.model {
+            PercussionModel::Kick => {
+                let freq = self.pitch * 2.0_f64.powf(self.sweep * pitch_env * pitch_env);
+                self.body.set_frequency(freq);
+                let body = self.body.generate();
+                // A little filtered noise for the beater click
+                let click = self.filter.process(self.noise.generate()) * pitch_env * 0.3;
+                body + click * self.tone
+            }
+            PercussionModel::Snare => {
+                let freq = self.pitch * 2.0_f64.powf(0.5 * self.sweep * pitch_env * pitch_env);
+                self.body.set_frequency(freq);
+                let body = self.body.generate() * pitch_env;
+                let noise = self.filter.process(self.noise.generate());
+                body * (1.0 - self.tone) + noise * (0.5 + self.tone)
+            }
+            PercussionModel::Hat | PercussionModel::Click => {
+                self.filter.process(self.noise.generate()) * (0.5 + self.tone)
+            }
+        };
+
+        // Decay finished: sustain is zero (or a reshaped envelope ran out),
+        // so the hit is over
+        if self.amp_envelope.stage() == EnvelopeStage::Sustain || !self.amp_envelope.is_active() {
+            self.amp_envelope.reset();
+            self.pitch_envelope.reset();
+            self.active = false;
+        }
+
+        (sample * amp * self.amplitude * self.hit_velocity).clamp(-1.0, 1.0)
+    }
+
+    fn envelope_mut(&mut self) -> Option<&mut Envelope> {
+        Some(&mut self.amp_envelope)
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+
+        self.body = Oscillator::new(Waveform::Sine, self.pitch, sample_rate);
+        self.noise = Oscillator::new(Waveform::WhiteNoise, 1.0, sample_rate);
+        self.filter = Filter::new(sample_rate);
+        self.amp_envelope = Envelope::new(sample_rate);
+        self.pitch_envelope = Envelope::new(sample_rate);
+        self.active = false;
+        self.shape_envelopes();
+        self.configure();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(voice: &mut PercussionVoice, samples: usize) -> Vec<f64> {
+        (0..samples).map(|_| voice.process()).collect()
+    }
+
+    fn peak(samples: &[f64]) -> f64 {
+        samples.iter().fold(0.0f64, |a, &b| a.max(b.abs()))
+    }
+
+    #[test]
+    fn test_percussion_silent_until_triggered() {
+        let mut voice = PercussionVoice::new(44100.0, PercussionModel::Kick);
+        assert!(!voice.is_active());
+        assert_eq!(peak(&render(&mut voice, 1000)), 0.0);
+    }
+
+    #[test]
+    fn test_percussion_models_produce_hits() {
+        for model in PercussionModel::ALL {
+            let mut voice = PercussionVoice::new(44100.0, model);
+            voice.trigger();
+            assert!(voice.is_active());
+
+            let hit = render(&mut voice, 44100);
+            assert!(peak(&hit[..2000]) > 0.01, "{:?} hit is silent", model);
+
+            // One-shot hits decay to silence on their own
+            assert!(!voice.is_active(), "{:?} did not finish", model);
+            assert_eq!(voice.process(), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_percussion_decay_sets_length() {
+        let mut voice = PercussionVoice::new(44100.0, PercussionModel::Hat);
+        voice.set_parameter("decay", 0.05);
+        voice.trigger();
+
+        render(&mut voice, 2000); // ~45ms
+        assert!(voice.is_active());
+        render(&mut voice, 400);
+        assert!(!voice.is_active());
+    }
+
+    #[test]
+    fn test_percussion_velocity() {
+        let mut loud = PercussionVoice::new(44100.0, PercussionModel::Kick);
+        let mut soft = PercussionVoice::new(44100.0, PercussionModel::Kick);
+        soft.set_parameter("velocity", 0.25);
+        loud.trigger();
+        soft.trigger();
+
+        let loud_peak = peak(&render(&mut loud, 4000));
+        let soft_peak = peak(&render(&mut soft, 4000));
+        assert!((soft_peak - loud_peak * 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_percussion_model_parameter() {
+        let mut voice = PercussionVoice::new(44100.0, PercussionModel::Kick);
+        voice.set_parameter("model", 2.0);
+        assert_eq!(voice.model(), PercussionModel::Hat);
+        assert_eq!(voice.get_parameter("model"), Some(2.0));
+        assert_eq!(voice.get_parameter("pitch"), Some(8000.0));
+
+        // Out of range values clamp to the nearest model
+        voice.set_parameter("model", 9.0);
+        assert_eq!(voice.model(), PercussionModel::Click);
+
+        assert_eq!(PercussionModel::from_name("Snare"), Some(PercussionModel::Snare));
+        assert_eq!(PercussionModel::from_name("cowbell"), None);
+    }
+
+    #[test]
+    fn test_percussion_keeps_envelope_override() {
+        let mut voice = PercussionVoice::new(44100.0, PercussionModel::Kick);
+        voice.envelope_mut().unwrap().set_attack(0.5);
+        // A mapped decay only changes the decay
+        voice.set_parameter("decay", 1.0);
+
+        voice.trigger();
+        let mut level = 0.0;
+        for _ in 0..441 {
+            voice.process();
+            level = voice.amp_envelope.level();
+        }
+        assert!(level < 0.05, "{}", level);
+    }
+}
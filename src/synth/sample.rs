@@ -0,0 +1,199 @@
+//! Sample buffers
+//!
+//! Mono audio loaded from WAV files (or rendered in memory) that voices can
+//! read at fractional positions. Buffers are reference counted so many
+//! voices can share one file.
+
+use anyhow::{bail, Context, Result};
+use hound::{SampleFormat, WavReader};
+use std::path::Path;
+use std::sync::Arc;
+
+/// A mono sample buffer with its native sample rate
+#[derive(Debug, Clone)]
+pub struct SampleBuffer {
+    samples: Arc<Vec<f32>>,
+    sample_rate: f64,
+}
+
+impl SampleBuffer {
+    /// Create a buffer from raw samples
+    pub fn from_samples(samples: Vec<f32>, sample_rate: f64) -> Self {
+        Self {
+            samples: Arc::new(samples),
+            sample_rate,
+        }
+    }
+
+    /// Load a WAV file, mixing all channels down to mono
+    pub fn load(path: &Path) -> Result<Self> {
+        let (interleaved, channels, sample_rate) = Self::read_wav(path)?;
+        let samples = interleaved
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+            .collect();
+
+        Ok(Self::from_samples(samples, sample_rate))
+    }
+
+    /// Load a WAV file as one buffer per channel
+    pub fn load_channels(path: &Path) -> Result<Vec<Self>> {
+        let (interleaved, channels, sample_rate) = Self::read_wav(path)?;
+        Ok((0..channels)
+            .map(|channel| {
+                let samples = interleaved.iter().skip(channel).step_by(channels).copied().collect();
+                Self::from_samples(samples, sample_rate)
+            })
+            .collect())
+    }
+
+    /// Read a WAV file's interleaved samples, channel count and sample rate
+    fn read_wav(path: &Path) -> Result<(Vec<f32>, usize, f64)> {
+        let mut reader = WavReader::open(path)
+            .with_context(|| format!("failed to open WAV file: {:?}", path))?;
+        let spec = reader.spec();
+        let channels = spec.channels.max(1) as usize;
+
+        let interleaved: Vec<f32> = match spec.sample_format {
+            SampleFormat::Float => reader
+                .samples::<f32>()
+                .collect::<Result<_, _>>()
+                .with_context(|| format!("failed to read WAV file: {:?}", path))?,
+            SampleFormat::Int => {
+                let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|s| s.map(|s| s as f32 * scale))
+                    .collect::<Result<_, _>>()
+                    .with_context(|| format!("failed to read WAV file: {:?}", path))?
+            }
+        };
+
+        if interleaved.is_empty() {
+            bail!("WAV file has no samples: {:?}", path);
+        }
+
+        Ok((interleaved, channels, spec.sample_rate as f64))
+    }
+
+    /// Get the number of samples
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Check if the buffer is empty
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Get the native sample rate
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Get the duration in seconds
+    pub fn duration(&self) -> f64 {
+        self.samples.len() as f64 / self.sample_rate
+    }
+
+    /// Get the raw samples
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    /// Read at a fractional position with linear interpolation
+    ///
+    /// Positions outside the buffer read as silence.
+    pub fn read(&self, position: f64) -> f64 {
+        if position < 0.0 || self.samples.is_empty() {
+            return 0.0;
+        }
+
+        let index = position.floor() as usize;
+        let frac = position - index as f64;
+        let a = match self.samples.get(index) {
+            Some(&a) => a as f64,
+            None => return 0.0,
+        };
+        let b = self.samples.get(index + 1).copied().unwrap_or(0.0) as f64;
+        a + (b - a) * frac
+    }
+
+    /// Read at a fractional position, wrapping around the end (for loops)
+    pub fn read_wrapped(&self, position: f64) -> f64 {
+        let len = self.samples.len();
+        if len == 0 {
+            return 0.0;
+        }
+
+        let position = position.rem_euclid(len as f64);
+        let index = position.floor() as usize % len;
+        let frac = position - position.floor();
+        let a = self.samples[index] as f64;
+        let b = self.samples[(index + 1) % len] as f64;
+        a + (b - a) * frac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::{WavSpec, WavWriter};
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_read_interpolates() {
+        let buffer = SampleBuffer::from_samples(vec![0.0, 1.0, 0.0], 44100.0);
+        assert_eq!(buffer.read(0.0), 0.0);
+        assert_eq!(buffer.read(0.5), 0.5);
+        assert_eq!(buffer.read(1.0), 1.0);
+        assert_eq!(buffer.read(1.75), 0.25);
+        assert_eq!(buffer.read(-1.0), 0.0);
+        assert_eq!(buffer.read(3.0), 0.0);
+    }
+
+    #[test]
+    fn test_read_wrapped() {
+        let buffer = SampleBuffer::from_samples(vec![0.0, 1.0], 44100.0);
+        assert_eq!(buffer.read_wrapped(1.5), 0.5);
+        assert_eq!(buffer.read_wrapped(2.0), 0.0);
+        assert_eq!(buffer.read_wrapped(-1.0), 1.0);
+    }
+
+    #[test]
+    fn test_load_stereo_int_wav() {
+        let file = NamedTempFile::new().unwrap();
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 22050,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        {
+            let mut writer = WavWriter::create(file.path(), spec).unwrap();
+            for _ in 0..100 {
+                writer.write_sample(16384i16).unwrap();
+                writer.write_sample(0i16).unwrap();
+            }
+            writer.finalize().unwrap();
+        }
+
+        let buffer = SampleBuffer::load(file.path()).unwrap();
+        assert_eq!(buffer.len(), 100);
+        assert_eq!(buffer.sample_rate(), 22050.0);
+        // Channels are averaged: (0.5 + 0.0) / 2
+        assert!((buffer.samples()[0] - 0.25).abs() < 1e-6);
+
+        let channels = SampleBuffer::load_channels(file.path()).unwrap();
+        assert_eq!(channels.len(), 2);
+        assert_eq!(channels[1].len(), 100);
+        assert!((channels[0].samples()[0] - 0.5).abs() < 1e-6);
+        assert_eq!(channels[1].samples()[0], 0.0);
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let err = SampleBuffer::load(Path::new("/nonexistent/grain.wav")).unwrap_err();
+        assert!(err.to_string().contains("failed to open WAV file"));
+    }
+}
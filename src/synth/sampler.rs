@@ -0,0 +1,273 @@
+//! Sampler voice implementation
+//!
+//! Plays loaded samples one-shot or looped, with rate and pitch control.
+//! The `sample` parameter picks which of the loaded samples the next trigger
+//! plays, so layers can map events to different sounds.
+
+use super::{Envelope, ParamSpec, SampleBuffer, Voice};
+
+/// Sampler parameters
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec::stepped("sample", 0.0, 255.0, 0.0),
+    ParamSpec::logarithmic("rate", 0.0625, 16.0, 1.0).with_aliases(&["speed"]),
+    ParamSpec::continuous("pitch", -48.0, 48.0, 0.0),
+    ParamSpec::stepped("loop", 0.0, 1.0, 0.0).with_options(&["off", "on"]),
+    ParamSpec::continuous("start", 0.0, 1.0, 0.0),
+    ParamSpec::continuous("amplitude", 0.0, 1.0, 0.8).with_aliases(&["volume"]),
+    ParamSpec::continuous("attack", 0.001, 10.0, 0.002),
+    ParamSpec::continuous("release", 0.001, 30.0, 0.05),
+];
+
+/// A sample playback voice
+pub struct SamplerVoice {
+    samples: Vec<SampleBuffer>,
+    /// Click-free start and release
+    envelope: Envelope,
+
+    sample_rate: f64,
+
+    // Parameters
+    /// Sample played by the next trigger
+    selected: usize,
+    /// Playback rate multiplier
+    rate: f64,
+    /// Transposition in semitones (combined with rate)
+    pitch: f64,
+    /// Loop until released instead of playing once
+    looping: bool,
+    /// Start offset as a fraction of the sample
+    start: f64,
+    amplitude: f64,
+
+    // Playback state
+    /// Sample currently playing
+    playing: usize,
+    /// Read position in source samples
+    position: f64,
+    active: bool,
+}
+
+impl SamplerVoice {
+    /// Parameters every sampler voice accepts (also returned by `params`)
+    pub const PARAMS: &'static [ParamSpec] = PARAMS;
+
+    /// Create a sampler voice over the given samples
+    pub fn new(sample_rate: f64, samples: Vec<SampleBuffer>) -> Self {
+        let mut envelope = Envelope::new(sample_rate);
+        envelope.configure(0.002, 0.001, 1.0, 0.05);
+
+        Self {
+            samples,
+            envelope,
+            sample_rate,
+            selected: 0,
+            rate: 1.0,
+            pitch: 0.0,
+            looping: false,
+            start: 0.0,
+            amplitude: 0.8,
+            playing: 0,
+            position: 0.0,
+            active: false,
+        }
+    }
+
+    /// Get the number of loaded samples
+    pub fn sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Source samples advanced per output sample
+    fn increment(&self) -> f64 {
+        let native = self.samples[self.playing].sample_rate() / self.sample_rate;
+        native * self.rate * 2.0_f64.powf(self.pitch / 12.0)
+    }
+}
+
+impl Voice for SamplerVoice {
+    fn set_parameter(&mut self, name: &str, value: f64) {
+        match name {
+            "sample" => {
+                let last = self.samples.len().saturating_sub(1) as f64;
+                self.selected = value.round().clamp(0.0, last) as usize;
+            }
+            "rate" | "speed" => {
+                self.rate = value.clamp(0.0625, 16.0);
+            }
+            "pitch" => {
+                self.pitch = value.clamp(-48.0, 48.0);
+            }
+            "loop" => {
+                self.looping = value > 0.5;
+            }
+            "start" => {
+                self.start = value.clamp(0.0, 1.0);
+            }
+            "amplitude" | "volume" => {
+                self.amplitude = value.clamp(0.0, 1.0);
+            }
+            "attack" => {
+                self.envelope.set_attack(value.clamp(0.001, 10.0));
+            }
+            "release" => {
+                self.envelope.set_release(value.clamp(0.001, 30.0));
+            }
+            _ => {}
+        }
+    }
+
+    fn get_parameter(&self, name: &str) -> Option<f64> {
+        match name {
+            "sample" => Some(self.selected as f64),
+            "rate" | "speed" => Some(self.rate),
+            "pitch" => Some(self.pitch),
+            "loop" => Some(if self.looping { 1.0 } else { 0.0 }),
+            "start" => Some(self.start),
+            "amplitude" | "volume" => Some(self.amplitude),
+            "attack" => Some(self.envelope.attack_time()),
+            "release" => Some(self.envelope.release_time()),
+            _ => None,
+        }
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn trigger(&mut self) {
+        if self.samples.is_empty() {
+            return;
+        }
+
+        self.playing = self.selected;
+        self.position = self.start * self.samples[self.playing].len() as f64;
+        self.envelope.reset();
+        self.envelope.trigger();
+        self.active = true;
+    }
+
+    fn release(&mut self) {
+        self.envelope.release();
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn process(&mut self) -> f64 {
+        if !self.active {
+            return 0.0;
+        }
+
+        let buffer = &self.samples[self.playing];
+        let len = buffer.len() as f64;
+        let sample = if self.looping {
+            buffer.read_wrapped(self.position)
+        } else {
+            buffer.read(self.position)
+        };
+
+        self.position += self.increment();
+        if self.looping && self.position >= len {
+            self.position -= len;
+        }
+
+        let env_level = self.envelope.process();
+        if (!self.looping && self.position >= len) || !self.envelope.is_active() {
+            self.active = false;
+            self.envelope.reset();
+        }
+
+        sample * env_level * self.amplitude
+    }
+
+    fn envelope_mut(&mut self) -> Option<&mut Envelope> {
+        Some(&mut self.envelope)
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+        self.envelope = Envelope::new(sample_rate);
+        self.envelope.configure(0.002, 0.001, 1.0, 0.05);
+        self.active = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two short constant samples at half the output rate
+    fn voice() -> SamplerVoice {
+        let a = SampleBuffer::from_samples(vec![0.5; 100], 22050.0);
+        let b = SampleBuffer::from_samples(vec![-1.0; 50], 22050.0);
+        SamplerVoice::new(44100.0, vec![a, b])
+    }
+
+    fn count_active(voice: &mut SamplerVoice) -> usize {
+        let mut samples = 0;
+        while voice.is_active() && samples < 100_000 {
+            voice.process();
+            samples += 1;
+        }
+        samples
+    }
+
+    #[test]
+    fn test_sampler_one_shot() {
+        let mut voice = voice();
+        assert!(!voice.is_active());
+        assert_eq!(voice.process(), 0.0);
+
+        voice.trigger();
+        // 100 samples at 22.05 kHz last 200 output samples at 44.1 kHz
+        assert_eq!(count_active(&mut voice), 200);
+    }
+
+    #[test]
+    fn test_sampler_rate_and_pitch() {
+        let mut voice = voice();
+        voice.set_parameter("rate", 2.0);
+        voice.trigger();
+        assert_eq!(count_active(&mut voice), 100);
+
+        voice.set_parameter("rate", 1.0);
+        voice.set_parameter("pitch", -12.0);
+        voice.trigger();
+        assert_eq!(count_active(&mut voice), 400);
+    }
+
+    #[test]
+    fn test_sampler_selects_sample() {
+        let mut voice = voice();
+        assert_eq!(voice.sample_count(), 2);
+
+        voice.set_parameter("sample", 1.0);
+        voice.trigger();
+        let output: Vec<f64> = (0..50).map(|_| voice.process()).collect();
+        assert!(output[20] < 0.0);
+        assert_eq!(count_active(&mut voice), 50);
+
+        // Out of range selects the last sample
+        voice.set_parameter("sample", 7.0);
+        assert_eq!(voice.get_parameter("sample"), Some(1.0));
+    }
+
+    #[test]
+    fn test_sampler_loop_until_release() {
+        let mut voice = voice();
+        voice.set_parameter("loop", 1.0);
+        voice.set_parameter("release", 0.01);
+        voice.trigger();
+
+        for _ in 0..10_000 {
+            voice.process();
+        }
+        assert!(voice.is_active());
+
+        voice.release();
+        // 10 ms release at 44.1 kHz
+        let remaining = count_active(&mut voice);
+        assert!((440..=450).contains(&remaining), "{}", remaining);
+    }
+}
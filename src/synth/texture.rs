@@ -0,0 +1,424 @@
+//! Texture voice implementation
+//!
+//! A granular synthesizer. Short Hann-windowed grains are read from a source
+//! buffer (rendered noise or a loaded WAV file) at a scan position, with
+//! random position jitter and pitch spread per grain. Grain size and density
+//! turn a smooth wash into sparse crackle. Each grain lands at a random spot
+//! in the stereo field.
+
+use super::{Envelope, Filter, FilterType, Frame, Oscillator, ParamSpec, SampleBuffer, Voice, Waveform};
+use std::f64::consts::PI;
+
+/// Maximum number of simultaneous grains
+const MAX_GRAINS: usize = 64;
+
+/// Length of the rendered noise source in seconds
+const NOISE_SECONDS: f64 = 2.0;
+
+/// Noise waveforms selectable with the `noise` parameter
+const NOISE_WAVEFORMS: [Waveform; 3] = [Waveform::WhiteNoise, Waveform::PinkNoise, Waveform::BrownNoise];
+
+/// Texture parameters
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec::logarithmic("grain_size", 0.005, 1.0, 0.08).with_aliases(&["size"]),
+    ParamSpec::logarithmic("density", 0.5, 200.0, 20.0),
+    ParamSpec::continuous("position", 0.0, 1.0, 0.5),
+    ParamSpec::continuous("jitter", 0.0, 1.0, 0.2),
+    ParamSpec::continuous("pitch", -24.0, 24.0, 0.0),
+    ParamSpec::continuous("spread", 0.0, 24.0, 0.0).with_aliases(&["pitch_spread"]),
+    ParamSpec::stepped("noise", 0.0, 2.0, 1.0).with_options(&["white", "pink", "brown"]),
+    ParamSpec::logarithmic("filter", 20.0, 20000.0, 6000.0).with_aliases(&["filter_cutoff", "cutoff"]),
+    ParamSpec::continuous("amplitude", 0.0, 1.0, 0.6).with_aliases(&["volume"]),
+    ParamSpec::continuous("attack", 0.001, 10.0, 1.0),
+    ParamSpec::continuous("release", 0.001, 30.0, 2.0),
+];
+
+/// A single grain
+struct Grain {
+    /// Read position in source samples
+    position: f64,
+    /// Source samples advanced per output sample
+    rate: f64,
+    /// Samples played so far
+    age: usize,
+    /// Total length in output samples
+    length: usize,
+    /// Stereo position (-1.0 left to 1.0 right)
+    pan: f64,
+}
+
+/// A granular texture voice
+pub struct TextureVoice {
+    /// Buffer grains are read from
+    source: SampleBuffer,
+    /// Rendered noise per entry of `NOISE_WAVEFORMS` (empty when granulating
+    /// a loaded sample), so switching colour never renders on the audio thread
+    noise: Vec<SampleBuffer>,
+    /// Index into `NOISE_WAVEFORMS`
+    noise_index: usize,
+    grains: Vec<Grain>,
+    /// Overall amplitude envelope
+    envelope: Envelope,
+    /// Tone control (left and right)
+    filters: [Filter; 2],
+
+    sample_rate: f64,
+
+    // Parameters
+    /// Grain length in seconds
+    grain_size: f64,
+    /// Grains started per second
+    density: f64,
+    /// Scan position in the source (0.0 to 1.0)
+    position: f64,
+    /// Random position offset per grain, as a fraction of the source
+    jitter: f64,
+    /// Transposition in semitones
+    pitch: f64,
+    /// Random transposition per grain in semitones (+/-)
+    spread: f64,
+    filter_cutoff: f64,
+    amplitude: f64,
+
+    /// Samples until the next grain starts
+    samples_to_grain: f64,
+    /// RNG state (xorshift)
+    rng_state: u64,
+    active: bool,
+}
+
+impl TextureVoice {
+    /// Parameters every texture voice accepts (also returned by `params`)
+    pub const PARAMS: &'static [ParamSpec] = PARAMS;
+
+    /// Create a texture voice that granulates pink noise
+    pub fn new(sample_rate: f64) -> Self {
+        let noise = Self::render_noise(sample_rate);
+        let mut voice = Self::build(noise[1].clone(), sample_rate);
+        voice.noise = noise;
+        voice
+    }
+
+    /// Create a texture voice that granulates a sample
+    pub fn with_sample(sample_rate: f64, sample: SampleBuffer) -> Self {
+        Self::build(sample, sample_rate)
+    }
+
+    fn build(source: SampleBuffer, sample_rate: f64) -> Self {
+        let mut envelope = Envelope::new(sample_rate);
+        envelope.configure(1.0, 0.5, 0.9, 2.0);
+
+        let filters = [0, 1].map(|_| {
+            let mut filter = Filter::with_type(sample_rate, FilterType::LowPass);
+            filter.set_cutoff(6000.0);
+            filter
+        });
+
+        let mut voice = Self {
+            source,
+            noise: Vec::new(),
+            noise_index: 1,
+            grains: Vec::with_capacity(MAX_GRAINS),
+            envelope,
+            filters,
+            sample_rate,
+            grain_size: 0.08,
+            density: 20.0,
+            position: 0.5,
+            jitter: 0.2,
+            pitch: 0.0,
+            spread: 0.0,
+            filter_cutoff: 6000.0,
+            amplitude: 0.6,
+            samples_to_grain: 0.0,
+            rng_state: 0x9E37_79B9_7F4A_7C15,
+            active: true,
+        };
+
+        // Auto-trigger like the drone: textures are sustained beds
+        voice.envelope.trigger();
+        voice
+    }
+
+    /// Render each noise waveform into a buffer to granulate
+    fn render_noise(sample_rate: f64) -> Vec<SampleBuffer> {
+        NOISE_WAVEFORMS
+            .iter()
+            .map(|&waveform| {
+                let mut oscillator = Oscillator::new(waveform, 1.0, sample_rate);
+                let samples = (0..(sample_rate * NOISE_SECONDS) as usize)
+                    .map(|_| oscillator.generate() as f32)
+                    .collect();
+                SampleBuffer::from_samples(samples, sample_rate)
+            })
+            .collect()
+    }
+
+    /// Get the number of grains currently playing
+    pub fn grain_count(&self) -> usize {
+        self.grains.len()
+    }
+
+    /// Xorshift RNG returning -1.0..1.0
+    fn random(&mut self) -> f64 {
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng_state = x;
+        (x as f64 / u64::MAX as f64) * 2.0 - 1.0
+    }
+
+    /// Start a new grain (dropped if all grain slots are busy)
+    fn spawn_grain(&mut self) {
+        if self.grains.len() >= MAX_GRAINS {
+            return;
+        }
+
+        let len = self.source.len() as f64;
+        let offset = self.random() * self.jitter;
+        let position = (self.position + offset).rem_euclid(1.0) * len;
+
+        let semitones = self.pitch + self.random() * self.spread;
+        let rate = self.source.sample_rate() / self.sample_rate * 2.0_f64.powf(semitones / 12.0);
+
+        let pan = self.random();
+        self.grains.push(Grain {
+            position,
+            rate,
+            age: 0,
+            length: ((self.grain_size * self.sample_rate) as usize).max(2),
+            pan,
+        });
+    }
+}
+
+impl Voice for TextureVoice {
+    fn set_parameter(&mut self, name: &str, value: f64) {
+        match name {
+            "grain_size" | "size" => {
+                self.grain_size = value.clamp(0.005, 1.0);
+            }
+            "density" => {
+                self.density = value.clamp(0.5, 200.0);
+            }
+            "position" => {
+                self.position = value.clamp(0.0, 1.0);
+            }
+            "jitter" => {
+                self.jitter = value.clamp(0.0, 1.0);
+            }
+            "pitch" => {
+                self.pitch = value.clamp(-24.0, 24.0);
+            }
+            "spread" | "pitch_spread" => {
+                self.spread = value.clamp(0.0, 24.0);
+            }
+            "noise" => {
+                // Only meaningful when granulating noise
+                let index = value.round().clamp(0.0, (NOISE_WAVEFORMS.len() - 1) as f64) as usize;
+                if !self.noise.is_empty() && index != self.noise_index {
+                    self.noise_index = index;
+                    self.source = self.noise[index].clone();
+                    self.grains.clear();
+                }
+            }
+            "filter" | "filter_cutoff" | "cutoff" => {
+                self.filter_cutoff = value.clamp(20.0, 20000.0);
+                for filter in &mut self.filters {
+                    filter.set_cutoff(self.filter_cutoff);
+                }
+            }
+            "amplitude" | "volume" => {
+                self.amplitude = value.clamp(0.0, 1.0);
+            }
+            "attack" => {
+                self.envelope.set_attack(value.clamp(0.001, 10.0));
+            }
+            "release" => {
+                self.envelope.set_release(value.clamp(0.001, 30.0));
+            }
+            _ => {}
+        }
+    }
+
+    fn get_parameter(&self, name: &str) -> Option<f64> {
+        match name {
+            "grain_size" | "size" => Some(self.grain_size),
+            "density" => Some(self.density),
+            "position" => Some(self.position),
+            "jitter" => Some(self.jitter),
+            "pitch" => Some(self.pitch),
+            "spread" | "pitch_spread" => Some(self.spread),
+            "noise" => Some(self.noise_index as f64),
+            "filter" | "filter_cutoff" | "cutoff" => Some(self.filter_cutoff),
+            "amplitude" | "volume" => Some(self.amplitude),
+            "attack" => Some(self.envelope.attack_time()),
+            "release" => Some(self.envelope.release_time()),
+            _ => None,
+        }
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn trigger(&mut self) {
+        self.active = true;
+        self.envelope.trigger();
+    }
+
+    fn release(&mut self) {
+        self.envelope.release();
+    }
+
+    fn is_active(&self) -> bool {
+        self.active && self.envelope.is_active()
+    }
+
+    fn process(&mut self) -> f64 {
+        self.process_frame().mid()
+    }
+
+    fn process_frame(&mut self) -> Frame {
+        if !self.active {
+            return Frame::ZERO;
+        }
+
+        // Schedule grains with a little timing randomness so they don't buzz
+        self.samples_to_grain -= 1.0;
+        if self.samples_to_grain <= 0.0 {
+            self.spawn_grain();
+            let interval = self.sample_rate / self.density;
+            self.samples_to_grain += interval * (1.0 + self.random() * 0.25);
+        }
+
+        let mut sum = Frame::ZERO;
+        for grain in &mut self.grains {
+            // Hann window
+            let phase = grain.age as f64 / grain.length as f64;
+            let window = 0.5 - 0.5 * (2.0 * PI * phase).cos();
+            // Linear pan keeps the mono mix of every grain at full level
+            let sample = self.source.read_wrapped(grain.position) * window;
+            sum += Frame::new(sample * (1.0 - grain.pan), sample * (1.0 + grain.pan));
+
+            grain.position += grain.rate;
+            grain.age += 1;
+        }
+        self.grains.retain(|g| g.age < g.length);
+
+        // Keep loudness steady as grains overlap
+        let overlap = (self.density * self.grain_size).max(1.0);
+        let scale = overlap.sqrt();
+        let [left, right] = &mut self.filters;
+        let output = Frame::new(left.process(sum.left / scale), right.process(sum.right / scale));
+
+        let env_level = self.envelope.process();
+        if !self.envelope.is_active() {
+            self.active = false;
+            self.grains.clear();
+        }
+
+        output * env_level * self.amplitude
+    }
+
+    fn envelope_mut(&mut self) -> Option<&mut Envelope> {
+        Some(&mut self.envelope)
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+
+        if !self.noise.is_empty() {
+            self.noise = Self::render_noise(sample_rate);
+            self.source = self.noise[self.noise_index].clone();
+        }
+        self.grains.clear();
+
+        self.envelope = Envelope::new(sample_rate);
+        self.envelope.configure(1.0, 0.5, 0.9, 2.0);
+        self.envelope.trigger();
+
+        self.filters = [0, 1].map(|_| {
+            let mut filter = Filter::with_type(sample_rate, FilterType::LowPass);
+            filter.set_cutoff(self.filter_cutoff);
+            filter
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rms(voice: &mut TextureVoice, samples: usize) -> f64 {
+        let sum: f64 = (0..samples).map(|_| voice.process().powi(2)).sum();
+        (sum / samples as f64).sqrt()
+    }
+
+    #[test]
+    fn test_texture_noise_output() {
+        let mut voice = TextureVoice::new(44100.0);
+        assert!(voice.is_active());
+        assert!(rms(&mut voice, 44100) > 0.001);
+    }
+
+    #[test]
+    fn test_texture_density_sets_grain_count() {
+        let mut voice = TextureVoice::new(44100.0);
+        voice.set_parameter("density", 100.0);
+        voice.set_parameter("grain_size", 0.1);
+        rms(&mut voice, 22050);
+        // ~100 grains/s * 0.1 s each = ~10 overlapping
+        assert!((5..=15).contains(&voice.grain_count()), "{}", voice.grain_count());
+
+        voice.set_parameter("density", 2.0);
+        voice.set_parameter("grain_size", 0.01);
+        rms(&mut voice, 22050);
+        assert!(voice.grain_count() <= 1);
+    }
+
+    #[test]
+    fn test_texture_sample_source() {
+        // A constant buffer: every grain reads 1.0, so output follows the windows
+        let sample = SampleBuffer::from_samples(vec![1.0; 1000], 22050.0);
+        let mut voice = TextureVoice::with_sample(44100.0, sample);
+        voice.set_parameter("jitter", 1.0);
+        voice.set_parameter("spread", 12.0);
+        voice.set_parameter("filter", 20000.0);
+
+        let max = (0..44100).map(|_| voice.process()).fold(0.0, f64::max);
+        assert!(max > 0.1);
+
+        // Noise selection does nothing for sample sources
+        voice.set_parameter("noise", 0.0);
+        assert_eq!(voice.get_parameter("noise"), Some(1.0));
+    }
+
+    #[test]
+    fn test_texture_noise_selection() {
+        let mut voice = TextureVoice::new(44100.0);
+        voice.set_parameter("noise", 2.0);
+        assert_eq!(voice.get_parameter("noise"), Some(2.0));
+        assert!(rms(&mut voice, 22050) > 0.0);
+
+        // Switching colour reuses the buffers rendered up front
+        voice.set_parameter("noise", 0.0);
+        assert!(std::ptr::eq(voice.source.samples(), voice.noise[0].samples()));
+        voice.set_sample_rate(48000.0);
+        assert_eq!(voice.noise.len(), NOISE_WAVEFORMS.len());
+        assert!(std::ptr::eq(voice.source.samples(), voice.noise[0].samples()));
+        assert_eq!(voice.source.sample_rate(), 48000.0);
+    }
+
+    #[test]
+    fn test_texture_release() {
+        let mut voice = TextureVoice::new(44100.0);
+        voice.set_parameter("release", 0.1);
+        rms(&mut voice, 1000);
+        voice.release();
+        rms(&mut voice, 10000);
+        assert!(!voice.is_active());
+        assert_eq!(voice.grain_count(), 0);
+    }
+}
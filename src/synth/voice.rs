@@ -1,5 +1,7 @@
 //! Voice trait for sound generators
 
+use super::{Envelope, Frame, ParamSpec};
+
 /// Trait for voice implementations
 pub trait Voice: Send + Sync {
     /// Set a parameter value
@@ -8,6 +10,11 @@ pub trait Voice: Send + Sync {
     /// Get a parameter value
     fn get_parameter(&self, name: &str) -> Option<f64>;
     
+    /// Describe the parameters this voice accepts
+    fn params(&self) -> &'static [ParamSpec] {
+        &[]
+    }
+    
     /// Trigger the voice (start a note)
     fn trigger(&mut self);
     
@@ -20,6 +27,27 @@ pub trait Voice: Send + Sync {
     /// Generate the next sample
     fn process(&mut self) -> f64;
     
+    /// Generate the next stereo frame
+    ///
+    /// Mono voices use the default, which plays `process` in both channels;
+    /// stereo voices override it (and return its mid from `process`).
+    fn process_frame(&mut self) -> Frame {
+        Frame::mono(self.process())
+    }
+    
+    /// Get the amplitude envelope, for layers that reshape it
+    ///
+    /// Voices without a single amplitude envelope return `None`.
+    fn envelope_mut(&mut self) -> Option<&mut Envelope> {
+        None
+    }
+    
+    /// Follow the transport tempo and beat position
+    ///
+    /// Tempo-synced voices (sequencers, synced LFOs) override this; the
+    /// default ignores the transport.
+    fn sync(&mut self, _bpm: f64, _beat: f64) {}
+    
     /// Set the sample rate
     fn set_sample_rate(&mut self, sample_rate: f64);
 }
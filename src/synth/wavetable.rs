@@ -0,0 +1,365 @@
+//! Wavetable oscillator
+//!
+//! A wavetable is a stack of single-cycle frames. The oscillator reads the
+//! current frame with linear interpolation and crossfades between adjacent
+//! frames by `position`, so sweeping position morphs the timbre smoothly.
+
+use super::SampleBuffer;
+use anyhow::{bail, Result};
+use std::f64::consts::PI;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+/// Default samples per frame (also used to slice WAV tables)
+pub const DEFAULT_FRAME_SIZE: usize = 2048;
+
+/// Names of the built-in tables
+pub const BUILTIN_TABLES: [&str; 3] = ["basic", "harmonics", "pulse"];
+
+/// A set of single-cycle frames
+#[derive(Debug, Clone)]
+pub struct Wavetable {
+    frames: Arc<Vec<Vec<f32>>>,
+    frame_size: usize,
+}
+
+impl Wavetable {
+    /// Create a wavetable from frames (all frames must have the same length)
+    pub fn from_frames(frames: Vec<Vec<f32>>) -> Result<Self> {
+        let Some(first) = frames.first() else {
+            bail!("wavetable has no frames");
+        };
+        let frame_size = first.len();
+        if frame_size < 2 {
+            bail!("wavetable frames need at least 2 samples");
+        }
+        if frames.iter().any(|f| f.len() != frame_size) {
+            bail!("wavetable frames must all be the same length");
+        }
+
+        Ok(Self {
+            frames: Arc::new(frames),
+            frame_size,
+        })
+    }
+
+    /// Build a frame from a function of phase (0.0 to 1.0)
+    fn render(frame_size: usize, f: impl Fn(f64) -> f64) -> Vec<f32> {
+        (0..frame_size)
+            .map(|i| f(i as f64 / frame_size as f64) as f32)
+            .collect()
+    }
+
+    /// Additive frame from harmonic amplitudes, normalized to a peak of 1.0
+    fn additive(frame_size: usize, harmonics: &[f64]) -> Vec<f32> {
+        let mut frame = Self::render(frame_size, |p| {
+            harmonics
+                .iter()
+                .enumerate()
+                .map(|(n, a)| a * (2.0 * PI * (n + 1) as f64 * p).sin())
+                .sum()
+        });
+        let peak = frame.iter().fold(0.0f32, |a, &b| a.max(b.abs()));
+        if peak > 0.0 {
+            frame.iter_mut().for_each(|s| *s /= peak);
+        }
+        frame
+    }
+
+    /// Sine -> triangle -> saw -> square, band-limited to 32 harmonics
+    pub fn basic() -> Self {
+        // Built-ins are rendered once and shared
+        static TABLE: OnceLock<Wavetable> = OnceLock::new();
+        TABLE.get_or_init(Self::render_basic).clone()
+    }
+
+    fn render_basic() -> Self {
+        let n = DEFAULT_FRAME_SIZE;
+        let harmonics = 32;
+        let sine = vec![1.0];
+        let triangle: Vec<f64> = (1..=harmonics)
+            .map(|k| {
+                if k % 2 == 0 {
+                    0.0
+                } else {
+                    let sign = if (k / 2) % 2 == 0 { 1.0 } else { -1.0 };
+                    sign / (k * k) as f64
+                }
+            })
+            .collect();
+        let saw: Vec<f64> = (1..=harmonics).map(|k| 1.0 / k as f64).collect();
+        let square: Vec<f64> = (1..=harmonics)
+            .map(|k| if k % 2 == 0 { 0.0 } else { 1.0 / k as f64 })
+            .collect();
+
+        let frames = [sine, triangle, saw, square]
+            .iter()
+            .map(|h| Self::additive(n, h))
+            .collect();
+        Self::from_frames(frames).expect("built-in table is valid")
+    }
+
+    /// Eight frames adding harmonics one octave band at a time (dark to bright)
+    pub fn harmonics() -> Self {
+        static TABLE: OnceLock<Wavetable> = OnceLock::new();
+        TABLE.get_or_init(Self::render_harmonics).clone()
+    }
+
+    fn render_harmonics() -> Self {
+        let frames = (0..8)
+            .map(|i| {
+                let count = 1usize << i;
+                let h: Vec<f64> = (1..=count).map(|k| 1.0 / k as f64).collect();
+                Self::additive(DEFAULT_FRAME_SIZE, &h)
+            })
+            .collect();
+        Self::from_frames(frames).expect("built-in table is valid")
+    }
+
+    /// Band-limited pulse with width sweeping from 50% to 5%
+    pub fn pulse() -> Self {
+        static TABLE: OnceLock<Wavetable> = OnceLock::new();
+        TABLE.get_or_init(Self::render_pulse).clone()
+    }
+
+    fn render_pulse() -> Self {
+        let frames = (0..8)
+            .map(|i| {
+                let width = 0.5 - 0.45 * i as f64 / 7.0;
+                // Fourier series of a pulse: a_k = sin(pi k w) / k, as cosines
+                let mut frame = Self::render(DEFAULT_FRAME_SIZE, |p| {
+                    (1..=32)
+                        .map(|k| {
+                            let k = k as f64;
+                            (PI * k * width).sin() / k * (2.0 * PI * k * p).cos()
+                        })
+                        .sum()
+                });
+                let peak = frame.iter().fold(0.0f32, |a, &b| a.max(b.abs()));
+                frame.iter_mut().for_each(|s| *s /= peak);
+                frame
+            })
+            .collect();
+        Self::from_frames(frames).expect("built-in table is valid")
+    }
+
+    /// Get a built-in table by name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "basic" => Some(Self::basic()),
+            "harmonics" => Some(Self::harmonics()),
+            "pulse" => Some(Self::pulse()),
+            _ => None,
+        }
+    }
+
+    /// Get a built-in table by name, or load a WAV file from a path
+    pub fn load(name_or_path: &str) -> Result<Self> {
+        match Self::from_name(name_or_path) {
+            Some(table) => Ok(table),
+            None => Self::from_wav(Path::new(name_or_path), DEFAULT_FRAME_SIZE),
+        }
+    }
+
+    /// Load a table from a WAV file, slicing it into frames of `frame_size`
+    ///
+    /// A file shorter than one frame is used as a single frame.
+    pub fn from_wav(path: &Path, frame_size: usize) -> Result<Self> {
+        let buffer = SampleBuffer::load(path)?;
+        let samples = buffer.samples();
+
+        let frames = if samples.len() < frame_size {
+            vec![samples.to_vec()]
+        } else {
+            samples
+                .chunks_exact(frame_size)
+                .map(|c| c.to_vec())
+                .collect()
+        };
+        Self::from_frames(frames)
+    }
+
+    /// Get the number of frames
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Get the samples per frame
+    pub fn frame_size(&self) -> usize {
+        self.frame_size
+    }
+
+    /// Read a frame at a fractional phase (0.0 to 1.0)
+    fn read_frame(&self, frame: usize, phase: f64) -> f64 {
+        let frame = &self.frames[frame];
+        let pos = phase * self.frame_size as f64;
+        let index = pos.floor() as usize % self.frame_size;
+        let frac = pos - pos.floor();
+        let a = frame[index] as f64;
+        let b = frame[(index + 1) % self.frame_size] as f64;
+        a + (b - a) * frac
+    }
+
+    /// Read the table at a phase and morph position (both 0.0 to 1.0)
+    pub fn read(&self, phase: f64, position: f64) -> f64 {
+        let last = self.frames.len() - 1;
+        let pos = position.clamp(0.0, 1.0) * last as f64;
+        let frame = (pos.floor() as usize).min(last);
+        let frac = pos - frame as f64;
+
+        let a = self.read_frame(frame, phase);
+        if frac <= 0.0 || frame == last {
+            return a;
+        }
+        let b = self.read_frame(frame + 1, phase);
+        a + (b - a) * frac
+    }
+}
+
+/// An oscillator that plays a wavetable
+pub struct WavetableOscillator {
+    table: Wavetable,
+    phase: f64,
+    frequency: f64,
+    sample_rate: f64,
+    /// Morph position through the frames (0.0 to 1.0)
+    position: f64,
+}
+
+impl WavetableOscillator {
+    /// Create a new wavetable oscillator
+    pub fn new(table: Wavetable, frequency: f64, sample_rate: f64) -> Self {
+        Self {
+            table,
+            phase: 0.0,
+            frequency,
+            sample_rate,
+            position: 0.0,
+        }
+    }
+
+    /// Set the frequency
+    pub fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+    }
+
+    /// Get the current frequency
+    pub fn frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    /// Set the morph position (0.0 to 1.0)
+    pub fn set_position(&mut self, position: f64) {
+        self.position = position.clamp(0.0, 1.0);
+    }
+
+    /// Get the morph position
+    pub fn position(&self) -> f64 {
+        self.position
+    }
+
+    /// Replace the table
+    pub fn set_table(&mut self, table: Wavetable) {
+        self.table = table;
+    }
+
+    /// Get the table
+    pub fn table(&self) -> &Wavetable {
+        &self.table
+    }
+
+    /// Reset the phase
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
+    /// Generate the next sample
+    pub fn generate(&mut self) -> f64 {
+        let sample = self.table.read(self.phase, self.position);
+
+        self.phase += self.frequency / self.sample_rate;
+        self.phase -= self.phase.floor();
+
+        sample
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::{SampleFormat, WavSpec, WavWriter};
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_builtin_tables() {
+        for name in BUILTIN_TABLES {
+            let table = Wavetable::from_name(name).unwrap();
+            assert!(table.frame_count() >= 4);
+            assert_eq!(table.frame_size(), DEFAULT_FRAME_SIZE);
+        }
+        assert!(Wavetable::from_name("vocal").is_none());
+    }
+
+    #[test]
+    fn test_first_basic_frame_is_sine() {
+        let table = Wavetable::basic();
+        assert!(table.read(0.0, 0.0).abs() < 1e-6);
+        assert!((table.read(0.25, 0.0) - 1.0).abs() < 1e-6);
+        assert!((table.read(0.75, 0.0) + 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_position_morphs_between_frames() {
+        let table = Wavetable::from_frames(vec![vec![0.0; 4], vec![1.0; 4]]).unwrap();
+        assert_eq!(table.read(0.3, 0.0), 0.0);
+        assert_eq!(table.read(0.3, 0.25), 0.25);
+        assert_eq!(table.read(0.3, 1.0), 1.0);
+        // Out of range positions clamp
+        assert_eq!(table.read(0.3, 2.0), 1.0);
+    }
+
+    #[test]
+    fn test_invalid_frames() {
+        assert!(Wavetable::from_frames(vec![]).is_err());
+        assert!(Wavetable::from_frames(vec![vec![0.0; 4], vec![0.0; 3]]).is_err());
+    }
+
+    #[test]
+    fn test_oscillator_frequency() {
+        let mut osc = WavetableOscillator::new(Wavetable::basic(), 1.0, 4.0);
+        // Sine frame, 4 samples per cycle
+        assert!(osc.generate().abs() < 1e-6);
+        assert!((osc.generate() - 1.0).abs() < 1e-6);
+        assert!(osc.generate().abs() < 1e-6);
+        assert!((osc.generate() + 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_load_wav_table() {
+        let file = NamedTempFile::new().unwrap();
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 44100,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        {
+            let mut writer = WavWriter::create(file.path(), spec).unwrap();
+            for i in 0..(256 * 3 + 10) {
+                writer.write_sample((i / 256) as f32).unwrap();
+            }
+            writer.finalize().unwrap();
+        }
+
+        let table = Wavetable::from_wav(file.path(), 256).unwrap();
+        // Trailing partial frame is dropped
+        assert_eq!(table.frame_count(), 3);
+        assert_eq!(table.read(0.5, 1.0), 2.0);
+
+        let single = Wavetable::from_wav(file.path(), 4096).unwrap();
+        assert_eq!(single.frame_count(), 1);
+
+        assert_eq!(Wavetable::load("pulse").unwrap().frame_count(), 8);
+        assert!(Wavetable::load("/nonexistent/table.wav").is_err());
+    }
+}